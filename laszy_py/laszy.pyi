@@ -1,5 +1,45 @@
 import numpy as np
-from typing import Self
+from typing import Callable, Literal, Optional, Self
+
+class LaszyError(Exception):
+    """Base class of all errors raised by laszy."""
+
+class FileAccessError(LaszyError):
+    """A file could not be read or written."""
+    path: Optional[str]
+
+class LasFormatError(LaszyError):
+    """A LAS/LAZ file or a point in it is invalid."""
+    path: Optional[str]
+    point_index: Optional[int]
+
+class InvalidParameterError(LaszyError):
+    """A parameter has an invalid value."""
+    parameter: str
+    value: str
+
+class UnsupportedError(LaszyError):
+    """The requested method is not implemented yet."""
+
+class EmptyCloudError(LaszyError):
+    """No points were left to process."""
+
+class InvalidPointError(LaszyError):
+    """A point can't be used, for example because its coordinates are not finite."""
+
+class TriangulationError(LaszyError):
+    """Points can't be triangulated into a TIN."""
+
+class InvalidFileExtensionError(LaszyError):
+    """The output file has an unsupported extension."""
+
+class OutputExistsError(LaszyError):
+    """The output file already exists."""
+    path: str
+
+class CancelledError(LaszyError):
+    """The run was cancelled with a CancellationToken."""
+
 
 class PointCloud:
     @property
@@ -12,12 +52,461 @@ class PointCloud:
         """A boolean array of shape (N,) indicating which points are ground points"""
         ...
 
+    @property
+    def colours(self) -> Optional[np.ndarray[np.uint16]]:
+        """The red, green and blue of the points as a numpy array of shape (N, 3), or None when the points have no
+        colour, because the input has no colour and the builder didn't colour them."""
+        ...
+
+    @property
+    def normals(self) -> Optional[np.ndarray[np.float64]]:
+        """The unit normals of the points, pointing up, as a numpy array of shape (N, 3), or None when the builder
+        didn't compute the geometric features. They are NaN for points without features."""
+        ...
+
+    @property
+    def curvature(self) -> Optional[np.ndarray[np.float64]]:
+        """The change of curvature of the points as a numpy array of shape (N,), from 0.0 for a plane to 1/3 for
+        points scattered in all directions, or None when the builder didn't compute the geometric features."""
+        ...
+
+    @property
+    def planarity(self) -> Optional[np.ndarray[np.float64]]:
+        """How much the neighbourhoods of the points lie in a plane, from 0.0 to 1.0, as a numpy array of shape
+        (N,), or None when the builder didn't compute the geometric features."""
+        ...
+
+    @property
+    def linearity(self) -> Optional[np.ndarray[np.float64]]:
+        """How much the neighbourhoods of the points lie on a line, from 0.0 to 1.0, as a numpy array of shape (N,),
+        or None when the builder didn't compute the geometric features."""
+        ...
+
+    @property
+    def sphericity(self) -> Optional[np.ndarray[np.float64]]:
+        """How much the neighbourhoods of the points are scattered in all directions, from 0.0 to 1.0, as a numpy
+        array of shape (N,), or None when the builder didn't compute the geometric features."""
+        ...
+
+    @property
+    def verticality(self) -> Optional[np.ndarray[np.float64]]:
+        """How vertical the planes through the neighbourhoods of the points are, from 0.0 for horizontal to 1.0
+        for vertical, as a numpy array of shape (N,), or None when the builder didn't compute the geometric
+        features."""
+        ...
+
+    @property
+    def extra_bytes_names(self) -> list[str]:
+        """Names of the fields in the extra bytes of the points, those of the input followed by those added by the
+        builder, such as "HeightAboveGround". Bytes that the input doesn't describe are called "undocumented 1",
+        "undocumented 2" and so on."""
+        ...
+
+    def extra_bytes(self, name: str) -> Optional[np.ndarray[np.float64]]:
+        """The values of the extra bytes field called name of the points as a numpy array of shape (N,), with the
+        scale and offset of the field applied. Values that are the no data value of the field are NaN. None when
+        there is no such field, or when it is undocumented or an array."""
+        ...
+
+
+class CancellationToken:
+    def __init__(self) -> None:
+        ...
+
+    def cancel(self) -> None:
+        """Request cancellation. The running to_* method of the builder stops and raises an exception."""
+        ...
+
+    def reset(self) -> None:
+        """Reset the token, so it can be used for a new run after a cancelled one."""
+        ...
+
+    def is_cancelled(self) -> bool:
+        ...
+
+
+class CsfParams:
+    """Parameters of the cloth simulation filter (CSF). The constructor uses the defaults of the "relief" preset.
+
+    Attributes:
+        rigidness: Rigidity of the cloth between 0.0 and 1.0. 0.0 will classify all points as ground, 1.0 will
+            classify points in a strict manner.
+        cloth_resolution: Distance between the cloth particles in meters.
+        simulation_threshold: The simulation stops when no particle moved more than this many meters.
+        classification_threshold: The maximum distance between a point and the cloth for it to be classified as
+            ground in meters.
+        collision_height: How the height the cloth collides with is taken from the points in a cloth cell: "nearest"
+            (the point closest to the particle), "lowest", "percentile" or "median_of_lowest". The last two are robust
+            against low noise points.
+        collision_percentile: The percentile between 0.0 and 1.0 used by the "percentile" collision height.
+        collision_lowest_k: The amount of lowest points per cell the "median_of_lowest" collision height takes the
+            median of.
+        time_step: Time step of one iteration. Smaller steps are slower but more precise.
+        gravity: Acceleration of the cloth towards the points.
+        max_iterations: The simulation stops after this many iterations, even when the simulation threshold was not
+            reached.
+        slope_smoothing: Snap parts of the cloth that hang just above steep slopes onto the points after the
+            simulation.
+        slope_smoothing_threshold: Maximum distance in meters between the cloth and the points for slope smoothing
+            to snap the cloth onto them.
+        tile_size: Simulate the cloth in tiles of this size in meters, to limit memory usage for large extents. The
+            points and simulated tiles are kept in temporary files. None simulates the whole extent at once.
+        tile_buffer: Distance in meters the simulation of a tile extends beyond the tile. Should be larger than the
+            largest object that is not ground, such as a building, for seamless results.
+    """
+    rigidness: float
+    cloth_resolution: float
+    simulation_threshold: float
+    classification_threshold: float
+    collision_height: Literal["nearest", "lowest", "percentile", "median_of_lowest"]
+    collision_percentile: float
+    collision_lowest_k: int
+    time_step: float
+    gravity: float
+    max_iterations: int
+    slope_smoothing: bool
+    slope_smoothing_threshold: float
+    tile_size: Optional[float]
+    tile_buffer: float
+
+    def __init__(
+            self,
+            rigidness: float = 0.5,
+            cloth_resolution: float = 5.0,
+            simulation_threshold: float = 0.1,
+            classification_threshold: float = 0.5,
+            collision_height: Literal["nearest", "lowest", "percentile", "median_of_lowest"] = "nearest",
+            collision_percentile: float = 0.05,
+            collision_lowest_k: int = 5,
+            time_step: float = 0.65,
+            gravity: float = 0.2,
+            max_iterations: int = 500,
+            slope_smoothing: bool = False,
+            slope_smoothing_threshold: float = 0.3,
+            tile_size: Optional[float] = None,
+            tile_buffer: float = 50.0
+    ) -> None:
+        ...
+
+    @staticmethod
+    def flat() -> CsfParams:
+        """Preset for flat terrain, such as urban areas: a rigid cloth without slope smoothing."""
+        ...
+
+    @staticmethod
+    def relief() -> CsfParams:
+        """Preset for terrain with gentle relief. This is the default."""
+        ...
+
+    @staticmethod
+    def steep_slope() -> CsfParams:
+        """Preset for steep slopes, such as mountains: a soft cloth with slope smoothing."""
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range.
+        """
+        ...
+
+
+class SmrfParams:
+    """Parameters of the Simple Morphological Filter (SMRF) of Pingel et al. (2013). The defaults are those of PDAL,
+    which work well for urban areas.
+
+    Attributes:
+        cell_size: Cell size in meters of the grid of the lowest points.
+        slope: Slope as rise over run that terrain can have, larger slopes are objects.
+        window: Radius in meters of the largest window, which should be larger than the largest object, such as a
+            building.
+        elevation_threshold: Distance in meters a point may be from the ground surface on flat terrain to be ground.
+        elevation_scaler: Factor of the slope of the ground surface added to the elevation threshold.
+    """
+    cell_size: float
+    slope: float
+    window: float
+    elevation_threshold: float
+    elevation_scaler: float
+
+    def __init__(
+        self,
+        cell_size: float = 1.0,
+        slope: float = 0.15,
+        window: float = 18.0,
+        elevation_threshold: float = 0.5,
+        elevation_scaler: float = 1.25,
+    ) -> None:
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range.
+        """
+        ...
+
+
+class PmfParams:
+    """Parameters of the Progressive Morphological Filter (PMF) of Zhang et al. (2003). The defaults are those of
+    PDAL.
+
+    Attributes:
+        cell_size: Cell size in meters of the grid of the lowest points.
+        max_window: Size in meters of the largest window, which should be larger than the largest object, such as a
+            building.
+        slope: Slope as rise over run of the terrain, used to grow the elevation difference threshold.
+        initial_distance: Elevation difference threshold in meters of the first window.
+        max_distance: Largest elevation difference threshold in meters.
+        exponential: Grow the windows exponentially, with sizes of 3, 5, 9, 17 and so on cells, instead of linearly
+            with sizes of 3, 5, 7 and so on cells.
+    """
+    cell_size: float
+    max_window: float
+    slope: float
+    initial_distance: float
+    max_distance: float
+    exponential: bool
+
+    def __init__(
+        self,
+        cell_size: float = 1.0,
+        max_window: float = 33.0,
+        slope: float = 1.0,
+        initial_distance: float = 0.15,
+        max_distance: float = 2.5,
+        exponential: bool = True,
+    ) -> None:
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range.
+        """
+        ...
+
+
+class PtdParams:
+    """Parameters of the progressive TIN densification (PTD) of Axelsson (2000), the ground filter of TerraScan. The
+    defaults are those of TerraScan.
+
+    The filter seeds a TIN with the lowest point of each cell of max_building_size, and then repeatedly adds the
+    points that are close to a triangle of the TIN, at most one per triangle per round, until no more points are
+    close. The points in the TIN are ground.
+
+    Attributes:
+        max_building_size: Size in meters of the largest building, the cell size of the grid of seed points.
+        terrain_angle: Steepest slope of the terrain in degrees. Seeds that are higher than the seed in a cell next to
+            them by a steeper slope are not used.
+        iteration_angle: Largest angle in degrees between a triangle and the lines from a point to its corners for
+            the point to be added.
+        iteration_distance: Largest distance in meters from a point to the plane of a triangle for the point to be
+            added.
+    """
+    max_building_size: float
+    terrain_angle: float
+    iteration_angle: float
+    iteration_distance: float
+
+    def __init__(
+        self,
+        max_building_size: float = 60.0,
+        terrain_angle: float = 88.0,
+        iteration_angle: float = 6.0,
+        iteration_distance: float = 1.4,
+    ) -> None:
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range.
+        """
+        ...
+
+
+class ObjectParams:
+    """Parameters of the classification of the points that are not ground into low (3), medium (4) and high (5)
+    vegetation and buildings (6).
+
+    Points are buildings when they are at least building_min_height above the ground, and they and their k nearest
+    neighbours lie in a smooth plane. The other points are vegetation by their height above the ground. Only points
+    that are never classified, unclassified, vegetation or building are classified, other classes such as ground,
+    noise and water are kept.
+
+    Attributes:
+        surface: The ground surface the heights above ground are computed from: "cloth" (the cloth of the CSF ground
+            reclassification, which must be configured), "tin" (a TIN of the ground points) or "dtm" (the raster at
+            dtm_path).
+        dtm_path: The .asc, .tif or .tiff DTM of the "dtm" surface.
+        min_height: Points lower than this above the ground keep their class.
+        medium_vegetation_height: Points from this height above the ground are medium vegetation, lower points are
+            low vegetation.
+        high_vegetation_height: Points from this height above the ground are high vegetation.
+        building_min_height: Points lower than this above the ground are never buildings.
+        k: Amount of nearest neighbours the plane through a point is fitted to.
+        min_planarity: Smallest planarity of the point and its neighbours of a building point, from 0.0 to 1.0.
+        max_roughness: Largest root mean square distance in meters of the point and its neighbours to their plane of
+            a building point.
+    """
+    surface: Literal["cloth", "tin", "dtm"]
+    dtm_path: Optional[str]
+    min_height: float
+    medium_vegetation_height: float
+    high_vegetation_height: float
+    building_min_height: float
+    k: int
+    min_planarity: float
+    max_roughness: float
+
+    def __init__(
+        self,
+        surface: Literal["cloth", "tin", "dtm"] = "tin",
+        dtm_path: Optional[str] = None,
+        min_height: float = 0.5,
+        medium_vegetation_height: float = 2.0,
+        high_vegetation_height: float = 5.0,
+        building_min_height: float = 2.5,
+        k: int = 50,
+        min_planarity: float = 0.4,
+        max_roughness: float = 0.15,
+    ) -> None:
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range or the surface is unknown.
+        """
+        ...
+
+
+class DtmParams:
+    """Parameters of a DTM interpolated from ground points.
+
+    Attributes:
+        resolution: Cell size of the DTM in meters.
+        interpolation: How the height of a cell is interpolated from the ground points: "tin" (linear in the
+            Delaunay triangulation), "natural_neighbour" or "idw" (inverse distance weighting).
+        idw_power: The power of the distance in the weights of the "idw" interpolation.
+        idw_neighbours: The amount of closest ground points the "idw" interpolation uses.
+        max_distance: Cells further than this many meters from the closest ground point get no data, or are filled as
+            a void. None doesn't limit the distance.
+        fill_voids: Fill areas without data that are enclosed by cells with data, such as under buildings, from the
+            cells around them. The area outside of the ground points stays without data.
+    """
+    resolution: float
+    interpolation: Literal["tin", "natural_neighbour", "idw"]
+    idw_power: float
+    idw_neighbours: int
+    max_distance: Optional[float]
+    fill_voids: bool
+
+    def __init__(
+            self,
+            resolution: float = 1.0,
+            interpolation: Literal["tin", "natural_neighbour", "idw"] = "tin",
+            idw_power: float = 2.0,
+            idw_neighbours: int = 12,
+            max_distance: Optional[float] = None,
+            fill_voids: bool = True
+    ) -> None:
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range.
+        """
+        ...
+
+
+class DsmParams:
+    """Parameters of a DSM (Digital Surface Model), the height of the highest point in each cell.
+
+    Attributes:
+        resolution: Cell size of the DSM in meters.
+        pit_free: Fill cells without points that are enclosed by cells with points, and raise pits: cells that are
+            more than pit_threshold below the median of the cells around them.
+        pit_threshold: Distance in meters below the median of the cells around it from which a cell is a pit.
+    """
+    resolution: float
+    pit_free: bool
+    pit_threshold: float
+
+    def __init__(self, resolution: float = 1.0, pit_free: bool = False, pit_threshold: float = 1.0) -> None:
+        ...
+
+    def validate(self) -> None:
+        """Check the parameters.
+
+        Raises:
+            InvalidParameterError: When a parameter is out of range.
+        """
+        ...
+
+
+AggregationAttribute = Literal[
+    "z", "intensity", "return_number", "number_of_returns", "classification", "scan_angle", "user_data",
+    "point_source_id", "gps_time", "red", "green", "blue", "nir"
+]
+AggregationReducer = Literal["count", "min", "max", "mean", "stddev", "percentile", "mode"]
+
+
+class Aggregation:
+    """An attribute of the points and how it is reduced per cell, written as a band of a raster by to_raster.
+
+    Attributes:
+        attribute: The attribute of the points. Points without it, such as points without colours for "red", are
+            left out.
+        reducer: How the values of the points in a cell are reduced: "count" (the point density, cells without points
+            are 0), "min", "max", "mean", "stddev" (population standard deviation), "percentile" or "mode" (the most
+            common value, the lowest value wins a tie).
+        percent: The percentile between 0.0 and 1.0 of the "percentile" reducer.
+    """
+    attribute: AggregationAttribute
+    reducer: AggregationReducer
+    percent: float
+
+    def __init__(self, attribute: AggregationAttribute, reducer: AggregationReducer, percent: float = 0.5) -> None:
+        ...
+
+    @staticmethod
+    def count() -> Aggregation:
+        """The amount of points per cell."""
+        ...
+
+    @property
+    def name(self) -> str:
+        """The name of the band of the aggregation, such as "intensity_mean" or "z_p95"."""
+        ...
+
+    def validate(self) -> None:
+        """Check the aggregation.
+
+        Raises:
+            InvalidParameterError: When the attribute or reducer is unknown or the percent is out of range.
+        """
+        ...
+
 
 class PointCloudBuilder:
     def from_file(self, filename: str) -> Self:
         """Configure the builder from a .las or .laz file."""
         ...
 
+    def from_files(self, filenames: list[str]) -> Self:
+        """Configure the builder from several .las or .laz files with the same point format, such as overlapping
+        tiles, which are processed as a single file."""
+        ...
+
     def with_crop(self, lower_left: tuple[float, float], upper_right: tuple[float, float]) -> Self:
         """Configure the builder to crop the point cloud to the given rectangle."""
         ...
@@ -32,22 +521,216 @@ class PointCloudBuilder:
         ...
 
 
-    def with_csf_ground_reclassification(
-            self,
-            rigidness: float,
-            cloth_resolution: float,
-            simulation_threshold: float,
-            classification_threshold: float
+    def with_progress_callback(self, callback: Callable[[str, int, Optional[int]], None]) -> Self:
+        """Report progress to the callback instead of drawing a progress bar.
+
+        Args:
+            callback: Called as callback(task, position, total). total is None when the amount of steps is not known
+                beforehand. Status messages are sent to the "laszy" logger of the logging module.
+        """
+        ...
+
+    def with_silent_progress(self) -> Self:
+        """Don't report any progress. Status messages are still sent to the "laszy" logger of the logging module."""
+        ...
+
+    def with_cancellation_token(self, token: CancellationToken) -> Self:
+        """Stop running when the token is cancelled. The GIL is released while the builder runs, so the token can be
+        cancelled from another thread or from a progress callback."""
+        ...
+
+    def with_overwrite_policy(self, policy: Literal["overwrite", "error", "skip"]) -> Self:
+        """Set what happens when an output file already exists. Output is always written to a temporary file first,
+        which replaces the target only when writing succeeded."""
+        ...
+
+    def with_csf_ground_reclassification(self, params: Optional[CsfParams] = None) -> Self:
+        """Configure the builder to reclassify ground points using the cloth simulation filter (CSF).
+
+        Args:
+            params: The parameters of the cloth simulation. When None, the defaults of the "relief" preset are used.
+        """
+        ...
+
+    def with_smrf_ground_reclassification(self, params: Optional[SmrfParams] = None) -> Self:
+        """Configure the builder to reclassify ground points using the Simple Morphological Filter (SMRF), instead of
+        another ground reclassification.
+
+        Args:
+            params: The parameters of the filter. When None, the defaults are used.
+        """
+        ...
+
+    def with_pmf_ground_reclassification(self, params: Optional[PmfParams] = None) -> Self:
+        """Configure the builder to reclassify ground points using the Progressive Morphological Filter (PMF), instead
+        of another ground reclassification.
+
+        Args:
+            params: The parameters of the filter. When None, the defaults are used.
+        """
+        ...
+
+    def with_ptd_ground_reclassification(self, params: Optional[PtdParams] = None) -> Self:
+        """Configure the builder to reclassify ground points using progressive TIN densification (PTD), as in
+        TerraScan, instead of another ground reclassification. The densification is not tiled: the x, y and z of all
+        included points are kept in memory with its state per point, about 70 bytes per point or 7 GB for 100
+        million points. Crop or thin larger inputs first.
+
+        Args:
+            params: The parameters of the densification. When None, the defaults are used.
+        """
+        ...
+
+    def with_object_reclassification(self, params: Optional[ObjectParams] = None) -> Self:
+        """Configure the builder to classify the points that are not ground as vegetation by their height above the
+        ground, or as buildings, for to_cloud, to_file and to_raster. The ground is that of the ground
+        reclassification when it is configured, or else the points classified as ground in the file.
+
+        Args:
+            params: The height bands and the shape of buildings. When None, the defaults are used.
+        """
+        ...
+
+    def with_duplicate_detection(
+        self, tolerance: float = 0.0, same_gps_time: bool = False, action: Literal["remove", "withhold"] = "remove"
+    ) -> Self:
+        """Detect points that are duplicates of points before them in the input, also across the files of
+        from_files. Duplicates are not used by the noise filters, the ground reclassification or the rasters.
+
+        Args:
+            tolerance: The largest distance between duplicates. With 0.0 only points with exactly the same x, y and z
+                are duplicates.
+            same_gps_time: Only points with the same GPS time are duplicates.
+            action: "remove" to leave duplicates out before the thinning, "withhold" to set their withheld flag.
+        """
+        ...
+
+    def with_statistical_noise_filter(
+        self, k: int = 8, multiplier: float = 2.0, action: Literal["classify", "remove"] = "classify"
+    ) -> Self:
+        """Add a noise filter that finds points whose mean distance to their k nearest neighbours is more than
+        multiplier standard deviations above the mean of that distance over all points. The ground reclassification,
+        DTMs, DSMs and the height above ground don't use the noise points.
+
+        Args:
+            k: The amount of nearest neighbours.
+            multiplier: The amount of standard deviations above the mean distance.
+            action: "classify" to classify noise as low (7) or high (18) noise, "remove" to leave it out.
+        """
+        ...
+
+    def with_radius_noise_filter(
+        self, radius: float = 1.0, min_neighbours: int = 2, action: Literal["classify", "remove"] = "classify"
     ) -> Self:
-        """Configure the builder to reclassify ground points as CSF.
+        """Add a noise filter that finds points with fewer than min_neighbours other points within radius.
 
         Args:
-            rigidness: The rigidness parameter for the CSF method. Must be between 0.0 and 1.0. 0.0 will classify all
-                points as ground, 1.0 will classify points in a strict manner.
-            cloth_resolution: The resolution of the cloth in meters.
-            simulation_threshold: The threshold for the simulation to stop in meters.
-            classification_threshold: The maximum distance between a point and the cloth for it to be classified as
-                ground in meters.
+            radius: The distance to count neighbours within.
+            min_neighbours: The least amount of neighbours of a point that isn't noise.
+            action: "classify" to classify noise as low (7) or high (18) noise, "remove" to leave it out.
+        """
+        ...
+
+    def with_isolated_noise_filter(
+        self, radius: float = 5.0, height: float = 5.0, action: Literal["classify", "remove"] = "classify"
+    ) -> Self:
+        """Add a noise filter that finds points more than height below or above all other points within a
+        horizontal radius, such as reflections below the ground or birds above the canopy.
+
+        Args:
+            radius: The horizontal distance to compare heights within.
+            height: The least difference in height with all neighbours of a noise point.
+            action: "classify" to classify noise as low (7) or high (18) noise, "remove" to leave it out.
+        """
+        ...
+
+    def with_threads(self, threads: int) -> Self:
+        """Set the amount of threads used for the ground reclassification. By default all cores are used. The result
+        of the reclassification doesn't depend on the amount of threads."""
+        ...
+
+    def with_colourisation(self, path: str, bands: tuple[int, int, int] = (1, 2, 3)) -> Self:
+        """Colour the points with the red, green and blue bands of a GeoTIFF orthophoto at their x and y. Points get
+        the colour of the cell they are in, and only the part of the image in the area of the points is read. 8 bit
+        values are scaled to 16 bits by multiplying them by 257, so that 255 becomes 65535. Points outside the image
+        or in cells with its NODATA value keep their colour, or are black when the input has no colour. When the point
+        format of the input has no colour, to_file writes the point format with colour that has its other fields, such
+        as 2 for format 0.
+
+        Args:
+            path: The .tif or .tiff orthophoto, in the coordinate reference system of the points, with 8 or 16 bit
+                unsigned bands.
+            bands: The numbers of the red, green and blue bands, starting at 1.
+        """
+        ...
+
+    def with_las_options(self, point_format: Optional[int] = None, version: Optional[str] = None) -> Self:
+        """Set the point format and LAS version of LAS/LAZ outputs. By default those of the input are kept.
+
+        Args:
+            point_format: The point format of the output, from 0 to 10. Fields of the input that it doesn't have, such
+                as colour or GPS time, are dropped with a warning. Fields that the input doesn't have get default
+                values: a GPS time of 0.0, black colour and a NIR of 0. The formats 0 to 5 don't support return
+                numbers above 7, which become 7, and classes above 31, which become unclassified (1).
+            version: The LAS version of the output, from "1.0" to "1.4", which must support the point format. When
+                None, the version of the input is kept, or raised to the lowest version that supports the format.
+        """
+        ...
+
+    def with_raster_options(
+        self,
+        nodata: float = -9999.0,
+        compression: Literal["deflate", "lzw", "none"] = "deflate",
+        epsg: Optional[int] = None,
+    ) -> Self:
+        """Set how raster outputs such as a DTM are written.
+
+        Args:
+            nodata: The value written for cells without data.
+            compression: The compression of GeoTIFF files.
+            epsg: The EPSG code of the coordinate reference system written to GeoTIFF files. When None, the projection
+                of the input file is used, if it has one.
+        """
+        ...
+
+    def with_height_above_ground(
+        self,
+        surface: Literal["cloth", "tin", "dtm"] = "tin",
+        dtm_path: Optional[str] = None,
+        replace_z: bool = False,
+    ) -> Self:
+        """Compute the height above ground of each point, for to_cloud, to_file and to_raster.
+
+        Args:
+            surface: The ground surface: "cloth" (the cloth of the CSF ground reclassification, which must be
+                configured), "tin" (a TIN of the ground points, classified by the ground reclassification when it is
+                configured or else in the file) or "dtm" (the DTM raster at dtm_path, bilinearly interpolated).
+            dtm_path: The .asc, .tif or .tiff DTM of the "dtm" surface.
+            replace_z: Replace z with the height above ground, leaving out points without ground below them. When
+                False, the height is stored in a "HeightAboveGround" extra bytes field of 64 bit floats, which is
+                -9999 for points without ground below them.
+        """
+        ...
+
+    def with_geometric_features(self, k: int = 10, radius: Optional[float] = None) -> Self:
+        """Compute the normal, curvature, planarity, linearity, sphericity and verticality of each point from the
+        principal components of its neighbourhood, for to_cloud and to_file. to_file stores them in "NormalX",
+        "NormalY", "NormalZ", "Curvature", "Planarity", "Linearity", "Sphericity" and "Verticality" extra bytes
+        fields of 64 bit floats. They are NaN for points with fewer than 3 neighbours, duplicates and noise.
+
+        Args:
+            k: The amount of nearest points in the neighbourhood, including the point itself, at least 3.
+            radius: When set, the neighbourhood is the points within this radius in meters instead of the k nearest
+                points.
+        """
+        ...
+
+    def validate(self) -> None:
+        """Check the configuration without reading any points. All to_* methods do this before they start.
+
+        Raises:
+            InvalidParameterError: When a parameter has an invalid value, e.g. a crop outside the file bounds.
+            UnsupportedError: When a configured method is not implemented yet.
         """
         ...
 
@@ -63,16 +746,104 @@ class PointCloudBuilder:
         """
         ...
 
-    def to_dtm_using_csf(self, filename: str, rigidness: float, cloth_resolution: float, distance_threshold: float) -> None:
+    def to_tiles(
+        self,
+        directory: str,
+        tile_size: float,
+        origin: tuple[float, float] = (0.0, 0.0),
+        buffer: float = 0.0,
+        name: str = "{minx}_{miny}.laz",
+        max_open_files: int = 64,
+    ) -> list[str]:
+        """Split the points over a regular grid of .las/.laz files, for example to retile a delivery to a national
+        grid. Each tile is only created once all of its points have been written, and the overwrite policy decides
+        what happens to tiles that already exist.
+
+        Args:
+            directory: The directory to write the tiles to, which is created when it doesn't exist.
+            tile_size: The width and height of the tiles in meters.
+            origin: The point the grid of tiles is aligned to. A tile has its lower left corner at the origin plus a
+                whole number of tiles in x and y.
+            buffer: Points within this many meters outside a tile are also written to it, so the tiles overlap. Tiles
+                that only have such buffer points are not written.
+            name: The name of the tile files, where {minx}, {miny}, {maxx} and {maxy} are replaced by the bounds of a
+                tile. Must end in .las or .laz, .laz files are compressed.
+            max_open_files: The largest number of tile files that are open at the same time. When more tiles are being
+                written, the one written to longest ago is closed and its parts are joined at the end, which keeps the
+                memory use bounded for inputs that are not sorted.
+
+        Returns:
+            The paths of the tiles that were written, ordered by row and column.
+        """
+        ...
+
+    def to_dtm(self, filename: str, params: Optional[DtmParams] = None) -> None:
+        """Interpolates a DTM from the ground points and writes it to the given filename. When a ground
+        reclassification is configured the points it classifies as ground are used, otherwise the points classified
+        as ground in the file.
+
+        Args:
+            filename: The filename to write the DTM to. Must end in .asc for an ESRI ASCII grid, or in .tif or .tiff
+                for a GeoTIFF.
+            params: The resolution, interpolation and void filling of the DTM. When None, the defaults are used.
+
+        Raises:
+            EmptyCloudError: When none of the included points are ground.
+        """
+        ...
+
+    def to_dsm(self, filename: str, params: Optional[DsmParams] = None) -> None:
+        """Creates a DSM with the height of the highest point in each cell, in a single pass over the points.
+
+        Args:
+            filename: The filename to write the DSM to. Must end in .asc for an ESRI ASCII grid, or in .tif or .tiff
+                for a GeoTIFF.
+            params: The resolution and pit-free gap filling of the DSM. When None, the defaults are used.
+        """
+        ...
+
+    def to_chm(
+            self,
+            filename: str,
+            params: Optional[DsmParams] = None,
+            dtm_params: Optional[DtmParams] = None
+    ) -> None:
+        """Creates a CHM (Canopy Height Model) with the height of the surface above the ground: the DSM minus a DTM
+        interpolated from the ground points, as in to_dtm.
+
+        Args:
+            filename: The filename to write the CHM to. Must end in .asc for an ESRI ASCII grid, or in .tif or .tiff
+                for a GeoTIFF.
+            params: The parameters of the DSM, which also set the cells of the CHM. When None, the defaults are used.
+            dtm_params: The interpolation and void filling of the DTM. Its resolution is not used.
+
+        Raises:
+            EmptyCloudError: When none of the included points are ground.
+        """
+        ...
+
+    def to_raster(self, filename: str, resolution: float, aggregations: list[Aggregation]) -> None:
+        """Creates a raster with a band for each aggregation of the points in its cells, such as the point density or
+        the mean intensity, in a single pass over the points. When a ground reclassification is configured the
+        reclassified classes are aggregated.
+
+        Args:
+            filename: The filename to write the raster to. Must end in .tif or .tiff for a GeoTIFF, or in .asc for an
+                ESRI ASCII grid, which can only have a single aggregation. The name of each band is written as its
+                description in the GeoTIFF.
+            resolution: Cell size of the raster in meters.
+            aggregations: The aggregations to write, one band each.
+        """
+        ...
+
+    def to_dtm_using_csf(self, filename: str, params: Optional[CsfParams] = None) -> None:
         """Uses the CSF method to create a DTM from the point cloud. The DTM is written to the given filename.
 
         Args:
-            filename: The filename to write the DTM to. Must end in .asc
-            rigidness: The rigidness parameter for the CSF method. Must be between 0.0 and 1.0. 0.0 will classify all
-                points as ground, 1.0 will classify points in a strict manner.
-            cloth_resolution: The cloth resolution parameter for the CSF method in meters.
-            distance_threshold: The distance threshold parameter for the CSF method that determines if the simulation
-                should stop.
+            filename: The filename to write the DTM to. Must end in .asc for an ESRI ASCII grid, or in .tif or .tiff
+                for a GeoTIFF.
+            params: The parameters of the cloth simulation. The cell size of the DTM is the cloth resolution. When
+                None, the defaults of the "relief" preset are used.
         """
         ...
 
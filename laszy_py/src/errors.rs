@@ -0,0 +1,143 @@
+// `create_exception!` of PyO3 0.18 checks a `cfg(addr_of)` that newer compilers don't know
+#![allow(unexpected_cfgs)]
+
+use laszy_rs::LaszyError as _LaszyError;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(
+    laszy,
+    LaszyError,
+    PyException,
+    "Base class of all errors raised by laszy."
+);
+create_exception!(
+    laszy,
+    FileAccessError,
+    LaszyError,
+    "A file could not be read or written."
+);
+create_exception!(
+    laszy,
+    LasFormatError,
+    LaszyError,
+    "A LAS/LAZ file or a point in it is invalid."
+);
+create_exception!(
+    laszy,
+    InvalidParameterError,
+    LaszyError,
+    "A parameter has an invalid value."
+);
+create_exception!(
+    laszy,
+    UnsupportedError,
+    LaszyError,
+    "The requested method is not implemented yet."
+);
+create_exception!(
+    laszy,
+    EmptyCloudError,
+    LaszyError,
+    "No points were left to process."
+);
+create_exception!(
+    laszy,
+    InvalidPointError,
+    LaszyError,
+    "A point can't be used, for example because its coordinates are not finite."
+);
+create_exception!(
+    laszy,
+    TriangulationError,
+    LaszyError,
+    "Points can't be triangulated into a TIN."
+);
+create_exception!(
+    laszy,
+    InvalidFileExtensionError,
+    LaszyError,
+    "The output file has an unsupported extension."
+);
+create_exception!(
+    laszy,
+    OutputExistsError,
+    LaszyError,
+    "The output file already exists."
+);
+create_exception!(
+    laszy,
+    CancelledError,
+    LaszyError,
+    "The run was cancelled with a CancellationToken."
+);
+
+/// Convert an error of the Rust crate to the matching Python exception. The context of structured
+/// errors is set as attributes on the exception, i.e. `path`, `point_index` and `parameter`.
+pub fn to_py_err(py: Python, error: _LaszyError) -> PyErr {
+    let message = error.to_string();
+    let (err, attributes): (PyErr, Vec<(&str, PyObject)>) = match error {
+        _LaszyError::IoError { path, .. } => (
+            FileAccessError::new_err(message),
+            vec![("path", path.into_py(py))],
+        ),
+        _LaszyError::LasError {
+            path, point_index, ..
+        } => (
+            LasFormatError::new_err(message),
+            vec![
+                ("path", path.into_py(py)),
+                ("point_index", point_index.into_py(py)),
+            ],
+        ),
+        _LaszyError::InvalidParameter {
+            parameter, value, ..
+        } => (
+            InvalidParameterError::new_err(message),
+            vec![
+                ("parameter", parameter.into_py(py)),
+                ("value", value.into_py(py)),
+            ],
+        ),
+        _LaszyError::NotImplemented(_) => (UnsupportedError::new_err(message), vec![]),
+        _LaszyError::EmptyCloud(_) => (EmptyCloudError::new_err(message), vec![]),
+        _LaszyError::InvalidPoint(_) => (InvalidPointError::new_err(message), vec![]),
+        _LaszyError::Triangulation(_) => (TriangulationError::new_err(message), vec![]),
+        _LaszyError::InvalidFileExtension(_) => {
+            (InvalidFileExtensionError::new_err(message), vec![])
+        }
+        _LaszyError::OutputExists(path) => (
+            OutputExistsError::new_err(message),
+            vec![("path", path.into_py(py))],
+        ),
+        _LaszyError::Cancelled => (CancelledError::new_err(message), vec![]),
+    };
+    let value = err.value(py);
+    for (name, attribute) in attributes {
+        // Attributes are extra context, the exception is still useful when setting them fails
+        let _ = value.setattr(name, attribute);
+    }
+    err
+}
+
+pub fn add_exceptions(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("LaszyError", py.get_type::<LaszyError>())?;
+    m.add("FileAccessError", py.get_type::<FileAccessError>())?;
+    m.add("LasFormatError", py.get_type::<LasFormatError>())?;
+    m.add(
+        "InvalidParameterError",
+        py.get_type::<InvalidParameterError>(),
+    )?;
+    m.add("UnsupportedError", py.get_type::<UnsupportedError>())?;
+    m.add("EmptyCloudError", py.get_type::<EmptyCloudError>())?;
+    m.add("InvalidPointError", py.get_type::<InvalidPointError>())?;
+    m.add("TriangulationError", py.get_type::<TriangulationError>())?;
+    m.add(
+        "InvalidFileExtensionError",
+        py.get_type::<InvalidFileExtensionError>(),
+    )?;
+    m.add("OutputExistsError", py.get_type::<OutputExistsError>())?;
+    m.add("CancelledError", py.get_type::<CancelledError>())?;
+    Ok(())
+}
@@ -1,10 +1,36 @@
 extern crate laszy as laszy_rs;
 
+mod errors;
+
+use laszy_rs::Aggregation as _Aggregation;
+use laszy_rs::CancellationToken as _CancellationToken;
+use laszy_rs::CollisionHeight;
+use laszy_rs::ColourParams;
+use laszy_rs::CsfParams as _CsfParams;
+use laszy_rs::CsfTiling;
+use laszy_rs::DsmParams as _DsmParams;
+use laszy_rs::DtmParams as _DtmParams;
+use laszy_rs::GeometricFeature;
+use laszy_rs::Interpolation;
+use laszy_rs::LasOptions;
+use laszy_rs::Neighbourhood;
+use laszy_rs::ObjectParams as _ObjectParams;
+use laszy_rs::OverwritePolicy;
+use laszy_rs::PmfParams as _PmfParams;
 use laszy_rs::PointCloud as _PointCloud;
 use laszy_rs::PointCloudBuilder as _PointCloudBuilder;
+use laszy_rs::ProgressReporter;
+use laszy_rs::PtdParams as _PtdParams;
+use laszy_rs::SmrfParams as _SmrfParams;
+use laszy_rs::TileParams;
+use laszy_rs::{Attribute, Reducer};
+use laszy_rs::{DuplicateAction, DuplicateParams};
+use laszy_rs::{GroundSurface, HagParams};
+use laszy_rs::{NoiseAction, NoiseFilter, OutlierMethod};
+use laszy_rs::{RasterCompression, RasterOptions};
 use numpy::PyArray;
 use pyo3::prelude::*;
-use std::ops::DerefMut;
+use std::sync::Mutex;
 
 #[pyclass]
 struct PointCloud {
@@ -31,58 +57,1522 @@ impl PointCloud {
         Ok(PyArray::from_owned_array(py, xyz))
     }
 
-    #[getter]
-    pub fn ground_points<'py>(
-        &self,
-        py: Python<'py>,
-    ) -> PyResult<&'py PyArray<bool, ndarray::Ix1>> {
-        let mut ground_pts = ndarray::Array1::<bool>::default(self.cloud.points.len());
-        for (i, point) in self.cloud.points.iter().enumerate() {
-            ground_pts[[i]] = point.classification == las::point::Classification::Ground;
-        }
-        Ok(PyArray::from_owned_array(py, ground_pts))
+    #[getter]
+    pub fn ground_points<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<&'py PyArray<bool, ndarray::Ix1>> {
+        let mut ground_pts = ndarray::Array1::<bool>::default(self.cloud.points.len());
+        for (i, point) in self.cloud.points.iter().enumerate() {
+            ground_pts[[i]] = point.classification == las::point::Classification::Ground;
+        }
+        Ok(PyArray::from_owned_array(py, ground_pts))
+    }
+
+    #[getter]
+    pub fn colours<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<u16, ndarray::Ix2>> {
+        self.cloud.points.first()?.color?;
+        let mut rgb = ndarray::Array2::<u16>::zeros((self.cloud.points.len(), 3));
+        for (i, point) in self.cloud.points.iter().enumerate() {
+            if let Some(colour) = point.color {
+                rgb[[i, 0]] = colour.red;
+                rgb[[i, 1]] = colour.green;
+                rgb[[i, 2]] = colour.blue;
+            }
+        }
+        Some(PyArray::from_owned_array(py, rgb))
+    }
+
+    #[getter]
+    pub fn normals<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<f64, ndarray::Ix2>> {
+        let normals = self.cloud.normals()?;
+        let mut xyz = ndarray::Array2::<f64>::zeros((normals.len(), 3));
+        for (i, normal) in normals.iter().enumerate() {
+            for (axis, value) in normal.iter().enumerate() {
+                xyz[[i, axis]] = *value;
+            }
+        }
+        Some(PyArray::from_owned_array(py, xyz))
+    }
+
+    #[getter]
+    pub fn curvature<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        self.feature(py, GeometricFeature::Curvature)
+    }
+
+    #[getter]
+    pub fn planarity<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        self.feature(py, GeometricFeature::Planarity)
+    }
+
+    #[getter]
+    pub fn linearity<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        self.feature(py, GeometricFeature::Linearity)
+    }
+
+    #[getter]
+    pub fn sphericity<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        self.feature(py, GeometricFeature::Sphericity)
+    }
+
+    #[getter]
+    pub fn verticality<'py>(&self, py: Python<'py>) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        self.feature(py, GeometricFeature::Verticality)
+    }
+
+    #[getter]
+    pub fn extra_bytes_names(&self) -> Vec<String> {
+        self.cloud.extra_bytes_names()
+    }
+
+    pub fn extra_bytes<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+    ) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        let values = self.cloud.extra_bytes(name)?;
+        Some(PyArray::from_vec(py, values))
+    }
+}
+
+impl PointCloud {
+    /// A geometric feature of the points as a NumPy array, or None when it isn't computed.
+    fn feature<'py>(
+        &self,
+        py: Python<'py>,
+        feature: GeometricFeature,
+    ) -> Option<&'py PyArray<f64, ndarray::Ix1>> {
+        let values = self.cloud.feature(feature)?;
+        Some(PyArray::from_vec(py, values))
+    }
+}
+
+/// Token to cancel a running builder from another thread, or from a progress callback.
+#[pyclass]
+#[derive(Clone)]
+struct CancellationToken {
+    token: _CancellationToken,
+}
+
+#[pymethods]
+impl CancellationToken {
+    #[new]
+    pub fn new() -> Self {
+        CancellationToken {
+            token: _CancellationToken::new(),
+        }
+    }
+
+    /// Request cancellation. The running `to_*` method of the builder stops and raises an
+    /// exception.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Reset the token, so it can be used for a new run after a cancelled one.
+    pub fn reset(&self) {
+        self.token.reset();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+/// Parameters of the cloth simulation filter (CSF). Create them with the constructor, which uses
+/// the defaults of the "relief" preset, or start from one of the presets `flat`, `relief` and
+/// `steep_slope` and change single attributes.
+#[pyclass]
+#[derive(Clone)]
+struct CsfParams {
+    /// Float between 0.0 and 1.0, the rigidity of the cloth. 0.0 will most likely classify all
+    /// points as ground, while 1.0 is the strictest possible classification.
+    #[pyo3(get, set)]
+    rigidness: f64,
+    /// Float, distance in meters between the cloth particles.
+    #[pyo3(get, set)]
+    cloth_resolution: f64,
+    /// Float, the simulation stops when no particle moved more than this many meters.
+    #[pyo3(get, set)]
+    simulation_threshold: f64,
+    /// Float, maximum distance in meters between a point and the cloth for the point to be
+    /// classified as ground.
+    #[pyo3(get, set)]
+    classification_threshold: f64,
+    /// Str, how the height the cloth collides with is taken from the points in a cloth cell:
+    /// "nearest" (the point closest to the particle), "lowest", "percentile" or
+    /// "median_of_lowest".
+    #[pyo3(get, set)]
+    collision_height: String,
+    /// Float between 0.0 and 1.0, the percentile used by the "percentile" collision height.
+    #[pyo3(get, set)]
+    collision_percentile: f64,
+    /// Int, amount of lowest points per cell the "median_of_lowest" collision height takes the
+    /// median of.
+    #[pyo3(get, set)]
+    collision_lowest_k: usize,
+    /// Float, time step of one iteration. Smaller steps are slower but more precise.
+    #[pyo3(get, set)]
+    time_step: f64,
+    /// Float, acceleration of the cloth towards the points.
+    #[pyo3(get, set)]
+    gravity: f64,
+    /// Int, the simulation stops after this many iterations, even when the simulation threshold
+    /// was not reached.
+    #[pyo3(get, set)]
+    max_iterations: usize,
+    /// Bool, snap parts of the cloth that hang just above steep slopes onto the points after the
+    /// simulation.
+    #[pyo3(get, set)]
+    slope_smoothing: bool,
+    /// Float, maximum distance in meters between the cloth and the points for slope smoothing to
+    /// snap the cloth onto them.
+    #[pyo3(get, set)]
+    slope_smoothing_threshold: f64,
+    /// Optional float, simulate the cloth in tiles of this size in meters to limit memory usage
+    /// for large extents. None simulates the whole extent at once.
+    #[pyo3(get, set)]
+    tile_size: Option<f64>,
+    /// Float, distance in meters the simulation of a tile extends beyond the tile. Should be
+    /// larger than the largest object that is not ground, such as a building.
+    #[pyo3(get, set)]
+    tile_buffer: f64,
+}
+
+impl From<_CsfParams> for CsfParams {
+    fn from(params: _CsfParams) -> Self {
+        let (collision_height, collision_percentile, collision_lowest_k) =
+            match params.collision_height {
+                CollisionHeight::Nearest => ("nearest", 0.05, 5),
+                CollisionHeight::Lowest => ("lowest", 0.05, 5),
+                CollisionHeight::Percentile { percent } => ("percentile", percent, 5),
+                CollisionHeight::MedianOfLowest { k } => ("median_of_lowest", 0.05, k),
+            };
+        CsfParams {
+            rigidness: params.rigidness,
+            cloth_resolution: params.cloth_resolution,
+            simulation_threshold: params.simulation_threshold,
+            classification_threshold: params.classification_threshold,
+            collision_height: collision_height.to_string(),
+            collision_percentile,
+            collision_lowest_k,
+            time_step: params.physics.time_step,
+            gravity: params.physics.gravity,
+            max_iterations: params.physics.max_iterations,
+            slope_smoothing: params.physics.slope_smoothing,
+            slope_smoothing_threshold: params.physics.slope_smoothing_threshold,
+            tile_size: params.tiling.map(|tiling| tiling.tile_size),
+            tile_buffer: params.tiling.map_or(50.0, |tiling| tiling.buffer),
+        }
+    }
+}
+
+impl TryFrom<&CsfParams> for _CsfParams {
+    type Error = PyErr;
+
+    fn try_from(params: &CsfParams) -> PyResult<Self> {
+        let collision_height = match params.collision_height.as_str() {
+            "nearest" => CollisionHeight::Nearest,
+            "lowest" => CollisionHeight::Lowest,
+            "percentile" => CollisionHeight::Percentile {
+                percent: params.collision_percentile,
+            },
+            "median_of_lowest" => CollisionHeight::MedianOfLowest {
+                k: params.collision_lowest_k,
+            },
+            other => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown collision height '{other}', use 'nearest', 'lowest', 'percentile' \
+                     or 'median_of_lowest'"
+                )))
+            }
+        };
+        Ok(_CsfParams {
+            rigidness: params.rigidness,
+            cloth_resolution: params.cloth_resolution,
+            simulation_threshold: params.simulation_threshold,
+            classification_threshold: params.classification_threshold,
+            collision_height,
+            physics: laszy_rs::ClothPhysics {
+                time_step: params.time_step,
+                gravity: params.gravity,
+                max_iterations: params.max_iterations,
+                slope_smoothing: params.slope_smoothing,
+                slope_smoothing_threshold: params.slope_smoothing_threshold,
+            },
+            tiling: params
+                .tile_size
+                .map(|tile_size| CsfTiling::new(tile_size, params.tile_buffer)),
+        })
+    }
+}
+
+/// Turn optional Python parameters into CSF parameters, using the defaults when they are None.
+fn csf_params_or_default(params: Option<CsfParams>) -> PyResult<_CsfParams> {
+    match params {
+        Some(params) => _CsfParams::try_from(&params),
+        None => Ok(_CsfParams::default()),
+    }
+}
+
+/// Turn the Python name of a noise action into a noise filter with `method`.
+fn noise_filter(method: OutlierMethod, action: &str) -> PyResult<NoiseFilter> {
+    let action = match action {
+        "classify" => NoiseAction::Classify,
+        "remove" => NoiseAction::Remove,
+        _ => {
+            return Err(errors::InvalidParameterError::new_err(format!(
+                "Unknown noise action '{action}', use 'classify' or 'remove'"
+            )))
+        }
+    };
+    Ok(NoiseFilter::new(method).with_action(action))
+}
+
+#[pymethods]
+impl CsfParams {
+    #[new]
+    #[pyo3(signature = (
+        rigidness = 0.5,
+        cloth_resolution = 5.0,
+        simulation_threshold = 0.1,
+        classification_threshold = 0.5,
+        collision_height = "nearest".to_string(),
+        collision_percentile = 0.05,
+        collision_lowest_k = 5,
+        time_step = 0.65,
+        gravity = 0.2,
+        max_iterations = 500,
+        slope_smoothing = false,
+        slope_smoothing_threshold = 0.3,
+        tile_size = None,
+        tile_buffer = 50.0
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        rigidness: f64,
+        cloth_resolution: f64,
+        simulation_threshold: f64,
+        classification_threshold: f64,
+        collision_height: String,
+        collision_percentile: f64,
+        collision_lowest_k: usize,
+        time_step: f64,
+        gravity: f64,
+        max_iterations: usize,
+        slope_smoothing: bool,
+        slope_smoothing_threshold: f64,
+        tile_size: Option<f64>,
+        tile_buffer: f64,
+    ) -> Self {
+        CsfParams {
+            rigidness,
+            cloth_resolution,
+            simulation_threshold,
+            classification_threshold,
+            collision_height,
+            collision_percentile,
+            collision_lowest_k,
+            time_step,
+            gravity,
+            max_iterations,
+            slope_smoothing,
+            slope_smoothing_threshold,
+            tile_size,
+            tile_buffer,
+        }
+    }
+
+    /// Preset for flat terrain, such as urban areas: a rigid cloth without slope smoothing.
+    #[staticmethod]
+    pub fn flat() -> Self {
+        _CsfParams::flat().into()
+    }
+
+    /// Preset for terrain with gentle relief. This is the default.
+    #[staticmethod]
+    pub fn relief() -> Self {
+        _CsfParams::relief().into()
+    }
+
+    /// Preset for steep slopes, such as mountains: a soft cloth with slope smoothing.
+    #[staticmethod]
+    pub fn steep_slope() -> Self {
+        _CsfParams::steep_slope().into()
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _CsfParams::try_from(self)?
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CsfParams(rigidness={:?}, cloth_resolution={:?}, simulation_threshold={:?}, \
+             classification_threshold={:?}, collision_height='{}', collision_percentile={:?}, \
+             collision_lowest_k={}, time_step={:?}, gravity={:?}, max_iterations={}, \
+             slope_smoothing={}, slope_smoothing_threshold={:?}, tile_size={}, tile_buffer={:?})",
+            self.rigidness,
+            self.cloth_resolution,
+            self.simulation_threshold,
+            self.classification_threshold,
+            self.collision_height,
+            self.collision_percentile,
+            self.collision_lowest_k,
+            self.time_step,
+            self.gravity,
+            self.max_iterations,
+            if self.slope_smoothing {
+                "True"
+            } else {
+                "False"
+            },
+            self.slope_smoothing_threshold,
+            match self.tile_size {
+                Some(tile_size) => format!("{tile_size:?}"),
+                None => "None".to_string(),
+            },
+            self.tile_buffer,
+        )
+    }
+}
+
+/// Parameters of a DTM interpolated from ground points.
+#[pyclass]
+#[derive(Clone)]
+struct DtmParams {
+    /// Float, cell size of the DTM in meters.
+    #[pyo3(get, set)]
+    resolution: f64,
+    /// Str, how the height of a cell is interpolated from the ground points: "tin" (linear in the
+    /// Delaunay triangulation), "natural_neighbour" or "idw" (inverse distance weighting).
+    #[pyo3(get, set)]
+    interpolation: String,
+    /// Float, the power of the distance in the weights of the "idw" interpolation.
+    #[pyo3(get, set)]
+    idw_power: f64,
+    /// Int, amount of closest ground points the "idw" interpolation uses.
+    #[pyo3(get, set)]
+    idw_neighbours: usize,
+    /// Optional float, cells further than this many meters from the closest ground point get no
+    /// data, or are filled as a void.
+    #[pyo3(get, set)]
+    max_distance: Option<f64>,
+    /// Bool, fill areas without data that are enclosed by cells with data, such as under
+    /// buildings, from the cells around them.
+    #[pyo3(get, set)]
+    fill_voids: bool,
+}
+
+impl TryFrom<&DtmParams> for _DtmParams {
+    type Error = PyErr;
+
+    fn try_from(params: &DtmParams) -> PyResult<Self> {
+        let interpolation = match params.interpolation.as_str() {
+            "tin" => Interpolation::Tin,
+            "natural_neighbour" => Interpolation::NaturalNeighbour,
+            "idw" => Interpolation::Idw {
+                power: params.idw_power,
+                neighbours: params.idw_neighbours,
+            },
+            other => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown interpolation '{other}', use 'tin', 'natural_neighbour' or 'idw'"
+                )))
+            }
+        };
+        Ok(_DtmParams {
+            resolution: params.resolution,
+            interpolation,
+            max_distance: params.max_distance,
+            fill_voids: params.fill_voids,
+        })
+    }
+}
+
+/// Turn optional Python parameters into DTM parameters, using the defaults when they are None.
+fn dtm_params_or_default(params: Option<DtmParams>) -> PyResult<_DtmParams> {
+    match params {
+        Some(params) => _DtmParams::try_from(&params),
+        None => Ok(_DtmParams::default()),
+    }
+}
+
+#[pymethods]
+impl DtmParams {
+    #[new]
+    #[pyo3(signature = (
+        resolution = 1.0,
+        interpolation = "tin".to_string(),
+        idw_power = 2.0,
+        idw_neighbours = 12,
+        max_distance = None,
+        fill_voids = true
+    ))]
+    pub fn new(
+        resolution: f64,
+        interpolation: String,
+        idw_power: f64,
+        idw_neighbours: usize,
+        max_distance: Option<f64>,
+        fill_voids: bool,
+    ) -> Self {
+        DtmParams {
+            resolution,
+            interpolation,
+            idw_power,
+            idw_neighbours,
+            max_distance,
+            fill_voids,
+        }
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _DtmParams::try_from(self)?
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DtmParams(resolution={:?}, interpolation='{}', idw_power={:?}, idw_neighbours={}, \
+             max_distance={}, fill_voids={})",
+            self.resolution,
+            self.interpolation,
+            self.idw_power,
+            self.idw_neighbours,
+            match self.max_distance {
+                Some(max_distance) => format!("{max_distance:?}"),
+                None => "None".to_string(),
+            },
+            if self.fill_voids { "True" } else { "False" },
+        )
+    }
+}
+
+/// Parameters of the Simple Morphological Filter (SMRF) ground reclassification.
+#[pyclass]
+#[derive(Clone)]
+struct SmrfParams {
+    /// Float, cell size in meters of the grid of the lowest points.
+    #[pyo3(get, set)]
+    cell_size: f64,
+    /// Float, slope as rise over run that terrain can have, larger slopes are objects.
+    #[pyo3(get, set)]
+    slope: f64,
+    /// Float, radius in meters of the largest window, which should be larger than the largest
+    /// object, such as a building.
+    #[pyo3(get, set)]
+    window: f64,
+    /// Float, distance in meters a point may be from the ground surface on flat terrain to be
+    /// ground.
+    #[pyo3(get, set)]
+    elevation_threshold: f64,
+    /// Float, factor of the slope of the ground surface added to the elevation threshold.
+    #[pyo3(get, set)]
+    elevation_scaler: f64,
+}
+
+impl From<&SmrfParams> for _SmrfParams {
+    fn from(params: &SmrfParams) -> Self {
+        _SmrfParams {
+            cell_size: params.cell_size,
+            slope: params.slope,
+            window: params.window,
+            elevation_threshold: params.elevation_threshold,
+            elevation_scaler: params.elevation_scaler,
+        }
+    }
+}
+
+#[pymethods]
+impl SmrfParams {
+    #[new]
+    #[pyo3(signature = (
+        cell_size = 1.0,
+        slope = 0.15,
+        window = 18.0,
+        elevation_threshold = 0.5,
+        elevation_scaler = 1.25,
+    ))]
+    pub fn new(
+        cell_size: f64,
+        slope: f64,
+        window: f64,
+        elevation_threshold: f64,
+        elevation_scaler: f64,
+    ) -> Self {
+        SmrfParams {
+            cell_size,
+            slope,
+            window,
+            elevation_threshold,
+            elevation_scaler,
+        }
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _SmrfParams::from(self)
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SmrfParams(cell_size={:?}, slope={:?}, window={:?}, elevation_threshold={:?}, \
+             elevation_scaler={:?})",
+            self.cell_size,
+            self.slope,
+            self.window,
+            self.elevation_threshold,
+            self.elevation_scaler,
+        )
+    }
+}
+
+/// Parameters of the Progressive Morphological Filter (PMF) ground reclassification.
+#[pyclass]
+#[derive(Clone)]
+struct PmfParams {
+    /// Float, cell size in meters of the grid of the lowest points.
+    #[pyo3(get, set)]
+    cell_size: f64,
+    /// Float, size in meters of the largest window, which should be larger than the largest
+    /// object, such as a building.
+    #[pyo3(get, set)]
+    max_window: f64,
+    /// Float, slope as rise over run of the terrain, used to grow the elevation difference
+    /// threshold.
+    #[pyo3(get, set)]
+    slope: f64,
+    /// Float, elevation difference threshold in meters of the first window.
+    #[pyo3(get, set)]
+    initial_distance: f64,
+    /// Float, largest elevation difference threshold in meters.
+    #[pyo3(get, set)]
+    max_distance: f64,
+    /// Bool, grow the windows exponentially instead of linearly.
+    #[pyo3(get, set)]
+    exponential: bool,
+}
+
+impl From<&PmfParams> for _PmfParams {
+    fn from(params: &PmfParams) -> Self {
+        _PmfParams {
+            cell_size: params.cell_size,
+            max_window: params.max_window,
+            slope: params.slope,
+            initial_distance: params.initial_distance,
+            max_distance: params.max_distance,
+            exponential: params.exponential,
+        }
+    }
+}
+
+#[pymethods]
+impl PmfParams {
+    #[new]
+    #[pyo3(signature = (
+        cell_size = 1.0,
+        max_window = 33.0,
+        slope = 1.0,
+        initial_distance = 0.15,
+        max_distance = 2.5,
+        exponential = true,
+    ))]
+    pub fn new(
+        cell_size: f64,
+        max_window: f64,
+        slope: f64,
+        initial_distance: f64,
+        max_distance: f64,
+        exponential: bool,
+    ) -> Self {
+        PmfParams {
+            cell_size,
+            max_window,
+            slope,
+            initial_distance,
+            max_distance,
+            exponential,
+        }
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _PmfParams::from(self)
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PmfParams(cell_size={:?}, max_window={:?}, slope={:?}, initial_distance={:?}, \
+             max_distance={:?}, exponential={})",
+            self.cell_size,
+            self.max_window,
+            self.slope,
+            self.initial_distance,
+            self.max_distance,
+            if self.exponential { "True" } else { "False" },
+        )
+    }
+}
+
+/// Parameters of the progressive TIN densification (PTD) ground reclassification.
+#[pyclass]
+#[derive(Clone)]
+struct PtdParams {
+    /// Float, size in meters of the largest building, the cell size of the grid of seed points.
+    #[pyo3(get, set)]
+    max_building_size: f64,
+    /// Float, steepest slope of the terrain in degrees.
+    #[pyo3(get, set)]
+    terrain_angle: f64,
+    /// Float, largest angle in degrees between a triangle and the lines from a point to its
+    /// corners for the point to be added.
+    #[pyo3(get, set)]
+    iteration_angle: f64,
+    /// Float, largest distance in meters from a point to the plane of a triangle for the point to
+    /// be added.
+    #[pyo3(get, set)]
+    iteration_distance: f64,
+}
+
+impl From<&PtdParams> for _PtdParams {
+    fn from(params: &PtdParams) -> Self {
+        _PtdParams {
+            max_building_size: params.max_building_size,
+            terrain_angle: params.terrain_angle,
+            iteration_angle: params.iteration_angle,
+            iteration_distance: params.iteration_distance,
+        }
+    }
+}
+
+#[pymethods]
+impl PtdParams {
+    #[new]
+    #[pyo3(signature = (
+        max_building_size = 60.0,
+        terrain_angle = 88.0,
+        iteration_angle = 6.0,
+        iteration_distance = 1.4,
+    ))]
+    pub fn new(
+        max_building_size: f64,
+        terrain_angle: f64,
+        iteration_angle: f64,
+        iteration_distance: f64,
+    ) -> Self {
+        PtdParams {
+            max_building_size,
+            terrain_angle,
+            iteration_angle,
+            iteration_distance,
+        }
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _PtdParams::from(self)
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PtdParams(max_building_size={:?}, terrain_angle={:?}, iteration_angle={:?}, \
+             iteration_distance={:?})",
+            self.max_building_size,
+            self.terrain_angle,
+            self.iteration_angle,
+            self.iteration_distance,
+        )
+    }
+}
+
+/// Turn the name of a ground surface and the path of the DTM of the "dtm" surface into a
+/// ground surface.
+fn ground_surface(surface: &str, dtm_path: Option<String>) -> PyResult<GroundSurface> {
+    match (surface, dtm_path) {
+        ("cloth", None) => Ok(GroundSurface::Cloth),
+        ("tin", None) => Ok(GroundSurface::Tin),
+        ("dtm", Some(path)) => Ok(GroundSurface::Dtm { path }),
+        ("dtm", None) => Err(errors::InvalidParameterError::new_err(
+            "The 'dtm' surface needs a dtm_path",
+        )),
+        ("cloth" | "tin", Some(_)) => Err(errors::InvalidParameterError::new_err(
+            "dtm_path is only used by the 'dtm' surface",
+        )),
+        (other, _) => Err(errors::InvalidParameterError::new_err(format!(
+            "Unknown ground surface '{other}', use 'cloth', 'tin' or 'dtm'"
+        ))),
+    }
+}
+
+/// Parameters of the classification of the points that are not ground into vegetation and
+/// buildings.
+#[pyclass]
+#[derive(Clone)]
+struct ObjectParams {
+    /// Str, the ground surface the heights above ground are computed from: "cloth" (the cloth of
+    /// the CSF ground reclassification), "tin" (a TIN of the ground points) or "dtm" (the raster at
+    /// `dtm_path`).
+    #[pyo3(get, set)]
+    surface: String,
+    /// Optional str, the .asc, .tif or .tiff DTM of the "dtm" surface.
+    #[pyo3(get, set)]
+    dtm_path: Option<String>,
+    /// Float, points lower than this above the ground keep their class.
+    #[pyo3(get, set)]
+    min_height: f64,
+    /// Float, points from this height above the ground are medium vegetation, lower points are
+    /// low vegetation.
+    #[pyo3(get, set)]
+    medium_vegetation_height: f64,
+    /// Float, points from this height above the ground are high vegetation.
+    #[pyo3(get, set)]
+    high_vegetation_height: f64,
+    /// Float, points lower than this above the ground are never buildings.
+    #[pyo3(get, set)]
+    building_min_height: f64,
+    /// Int, amount of nearest neighbours the plane through a point is fitted to.
+    #[pyo3(get, set)]
+    k: usize,
+    /// Float, smallest planarity of the point and its neighbours of a building point, from 0.0 to
+    /// 1.0.
+    #[pyo3(get, set)]
+    min_planarity: f64,
+    /// Float, largest root mean square distance in meters of the point and its neighbours to
+    /// their plane of a building point.
+    #[pyo3(get, set)]
+    max_roughness: f64,
+}
+
+impl TryFrom<&ObjectParams> for _ObjectParams {
+    type Error = PyErr;
+
+    fn try_from(params: &ObjectParams) -> PyResult<Self> {
+        Ok(_ObjectParams {
+            surface: ground_surface(&params.surface, params.dtm_path.clone())?,
+            min_height: params.min_height,
+            medium_vegetation_height: params.medium_vegetation_height,
+            high_vegetation_height: params.high_vegetation_height,
+            building_min_height: params.building_min_height,
+            k: params.k,
+            min_planarity: params.min_planarity,
+            max_roughness: params.max_roughness,
+        })
+    }
+}
+
+#[pymethods]
+impl ObjectParams {
+    #[new]
+    #[pyo3(signature = (
+        surface = "tin".to_string(),
+        dtm_path = None,
+        min_height = 0.5,
+        medium_vegetation_height = 2.0,
+        high_vegetation_height = 5.0,
+        building_min_height = 2.5,
+        k = 50,
+        min_planarity = 0.4,
+        max_roughness = 0.15,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        surface: String,
+        dtm_path: Option<String>,
+        min_height: f64,
+        medium_vegetation_height: f64,
+        high_vegetation_height: f64,
+        building_min_height: f64,
+        k: usize,
+        min_planarity: f64,
+        max_roughness: f64,
+    ) -> Self {
+        ObjectParams {
+            surface,
+            dtm_path,
+            min_height,
+            medium_vegetation_height,
+            high_vegetation_height,
+            building_min_height,
+            k,
+            min_planarity,
+            max_roughness,
+        }
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _ObjectParams::try_from(self)?
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ObjectParams(surface='{}', dtm_path={}, min_height={:?}, \
+             medium_vegetation_height={:?}, high_vegetation_height={:?}, \
+             building_min_height={:?}, k={}, min_planarity={:?}, max_roughness={:?})",
+            self.surface,
+            match &self.dtm_path {
+                Some(path) => format!("'{path}'"),
+                None => "None".to_string(),
+            },
+            self.min_height,
+            self.medium_vegetation_height,
+            self.high_vegetation_height,
+            self.building_min_height,
+            self.k,
+            self.min_planarity,
+            self.max_roughness,
+        )
+    }
+}
+
+/// Parameters of a DSM (Digital Surface Model), the height of the highest point in each cell.
+#[pyclass]
+#[derive(Clone)]
+struct DsmParams {
+    /// Float, cell size of the DSM in meters.
+    #[pyo3(get, set)]
+    resolution: f64,
+    /// Bool, fill cells without points that are enclosed by cells with points, and raise pits:
+    /// cells that are more than `pit_threshold` below the median of the cells around them.
+    #[pyo3(get, set)]
+    pit_free: bool,
+    /// Float, distance in meters below the median of the cells around it from which a cell is a
+    /// pit.
+    #[pyo3(get, set)]
+    pit_threshold: f64,
+}
+
+impl From<&DsmParams> for _DsmParams {
+    fn from(params: &DsmParams) -> Self {
+        _DsmParams {
+            resolution: params.resolution,
+            pit_free: params.pit_free,
+            pit_threshold: params.pit_threshold,
+        }
+    }
+}
+
+#[pymethods]
+impl DsmParams {
+    #[new]
+    #[pyo3(signature = (resolution = 1.0, pit_free = false, pit_threshold = 1.0))]
+    pub fn new(resolution: f64, pit_free: bool, pit_threshold: f64) -> Self {
+        DsmParams {
+            resolution,
+            pit_free,
+            pit_threshold,
+        }
+    }
+
+    /// Checks the parameters, raising InvalidParameterError when one is out of range.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _DsmParams::from(self)
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DsmParams(resolution={:?}, pit_free={}, pit_threshold={:?})",
+            self.resolution,
+            if self.pit_free { "True" } else { "False" },
+            self.pit_threshold,
+        )
+    }
+}
+
+/// An attribute of the points and how it is reduced per cell, written as a band of a raster.
+#[pyclass]
+#[derive(Clone)]
+struct Aggregation {
+    /// Str, the attribute of the points: "z", "intensity", "return_number", "number_of_returns",
+    /// "classification", "scan_angle", "user_data", "point_source_id", "gps_time", "red",
+    /// "green", "blue" or "nir".
+    #[pyo3(get, set)]
+    attribute: String,
+    /// Str, how the values in a cell are reduced: "count", "min", "max", "mean", "stddev",
+    /// "percentile" or "mode".
+    #[pyo3(get, set)]
+    reducer: String,
+    /// Float, the percentile between 0.0 and 1.0 of the "percentile" reducer.
+    #[pyo3(get, set)]
+    percent: f64,
+}
+
+impl TryFrom<&Aggregation> for _Aggregation {
+    type Error = PyErr;
+
+    fn try_from(aggregation: &Aggregation) -> PyResult<Self> {
+        let attribute = match aggregation.attribute.as_str() {
+            "z" => Attribute::Z,
+            "intensity" => Attribute::Intensity,
+            "return_number" => Attribute::ReturnNumber,
+            "number_of_returns" => Attribute::NumberOfReturns,
+            "classification" => Attribute::Classification,
+            "scan_angle" => Attribute::ScanAngle,
+            "user_data" => Attribute::UserData,
+            "point_source_id" => Attribute::PointSourceId,
+            "gps_time" => Attribute::GpsTime,
+            "red" => Attribute::Red,
+            "green" => Attribute::Green,
+            "blue" => Attribute::Blue,
+            "nir" => Attribute::Nir,
+            other => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown attribute '{other}'"
+                )))
+            }
+        };
+        let reducer = match aggregation.reducer.as_str() {
+            "count" => Reducer::Count,
+            "min" => Reducer::Min,
+            "max" => Reducer::Max,
+            "mean" => Reducer::Mean,
+            "stddev" => Reducer::StdDev,
+            "percentile" => Reducer::Percentile {
+                percent: aggregation.percent,
+            },
+            "mode" => Reducer::Mode,
+            other => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown reducer '{other}', use 'count', 'min', 'max', 'mean', 'stddev', \
+                     'percentile' or 'mode'"
+                )))
+            }
+        };
+        Ok(_Aggregation::new(attribute, reducer))
+    }
+}
+
+#[pymethods]
+impl Aggregation {
+    #[new]
+    #[pyo3(signature = (attribute, reducer, percent = 0.5))]
+    pub fn new(attribute: String, reducer: String, percent: f64) -> Self {
+        Aggregation {
+            attribute,
+            reducer,
+            percent,
+        }
+    }
+
+    /// The amount of points per cell.
+    #[staticmethod]
+    pub fn count() -> Self {
+        Aggregation::new("z".to_string(), "count".to_string(), 0.5)
+    }
+
+    /// The name of the band of the aggregation, such as "intensity_mean".
+    #[getter]
+    pub fn name(&self) -> PyResult<String> {
+        Ok(_Aggregation::try_from(self)?.name())
+    }
+
+    /// Checks the aggregation, raising InvalidParameterError when it is invalid.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        _Aggregation::try_from(self)?
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Aggregation(attribute='{}', reducer='{}', percent={:?})",
+            self.attribute, self.reducer, self.percent,
+        )
+    }
+}
+
+/// Forwards the progress of a builder to a Python callable, which is called as
+/// `callback(task, position, total)` whenever the builder reports progress.
+struct PyProgressReporter {
+    callback: PyObject,
+    state: Mutex<(String, u64, Option<u64>)>,
+}
+
+impl PyProgressReporter {
+    fn new(callback: PyObject) -> Self {
+        PyProgressReporter {
+            callback,
+            state: Mutex::new((String::new(), 0, None)),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut (String, u64, Option<u64>))) {
+        let (task, position, total) = match self.state.lock() {
+            Ok(mut state) => {
+                f(&mut state);
+                state.clone()
+            }
+            Err(_) => return,
+        };
+        Python::with_gil(|py| {
+            if let Err(e) = self.callback.call1(py, (task, position, total)) {
+                e.print(py);
+            }
+        });
+    }
+}
+
+impl ProgressReporter for PyProgressReporter {
+    fn start(&self, task: &str, total: Option<u64>) {
+        self.update(|state| *state = (task.to_string(), 0, total));
+    }
+
+    fn advance(&self, delta: u64) {
+        self.update(|state| state.1 += delta);
+    }
+
+    fn finish(&self, _message: &str) {
+        self.update(|state| {
+            if let Some(total) = state.2 {
+                state.1 = total;
+            }
+        });
+    }
+}
+
+#[pyclass]
+struct PointCloudBuilder {
+    builder: _PointCloudBuilder,
+}
+
+// The `to_*` methods take `&mut self` because Python holds the builder and reuses it between
+// outputs, as the builder of the Rust crate does
+#[allow(clippy::wrong_self_convention)]
+#[pymethods]
+impl PointCloudBuilder {
+    /// Default constructor for PointCloudBuilder, based on LAS or LAZ file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath` - Path to LAS or LAZ file. If file doesn't exist, an error will be raised.
+    ///
+    /// returns: Result<PointCloudBuilder, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// ```
+    #[staticmethod]
+    pub fn from_file(py: Python, filepath: String) -> PyResult<Self> {
+        let builder = _PointCloudBuilder::from_file(&filepath);
+        let builder = match builder {
+            Ok(builder) => builder,
+            Err(e) => return Err(errors::to_py_err(py, e)),
+        };
+        Ok(PointCloudBuilder { builder })
+    }
+
+    /// Constructor for PointCloudBuilder based on several LAS or LAZ files with the same point
+    /// format, such as the tiles of a delivery, which are processed as a single file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepaths` - List of paths to LAS or LAZ files, at least one.
+    ///
+    /// returns: Result<PointCloudBuilder, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_files(["tile_1.laz", "tile_2.laz"])
+    /// ```
+    #[staticmethod]
+    pub fn from_files(py: Python, filepaths: Vec<String>) -> PyResult<Self> {
+        let filepaths: Vec<&str> = filepaths.iter().map(String::as_str).collect();
+        let builder = _PointCloudBuilder::from_files(&filepaths);
+        let builder = match builder {
+            Ok(builder) => builder,
+            Err(e) => return Err(errors::to_py_err(py, e)),
+        };
+        Ok(PointCloudBuilder { builder })
+    }
+
+    /// Configures the builder to use cropping based on a lower left and upper right corner.
+    ///
+    /// NOTE: This will not actually crop the file, it just configures the builder to do so when
+    /// you run a builder.to_*() method.
+    ///
+    /// # Arguments
+    ///
+    /// * `lower_left`: Tuple of (x, y) coordinates for lower left corner of bounding box.
+    /// * `upper_right`: Tuple of (x, y) coordinates for upper right corner of bounding box.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_cropping((12.0, 5.0), (14.0, 9.0))
+    /// # If you don't run `to_cloud` or another `to_*` method, nothing will happen.
+    /// cloud = builder.to_cloud()
+    /// ```
+    pub fn with_crop(
+        mut slf: PyRefMut<Self>,
+        lower_left: (f64, f64),
+        upper_right: (f64, f64),
+    ) -> PyResult<PyRefMut<Self>> {
+        slf.builder
+            .with_crop(laszy_rs::CroppingMethod::BoundingBox {
+                lower_left,
+                upper_right,
+            });
+        Ok(slf)
+    }
+
+    /// Configures the builder to discard a percentage of points randomly.
+    ///
+    /// NOTE: This will not actually thin the file, it just configures the builder to do so when
+    /// you run a builder.to_*() method.
+    ///
+    /// NOTE: This is not a deterministic process. If you run it twice, you will get a different
+    /// set and amount of points.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep_percentage`: Float between 0.0 and 1.0, representing the percentage of points to keep.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_thinning_random(0.2)
+    /// # If you don't run `to_cloud` or another `to_*` method, nothing will happen.
+    /// cloud = builder.to_cloud()
+    /// ```
+    pub fn with_thinning_random(
+        mut slf: PyRefMut<Self>,
+        keep_percentage: f64,
+    ) -> PyResult<PyRefMut<Self>> {
+        let method = laszy_rs::ThinningMethod::Random {
+            percent: keep_percentage,
+        };
+        slf.builder.with_thinning(method);
+        Ok(slf)
+    }
+
+    /// Configures the builder to only keep every nth point.
+    ///
+    /// NOTE: This will not actually thin the file, it just configures the builder to do so when
+    /// you run a builder.to_*() method.
+    ///
+    /// # Arguments
+    ///
+    /// * `nth`: Int representing the number of points to skip between each point kept. Will always
+    /// keep the first point.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_thinning_every_nth(10)
+    /// # If you don't run `to_cloud` or another `to_*` method, nothing will happen.
+    /// cloud = builder.to_cloud()
+    /// ```
+    pub fn with_thinning_every_nth(
+        mut slf: PyRefMut<Self>,
+        nth: usize,
+    ) -> PyResult<PyRefMut<Self>> {
+        let method = laszy_rs::ThinningMethod::EveryNth { nth };
+        slf.builder.with_thinning(method);
+        Ok(slf)
+    }
+
+    /// Configures the builder to reclassify points to ground or their original classification based
+    /// on the cloth simulation filter (CSF) algorithm.
+    ///
+    /// NOTE: This will not actually reclassify the file, it just configures the builder to do so
+    /// when you run a builder.to_*() method.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: CsfParams with the parameters of the cloth simulation. When None, the defaults
+    ///   of the "relief" preset are used.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// params = CsfParams.steep_slope()
+    /// params.cloth_resolution = 2.0
+    /// builder.with_csf_ground_reclassification(params)
+    /// ```
+    #[pyo3(signature = (params = None))]
+    pub fn with_csf_ground_reclassification(
+        mut slf: PyRefMut<Self>,
+        params: Option<CsfParams>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let params = csf_params_or_default(params)?;
+        slf.builder.with_csf_ground_reclassification(params);
+        Ok(slf)
+    }
+
+    /// Configures the builder to reclassify points to ground or their original classification based
+    /// on the Simple Morphological Filter (SMRF), which works well in urban areas. It replaces
+    /// another ground reclassification.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: SmrfParams with the parameters of the filter. When None, the defaults are used.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_smrf_ground_reclassification(SmrfParams(window=25.0))
+    /// ```
+    #[pyo3(signature = (params = None))]
+    pub fn with_smrf_ground_reclassification(
+        mut slf: PyRefMut<Self>,
+        params: Option<SmrfParams>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let params = params.as_ref().map(_SmrfParams::from).unwrap_or_default();
+        slf.builder.with_smrf_ground_reclassification(params);
+        Ok(slf)
+    }
+
+    /// Configures the builder to reclassify points to ground or their original classification based
+    /// on the Progressive Morphological Filter (PMF). It replaces another ground reclassification.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: PmfParams with the parameters of the filter. When None, the defaults are used.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_pmf_ground_reclassification(PmfParams(max_window=20.0))
+    /// ```
+    #[pyo3(signature = (params = None))]
+    pub fn with_pmf_ground_reclassification(
+        mut slf: PyRefMut<Self>,
+        params: Option<PmfParams>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let params = params.as_ref().map(_PmfParams::from).unwrap_or_default();
+        slf.builder.with_pmf_ground_reclassification(params);
+        Ok(slf)
+    }
+
+    /// Configures the builder to reclassify points to ground or their original classification based
+    /// on progressive TIN densification (PTD), as in TerraScan. It replaces another ground
+    /// reclassification. The x, y and z of all included points are kept in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: PtdParams with the parameters of the densification. When None, the defaults are
+    ///   used.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_ptd_ground_reclassification(PtdParams(max_building_size=40.0))
+    /// ```
+    #[pyo3(signature = (params = None))]
+    pub fn with_ptd_ground_reclassification(
+        mut slf: PyRefMut<Self>,
+        params: Option<PtdParams>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let params = params.as_ref().map(_PtdParams::from).unwrap_or_default();
+        slf.builder.with_ptd_ground_reclassification(params);
+        Ok(slf)
+    }
+
+    /// Configures the builder to classify the points that are not ground as low, medium or high
+    /// vegetation by their height above the ground, and as buildings when they lie in a smooth
+    /// plane with their nearest neighbours. The ground is that of the ground reclassification, or
+    /// else the points classified as ground in the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: ObjectParams with the height bands and the shape of buildings. When None, the
+    ///   defaults are used.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_smrf_ground_reclassification()
+    /// builder.with_object_reclassification(ObjectParams(high_vegetation_height=8.0))
+    /// ```
+    #[pyo3(signature = (params = None))]
+    pub fn with_object_reclassification(
+        mut slf: PyRefMut<Self>,
+        params: Option<ObjectParams>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let params = match params {
+            Some(params) => _ObjectParams::try_from(&params)?,
+            None => _ObjectParams::default(),
+        };
+        slf.builder.with_object_reclassification(params);
+        Ok(slf)
+    }
+
+    /// Configures the builder to detect points that are duplicates of points before them in the
+    /// input, also across the files of `from_files`, and to remove or withhold them. Duplicates
+    /// are not used by the noise filters, the ground reclassification or the rasters.
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance`: Float, the largest distance between duplicates. With 0.0 only points with
+    ///   exactly the same x, y and z are duplicates.
+    /// * `same_gps_time`: Bool, only points with the same GPS time are duplicates.
+    /// * `action`: Str, "remove" to leave duplicates out before the thinning, or "withhold" to set
+    ///   their withheld flag.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_files(["tile_1.laz", "tile_2.laz"])
+    /// builder.with_duplicate_detection(tolerance=0.001, same_gps_time=True)
+    /// ```
+    #[pyo3(signature = (tolerance = 0.0, same_gps_time = false, action = "remove".to_string()))]
+    pub fn with_duplicate_detection(
+        mut slf: PyRefMut<Self>,
+        tolerance: f64,
+        same_gps_time: bool,
+        action: String,
+    ) -> PyResult<PyRefMut<Self>> {
+        let action = match action.as_str() {
+            "remove" => DuplicateAction::Remove,
+            "withhold" => DuplicateAction::Withhold,
+            _ => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown duplicate action '{action}', use 'remove' or 'withhold'"
+                )))
+            }
+        };
+        let params = DuplicateParams::default()
+            .with_tolerance(tolerance)
+            .with_same_gps_time(same_gps_time)
+            .with_action(action);
+        slf.builder.with_duplicate_detection(params);
+        Ok(slf)
     }
-}
-
-#[pyclass]
-struct PointCloudBuilder {
-    builder: _PointCloudBuilder,
-}
 
-#[pymethods]
-impl PointCloudBuilder {
-    /// Default constructor for PointCloudBuilder, based on LAS or LAZ file.
+    /// Configures the builder to find noise with statistical outlier removal: points whose mean
+    /// distance to their `k` nearest neighbours is more than `multiplier` standard deviations above
+    /// the mean of that distance. Noise filters run before the ground reclassification, which
+    /// doesn't use the noise points. Each call adds a filter.
     ///
     /// # Arguments
     ///
-    /// * `filepath` - Path to LAS or LAZ file. If file doesn't exist, an error will be raised.
+    /// * `k`: Int, the amount of nearest neighbours.
+    /// * `multiplier`: Float, the amount of standard deviations above the mean distance.
+    /// * `action`: Str, "classify" to classify the noise as low (7) or high (18) noise, or
+    ///   "remove" to leave it out.
     ///
-    /// returns: Result<PointCloudBuilder, PyErr>
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
     ///
     /// # Examples
     ///
     /// ```
     /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_statistical_noise_filter(k=8, multiplier=2.0, action="remove")
     /// ```
-    #[staticmethod]
-    pub fn from_file(filepath: String) -> PyResult<Self> {
-        let builder = _PointCloudBuilder::from_file(&filepath);
-        let builder = match builder {
-            Ok(builder) => builder,
-            Err(e) => return Err(Self::parse_error_to_python_exception(e.to_string())),
+    #[pyo3(signature = (k = 8, multiplier = 2.0, action = "classify".to_string()))]
+    pub fn with_statistical_noise_filter(
+        mut slf: PyRefMut<Self>,
+        k: usize,
+        multiplier: f64,
+        action: String,
+    ) -> PyResult<PyRefMut<Self>> {
+        let filter = noise_filter(OutlierMethod::Statistical { k, multiplier }, &action)?;
+        slf.builder.with_noise_filter(filter);
+        Ok(slf)
+    }
+
+    /// Configures the builder to find noise with radius outlier removal: points with fewer than
+    /// `min_neighbours` other points within a distance of `radius`. Each call adds a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `radius`: Float, the distance to count neighbours within.
+    /// * `min_neighbours`: Int, the least amount of neighbours of a point that isn't noise.
+    /// * `action`: Str, "classify" or "remove", see `with_statistical_noise_filter`.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    #[pyo3(signature = (radius = 1.0, min_neighbours = 2, action = "classify".to_string()))]
+    pub fn with_radius_noise_filter(
+        mut slf: PyRefMut<Self>,
+        radius: f64,
+        min_neighbours: usize,
+        action: String,
+    ) -> PyResult<PyRefMut<Self>> {
+        let method = OutlierMethod::Radius {
+            radius,
+            min_neighbours,
         };
-        Ok(PointCloudBuilder { builder })
+        slf.builder
+            .with_noise_filter(noise_filter(method, &action)?);
+        Ok(slf)
     }
 
-    /// Configures the builder to use cropping based on a lower left and upper right corner.
+    /// Configures the builder to find isolated low and high points: points that are more than
+    /// `height` below or above all other points within a horizontal distance of `radius`. Each
+    /// call adds a filter.
     ///
-    /// NOTE: This will not actually crop the file, it just configures the builder to do so when
-    /// you run a builder.to_*() method.
+    /// # Arguments
+    ///
+    /// * `radius`: Float, the horizontal distance to compare heights within.
+    /// * `height`: Float, the least difference in height with all neighbours of a noise point.
+    /// * `action`: Str, "classify" or "remove", see `with_statistical_noise_filter`.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    #[pyo3(signature = (radius = 5.0, height = 5.0, action = "classify".to_string()))]
+    pub fn with_isolated_noise_filter(
+        mut slf: PyRefMut<Self>,
+        radius: f64,
+        height: f64,
+        action: String,
+    ) -> PyResult<PyRefMut<Self>> {
+        let method = OutlierMethod::Isolated { radius, height };
+        slf.builder
+            .with_noise_filter(noise_filter(method, &action)?);
+        Ok(slf)
+    }
+
+    /// Configures the builder to report its progress to a Python callable instead of drawing a
+    /// progress bar. The callable is called as `callback(task, position, total)`, where `total` is
+    /// None when the amount of steps is not known beforehand. Status messages are sent to the
+    /// `laszy` logger of the Python `logging` module.
     ///
     /// # Arguments
     ///
-    /// * `lower_left`: Tuple of (x, y) coordinates for lower left corner of bounding box.
-    /// * `upper_right`: Tuple of (x, y) coordinates for upper right corner of bounding box.
+    /// * `callback`: Callable taking the task name, the current position and the total.
     ///
     /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
     ///
@@ -90,34 +1580,57 @@ impl PointCloudBuilder {
     ///
     /// ```
     /// builder = PointCloudBuilder.from_file("test.las")
-    /// builder.with_cropping((12.0, 5.0), (14.0, 9.0))
-    /// # If you don't run `to_cloud` or another `to_*` method, nothing will happen.
+    /// builder.with_progress_callback(lambda task, position, total: print(task, position, total))
     /// cloud = builder.to_cloud()
     /// ```
-    pub fn with_crop(
+    pub fn with_progress_callback(
         mut slf: PyRefMut<Self>,
-        lower_left: (f64, f64),
-        upper_right: (f64, f64),
+        callback: PyObject,
     ) -> PyResult<PyRefMut<Self>> {
         slf.builder
-            .with_crop(laszy_rs::CroppingMethod::BoundingBox {
-                lower_left,
-                upper_right,
-            });
+            .with_progress_reporter(Box::new(PyProgressReporter::new(callback)));
         Ok(slf)
     }
 
-    /// Configures the builder to discard a percentage of points randomly.
+    /// Configures the builder to not report any progress. Status messages are still sent to the
+    /// `laszy` logger of the Python `logging` module.
+    pub fn with_silent_progress(mut slf: PyRefMut<Self>) -> PyResult<PyRefMut<Self>> {
+        slf.builder.with_silent_progress();
+        Ok(slf)
+    }
+
+    /// Configures the builder to stop running when the token is cancelled. The builder releases the
+    /// GIL while running, so the token can be cancelled from another Python thread.
     ///
-    /// NOTE: This will not actually thin the file, it just configures the builder to do so when
-    /// you run a builder.to_*() method.
+    /// # Arguments
     ///
-    /// NOTE: This is not a deterministic process. If you run it twice, you will get a different
-    /// set and amount of points.
+    /// * `token`: CancellationToken to check while running.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// token = CancellationToken()
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_cancellation_token(token)
+    /// # From another thread, or from a progress callback:
+    /// token.cancel()
+    /// ```
+    pub fn with_cancellation_token(
+        mut slf: PyRefMut<Self>,
+        token: CancellationToken,
+    ) -> PyResult<PyRefMut<Self>> {
+        slf.builder.with_cancellation_token(token.token);
+        Ok(slf)
+    }
+
+    /// Configures what happens when an output file already exists. Output is always written to a
+    /// temporary file first, which replaces the target only when writing succeeded.
     ///
     /// # Arguments
     ///
-    /// * `keep_percentage`: Float between 0.0 and 1.0, representing the percentage of points to keep.
+    /// * `policy`: One of "overwrite" (the default), "error" or "skip".
     ///
     /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
     ///
@@ -125,30 +1638,49 @@ impl PointCloudBuilder {
     ///
     /// ```
     /// builder = PointCloudBuilder.from_file("test.las")
-    /// builder.with_thinning_random(0.2)
-    /// # If you don't run `to_cloud` or another `to_*` method, nothing will happen.
-    /// cloud = builder.to_cloud()
+    /// builder.with_overwrite_policy("skip")
+    /// builder.to_file("output.las")
     /// ```
-    pub fn with_thinning_random(
+    pub fn with_overwrite_policy(
         mut slf: PyRefMut<Self>,
-        keep_percentage: f64,
+        policy: String,
     ) -> PyResult<PyRefMut<Self>> {
-        let method = laszy_rs::ThinningMethod::Random {
-            percent: keep_percentage,
+        let policy = match policy.as_str() {
+            "overwrite" => OverwritePolicy::Overwrite,
+            "error" => OverwritePolicy::Error,
+            "skip" => OverwritePolicy::Skip,
+            _ => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown overwrite policy '{policy}', use 'overwrite', 'error' or 'skip'"
+                )))
+            }
         };
-        slf.builder.with_thinning(method);
+        slf.builder.with_overwrite_policy(policy);
         Ok(slf)
     }
 
-    /// Configures the builder to only keep every nth point.
+    /// Configures the amount of threads used for the ground reclassification. By default all cores
+    /// are used. The result of the reclassification doesn't depend on the amount of threads.
     ///
-    /// NOTE: This will not actually thin the file, it just configures the builder to do so when
-    /// you run a builder.to_*() method.
+    /// # Arguments
+    ///
+    /// * `threads`: Int, the amount of threads to use. Must be at least 1.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    pub fn with_threads(mut slf: PyRefMut<Self>, threads: usize) -> PyResult<PyRefMut<Self>> {
+        slf.builder.with_threads(threads);
+        Ok(slf)
+    }
+
+    /// Configures how raster outputs such as a DTM are written.
     ///
     /// # Arguments
     ///
-    /// * `nth`: Int representing the number of points to skip between each point kept. Will always
-    /// keep the first point.
+    /// * `nodata`: Float, the value written for cells without data.
+    /// * `compression`: Compression of GeoTIFF files, one of "deflate" (the default), "lzw" or
+    ///   "none".
+    /// * `epsg`: Optional int, the EPSG code of the coordinate reference system written to GeoTIFF
+    ///   files. When None, the projection of the input file is used, if it has one.
     ///
     /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
     ///
@@ -156,107 +1688,383 @@ impl PointCloudBuilder {
     ///
     /// ```
     /// builder = PointCloudBuilder.from_file("test.las")
-    /// builder.with_thinning_every_nth(10)
-    /// # If you don't run `to_cloud` or another `to_*` method, nothing will happen.
-    /// cloud = builder.to_cloud()
+    /// builder.with_raster_options(compression="lzw", epsg=28992)
+    /// builder.to_dtm_using_csf("dtm.tif")
     /// ```
-    pub fn with_thinning_every_nth(
+    #[pyo3(signature = (nodata = -9999.0, compression = "deflate".to_string(), epsg = None))]
+    pub fn with_raster_options(
         mut slf: PyRefMut<Self>,
-        nth: usize,
+        nodata: f64,
+        compression: String,
+        epsg: Option<u16>,
     ) -> PyResult<PyRefMut<Self>> {
-        let method = laszy_rs::ThinningMethod::EveryNth { nth };
-        slf.builder.with_thinning(method);
+        let compression = match compression.as_str() {
+            "none" => RasterCompression::None,
+            "deflate" => RasterCompression::Deflate,
+            "lzw" => RasterCompression::Lzw,
+            _ => {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown raster compression '{compression}', use 'deflate', 'lzw' or 'none'"
+                )))
+            }
+        };
+        let mut options = RasterOptions::default()
+            .with_nodata(nodata)
+            .with_compression(compression);
+        options.epsg = epsg;
+        slf.builder.with_raster_options(options);
         Ok(slf)
     }
 
-    /// Configures the builder to reclassify points to ground or their original classification based
-    /// on the cloth simulation filter (CSF) algorithm.
+    /// Configures the builder to colour the points with the red, green and blue bands of a GeoTIFF
+    /// orthophoto at their x and y.
     ///
-    /// NOTE: This will not actually reclassify the file, it just configures the builder to do so
-    /// when you run a builder.to_*() method.
+    /// # Arguments
+    ///
+    /// * `path`: Str, the .tif or .tiff orthophoto, with 8 or 16 bit bands.
+    /// * `bands`: Tuple of 3 ints, the numbers of the red, green and blue bands, starting at 1.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_colourisation("orthophoto.tif")
+    /// builder.to_file("coloured.laz")
+    /// ```
+    #[pyo3(signature = (path, bands = (1, 2, 3)))]
+    pub fn with_colourisation(
+        mut slf: PyRefMut<Self>,
+        path: String,
+        bands: (usize, usize, usize),
+    ) -> PyResult<PyRefMut<Self>> {
+        let params = ColourParams::new(&path).with_bands(bands.0, bands.1, bands.2);
+        slf.builder.with_colourisation(params);
+        Ok(slf)
+    }
+
+    /// Configures the point format and LAS version of LAS/LAZ outputs. By default those of the input
+    /// are kept.
     ///
     /// # Arguments
     ///
-    /// * `rigidness`: Float between 0.0 and 1.0, representing the rigidity of the cloth. 0.0 will
-    /// most likely result in all points being classified as ground, while 1.0 will be the strictest
-    /// possible classification.
-    /// * `cloth_resolution`: Float representing the resolution of the cloth in meters. It is not
-    /// recommended to go below 5 meters. A good default is 5.0 or 10.0 meters.
-    /// * `distance_threshold`: Float representing the distance threshold in meters where the
-    /// simulation will stop. A good default is 0.1 meters.
+    /// * `point_format`: Optional int from 0 to 10, the point format of the output. Fields that it
+    ///   doesn't have are dropped with a warning, fields that the input doesn't have get defaults.
+    /// * `version`: Optional str, the LAS version of the output from "1.0" to "1.4". When None, the
+    ///   version of the input is kept, or raised to the lowest version supporting the format.
     ///
     /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
     ///
     /// # Examples
     ///
     /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_las_options(point_format=6, version="1.4")
+    /// builder.to_file("archive.laz")
+    /// ```
+    #[pyo3(signature = (point_format = None, version = None))]
+    pub fn with_las_options(
+        mut slf: PyRefMut<Self>,
+        point_format: Option<u8>,
+        version: Option<String>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let mut options = LasOptions {
+            point_format,
+            version: None,
+        };
+        if let Some(version) = version {
+            let parsed = version
+                .split_once('.')
+                .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)));
+            let Some((major, minor)) = parsed else {
+                return Err(errors::InvalidParameterError::new_err(format!(
+                    "Unknown LAS version '{version}', use '1.0' to '1.4'"
+                )));
+            };
+            options = options.with_version(major, minor);
+        }
+        slf.builder.with_las_options(options);
+        Ok(slf)
+    }
+
+    /// Configures the builder to compute the height above ground of each point, stored in a
+    /// "HeightAboveGround" extra bytes field or replacing z.
+    ///
+    /// # Arguments
+    ///
+    /// * `surface`: Str, the ground surface: "cloth" (the cloth of the CSF ground
+    ///   reclassification), "tin" (a TIN of the ground points) or "dtm" (the raster at `dtm_path`).
+    /// * `dtm_path`: Optional str, the .asc, .tif or .tiff DTM of the "dtm" surface.
+    /// * `replace_z`: Bool, replace z with the height above ground instead of adding a field.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
     ///
     /// ```
-    #[args(
-        rigidness = "0.5",
-        cloth_resolution = "10.0",
-        distance_threshold = "0.1"
-    )]
-    pub fn with_csf_ground_reclassification(
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_height_above_ground("dtm", dtm_path="dtm.tif", replace_z=True)
+    /// builder.to_file("normalised.laz")
+    /// ```
+    #[pyo3(signature = (surface = "tin".to_string(), dtm_path = None, replace_z = false))]
+    pub fn with_height_above_ground(
         mut slf: PyRefMut<Self>,
-        rigidness: f64,
-        cloth_resolution: f64,
-        simulation_threshold: f64,
-        classification_threshold: f64,
+        surface: String,
+        dtm_path: Option<String>,
+        replace_z: bool,
     ) -> PyResult<PyRefMut<Self>> {
-        slf.builder.with_csf_ground_reclassification(
-            rigidness,
-            cloth_resolution,
-            simulation_threshold,
-            classification_threshold,
-        );
+        let params = HagParams::default()
+            .with_surface(ground_surface(&surface, dtm_path)?)
+            .with_replace_z(replace_z);
+        slf.builder.with_height_above_ground(params);
+        Ok(slf)
+    }
+
+    /// Configures the builder to compute the normal, curvature, planarity, linearity, sphericity
+    /// and verticality of each point from its neighbourhood. They are stored in extra bytes
+    /// fields by `to_file`, and are NumPy arrays of the PointCloud of `to_cloud`.
+    ///
+    /// # Arguments
+    ///
+    /// * `k`: Int, the amount of nearest points in the neighbourhood, including the point itself.
+    /// * `radius`: Optional float, when set the neighbourhood is the points within this radius
+    ///   instead of the k nearest points.
+    ///
+    /// returns: Result<PyRefMut<PointCloudBuilder>, PyErr>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// builder = PointCloudBuilder.from_file("test.las")
+    /// builder.with_geometric_features(k=20)
+    /// cloud = builder.to_cloud()
+    /// normals = cloud.normals
+    /// ```
+    #[pyo3(signature = (k = 10, radius = None))]
+    pub fn with_geometric_features(
+        mut slf: PyRefMut<Self>,
+        k: usize,
+        radius: Option<f64>,
+    ) -> PyResult<PyRefMut<Self>> {
+        let neighbourhood = match radius {
+            Some(radius) => Neighbourhood::Radius { radius },
+            None => Neighbourhood::Knn { k },
+        };
+        slf.builder.with_geometric_features(neighbourhood);
         Ok(slf)
     }
 
-    pub fn to_file(&mut self, filepath: String) -> PyResult<()> {
-        let re = self.builder.to_file(&filepath);
+    /// Checks the configuration of the builder without reading any points. All `to_*` methods do
+    /// this before they start, raising an exception such as InvalidParameterError on a mistake.
+    pub fn validate(&self, py: Python) -> PyResult<()> {
+        self.builder
+            .validate()
+            .map_err(|e| errors::to_py_err(py, e))
+    }
+
+    pub fn to_file(&mut self, py: Python, filepath: String) -> PyResult<()> {
+        let builder = &mut self.builder;
+        let re = py.allow_threads(|| builder.to_file(&filepath));
         match re {
             Ok(_) => Ok(()),
-            Err(e) => Err(Self::parse_error_to_python_exception(e.to_string())),
+            Err(e) => Err(errors::to_py_err(py, e)),
+        }
+    }
+
+    /// Splits the points over a regular grid of .las/.laz files in the directory, which is created
+    /// when it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory`: Str, the directory to write the tiles to.
+    /// * `tile_size`: Float, width and height of the tiles in meters.
+    /// * `origin`: Tuple of 2 floats, the point the grid of tiles is aligned to.
+    /// * `buffer`: Float, points within this many meters outside a tile are also written to it.
+    /// * `name`: Str, name of the tile files, where {minx}, {miny}, {maxx} and {maxy} are replaced
+    ///   by the bounds of a tile. Must end in .las or .laz.
+    /// * `max_open_files`: Int, largest number of tile files that are open at the same time.
+    ///
+    /// returns: Result<Vec<String>, PyErr>, the paths of the tiles that were written.
+    #[pyo3(signature = (
+        directory,
+        tile_size,
+        origin = (0.0, 0.0),
+        buffer = 0.0,
+        name = "{minx}_{miny}.laz".to_string(),
+        max_open_files = 64
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn to_tiles(
+        &mut self,
+        py: Python,
+        directory: String,
+        tile_size: f64,
+        origin: (f64, f64),
+        buffer: f64,
+        name: String,
+        max_open_files: usize,
+    ) -> PyResult<Vec<String>> {
+        let params = TileParams::new(tile_size)
+            .with_origin(origin.0, origin.1)
+            .with_buffer(buffer)
+            .with_name(&name)
+            .with_max_open_files(max_open_files);
+        let builder = &mut self.builder;
+        let re = py.allow_threads(move || builder.to_tiles(&directory, params));
+        match re {
+            Ok(paths) => Ok(paths),
+            Err(e) => Err(errors::to_py_err(py, e)),
         }
     }
 
-    pub fn to_cloud(&mut self) -> PyResult<PointCloud> {
-        let cloud = self.builder.to_cloud();
+    pub fn to_cloud(&mut self, py: Python) -> PyResult<PointCloud> {
+        let builder = &mut self.builder;
+        let cloud = py.allow_threads(|| builder.to_cloud());
         match cloud {
             Ok(cloud) => Ok(PointCloud { cloud }),
-            Err(e) => Err(Self::parse_error_to_python_exception(e.to_string())),
+            Err(e) => Err(errors::to_py_err(py, e)),
         }
     }
 
-    pub fn to_dtm_using_csf(
+    /// Creates a DTM by interpolating the ground points. When a ground reclassification is
+    /// configured the points it classifies as ground are used, otherwise the points classified as
+    /// ground in the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath`: Str, the .asc, .tif or .tiff file to write the DTM to.
+    /// * `params`: Optional DtmParams, the defaults are used when None.
+    ///
+    /// returns: Result<(), PyErr>
+    #[pyo3(signature = (filepath, params = None))]
+    pub fn to_dtm(
         &mut self,
+        py: Python,
         filepath: String,
-        rigidness: f64,
-        grid_resolution_meters: f64,
-        distance_threshold: f64,
+        params: Option<DtmParams>,
     ) -> PyResult<()> {
-        let re = self.builder.to_dtm_using_csf(
-            &filepath,
-            rigidness,
-            grid_resolution_meters,
-            distance_threshold,
-        );
+        let params = dtm_params_or_default(params)?;
+        let builder = &mut self.builder;
+        let re = py.allow_threads(move || builder.to_dtm(&filepath, params));
         match re {
             Ok(_) => Ok(()),
-            Err(e) => Err(Self::parse_error_to_python_exception(e.to_string())),
+            Err(e) => Err(errors::to_py_err(py, e)),
         }
     }
 
-    #[staticmethod]
-    fn parse_error_to_python_exception(e: String) -> PyErr {
-        PyErr::new::<pyo3::exceptions::PyException, _>(e)
+    /// Creates a DSM with the height of the highest point in each cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath`: Str, the .asc, .tif or .tiff file to write the DSM to.
+    /// * `params`: Optional DsmParams, the defaults are used when None.
+    ///
+    /// returns: Result<(), PyErr>
+    #[pyo3(signature = (filepath, params = None))]
+    pub fn to_dsm(
+        &mut self,
+        py: Python,
+        filepath: String,
+        params: Option<DsmParams>,
+    ) -> PyResult<()> {
+        let params = params.as_ref().map(_DsmParams::from).unwrap_or_default();
+        let builder = &mut self.builder;
+        let re = py.allow_threads(move || builder.to_dsm(&filepath, params));
+        match re {
+            Ok(_) => Ok(()),
+            Err(e) => Err(errors::to_py_err(py, e)),
+        }
+    }
+
+    /// Creates a CHM (Canopy Height Model), the DSM minus a DTM interpolated from the ground
+    /// points at the cells of the DSM.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath`: Str, the .asc, .tif or .tiff file to write the CHM to.
+    /// * `params`: Optional DsmParams of the DSM, the defaults are used when None.
+    /// * `dtm_params`: Optional DtmParams of the DTM, of which the resolution is not used.
+    ///
+    /// returns: Result<(), PyErr>
+    #[pyo3(signature = (filepath, params = None, dtm_params = None))]
+    pub fn to_chm(
+        &mut self,
+        py: Python,
+        filepath: String,
+        params: Option<DsmParams>,
+        dtm_params: Option<DtmParams>,
+    ) -> PyResult<()> {
+        let params = params.as_ref().map(_DsmParams::from).unwrap_or_default();
+        let dtm_params = dtm_params_or_default(dtm_params)?;
+        let builder = &mut self.builder;
+        let re = py.allow_threads(move || builder.to_chm(&filepath, params, dtm_params));
+        match re {
+            Ok(_) => Ok(()),
+            Err(e) => Err(errors::to_py_err(py, e)),
+        }
+    }
+
+    /// Creates a raster with a band for each aggregation of the points in its cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath`: Str, the .tif or .tiff file to write the raster to, or an .asc file for a
+    ///   single aggregation.
+    /// * `resolution`: Float, cell size of the raster in meters.
+    /// * `aggregations`: List of Aggregation, one band each.
+    ///
+    /// returns: Result<(), PyErr>
+    pub fn to_raster(
+        &mut self,
+        py: Python,
+        filepath: String,
+        resolution: f64,
+        aggregations: Vec<Aggregation>,
+    ) -> PyResult<()> {
+        let aggregations = aggregations
+            .iter()
+            .map(_Aggregation::try_from)
+            .collect::<PyResult<Vec<_>>>()?;
+        let builder = &mut self.builder;
+        let re = py.allow_threads(move || builder.to_raster(&filepath, resolution, &aggregations));
+        match re {
+            Ok(_) => Ok(()),
+            Err(e) => Err(errors::to_py_err(py, e)),
+        }
+    }
+
+    #[pyo3(signature = (filepath, params = None))]
+    pub fn to_dtm_using_csf(
+        &mut self,
+        py: Python,
+        filepath: String,
+        params: Option<CsfParams>,
+    ) -> PyResult<()> {
+        let params = csf_params_or_default(params)?;
+        let builder = &mut self.builder;
+        let re = py.allow_threads(move || builder.to_dtm_using_csf(&filepath, params));
+        match re {
+            Ok(_) => Ok(()),
+            Err(e) => Err(errors::to_py_err(py, e)),
+        }
     }
 }
 
 /// A Python module implemented in Rust.
 #[pymodule]
-fn laszy(_py: Python, m: &PyModule) -> PyResult<()> {
+fn laszy(py: Python, m: &PyModule) -> PyResult<()> {
+    pyo3_log::init();
+    errors::add_exceptions(py, m)?;
     m.add_class::<PointCloudBuilder>()?;
+    m.add_class::<CancellationToken>()?;
+    m.add_class::<CsfParams>()?;
+    m.add_class::<SmrfParams>()?;
+    m.add_class::<PmfParams>()?;
+    m.add_class::<PtdParams>()?;
+    m.add_class::<ObjectParams>()?;
+    m.add_class::<DtmParams>()?;
+    m.add_class::<DsmParams>()?;
+    m.add_class::<Aggregation>()?;
     Ok(())
 }
@@ -0,0 +1,333 @@
+use crate::raster::{RasterGrid, RasterSource};
+use crate::LaszyError;
+use las::Point;
+use ndarray::Array2;
+
+/// An attribute of the points that can be aggregated per raster cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attribute {
+    Z,
+    Intensity,
+    ReturnNumber,
+    NumberOfReturns,
+    Classification,
+    ScanAngle,
+    UserData,
+    PointSourceId,
+    GpsTime,
+    Red,
+    Green,
+    Blue,
+    Nir,
+}
+
+impl Attribute {
+    /// The value of the attribute of a point, or None when the point format doesn't have it.
+    fn value(&self, point: &Point) -> Option<f64> {
+        Some(match self {
+            Attribute::Z => point.z,
+            Attribute::Intensity => point.intensity as f64,
+            Attribute::ReturnNumber => point.return_number as f64,
+            Attribute::NumberOfReturns => point.number_of_returns as f64,
+            Attribute::Classification => u8::from(point.classification) as f64,
+            Attribute::ScanAngle => point.scan_angle as f64,
+            Attribute::UserData => point.user_data as f64,
+            Attribute::PointSourceId => point.point_source_id as f64,
+            Attribute::GpsTime => point.gps_time?,
+            Attribute::Red => point.color?.red as f64,
+            Attribute::Green => point.color?.green as f64,
+            Attribute::Blue => point.color?.blue as f64,
+            Attribute::Nir => point.nir? as f64,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Attribute::Z => "z",
+            Attribute::Intensity => "intensity",
+            Attribute::ReturnNumber => "return_number",
+            Attribute::NumberOfReturns => "number_of_returns",
+            Attribute::Classification => "classification",
+            Attribute::ScanAngle => "scan_angle",
+            Attribute::UserData => "user_data",
+            Attribute::PointSourceId => "point_source_id",
+            Attribute::GpsTime => "gps_time",
+            Attribute::Red => "red",
+            Attribute::Green => "green",
+            Attribute::Blue => "blue",
+            Attribute::Nir => "nir",
+        }
+    }
+}
+
+/// How the values of an attribute of the points in a cell are reduced to the value of the cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reducer {
+    /// Amount of points in the cell, the point density. Cells without points are 0.
+    Count,
+    Min,
+    Max,
+    Mean,
+    /// Population standard deviation.
+    StdDev,
+    /// Value at a percentile, for example 0.95 for the value that 95% of the values are below.
+    /// Keeps the values of all points in memory.
+    Percentile {
+        percent: f64,
+    },
+    /// Most common value, such as the main class of a cell. The lowest value wins a tie.
+    Mode,
+}
+
+/// An attribute and how it is reduced per cell, written as a band of a raster.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{Aggregation, Attribute, Reducer};
+/// let aggregations = [
+///     Aggregation::count(),
+///     Aggregation::new(Attribute::Intensity, Reducer::Mean),
+///     Aggregation::new(Attribute::Classification, Reducer::Mode),
+/// ];
+/// assert_eq!(aggregations[1].name(), "intensity_mean");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aggregation {
+    pub attribute: Attribute,
+    pub reducer: Reducer,
+}
+
+impl Aggregation {
+    pub fn new(attribute: Attribute, reducer: Reducer) -> Self {
+        Aggregation { attribute, reducer }
+    }
+
+    /// The amount of points per cell.
+    pub fn count() -> Self {
+        Aggregation::new(Attribute::Z, Reducer::Count)
+    }
+
+    /// Name of the aggregation, used as description of its band.
+    pub fn name(&self) -> String {
+        let attribute = self.attribute.name();
+        match self.reducer {
+            Reducer::Count => "count".to_string(),
+            Reducer::Min => format!("{attribute}_min"),
+            Reducer::Max => format!("{attribute}_max"),
+            Reducer::Mean => format!("{attribute}_mean"),
+            Reducer::StdDev => format!("{attribute}_stddev"),
+            Reducer::Percentile { percent } => {
+                // Rounded to a tenth, as 0.07 * 100.0 is 7.000000000000001
+                format!("{attribute}_p{}", (percent * 1000.0).round() / 10.0)
+            }
+            Reducer::Mode => format!("{attribute}_mode"),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        match self.reducer {
+            Reducer::Percentile { percent } if !(0.0..=1.0).contains(&percent) => {
+                Err(LaszyError::invalid_parameter(
+                    "Percentile { percent }",
+                    percent,
+                    "must be between 0.0 and 1.0",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The state of one aggregation for all cells, while points are added.
+enum BandState {
+    Count(Array2<u32>),
+    Extreme {
+        values: Array2<f64>,
+        highest: bool,
+    },
+    /// Running mean and sum of squared differences from the mean, after Welford, of which the mean
+    /// or with `std_dev` the standard deviation is taken.
+    Moments {
+        counts: Array2<u32>,
+        means: Array2<f64>,
+        squares: Array2<f64>,
+        std_dev: bool,
+    },
+    /// All values, of which the value at `percent` is taken.
+    Values {
+        values: Array2<Vec<f64>>,
+        percent: f64,
+    },
+    /// Each distinct value with its amount of points.
+    Frequencies(Array2<Vec<(f64, u32)>>),
+}
+
+impl BandState {
+    fn new(reducer: Reducer, shape: (usize, usize)) -> Self {
+        match reducer {
+            Reducer::Count => BandState::Count(Array2::zeros(shape)),
+            Reducer::Min | Reducer::Max => BandState::Extreme {
+                values: Array2::from_elem(shape, f64::NAN),
+                highest: reducer == Reducer::Max,
+            },
+            Reducer::Mean | Reducer::StdDev => BandState::Moments {
+                counts: Array2::zeros(shape),
+                means: Array2::zeros(shape),
+                squares: Array2::zeros(shape),
+                std_dev: reducer == Reducer::StdDev,
+            },
+            Reducer::Percentile { percent } => BandState::Values {
+                values: Array2::default(shape),
+                percent,
+            },
+            Reducer::Mode => BandState::Frequencies(Array2::default(shape)),
+        }
+    }
+
+    fn add(&mut self, cell: (usize, usize), value: f64) {
+        match self {
+            BandState::Count(counts) => counts[cell] += 1,
+            BandState::Extreme { values, highest } => {
+                let current = &mut values[cell];
+                if current.is_nan()
+                    || (*highest && value > *current)
+                    || (!*highest && value < *current)
+                {
+                    *current = value;
+                }
+            }
+            BandState::Moments {
+                counts,
+                means,
+                squares,
+                ..
+            } => {
+                counts[cell] += 1;
+                let delta = value - means[cell];
+                means[cell] += delta / counts[cell] as f64;
+                squares[cell] += delta * (value - means[cell]);
+            }
+            BandState::Values { values, .. } => values[cell].push(value),
+            BandState::Frequencies(frequencies) => {
+                let frequencies = &mut frequencies[cell];
+                match frequencies.iter_mut().find(|(known, _)| *known == value) {
+                    Some((_, count)) => *count += 1,
+                    None => frequencies.push((value, 1)),
+                }
+            }
+        }
+    }
+
+    /// The values of the cells, NaN for cells without points.
+    fn finish(self) -> Array2<f64> {
+        match self {
+            BandState::Count(counts) => counts.mapv(|count| count as f64),
+            BandState::Extreme { values, .. } => values,
+            BandState::Moments {
+                counts,
+                means,
+                squares,
+                std_dev,
+            } => ndarray::Zip::from(&counts)
+                .and(&means)
+                .and(&squares)
+                .map_collect(|count, mean, squares| match *count {
+                    0 => f64::NAN,
+                    count if std_dev => (squares / count as f64).sqrt(),
+                    _ => *mean,
+                }),
+            BandState::Values {
+                mut values,
+                percent,
+            } => values.map_mut(|values| {
+                if values.is_empty() {
+                    return f64::NAN;
+                }
+                values.sort_by(f64::total_cmp);
+                let index = (percent * (values.len() - 1) as f64).round() as usize;
+                values[index]
+            }),
+            BandState::Frequencies(frequencies) => frequencies.map(|frequencies| {
+                frequencies
+                    .iter()
+                    .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.total_cmp(a)))
+                    .map_or(f64::NAN, |(value, _)| *value)
+            }),
+        }
+    }
+}
+
+/// Aggregates points into the cells of a raster, one band per aggregation, in a single pass over
+/// the points.
+pub(crate) struct CellAggregator {
+    grid: RasterGrid,
+    aggregations: Vec<Aggregation>,
+    bands: Vec<BandState>,
+}
+
+impl CellAggregator {
+    pub fn new(grid: RasterGrid, aggregations: &[Aggregation]) -> Self {
+        let shape = (grid.rows, grid.columns);
+        CellAggregator {
+            grid,
+            aggregations: aggregations.to_vec(),
+            bands: aggregations
+                .iter()
+                .map(|aggregation| BandState::new(aggregation.reducer, shape))
+                .collect(),
+        }
+    }
+
+    pub fn add_point(&mut self, point: &Point) {
+        let Some(cell) = self.grid.cell(point.x, point.y) else {
+            return;
+        };
+        for (aggregation, band) in self.aggregations.iter().zip(&mut self.bands) {
+            if let Some(value) = aggregation.attribute.value(point) {
+                band.add(cell, value);
+            }
+        }
+    }
+
+    /// The raster after all points are added.
+    pub fn finish(self) -> AggregatedRaster {
+        let bands = self.bands.into_iter().map(BandState::finish).collect();
+        AggregatedRaster {
+            grid: self.grid,
+            aggregations: self.aggregations,
+            bands,
+        }
+    }
+}
+
+/// A raster with a band for each aggregation, held in memory.
+pub(crate) struct AggregatedRaster {
+    grid: RasterGrid,
+    aggregations: Vec<Aggregation>,
+    pub bands: Vec<Array2<f64>>,
+}
+
+impl RasterSource for AggregatedRaster {
+    fn grid(&self) -> RasterGrid {
+        self.grid
+    }
+
+    fn bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    fn band_name(&self, band: usize) -> Option<String> {
+        Some(self.aggregations[band].name())
+    }
+
+    fn read_row(&mut self, row: usize, values: &mut [f64]) -> Result<(), LaszyError> {
+        let bands = self.bands.len();
+        for (band, cells) in self.bands.iter().enumerate() {
+            for (column, value) in cells.row(row).iter().enumerate() {
+                values[column * bands + band] = *value;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,103 @@
+use crate::aggregation::{Aggregation, CellAggregator};
+use crate::cancellation::CancellationToken;
 use crate::cloud::PointCloud;
+use crate::colour::{ColourParams, Colouriser};
 use crate::cropping::CroppingMethod;
+use crate::csf::params::CsfParams;
 use crate::csf::surface::ClothSurface;
+use crate::csf::tiled::TiledCloth;
+use crate::csf::Cloth;
+use crate::dsm::{self, DsmParams, SurfaceModel};
+use crate::dtm::{self, DtmParams, GroundTin};
+use crate::duplicates::{self, DuplicateParams, DuplicatePoints};
+use crate::error::ErrorContext;
+use crate::extra_bytes::{self, ExtraBytesField};
+use crate::features::{self, FeatureStore, Neighbourhood};
+use crate::ground::pmf::{self, PmfParams};
+use crate::ground::ptd::{self, PtdParams};
+use crate::ground::smrf::{self, SmrfParams};
+use crate::ground::{GroundClassifier, GroundFilter, MinimumSurface};
+use crate::hag::{GroundHeights, GroundSurface, HagParams, HagStage};
+use crate::las_output::{self, LasOptions, PointConversion};
 use crate::metadata::Metadata;
+use crate::noise::{self, NoiseFilter, NoisePoints};
+use crate::objects::{self, ObjectClassifier, ObjectParams, OBJECT_TILE_BUFFER};
+use crate::output::{self, OverwritePolicy};
+use crate::progress::{self, ProgressReporter, SilentReporter};
+use crate::raster::{self, RasterFormat, RasterGrid, RasterOptions};
 use crate::thinning::ThinningMethod;
+use crate::tiles::{PointId, PointTiles, TILE_SIZE};
+use crate::tiling::{TileParams, TileWriter};
 use crate::LaszyError;
 use las::point::Classification;
-use las::Write;
+use las::{Point, Write};
 use las::{Read, Reader};
 use std::fs::File;
 use std::io::BufReader;
+use std::path::Path;
 
 pub struct PointCloudBuilder {
     filepaths: Vec<String>,
     metadata: Metadata,
     crop: CroppingMethod,
     thinning: ThinningMethod,
-    csf_filter: Option<(f64, f64, f64, f64)>,
+    /// Seed of the random thinning, drawn once so every pass over the points keeps the same ones.
+    thinning_seed: u64,
+    ground_filter: Option<GroundFilter>,
+    objects: Option<ObjectParams>,
+    duplicates: Option<DuplicateParams>,
+    noise_filters: Vec<NoiseFilter>,
+    hag: Option<HagParams>,
+    features: Option<Neighbourhood>,
+    colour: Option<ColourParams>,
     cloud: Option<PointCloud>,
     writer: Option<las::Writer<File>>,
+    tiles: Option<TileWriter>,
+    /// Converts the points to the point format of the files being written.
+    conversion: Option<PointConversion>,
+    progress: Box<dyn ProgressReporter>,
+    cancellation: CancellationToken,
+    overwrite: OverwritePolicy,
+    threads: Option<usize>,
+    raster: RasterOptions,
+    las: LasOptions,
+}
+
+/// The points found by the duplicate detection and the noise filters, which are left out of the
+/// ground reclassification and the rasters.
+#[derive(Default)]
+struct ExcludedPoints {
+    duplicates: Option<DuplicatePoints>,
+    noise: Option<NoisePoints>,
+}
+
+impl ExcludedPoints {
+    fn is_duplicate(&self, id: PointId) -> bool {
+        self.duplicates
+            .as_ref()
+            .is_some_and(|duplicates| duplicates.is_duplicate(id))
+    }
+
+    fn is_removed_duplicate(&self, id: PointId) -> bool {
+        self.duplicates
+            .as_ref()
+            .is_some_and(|duplicates| duplicates.is_removed(id))
+    }
+
+    fn is_excluded(&self, id: PointId) -> bool {
+        self.is_duplicate(id) || self.noise.as_ref().is_some_and(|noise| noise.is_noise(id))
+    }
+
+    /// Withhold the point when it is a duplicate and classify it when it is noise, returns false
+    /// when it should be left out.
+    fn apply(&self, id: PointId, point: &mut Point) -> bool {
+        if self.is_duplicate(id) {
+            point.is_withheld = true;
+        }
+        self.noise
+            .as_ref()
+            .is_none_or(|noise| noise.apply(id, point))
+    }
 }
 
 impl PointCloudBuilder {
@@ -25,240 +105,1434 @@ impl PointCloudBuilder {
     ///
     /// # Arguments
     ///
-    /// * `filepath`: Path to the las/laz file
+    /// * `filepath`: Path to the las/laz file
+    ///
+    /// returns: Result<PointCloudBuilder, LaszyError>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::PointCloudBuilder;
+    /// let path = "test.las".to_string();
+    /// let builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// ```
+    pub fn from_file(filepath: &str) -> Result<Self, LaszyError> {
+        let file = File::open(filepath).in_file(filepath)?;
+        let reader = Reader::new(BufReader::new(file)).in_file(filepath)?;
+        let header = reader.header();
+        let metadata = Metadata::from_las_header(header);
+        Ok(PointCloudBuilder {
+            filepaths: vec![filepath.to_string()],
+            metadata,
+            crop: CroppingMethod::None,
+            thinning: ThinningMethod::None,
+            thinning_seed: rand::random(),
+            ground_filter: None,
+            objects: None,
+            duplicates: None,
+            noise_filters: Vec::new(),
+            hag: None,
+            features: None,
+            colour: None,
+            cloud: None,
+            writer: None,
+            tiles: None,
+            conversion: None,
+            progress: progress::default_reporter(),
+            cancellation: CancellationToken::new(),
+            overwrite: OverwritePolicy::default(),
+            threads: None,
+            raster: RasterOptions::default(),
+            las: LasOptions::default(),
+        })
+    }
+
+    /// Initialize a new builder from several Las/Laz files, such as the tiles of a delivery, which
+    /// are processed as if they were a single file. The files must have the same point format.
+    /// Will load metadata but no points.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepaths`: Paths to the las/laz files, at least one.
+    ///
+    /// returns: Result<PointCloudBuilder, LaszyError>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::PointCloudBuilder;
+    /// let builder = PointCloudBuilder::from_files(&["test.las", "test.las"]).unwrap();
+    /// ```
+    pub fn from_files(filepaths: &[&str]) -> Result<Self, LaszyError> {
+        let Some((first, others)) = filepaths.split_first() else {
+            return Err(LaszyError::invalid_parameter(
+                "filepaths",
+                "[]",
+                "must have at least one file",
+            ));
+        };
+        let mut builder = Self::from_file(first)?;
+        for filepath in others {
+            let file = File::open(filepath).in_file(filepath)?;
+            let reader = Reader::new(BufReader::new(file)).in_file(filepath)?;
+            let metadata = Metadata::from_las_header(reader.header());
+            builder.metadata.merge(&metadata, filepath)?;
+            builder.filepaths.push(filepath.to_string());
+        }
+        Ok(builder)
+    }
+
+    /// After initializing a builder from a file, get the metadata from the file.
+    pub fn get_metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Set the cropping method for the builder. This will be applied when the builder is used to
+    /// create a point cloud.
+    ///
+    /// # Arguments
+    ///
+    /// * `crop`: Cropping method to use
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, CroppingMethod};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_crop(CroppingMethod::BoundingBox {           
+    ///     lower_left: (183_551.47, 332_414.45),
+    ///      upper_right: (183_564.09, 332_424.13)});
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_crop(&mut self, crop: CroppingMethod) -> &mut Self {
+        self.crop = crop;
+        self
+    }
+
+    /// Set the thinning method for the builder. This will be applied when the builder is used to
+    /// create a point cloud. The random thinning keeps the same points in every pass over the
+    /// points and every output of the builder, so stages such as the noise filters see the points
+    /// that are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `method`: Method to use for thinning, from the ThinningMethod enum.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, ThinningMethod};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_thinning(ThinningMethod::Random { percent: 0.5 });
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_thinning(&mut self, method: ThinningMethod) -> &mut Self {
+        self.thinning = method;
+        self
+    }
+
+    /// Set the cloth surface filter for the builder. This will be applied when the builder is used
+    /// to create a point cloud.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Parameters of the cloth simulation, see `CsfParams` for the defaults and the
+    ///   presets for different types of terrain.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{CsfParams, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_csf_ground_reclassification(CsfParams::flat().with_cloth_resolution(2.0));
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_csf_ground_reclassification(&mut self, params: CsfParams) -> &mut Self {
+        self.ground_filter = Some(GroundFilter::Csf(params));
+        self
+    }
+
+    /// Reclassify ground points with the Simple Morphological Filter (SMRF) instead of CSF. This
+    /// will be applied when the builder is used to create a point cloud, in the same way as the
+    /// CSF ground reclassification, which it replaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Parameters of the filter, see `SmrfParams` for the defaults.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, SmrfParams};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_smrf_ground_reclassification(SmrfParams::default().with_window(10.0));
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_smrf_ground_reclassification(&mut self, params: SmrfParams) -> &mut Self {
+        self.ground_filter = Some(GroundFilter::Smrf(params));
+        self
+    }
+
+    /// Reclassify ground points with the Progressive Morphological Filter (PMF) instead of CSF.
+    /// This will be applied when the builder is used to create a point cloud, in the same way as
+    /// the CSF ground reclassification, which it replaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Parameters of the filter, see `PmfParams` for the defaults.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PmfParams, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_pmf_ground_reclassification(PmfParams::default());
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_pmf_ground_reclassification(&mut self, params: PmfParams) -> &mut Self {
+        self.ground_filter = Some(GroundFilter::Pmf(params));
+        self
+    }
+
+    /// Reclassify ground points with progressive TIN densification (PTD) instead of CSF, as in
+    /// TerraScan. This will be applied when the builder is used to create a point cloud, in the
+    /// same way as the CSF ground reclassification, which it replaces. Unlike the other stages the
+    /// densification is not tiled: the x, y and z of all included points are kept in memory with
+    /// its state per point, about 70 bytes per point or 7 GB for 100 million points. Crop or thin
+    /// larger inputs first.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Parameters of the densification, see `PtdParams` for the defaults.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, PtdParams};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_ptd_ground_reclassification(PtdParams::default().with_max_building_size(20.0));
+    /// ```
+    pub fn with_ptd_ground_reclassification(&mut self, params: PtdParams) -> &mut Self {
+        self.ground_filter = Some(GroundFilter::Ptd(params));
+        self
+    }
+
+    /// Classify the points that are not ground as low (3), medium (4) or high (5) vegetation by
+    /// their height above the ground, and as buildings (6) when they lie in a smooth plane with
+    /// their nearest neighbours. The ground is that of the configured ground reclassification, or
+    /// else the points classified as ground in the file. The building points are found in a pass
+    /// over the points, in tiles so only the points of a single tile are kept in memory, and the
+    /// building points are kept as a bit per input point. This will be applied when the builder
+    /// is used to create a point cloud, a file or the raster of `to_raster`, after the ground
+    /// reclassification.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Height bands of the vegetation and the shape of buildings, see `ObjectParams`
+    ///   for the defaults.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{ObjectParams, PointCloudBuilder, PtdParams};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder
+    ///     .with_ptd_ground_reclassification(PtdParams::default())
+    ///     .with_object_reclassification(ObjectParams::default().with_min_planarity(0.6));
+    /// ```
+    pub fn with_object_reclassification(&mut self, params: ObjectParams) -> &mut Self {
+        self.objects = Some(params);
+        self
+    }
+
+    /// Detect points that are duplicates of points before them in the input, within a tolerance,
+    /// and remove or withhold them. This works across the files of `from_files`, for overlapping
+    /// tiles. Duplicates are found in a pass over the points in the crop before anything else, in
+    /// tiles so only the points of a single tile are kept in memory, and the duplicates are kept as
+    /// a bit per input point. Removed duplicates are left out before the thinning. Withheld
+    /// duplicates are written with their withheld flag set, and like removed ones are not used by
+    /// the noise filters, the ground reclassification or the rasters.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Tolerance, whether the GPS time must match and what happens to duplicates.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{DuplicateParams, PointCloudBuilder};
+    /// let paths = ["test.las", "test.las"];
+    /// let mut builder = PointCloudBuilder::from_files(&paths).unwrap();
+    /// builder.with_duplicate_detection(DuplicateParams::default().with_tolerance(0.001));
+    /// let cloud = builder.to_cloud().unwrap();
+    /// assert_eq!(cloud.len() as u64, builder.get_metadata().point_count() / 2);
+    /// ```
+    pub fn with_duplicate_detection(&mut self, params: DuplicateParams) -> &mut Self {
+        self.duplicates = Some(params);
+        self
+    }
+
+    /// Add a noise filter that finds outliers, such as reflections below the ground or birds
+    /// above the canopy, and classifies them as low (7) or high (18) noise or leaves them out.
+    /// Noise filters run in a pass over the points before anything else, in tiles so only the
+    /// points of a single tile are kept in memory, and the noise points are kept as bits per input
+    /// point. The noise points are not used by the ground reclassification, DTMs, DSMs or the
+    /// height above ground, and the noise class replaces the class of the ground
+    /// reclassification. Each call adds a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter`: How noise points are found and what happens to them.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{NoiseAction, NoiseFilter, OutlierMethod, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// let statistical = OutlierMethod::Statistical { k: 8, multiplier: 2.0 };
+    /// let isolated = OutlierMethod::Isolated { radius: 5.0, height: 5.0 };
+    /// builder
+    ///     .with_noise_filter(NoiseFilter::new(statistical))
+    ///     .with_noise_filter(NoiseFilter::new(isolated).with_action(NoiseAction::Remove));
+    /// ```
+    pub fn with_noise_filter(&mut self, filter: NoiseFilter) -> &mut Self {
+        self.noise_filters.push(filter);
+        self
+    }
+
+    /// Compute the height above ground (HAG) of each point from a ground surface: the cloth of the
+    /// CSF ground reclassification, a TIN of the ground points or an external DTM. The height is
+    /// stored in a "HeightAboveGround" extra bytes field of 64 bit floats, or replaces z. This
+    /// will be applied when the builder is used to create a point cloud, a file or the raster of
+    /// `to_raster`.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The ground surface and whether the height above ground replaces z.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{GroundSurface, HagParams, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_height_above_ground(
+    ///     HagParams::default()
+    ///         .with_surface(GroundSurface::Tin)
+    ///         .with_replace_z(true),
+    /// );
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_height_above_ground(&mut self, params: HagParams) -> &mut Self {
+        self.hag = Some(params);
+        self
+    }
+
+    /// Compute geometric features of each point from the principal components of its
+    /// neighbourhood: the unit normal, the change of curvature, the planarity, linearity and
+    /// sphericity, and the verticality. They are stored in "NormalX", "NormalY", "NormalZ",
+    /// "Curvature", "Planarity", "Linearity", "Sphericity" and "Verticality" extra bytes fields of
+    /// 64 bit floats, which are NaN for points with fewer than 3 neighbours, duplicates and noise.
+    /// The neighbourhoods are made of the included points, in a pass over the points in tiles so
+    /// only the points of a single tile are kept in memory. This will be applied when the builder
+    /// is used to create a point cloud or a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `neighbourhood`: The k nearest points or the points within a radius, including the point
+    ///   itself.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{GeometricFeature, Neighbourhood, PointCloudBuilder, ThinningMethod};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder
+    ///     .with_thinning(ThinningMethod::EveryNth { nth: 10 })
+    ///     .with_geometric_features(Neighbourhood::Knn { k: 10 });
+    /// let cloud = builder.to_cloud().unwrap();
+    /// let normals = cloud.normals().unwrap();
+    /// let planarity = cloud.feature(GeometricFeature::Planarity).unwrap();
+    /// assert_eq!(normals.len(), planarity.len());
+    /// ```
+    pub fn with_geometric_features(&mut self, neighbourhood: Neighbourhood) -> &mut Self {
+        self.features = Some(neighbourhood);
+        self
+    }
+
+    /// Set the reporter that receives the progress of reading points and of the CSF simulation.
+    /// By default a progress bar is drawn when the `indicatif` feature is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `reporter`: Reporter to send progress updates to.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{LogReporter, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_progress_reporter(Box::new(LogReporter));
+    /// let cloud = builder.to_cloud().unwrap();
+    /// ```
+    pub fn with_progress_reporter(&mut self, reporter: Box<dyn ProgressReporter>) -> &mut Self {
+        self.progress = reporter;
+        self
+    }
+
+    /// Don't report any progress. Status messages are still emitted through the `log` crate.
+    pub fn with_silent_progress(&mut self) -> &mut Self {
+        self.with_progress_reporter(Box::new(SilentReporter))
+    }
+
+    /// Set the token that is checked while the builder runs. Cancelling it from another thread
+    /// stops the running `to_*` method, which then returns `LaszyError::Cancelled`.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: Token to check for cancellation. Keep a clone to be able to cancel the run.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{CancellationToken, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let token = CancellationToken::new();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_cancellation_token(token.clone());
+    /// // From another thread:
+    /// token.cancel();
+    /// ```
+    pub fn with_cancellation_token(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Get a clone of the token that is checked while the builder runs, to cancel a run from
+    /// another thread.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Set what to do when the output file already exists. By default it is overwritten. Output
+    /// is always written to a temporary file first, which replaces the target only when writing
+    /// succeeded.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy`: Policy from the OverwritePolicy enum.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{OverwritePolicy, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_overwrite_policy(OverwritePolicy::Skip);
+    /// ```
+    pub fn with_overwrite_policy(&mut self, policy: OverwritePolicy) -> &mut Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// Colour the points with the red, green and blue bands of a GeoTIFF orthophoto at their x and
+    /// y. Only the part of the image in the area of the points is read. When the point format of
+    /// the input has no colour, `to_file` writes the point format with colour that has its other
+    /// fields, such as 2 for format 0 or 7 for format 6.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: Parameters of the colourisation, see `ColourParams`.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use laszy::{ColourParams, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_colourisation(ColourParams::new("orthophoto.tif"));
+    /// builder.to_file("coloured.laz").unwrap();
+    /// ```
+    pub fn with_colourisation(&mut self, params: ColourParams) -> &mut Self {
+        self.colour = Some(params);
+        self
+    }
+
+    /// Set the amount of threads used for the ground reclassification. By default all cores are
+    /// used. The result of the reclassification doesn't depend on the amount of threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `threads`: Amount of threads to use, at least 1.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::PointCloudBuilder;
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_threads(2);
+    /// ```
+    pub fn with_threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Set how raster outputs such as a DTM are written: the NODATA value, the compression of
+    /// GeoTIFF files and their coordinate reference system.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: Options for raster outputs.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, RasterCompression, RasterOptions};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_raster_options(
+    ///     RasterOptions::default()
+    ///         .with_compression(RasterCompression::Lzw)
+    ///         .with_epsg(28992),
+    /// );
+    /// ```
+    pub fn with_raster_options(&mut self, options: RasterOptions) -> &mut Self {
+        self.raster = options;
+        self
+    }
+
+    /// Set the point format and LAS version of LAS/LAZ outputs. By default those of the input are
+    /// kept. Fields that the point format doesn't have are dropped with a warning, fields that the
+    /// input doesn't have get default values.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: Options for LAS/LAZ outputs.
+    ///
+    /// returns: &mut PointCloudBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{LasOptions, PointCloudBuilder};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_las_options(LasOptions::default().with_point_format(6).with_version(1, 4));
+    /// ```
+    pub fn with_las_options(&mut self, options: LasOptions) -> &mut Self {
+        self.las = options;
+        self
+    }
+
+    /// Check the configuration of the builder without reading any points. All `to_*` methods do
+    /// this before they start, so mistakes are reported immediately instead of after a full pass
+    /// over the file.
+    ///
+    /// returns: Result<(), LaszyError>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, ThinningMethod};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// builder.with_thinning(ThinningMethod::EveryNth { nth: 0 });
+    /// assert!(builder.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        self.validate_pipeline()?;
+        self.las.validate()?;
+        let format = self.output_point_format()?;
+        self.las.version(self.metadata.version(), format)?;
+        Ok(())
+    }
+
+    /// Check the crop, the threads and the configured stages, without the options of LAS/LAZ
+    /// outputs.
+    fn validate_pipeline(&self) -> Result<(), LaszyError> {
+        self.crop.validate(self.metadata.bounds())?;
+        self.thinning.validate()?;
+        if self.threads == Some(0) {
+            return Err(LaszyError::invalid_parameter(
+                "threads",
+                0,
+                "must be at least 1",
+            ));
+        }
+        if let Some(filter) = &self.ground_filter {
+            filter.validate()?;
+        }
+        if let Some(params) = &self.duplicates {
+            params.validate()?;
+        }
+        for filter in &self.noise_filters {
+            filter.validate()?;
+        }
+        if let Some(params) = &self.objects {
+            params.validate()?;
+            self.validate_surface(&params.surface)?;
+        }
+        if let Some(params) = &self.hag {
+            params.validate()?;
+            self.validate_surface(&params.surface)?;
+        }
+        if let Some(neighbourhood) = &self.features {
+            neighbourhood.validate()?;
+        }
+        if let Some(params) = &self.colour {
+            params.validate()?;
+        }
+        Ok(())
+    }
+
+    /// The point format of LAS/LAZ outputs, upgraded to one with colour for the colourisation.
+    fn output_point_format(&self) -> Result<las::point::Format, LaszyError> {
+        let format = self.las.point_format(self.metadata.point_format())?;
+        if self.colour.is_none() || format.has_color {
+            return Ok(format);
+        }
+        if let Some(point_format) = self.las.point_format {
+            return Err(LaszyError::invalid_parameter(
+                "point_format",
+                point_format,
+                "has no colour, which the colourisation needs",
+            ));
+        }
+        las_output::with_colour(&format)
+    }
+
+    /// The header of LAS/LAZ outputs, with the point format and LAS version of the output, and the
+    /// conversion of the points to that point format.
+    fn output_header(
+        &self,
+        is_compressed: bool,
+    ) -> Result<(las::Header, PointConversion), LaszyError> {
+        let input_format = self.metadata.point_format();
+        let mut builder = las::Builder::default();
+        builder.point_format = self.output_point_format()?;
+        builder.version = self
+            .las
+            .version(self.metadata.version(), builder.point_format)?;
+        let dropped = las_output::dropped_fields(input_format, &builder.point_format);
+        if !dropped.is_empty() {
+            log::warn!(
+                "The {} of the points are dropped, {} doesn't have them",
+                dropped.join(", "),
+                builder.point_format
+            );
+        }
+        let conversion = PointConversion::new(&builder.point_format);
+        builder.point_format.is_compressed = is_compressed;
+        // The extra bytes of the input are kept, the fields added by the builder come after them
+        let added = self.added_extra_bytes_fields();
+        builder.point_format.extra_bytes += added.iter().map(|f| f.size() as u16).sum::<u16>();
+        let mut fields = self.metadata.extra_bytes().to_vec();
+        fields.extend(added);
+        if !fields.is_empty() {
+            builder.vlrs.push(extra_bytes::extra_bytes_vlr(&fields));
+        }
+        Ok((builder.into_header()?, conversion))
+    }
+
+    /// Read the part of the image of the colourisation in the area of the points.
+    fn colouriser(&self) -> Result<Option<Colouriser>, LaszyError> {
+        let Some(params) = &self.colour else {
+            return Ok(None);
+        };
+        let (ll, ur) = self.get_crop_corners();
+        Colouriser::new(params, ll, ur).map(Some)
+    }
+
+    /// Check that the cloth can be used as ground surface when it is the `surface`.
+    fn validate_surface(&self, surface: &GroundSurface) -> Result<(), LaszyError> {
+        let has_cloth = matches!(self.ground_filter, Some(GroundFilter::Csf(_)));
+        if *surface == GroundSurface::Cloth && !has_cloth {
+            return Err(cloth_without_csf());
+        }
+        Ok(())
+    }
+
+    /// Run the ground filter of the configured ground reclassification, if there is one, without
+    /// the `excluded` points.
+    fn ground_classifier(
+        &self,
+        excluded: &ExcludedPoints,
+    ) -> Result<Option<GroundClassifier>, LaszyError> {
+        let Some(filter) = self.ground_filter else {
+            return Ok(None);
+        };
+        let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+        let classifier = match filter {
+            GroundFilter::Csf(params) => {
+                GroundClassifier::Cloth(self.simulate_cloth(params, excluded)?)
+            }
+            GroundFilter::Smrf(params) => {
+                let minimum = self.minimum_surface(params.cell_size, excluded)?;
+                let ground = self.in_thread_pool(|| {
+                    smrf::filter(minimum.finish(), &params, progress, cancellation)
+                })??;
+                GroundClassifier::Morphological(ground)
+            }
+            GroundFilter::Pmf(params) => {
+                let minimum = self.minimum_surface(params.cell_size, excluded)?;
+                let ground = self.in_thread_pool(|| {
+                    pmf::filter(minimum.finish(), &params, progress, cancellation)
+                })??;
+                GroundClassifier::Morphological(ground)
+            }
+            GroundFilter::Ptd(params) => {
+                let mut points = Vec::new();
+                self.for_each_included_point(
+                    excluded,
+                    "Reading points for the TIN densification...",
+                    |point| {
+                        points.push([point.x, point.y, point.z]);
+                        Ok(())
+                    },
+                )?;
+                let extent = self.get_crop_corners();
+                let tin = self.in_thread_pool(|| {
+                    ptd::filter(points, extent, &params, progress, cancellation)
+                })??;
+                GroundClassifier::Densified(tin)
+            }
+        };
+        Ok(Some(classifier))
+    }
+
+    /// Prepare the ground surface of the configured height above ground normalisation, if there is
+    /// one. A TIN needs a pass over the points to read the ground points.
+    fn hag_stage<'a>(
+        &self,
+        classifier: Option<&'a GroundClassifier>,
+        excluded: &ExcludedPoints,
+    ) -> Result<Option<HagStage<'a>>, LaszyError> {
+        let Some(params) = &self.hag else {
+            return Ok(None);
+        };
+        Ok(Some(HagStage {
+            heights: self.ground_heights(&params.surface, classifier, excluded)?,
+            replace_z: params.replace_z,
+        }))
+    }
+
+    /// Prepare the configured object classification, if there is one. Finding the building
+    /// points needs a pass over the points, and a TIN another one to read the ground points.
+    fn object_classifier<'a>(
+        &self,
+        classifier: Option<&'a GroundClassifier>,
+        excluded: &ExcludedPoints,
+    ) -> Result<Option<ObjectClassifier<'a>>, LaszyError> {
+        let Some(params) = &self.objects else {
+            return Ok(None);
+        };
+        let heights = self.ground_heights(&params.surface, classifier, excluded)?;
+        let (ll, ur) = self.get_crop_corners();
+        let mut tiles = PointTiles::new(ll, ur, TILE_SIZE, OBJECT_TILE_BUFFER, "laszy-objects")?;
+        let duplicates = excluded.duplicates.as_ref();
+        let task = "Sorting points into building tiles...";
+        self.for_each_included_point_with_id(duplicates, task, |id, point| {
+            if excluded.is_excluded(id) {
+                return Ok(());
+            }
+            let mut reclassified = point.clone();
+            if let Some(classifier) = classifier {
+                classifier.reclassify(&mut reclassified)?;
+            }
+            if !objects::is_reclassifiable(reclassified.classification) {
+                return Ok(());
+            }
+            let height = heights.height_above(point)?;
+            if height.is_some_and(|height| height >= params.building_min_height) {
+                tiles.add_point(id, point)?;
+            }
+            Ok(())
+        })?;
+        let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+        let buildings = self
+            .in_thread_pool(|| objects::find_buildings(tiles, params, progress, cancellation))??;
+        Ok(Some(ObjectClassifier {
+            heights,
+            buildings,
+            params: params.clone(),
+        }))
+    }
+
+    /// The ground `surface` of the height above ground of the points. A TIN needs a pass over the
+    /// points to read the ground points.
+    fn ground_heights<'a>(
+        &self,
+        surface: &GroundSurface,
+        classifier: Option<&'a GroundClassifier>,
+        excluded: &ExcludedPoints,
+    ) -> Result<GroundHeights<'a>, LaszyError> {
+        let cloth = classifier.and_then(GroundClassifier::cloth);
+        let heights = match (surface, cloth) {
+            (GroundSurface::Cloth, Some(cloth)) => GroundHeights::Cloth(cloth),
+            (GroundSurface::Cloth, None) => return Err(cloth_without_csf()),
+            (GroundSurface::Tin, _) => {
+                let task = "Reading ground points...";
+                let ground = self.ground_points(classifier, excluded, task, |_| {})?;
+                GroundHeights::Tin(GroundTin::new(ground)?)
+            }
+            (GroundSurface::Dtm { path }, _) => GroundHeights::Dtm(raster::read_raster(path)?),
+        };
+        Ok(heights)
+    }
+
+    /// The lowest of the included points, without the `excluded` points, in cells of `cell_size`
+    /// over the crop, for the morphological ground filters.
+    fn minimum_surface(
+        &self,
+        cell_size: f64,
+        excluded: &ExcludedPoints,
+    ) -> Result<MinimumSurface, LaszyError> {
+        let (ll, ur) = self.get_crop_corners();
+        let mut minimum = MinimumSurface::new(RasterGrid::covering(ll, ur, cell_size));
+        let task = "Creating minimum surface...";
+        self.for_each_included_point(excluded, task, |point| {
+            minimum.add_point(point);
+            Ok(())
+        })?;
+        Ok(minimum)
+    }
+
+    /// Simulate the cloth without the `excluded` points, in tiles when the parameters have a
+    /// tiling.
+    fn simulate_cloth(
+        &self,
+        params: CsfParams,
+        excluded: &ExcludedPoints,
+    ) -> Result<Cloth, LaszyError> {
+        match params.tiling {
+            Some(tiling) => {
+                let (ll, ur) = self.get_crop_corners();
+                let top_z = self.metadata.bounds().min.z - 10.0;
+                let mut cloth = TiledCloth::new(ll, ur, params, tiling)?;
+                let task = "Sorting points into CSF tiles...";
+                self.for_each_included_point(excluded, task, |point| cloth.add_point(point))?;
+                log::info!("Sorted points into tiles, starting simulation...");
+                let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+                self.in_thread_pool(|| cloth.simulate(top_z, progress, cancellation))??;
+                Ok(Cloth::Tiled(cloth))
+            }
+            None => Ok(Cloth::Surface(
+                self.perform_csf_simulation(params, excluded)?,
+            )),
+        }
+    }
+
+    fn perform_csf_simulation(
+        &self,
+        params: CsfParams,
+        excluded: &ExcludedPoints,
+    ) -> Result<ClothSurface, LaszyError> {
+        let (ll, ur) = self.get_crop_corners();
+        let top_z = self.metadata.bounds().min.z - 10.0;
+        let mut cloth = ClothSurface::initialize(ll, ur, params, top_z);
+        let count = self.for_each_included_point(excluded, "Creating CSF surface...", |point| {
+            cloth.add_point(point);
+            Ok(())
+        })?;
+        log::info!("Added {count} points to the cloth surface");
+        cloth.set_collision_heights()?;
+
+        log::info!("Created cloth surface, starting simulation...");
+        let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+        self.in_thread_pool(|| cloth.simulate(progress, cancellation))??;
+        Ok(cloth)
+    }
+
+    /// Pass all points inside the crop and included by the thinning, except the `excluded`
+    /// points, to `f`, and return the amount of points.
+    fn for_each_included_point(
+        &self,
+        excluded: &ExcludedPoints,
+        task: &str,
+        mut f: impl FnMut(&Point) -> Result<(), LaszyError>,
+    ) -> Result<usize, LaszyError> {
+        let mut count = 0_usize;
+        let duplicates = excluded.duplicates.as_ref();
+        self.for_each_included_point_with_id(duplicates, task, |id, point| {
+            if excluded.is_excluded(id) {
+                return Ok(());
+            }
+            count += 1;
+            f(point)
+        })?;
+        if count == 0 {
+            return Err(LaszyError::EmptyCloud(
+                "All included points are duplicates or noise, check the parameters of the \
+                 duplicate detection and the noise filters"
+                    .to_string(),
+            ));
+        }
+        Ok(count)
+    }
+
+    /// Pass all points inside the crop and included by the thinning to `f`, with where they are in
+    /// the input, and return the amount of points. The `duplicates` that are removed are left out
+    /// before the thinning, the ones that are withheld are passed to `f`.
+    fn for_each_included_point_with_id(
+        &self,
+        duplicates: Option<&DuplicatePoints>,
+        task: &str,
+        mut f: impl FnMut(PointId, &Point) -> Result<(), LaszyError>,
+    ) -> Result<usize, LaszyError> {
+        let mut count = 0_usize;
+        let mut thin_count = 0_usize;
+        self.for_each_point_in_crop(task, |id, point| {
+            if duplicates.is_some_and(|duplicates| duplicates.is_removed(id)) {
+                return Ok(());
+            }
+            let is_included = self.thinning.is_included(id, thin_count, self.thinning_seed);
+            thin_count += 1;
+            if is_included {
+                count += 1;
+                f(id, point)?;
+            }
+            Ok(())
+        })?;
+
+        if count == 0 {
+            return Err(LaszyError::EmptyCloud(
+                "The provided cropping and thinning methods resulted in no points being included in the simulation.".to_string()));
+        }
+        Ok(count)
+    }
+
+    /// Pass all points of all files inside the crop to `f`, with where they are in the input.
+    fn for_each_point_in_crop(
+        &self,
+        task: &str,
+        mut f: impl FnMut(PointId, &Point) -> Result<(), LaszyError>,
+    ) -> Result<(), LaszyError> {
+        self.progress.start(task, Some(self.metadata.point_count()));
+        let pb_step = progress::step_size(self.metadata.point_count(), 100);
+        let mut count = 0_usize;
+        for (file_index, filepath) in self.filepaths.iter().enumerate() {
+            let file = File::open(filepath).in_file(filepath)?;
+            let mut reader = Reader::new(BufReader::new(file)).in_file(filepath)?;
+            for (i, point) in reader.points().enumerate() {
+                self.cancellation.check()?;
+                if (i as u64 + 1).is_multiple_of(pb_step) {
+                    self.progress.advance(pb_step);
+                }
+                let point = point.at_point(filepath, i as u64)?;
+                if !self.crop.is_in_bounds(&point) {
+                    continue;
+                }
+                count += 1;
+                f((file_index as u32, i as u64), &point)?;
+            }
+        }
+        self.progress.finish(&format!("Read {count} points"));
+        Ok(())
+    }
+
+    /// Find the points the processing leaves out with the configured duplicate detection and
+    /// noise filters. Both sort the points into tiles in temporary files, which are then searched
+    /// one at a time.
+    fn excluded_points(&self) -> Result<ExcludedPoints, LaszyError> {
+        let duplicates = self.duplicate_points()?;
+        let noise = self.noise_points(duplicates.as_ref())?;
+        Ok(ExcludedPoints { duplicates, noise })
+    }
+
+    /// Find the duplicate points inside the crop, if a duplicate detection is configured. This is
+    /// done before the thinning, so the thinning doesn't depend on the duplicates.
+    fn duplicate_points(&self) -> Result<Option<DuplicatePoints>, LaszyError> {
+        let Some(params) = self.duplicates else {
+            return Ok(None);
+        };
+        let (ll, ur) = self.get_crop_corners();
+        let buffer = params.tolerance;
+        let mut tiles = PointTiles::new(ll, ur, TILE_SIZE, buffer, "laszy-duplicates")?;
+        self.for_each_point_in_crop("Sorting points into duplicate tiles...", |id, point| {
+            tiles.add_point(id, point)
+        })?;
+        let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+        let duplicates =
+            self.in_thread_pool(|| duplicates::find(tiles, &params, progress, cancellation))??;
+        Ok(Some(duplicates))
+    }
+
+    /// Find the noise points with the configured noise filters, if there are any, without the
+    /// `duplicates`.
+    fn noise_points(
+        &self,
+        duplicates: Option<&DuplicatePoints>,
+    ) -> Result<Option<NoisePoints>, LaszyError> {
+        if self.noise_filters.is_empty() {
+            return Ok(None);
+        }
+        let (ll, ur) = self.get_crop_corners();
+        let buffer = noise::tile_buffer(&self.noise_filters);
+        let mut tiles = PointTiles::new(ll, ur, TILE_SIZE, buffer, "laszy-noise")?;
+        let task = "Sorting points into noise tiles...";
+        self.for_each_included_point_with_id(duplicates, task, |id, point| {
+            if duplicates.is_some_and(|duplicates| duplicates.is_duplicate(id)) {
+                return Ok(());
+            }
+            tiles.add_point(id, point)
+        })?;
+        let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+        let filters = &self.noise_filters;
+        let noise =
+            self.in_thread_pool(|| noise::find(tiles, filters, progress, cancellation))??;
+        Ok(Some(noise))
+    }
+
+    /// Compute the geometric features of the included points, if they are configured, without the
+    /// `excluded` points.
+    fn feature_store(&self, excluded: &ExcludedPoints) -> Result<Option<FeatureStore>, LaszyError> {
+        let Some(neighbourhood) = self.features else {
+            return Ok(None);
+        };
+        let (ll, ur) = self.get_crop_corners();
+        let buffer = neighbourhood.buffer();
+        let mut tiles = PointTiles::new(ll, ur, TILE_SIZE, buffer, "laszy-features")?;
+        let task = "Sorting points into feature tiles...";
+        let duplicates = excluded.duplicates.as_ref();
+        self.for_each_included_point_with_id(duplicates, task, |id, point| {
+            if excluded.is_excluded(id) {
+                return Ok(());
+            }
+            tiles.add_point(id, point)
+        })?;
+        let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+        let store = self
+            .in_thread_pool(|| features::compute(tiles, neighbourhood, progress, cancellation))??;
+        Ok(Some(store))
+    }
+
+    /// The fields the builder adds to the extra bytes of the points, after those of the input.
+    fn added_extra_bytes_fields(&self) -> Vec<ExtraBytesField> {
+        let mut fields: Vec<_> = self
+            .hag
+            .as_ref()
+            .and_then(HagParams::extra_bytes_field)
+            .into_iter()
+            .collect();
+        if self.features.is_some() {
+            fields.extend(features::extra_bytes_fields());
+        }
+        fields
+    }
+
+    /// Run `f` in a thread pool with the configured amount of threads, or in the global thread
+    /// pool when no amount is configured.
+    fn in_thread_pool<T: Send>(&self, f: impl FnOnce() -> T + Send) -> Result<T, LaszyError> {
+        match self.threads {
+            Some(threads) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| LaszyError::invalid_parameter("threads", threads, e))?;
+                Ok(pool.install(f))
+            }
+            None => Ok(f()),
+        }
+    }
+
+    fn get_crop_corners(&self) -> ((f64, f64), (f64, f64)) {
+        let ll;
+        let ur;
+        match self.crop {
+            CroppingMethod::None => {
+                ll = (self.metadata.bounds().min.x, self.metadata.bounds().min.y);
+                ur = (self.metadata.bounds().max.x, self.metadata.bounds().max.y);
+            }
+            CroppingMethod::BoundingBox {
+                lower_left,
+                upper_right,
+            } => {
+                ll = (lower_left.0, lower_left.1);
+                ur = (upper_right.0, upper_right.1);
+            }
+        }
+        (ll, ur)
+    }
+
+    /// Create a DTM (Digital Terrain Model) raster from the point cloud. This will use the
+    /// provided cropping and thinning methods and use a CSF simulation to classify ground points.
+    /// The raster is written as set with `with_raster_options`.
+    ///
+    /// # Arguments
+    ///
+    /// * `filepath`: Filepath to the raster to create. Must end in .asc for an ESRI ASCII grid, or
+    ///   in .tif or .tiff for a GeoTIFF.
+    /// * `params`: Parameters of the cloth simulation. The cell size of the DTM is the cloth
+    ///   resolution and the classification threshold is not used.
     ///
-    /// returns: Result<PointCloudBuilder, LaszyError>
+    /// returns: Result<(), LaszyError>
     ///
     /// # Examples
     ///
     /// ```
-    /// use laszy::PointCloudBuilder;
+    /// use laszy::{CsfParams, PointCloudBuilder};
     /// let path = "test.las".to_string();
-    /// let builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// let re = builder.to_dtm_using_csf(&"test.asc".to_string(), CsfParams::default());
+    /// assert!(re.is_ok());
     /// ```
-    pub fn from_file(filepath: &String) -> Result<Self, LaszyError> {
-        let file = File::open(&filepath)?;
-        let reader = Reader::new(BufReader::new(file))?;
-        let header = reader.header();
-        let metadata = Metadata::from_las_header(&header);
-        Ok(PointCloudBuilder {
-            filepaths: vec![filepath.clone()],
-            metadata,
-            crop: CroppingMethod::None,
-            thinning: ThinningMethod::None,
-            csf_filter: None,
-            cloud: None,
-            writer: None,
-        })
-    }
-
-    /// After initializing a builder from a file, get the metadata from the file.
-    pub fn get_metadata(&self) -> &Metadata {
-        &self.metadata
+    pub fn to_dtm_using_csf(&self, filepath: &str, params: CsfParams) -> Result<(), LaszyError> {
+        self.validate_pipeline()?;
+        params.validate()?;
+        let format = RasterFormat::from_path(filepath)?;
+        output::write_atomically(Path::new(filepath), self.overwrite, |temp_path| {
+            let excluded = self.excluded_points()?;
+            let mut cloth = self.simulate_cloth(params, &excluded)?;
+            let mut source = cloth.raster_source();
+            let geo_keys = self.metadata.geo_keys();
+            raster::write_raster(temp_path, format, source.as_mut(), &self.raster, geo_keys)
+                .in_file(filepath)
+        })?;
+        Ok(())
     }
 
-    /// Set the cropping method for the builder. This will be applied when the builder is used to
-    /// create a point cloud.
+    /// Create a DTM (Digital Terrain Model) raster by interpolating the ground points of the point
+    /// cloud. This will use the provided cropping and thinning methods. When a ground
+    /// reclassification with CSF, SMRF or PMF is configured the points it classifies as ground are
+    /// used, otherwise the points that are classified as ground in the file. The ground points are
+    /// kept in memory to interpolate the DTM.
     ///
     /// # Arguments
     ///
-    /// * `crop`: Cropping method to use
+    /// * `filepath`: Filepath to the raster to create. Must end in .asc for an ESRI ASCII grid, or
+    ///   in .tif or .tiff for a GeoTIFF.
+    /// * `params`: Resolution, interpolation method and void filling of the DTM.
+    ///
+    /// returns: Result<(), LaszyError>
     ///
     /// # Examples
     ///
     /// ```
-    /// use laszy::{PointCloudBuilder, CroppingMethod};
+    /// use laszy::{CsfParams, DtmParams, Interpolation, PointCloudBuilder};
     /// let path = "test.las".to_string();
     /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
-    /// builder.with_crop(CroppingMethod::BoundingBox {           
-    ///     lower_left: (183_551.47, 332_414.45),
-    ///      upper_right: (183_564.09, 332_424.13)});
-    /// let cloud = builder.to_cloud().unwrap();
+    /// builder.with_csf_ground_reclassification(CsfParams::default());
+    /// let params = DtmParams::default().with_interpolation(Interpolation::Tin);
+    /// let re = builder.to_dtm(&"dtm.asc".to_string(), params);
+    /// # std::fs::remove_file("dtm.asc").unwrap();
+    /// assert!(re.is_ok());
     /// ```
-    pub fn with_crop(&mut self, crop: CroppingMethod) -> &mut Self {
-        self.crop = crop;
-        self
+    pub fn to_dtm(&self, filepath: &str, params: DtmParams) -> Result<(), LaszyError> {
+        self.validate()?;
+        params.validate()?;
+        let format = RasterFormat::from_path(filepath)?;
+        output::write_atomically(Path::new(filepath), self.overwrite, |temp_path| {
+            let excluded = self.excluded_points()?;
+            let classifier = self.ground_classifier(&excluded)?;
+            let task = "Reading ground points...";
+            let ground = self.ground_points(classifier.as_ref(), &excluded, task, |_| {})?;
+            let (ll, ur) = self.get_crop_corners();
+            let grid = RasterGrid::covering(ll, ur, params.resolution);
+            let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+            let mut dtm = self.in_thread_pool(|| {
+                dtm::interpolate(ground, grid, &params, progress, cancellation)
+            })??;
+            let geo_keys = self.metadata.geo_keys();
+            raster::write_raster(temp_path, format, &mut dtm, &self.raster, geo_keys)
+                .in_file(filepath)
+        })?;
+        Ok(())
     }
 
-    /// Set the thinning method for the builder. This will be applied when the builder is used to
-    /// create a point cloud.
+    /// Create a DSM (Digital Surface Model) raster with the height of the highest point in each
+    /// cell, in a single pass over the points. This will use the provided cropping and thinning
+    /// methods.
     ///
     /// # Arguments
     ///
-    /// * `method`: Method to use for thinning, from the ThinningMethod enum.
+    /// * `filepath`: Filepath to the raster to create. Must end in .asc for an ESRI ASCII grid, or
+    ///   in .tif or .tiff for a GeoTIFF.
+    /// * `params`: Resolution and pit-free gap filling of the DSM.
     ///
-    /// returns: &mut PointCloudBuilder
+    /// returns: Result<(), LaszyError>
     ///
     /// # Examples
     ///
     /// ```
-    /// use laszy::{PointCloudBuilder, ThinningMethod};
+    /// use laszy::{DsmParams, PointCloudBuilder};
     /// let path = "test.las".to_string();
-    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
-    /// builder.with_thinning(ThinningMethod::Random { percent: 0.5 });
-    /// let cloud = builder.to_cloud().unwrap();
+    /// let builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// let re = builder.to_dsm(&"dsm.asc".to_string(), DsmParams::default());
+    /// # std::fs::remove_file("dsm.asc").unwrap();
+    /// assert!(re.is_ok());
     /// ```
-    pub fn with_thinning(&mut self, method: ThinningMethod) -> &mut Self {
-        self.thinning = method;
-        self
+    pub fn to_dsm(&self, filepath: &str, params: DsmParams) -> Result<(), LaszyError> {
+        self.validate()?;
+        params.validate()?;
+        let format = RasterFormat::from_path(filepath)?;
+        output::write_atomically(Path::new(filepath), self.overwrite, |temp_path| {
+            let excluded = self.excluded_points()?;
+            let (ll, ur) = self.get_crop_corners();
+            let mut dsm = SurfaceModel::new(RasterGrid::covering(ll, ur, params.resolution));
+            self.for_each_included_point(&excluded, "Creating DSM...", |point| {
+                dsm.add_point(point);
+                Ok(())
+            })?;
+            let mut dsm = dsm.finish(&params);
+            let geo_keys = self.metadata.geo_keys();
+            raster::write_raster(temp_path, format, &mut dsm, &self.raster, geo_keys)
+                .in_file(filepath)
+        })?;
+        Ok(())
     }
 
-    /// Set the cloth surface filter for the builder. This will be applied when the builder is used
-    /// to create a point cloud.
+    /// Create a CHM (Canopy Height Model) raster with the height of the surface above the ground:
+    /// a DSM minus a DTM interpolated from the ground points, see `to_dsm` and `to_dtm`. The DSM
+    /// and the ground points are read in the same pass over the points, after the ground filter
+    /// when a ground reclassification is configured.
     ///
     /// # Arguments
     ///
-    /// * `rigidness`: Value between 0.0 and 1.0. When 0.0, the cloth surface filter will classify
-    /// all points as ground. When 1.0, the cloth is at maximum rigidity and will classify points
-    /// as ground in a strict manner.
-    /// * `cloth_resolution`: Distance in meters between the cloth surface points.
-    /// * `simulation_threshold`: If the largest amount any particle moved during the simulation is
-    /// less than this value, the simulation will stop.
-    /// * `classification_threshold`: The maximum distance in meters between a point and the cloth
-    /// surface for the point to be classified as ground.
+    /// * `filepath`: Filepath to the raster to create. Must end in .asc for an ESRI ASCII grid, or
+    ///   in .tif or .tiff for a GeoTIFF.
+    /// * `params`: Resolution and pit-free gap filling of the DSM, which are also the cells of the
+    ///   CHM.
+    /// * `dtm_params`: Interpolation and void filling of the DTM. Its resolution is not used, the
+    ///   DTM is interpolated at the cells of the DSM.
     ///
-    /// returns: &mut PointCloudBuilder
+    /// returns: Result<(), LaszyError>
     ///
     /// # Examples
     ///
     /// ```
-    /// use laszy::PointCloudBuilder;
+    /// use laszy::{CsfParams, DsmParams, DtmParams, PointCloudBuilder};
     /// let path = "test.las".to_string();
     /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
-    /// builder.with_csf_ground_reclassification(0.5, 5.0, 0.01, 0.5);
-    /// let cloud = builder.to_cloud().unwrap();
+    /// builder.with_csf_ground_reclassification(CsfParams::default());
+    /// let params = DsmParams::default().with_pit_free(true);
+    /// let re = builder.to_chm(&"chm.tif".to_string(), params, DtmParams::default());
+    /// # std::fs::remove_file("chm.tif").unwrap();
+    /// assert!(re.is_ok());
     /// ```
-    pub fn with_csf_ground_reclassification(
-        &mut self,
-        rigidness: f64,
-        cloth_resolution: f64,
-        simulation_threshold: f64,
-        classification_threshold: f64,
-    ) -> &mut Self {
-        self.csf_filter = Some((
-            rigidness,
-            cloth_resolution,
-            simulation_threshold,
-            classification_threshold,
-        ));
-        self
-    }
-
-    fn perform_csf_simulation(
+    pub fn to_chm(
         &self,
-        rigidness: f64,
-        cell_resolution: f64,
-        simulation_threshold: f64,
-        classification_threshold: f64,
-    ) -> Result<ClothSurface, LaszyError> {
-        let (ll, ur) = self.get_crop_corners();
-        let top_z = self.metadata.bounds().min.z - 10.0;
-        let mut cloth = ClothSurface::initialize(
-            ll,
-            ur,
-            cell_resolution,
-            simulation_threshold,
-            classification_threshold,
-            rigidness,
-            top_z,
-        );
-
-        println!("Creating CSF surface...");
-        let pb = indicatif::ProgressBar::new(100);
-        let pb_step = (self.metadata.point_count() / 100) as usize;
-        let mut count = 0_usize;
-        let mut thin_count = 0_usize;
-        for filepath in &self.filepaths {
-            let file = File::open(&filepath)?;
-            let mut reader = Reader::new(BufReader::new(file))?;
-            let mut point_iter = reader.points();
-            for (i, point) in point_iter.enumerate() {
-                if i % pb_step == 0 {
-                    pb.inc(1);
-                }
-                let point = point?;
-                if !self.crop.is_in_bounds(&point) {
-                    continue;
-                }
-                if !self.thinning.is_included(thin_count) {
-                    thin_count += 1;
-                    continue;
-                }
-                thin_count += 1;
-
-                count += 1;
-                cloth.set_max_z_if_closest_to_particle(&point);
-            }
-        }
-        pb.finish();
-
-        if count == 0 {
-            return Err(LaszyError::EmptyCloud(
-                "The provided cropping and thinning methods resulted in no points being included in the simulation.".to_string()));
-        }
-        cloth.fix_zero_max_heights();
-
-        println!("Created cloth surface, starting simulation...");
-        cloth.simulate();
-        Ok(cloth)
-    }
-
-    fn get_crop_corners(&self) -> ((f64, f64), (f64, f64)) {
-        let ll;
-        let ur;
-        match self.crop {
-            CroppingMethod::None => {
-                ll = (self.metadata.bounds().min.x, self.metadata.bounds().min.y);
-                ur = (self.metadata.bounds().max.x, self.metadata.bounds().max.y);
-            }
-            CroppingMethod::BoundingBox {
-                lower_left,
-                upper_right,
-            } => {
-                ll = (lower_left.0, lower_left.1);
-                ur = (upper_right.0, upper_right.1);
-            }
-        }
-        (ll, ur)
+        filepath: &str,
+        params: DsmParams,
+        dtm_params: DtmParams,
+    ) -> Result<(), LaszyError> {
+        self.validate()?;
+        params.validate()?;
+        dtm_params.validate()?;
+        let format = RasterFormat::from_path(filepath)?;
+        output::write_atomically(Path::new(filepath), self.overwrite, |temp_path| {
+            let (ll, ur) = self.get_crop_corners();
+            let grid = RasterGrid::covering(ll, ur, params.resolution);
+            let mut dsm = SurfaceModel::new(grid);
+            let excluded = self.excluded_points()?;
+            let classifier = self.ground_classifier(&excluded)?;
+            let task = "Creating DSM and reading ground points...";
+            let ground = self.ground_points(classifier.as_ref(), &excluded, task, |point| {
+                dsm.add_point(point)
+            })?;
+            let dsm = dsm.finish(&params);
+            let (progress, cancellation) = (self.progress.as_ref(), &self.cancellation);
+            let dtm = self.in_thread_pool(|| {
+                dtm::interpolate(ground, grid, &dtm_params, progress, cancellation)
+            })??;
+            let mut chm = dsm::canopy_heights(dsm, &dtm);
+            let geo_keys = self.metadata.geo_keys();
+            raster::write_raster(temp_path, format, &mut chm, &self.raster, geo_keys)
+                .in_file(filepath)
+        })?;
+        Ok(())
     }
 
-    /// Create an .asc DTM (Digital Terrain Model) file from the point cloud. This will use the
-    /// provided cropping and thinning methods and use a CSF simulation to classify ground points.
+    /// Create a raster with a band for each aggregation of the points in its cells, such as the
+    /// point density, the mean intensity or the most common class. The points are read in a
+    /// single pass, after the ground filter when a ground reclassification is configured, in
+    /// which case the reclassified classes are aggregated. With a height above ground
+    /// normalisation that replaces z, the aggregations of z are of the heights above ground. This
+    /// will use the provided cropping and thinning methods.
     ///
     /// # Arguments
     ///
-    /// * `filepath`: Filepath to the .asc file to create, must end in .asc.
-    /// * `rigidness`: Value between 0.0 and 1.0. When 0.0, the cloth surface filter will classify
-    /// all points as ground. When 1.0, the cloth is at maximum rigidity and will classify points
-    /// as ground in a strict manner.
-    /// * `cloth_resolution`: Distance in meters between the cloth surface points.
-    /// * `distance_threshold`: If the largest amount any particle moved during the simulation is
-    /// less than this value (meters), the simulation will stop.
+    /// * `filepath`: Filepath to the raster to create. Must end in .tif or .tiff for a GeoTIFF, or
+    ///   in .asc for an ESRI ASCII grid, which can only have a single aggregation.
+    /// * `resolution`: Cell size of the raster.
+    /// * `aggregations`: The aggregations to write, one band each.
     ///
     /// returns: Result<(), LaszyError>
     ///
     /// # Examples
     ///
     /// ```
-    /// use laszy::PointCloudBuilder;
+    /// use laszy::{Aggregation, Attribute, PointCloudBuilder, Reducer};
     /// let path = "test.las".to_string();
-    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
-    /// let re = builder.to_dtm_using_csf(&"test.asc".to_string(), 0.5, 5.0, 0.01);
+    /// let builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// let aggregations = [
+    ///     Aggregation::count(),
+    ///     Aggregation::new(Attribute::Intensity, Reducer::Mean),
+    ///     Aggregation::new(Attribute::Classification, Reducer::Mode),
+    /// ];
+    /// let re = builder.to_raster(&"qa.tif".to_string(), 2.0, &aggregations);
+    /// # std::fs::remove_file("qa.tif").unwrap();
     /// assert!(re.is_ok());
     /// ```
-    pub fn to_dtm_using_csf(
+    pub fn to_raster(
         &self,
-        filepath: &String,
-        rigidness: f64,
-        cloth_resolution: f64,
-        distance_threshold: f64,
+        filepath: &str,
+        resolution: f64,
+        aggregations: &[Aggregation],
     ) -> Result<(), LaszyError> {
-        let cloth =
-            self.perform_csf_simulation(rigidness, cloth_resolution, distance_threshold, 0.0)?;
-        cloth.to_asc(filepath);
+        self.validate()?;
+        if !(resolution > 0.0 && resolution.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "resolution",
+                resolution,
+                "must be larger than 0.0",
+            ));
+        }
+        if aggregations.is_empty() {
+            return Err(LaszyError::invalid_parameter(
+                "aggregations",
+                0,
+                "must have at least one aggregation",
+            ));
+        }
+        for aggregation in aggregations {
+            aggregation.validate()?;
+        }
+        let format = RasterFormat::from_path(filepath)?;
+        if format == RasterFormat::Asc && aggregations.len() > 1 {
+            return Err(LaszyError::invalid_parameter(
+                "aggregations",
+                aggregations.len(),
+                "an ESRI ASCII grid has a single band, write a GeoTIFF for more aggregations",
+            ));
+        }
+        output::write_atomically(Path::new(filepath), self.overwrite, |temp_path| {
+            let excluded = self.excluded_points()?;
+            let classifier = self.ground_classifier(&excluded)?;
+            let objects = self.object_classifier(classifier.as_ref(), &excluded)?;
+            let hag = self.hag_stage(classifier.as_ref(), &excluded)?;
+            let colours = self.colouriser()?;
+            let (ll, ur) = self.get_crop_corners();
+            let grid = RasterGrid::covering(ll, ur, resolution);
+            let mut aggregator = CellAggregator::new(grid, aggregations);
+            let duplicates = excluded.duplicates.as_ref();
+            let task = "Aggregating points...";
+            self.for_each_included_point_with_id(duplicates, task, |id, point| {
+                if excluded.is_duplicate(id) {
+                    return Ok(());
+                }
+                let is_unchanged = classifier.is_none()
+                    && objects.is_none()
+                    && hag.is_none()
+                    && colours.is_none();
+                if is_unchanged && excluded.noise.is_none() {
+                    aggregator.add_point(point);
+                    return Ok(());
+                }
+                let mut point = point.clone();
+                if let Some(classifier) = &classifier {
+                    classifier.reclassify(&mut point)?;
+                }
+                if let Some(objects) = &objects {
+                    objects.reclassify(id, &mut point)?;
+                }
+                if !excluded.apply(id, &mut point) {
+                    return Ok(());
+                }
+                if let Some(hag) = &hag {
+                    if !hag.apply(&mut point)? {
+                        return Ok(());
+                    }
+                }
+                if let Some(colours) = &colours {
+                    colours.apply(&mut point);
+                }
+                aggregator.add_point(&point);
+                Ok(())
+            })?;
+            let mut raster = aggregator.finish();
+            let geo_keys = self.metadata.geo_keys();
+            raster::write_raster(temp_path, format, &mut raster, &self.raster, geo_keys)
+                .in_file(filepath)
+        })?;
         Ok(())
     }
 
+    /// The x, y and z of the included points that are ground, as classified by the ground filter
+    /// when one is configured, or else by the classification in the file. All included points
+    /// except the `excluded` points are also passed to `f`, to use the same pass over the points.
+    fn ground_points(
+        &self,
+        classifier: Option<&GroundClassifier>,
+        excluded: &ExcludedPoints,
+        task: &str,
+        mut f: impl FnMut(&Point),
+    ) -> Result<Vec<[f64; 3]>, LaszyError> {
+        let mut ground = Vec::new();
+        self.for_each_included_point(excluded, task, |point| {
+            f(point);
+            let is_ground = match classifier {
+                Some(classifier) => classifier.is_ground_point(point)?,
+                None => point.classification == Classification::Ground,
+            };
+            if is_ground {
+                ground.push([point.x, point.y, point.z]);
+            }
+            Ok(())
+        })?;
+        if ground.is_empty() {
+            return Err(LaszyError::EmptyCloud(
+                "None of the included points are classified as ground, use a ground \
+                 reclassification to classify them"
+                    .to_string(),
+            ));
+        }
+        log::info!("Read {} ground points", ground.len());
+        Ok(ground)
+    }
+
     /// Run the builder with the specified configuration and return a PointCloud.
     ///
     /// returns: Result<PointCloud, LaszyError>
@@ -273,17 +1547,22 @@ impl PointCloudBuilder {
     /// let cloud = builder.to_cloud().unwrap();
     /// ```
     pub fn to_cloud(&mut self) -> Result<PointCloud, LaszyError> {
-        self.cloud = Some(PointCloud::new());
+        self.validate()?;
+        let mut cloud = PointCloud::new();
+        let mut fields = self.metadata.extra_bytes().to_vec();
+        fields.extend(self.added_extra_bytes_fields());
+        cloud.set_extra_bytes(fields);
+        self.cloud = Some(cloud);
         let loaded_points = self.run_building_iterator("Processing points...")?;
-        println!(
-            "Succesfully loaded {} points into point cloud.",
-            loaded_points
-        );
-        Ok(self.cloud.take().unwrap())
+        log::info!("Succesfully loaded {loaded_points} points into point cloud.");
+        Ok(self.cloud.take().unwrap_or_default())
     }
 
     /// Run the builder with the specified configuration and save it as a .las/.laz file. If you
-    /// want compression, the filepath must end in .laz.
+    /// want compression, the filepath must end in .laz. The file is only created once all points
+    /// have been written, a failed or cancelled run leaves no file behind. When the file already
+    /// exists, the overwrite policy decides what happens. The point format and LAS version are
+    /// those of the input, unless they are set with `with_las_options`.
     ///
     /// returns: Result<(), LaszyError>
     ///
@@ -297,88 +1576,202 @@ impl PointCloudBuilder {
     /// // Use a filepath ending in .las or .laz, depending on whether you want to compress the file.
     /// let cloud = builder.to_file(&"test_output.las".to_string()).unwrap();
     /// ```
-    pub fn to_file(&mut self, filepath: &String) -> Result<(), LaszyError> {
+    pub fn to_file(&mut self, filepath: &str) -> Result<(), LaszyError> {
         if !filepath.ends_with(".las") && !filepath.ends_with(".laz") {
             return Err(LaszyError::InvalidFileExtension(
                 "Filepath must end in .las or .laz".to_string(),
             ));
         }
-        let file = std::fs::File::create(filepath)?;
-        let mut builder = las::Builder::default();
-        builder.point_format = self.metadata.point_format().clone();
-        builder.point_format.is_compressed = filepath.ends_with(".laz");
-        let writer = las::Writer::new(file, builder.into_header()?)?;
-        self.writer = Some(writer);
-        let loaded_points = self.run_building_iterator("Writing points...")?;
-        self.writer.take();
-        println!("Succesfully wrote {} points to {}", loaded_points, filepath);
+        self.validate()?;
+        let (header, conversion) = self.output_header(filepath.ends_with(".laz"))?;
+        let written = output::write_atomically(Path::new(filepath), self.overwrite, |temp_path| {
+            let file = File::create(temp_path).in_file(filepath)?;
+            self.writer = Some(las::Writer::new(file, header).in_file(filepath)?);
+            self.conversion = Some(conversion);
+            let result = self.run_building_iterator("Writing points...");
+            self.conversion = None;
+            if let (Some(mut writer), Ok(_)) = (self.writer.take(), &result) {
+                writer.close().in_file(filepath)?;
+            }
+            result
+        })?;
+        let Some(loaded_points) = written else {
+            return Ok(());
+        };
+        log::info!("Succesfully wrote {loaded_points} points to {filepath}");
         Ok(())
     }
 
+    /// Run the builder with the specified configuration and split the points over a regular grid
+    /// of .las/.laz files in `directory`, which is created when it doesn't exist. Each tile is
+    /// only created once all of its points have been written, a failed or cancelled run leaves no
+    /// tiles behind that are not complete. The overwrite policy decides what happens to tiles
+    /// that already exist, and the point format and LAS version are set as for `to_file`.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory`: Directory to write the tiles to.
+    /// * `params`: Size, alignment, buffer and names of the tiles, see `TileParams`.
+    ///
+    /// returns: Result<Vec<String>, LaszyError>, the paths of the tiles that were written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{PointCloudBuilder, TileParams};
+    /// let path = "test.las".to_string();
+    /// let mut builder = PointCloudBuilder::from_file(&path).unwrap();
+    /// let params = TileParams::new(20.0).with_name("tile_{minx}_{miny}.las");
+    /// let tiles = builder.to_tiles("tiles", params).unwrap();
+    /// # std::fs::remove_dir_all("tiles").unwrap();
+    /// assert_eq!(tiles.len(), 12);
+    /// ```
+    pub fn to_tiles(
+        &mut self,
+        directory: &str,
+        params: TileParams,
+    ) -> Result<Vec<String>, LaszyError> {
+        self.validate()?;
+        params.validate()?;
+        let (header, conversion) = self.output_header(params.is_compressed())?;
+        std::fs::create_dir_all(directory).in_file(directory)?;
+        self.tiles = Some(TileWriter::new(
+            Path::new(directory),
+            params,
+            header,
+            self.overwrite,
+        ));
+        self.conversion = Some(conversion);
+        let result = self.run_building_iterator("Writing tiles...");
+        self.conversion = None;
+        // Dropping the tiles of a failed run removes the parts that were written
+        let tiles = self.tiles.take();
+        let loaded_points = result?;
+        let paths = tiles
+            .map(TileWriter::finish)
+            .transpose()?
+            .unwrap_or_default();
+        log::info!(
+            "Wrote {loaded_points} points to {} tiles in {directory}",
+            paths.len()
+        );
+        Ok(paths)
+    }
+
     fn run_building_iterator(&mut self, message: &str) -> Result<usize, LaszyError> {
-        let cloth = match self.csf_filter {
-            Some((
-                rigidness,
-                grid_resolution_meters,
-                simulation_threshold,
-                classification_threshold,
-            )) => Some(self.perform_csf_simulation(
-                rigidness as f64,
-                grid_resolution_meters,
-                simulation_threshold,
-                classification_threshold,
-            )?),
-            None => None,
-        };
+        let excluded = self.excluded_points()?;
+        let classifier = self.ground_classifier(&excluded)?;
+        let objects = self.object_classifier(classifier.as_ref(), &excluded)?;
+        let hag = self.hag_stage(classifier.as_ref(), &excluded)?;
+        let mut features = self.feature_store(&excluded)?.map(FeatureStore::reader);
+        let colours = self.colouriser()?;
 
-        let mut pb = indicatif::ProgressBar::new(self.metadata.point_count() as u64);
-        println!("{message}");
-        let pb_increment = self.metadata.point_count() / 1000;
+        self.progress
+            .start(message, Some(self.metadata.point_count()));
+        let pb_increment = progress::step_size(self.metadata.point_count(), 1000);
         let mut count = 0_usize;
         let mut thin_count = 0_usize;
-        for filepath in &self.filepaths {
-            let file = File::open(&filepath)?;
-            let mut reader = Reader::new(BufReader::new(file))?;
-            let points = reader.points();
-            for (i, point) in points.enumerate() {
-                let mut point = point?;
-                if i % pb_increment as usize == 0 {
-                    pb.inc(pb_increment);
+        let mut without_ground = 0_usize;
+        let mut changed = 0_usize;
+        let mut without_colour = 0_usize;
+        for (file_index, filepath) in self.filepaths.iter().enumerate() {
+            let file = File::open(filepath).in_file(filepath)?;
+            let mut reader = Reader::new(BufReader::new(file)).in_file(filepath)?;
+            for (i, point) in reader.points().enumerate() {
+                self.cancellation.check()?;
+                let mut point = point.at_point(filepath, i as u64)?;
+                if (i as u64 + 1).is_multiple_of(pb_increment) {
+                    self.progress.advance(pb_increment);
                 }
                 if !self.crop.is_in_bounds(&point) {
                     continue;
                 }
-                if !self.thinning.is_included(thin_count) {
+                let id = (file_index as u32, i as u64);
+                if excluded.is_removed_duplicate(id) {
+                    continue;
+                }
+                if !self.thinning.is_included(id, thin_count, self.thinning_seed) {
                     thin_count += 1;
                     continue;
                 }
                 thin_count += 1;
 
-                if let Some(ref cloth) = cloth {
-                    if cloth.is_ground_point(&point) {
-                        point.classification = Classification::Ground;
-                    } else {
-                        // Only overwrite existing classification if it was classified ground before
-                        if point.classification == Classification::Ground {
-                            point.classification = Classification::Unclassified;
-                        }
+                if let Some(ref classifier) = classifier {
+                    classifier.reclassify(&mut point)?;
+                }
+                if let Some(ref objects) = objects {
+                    objects.reclassify(id, &mut point)?;
+                }
+                if !excluded.apply(id, &mut point) {
+                    continue;
+                }
+                if let Some(ref hag) = hag {
+                    if !hag.apply(&mut point)? {
+                        without_ground += 1;
+                        continue;
+                    }
+                }
+                if let Some(features) = features.as_mut() {
+                    for value in features.read(id)? {
+                        point.extra_bytes.extend(value.to_le_bytes());
+                    }
+                }
+                if let Some(colours) = &colours {
+                    if !colours.apply(&mut point) {
+                        without_colour += 1;
                     }
                 }
 
-                if self.cloud.is_some() {
-                    self.cloud.as_mut().unwrap().add_point(point);
-                } else if self.writer.is_some() {
-                    self.writer.as_mut().unwrap().write(point)?;
+                if let Some(cloud) = self.cloud.as_mut() {
+                    cloud.add_point(point);
+                } else if let Some(writer) = self.writer.as_mut() {
+                    if let Some(conversion) = &self.conversion {
+                        if !conversion.convert(&mut point) {
+                            changed += 1;
+                        }
+                    }
+                    writer.write(point).at_point(filepath, i as u64)?;
+                } else if let Some(tiles) = self.tiles.as_mut() {
+                    if let Some(conversion) = &self.conversion {
+                        if !conversion.convert(&mut point) {
+                            changed += 1;
+                        }
+                    }
+                    tiles.write(point)?;
                 }
 
                 count += 1;
             }
         }
-        pb.finish();
+        self.progress.finish(&format!("Processed {count} points"));
+        if without_ground > 0 {
+            log::warn!("Left out {without_ground} points without ground below them");
+        }
+        if without_colour > 0 {
+            log::warn!(
+                "{without_colour} points are outside the image of the colourisation or in cells \
+                 without data, they keep their colour or are black"
+            );
+        }
+        if changed > 0 {
+            log::warn!(
+                "Changed the return numbers above 7 or classes above 31 of {changed} points to 7 \
+                 and unclassified, the point format doesn't support them"
+            );
+        }
         Ok(count)
     }
 }
 
+/// The error of the cloth as ground surface without a CSF ground reclassification to simulate it.
+fn cloth_without_csf() -> LaszyError {
+    LaszyError::invalid_parameter(
+        "surface",
+        "Cloth",
+        "needs a CSF ground reclassification, configure one with with_csf_ground_reclassification",
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -397,7 +1790,7 @@ mod tests {
 
     #[test]
     fn test_get_metadata() {
-        let builder = PointCloudBuilder::from_file(&"test.las".to_string()).unwrap();
+        let builder = PointCloudBuilder::from_file("test.las").unwrap();
         let metadata = builder.get_metadata();
         assert_eq!(metadata.point_count(), 52_469);
         assert_eq!(metadata.center2d(), (183_557.575, 332_405.407));
@@ -434,6 +1827,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_csf_max_iterations() {
+        let builder = get_test_builder();
+        let params = CsfParams::default()
+            .with_cloth_resolution(2.0)
+            .with_simulation_threshold(1e-9)
+            .with_max_iterations(3);
+        // A threshold this small is not reached in 3 iterations, the cap must stop the simulation
+        let cloth = builder.perform_csf_simulation(params, &ExcludedPoints::default());
+        assert!(cloth.is_ok());
+    }
+
+    #[test]
+    fn test_csf_simulation_independent_of_threads() {
+        let mut builder = get_test_builder();
+        let heights = |builder: &PointCloudBuilder| {
+            builder
+                .perform_csf_simulation(
+                    CsfParams::default().with_cloth_resolution(2.0),
+                    &ExcludedPoints::default(),
+                )
+                .unwrap()
+                .particles
+                .map(|particle| particle.z)
+        };
+        let single_thread = heights(builder.with_threads(1));
+        let multi_thread = heights(builder.with_threads(4));
+        assert_eq!(single_thread, multi_thread);
+    }
+
     #[test]
     fn test_with_thinning() {
         let mut builder = get_test_builder();
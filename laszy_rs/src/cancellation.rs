@@ -0,0 +1,50 @@
+use crate::LaszyError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Token to cancel a running builder operation from another thread. Clones of a token share their
+/// state, so cancelling one clone cancels all of them.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{CancellationToken, LaszyError, PointCloudBuilder};
+/// let token = CancellationToken::new();
+/// let mut builder = PointCloudBuilder::from_file("test.las").unwrap();
+/// builder.with_cancellation_token(token.clone());
+/// token.cancel();
+/// let re = builder.to_cloud();
+/// assert!(matches!(re, Err(LaszyError::Cancelled)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation. Running operations stop at the next point or simulation iteration
+    /// and return `LaszyError::Cancelled`.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Reset the token, so it can be used for a new run after a cancelled one.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), LaszyError> {
+        if self.is_cancelled() {
+            return Err(LaszyError::Cancelled);
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,13 @@
+use crate::extra_bytes::ExtraBytesField;
+use crate::features::{GeometricFeature, NORMAL_FIELDS};
 use crate::{LaszyError, Point};
 use las::Bounds;
-use las::{Read, Reader, Write};
-use std::io::BufReader;
 
 pub struct PointCloud {
     pub points: Vec<Point>,
     bounds: Bounds,
+    /// The fields in the extra bytes of the points.
+    extra_bytes: Vec<ExtraBytesField>,
 }
 
 impl PointCloud {
@@ -13,6 +15,7 @@ impl PointCloud {
         PointCloud {
             points: Vec::new(),
             bounds: Bounds::default(),
+            extra_bytes: Vec::new(),
         }
     }
 
@@ -36,28 +39,85 @@ impl PointCloud {
         self.points.len()
     }
 
-    pub fn to_file(&self, filepath: &String) -> Result<(), LaszyError> {
-        unimplemented!();
-        println!("Writing to {}", filepath);
-        println!("Points: {}", self.points.len());
-        let mut pb = indicatif::ProgressBar::new(self.points.len() as u64);
-        let file = std::fs::File::open(&String::from("/Users/ole/Downloads/C_30GZ2_cropped.las"))?; //fixme
-        let mut reader = Reader::new(BufReader::new(file))?;
-        let header = reader.header().clone();
-
-        let mut file = std::fs::File::create(filepath).unwrap();
-        let mut writer = las::Writer::new(file, header).unwrap();
-
-        let pb_increment = self.points.len() / 1000;
-        let mut i = 0;
-        for point in &self.points {
-            if i % pb_increment == 0 {
-                pb.inc(pb_increment as u64);
-            }
-            i += 1;
-            writer.write(point.clone())?;
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The unit normals of the points, pointing up, when the builder computed the geometric
+    /// features. They are NaN for points without features.
+    pub fn normals(&self) -> Option<Vec<[f64; 3]>> {
+        let [x, y, z] = NORMAL_FIELDS.map(|name| self.extra_bytes(name));
+        let (x, y, z) = (x?, y?, z?);
+        Some((0..self.len()).map(|i| [x[i], y[i], z[i]]).collect())
+    }
+
+    /// A geometric feature of the points, when the builder computed the geometric features. It is
+    /// NaN for points without features.
+    pub fn feature(&self, feature: GeometricFeature) -> Option<Vec<f64>> {
+        self.extra_bytes(feature.name())
+    }
+
+    /// Names of the fields in the extra bytes of the points, those of the input followed by those
+    /// added by the builder, such as "HeightAboveGround". Bytes that the input doesn't describe
+    /// are called "undocumented 1", "undocumented 2" and so on.
+    pub fn extra_bytes_names(&self) -> Vec<String> {
+        self.extra_bytes.iter().map(ExtraBytesField::name).collect()
+    }
+
+    /// The values of the extra bytes field called `name` of the points, with the scale and offset
+    /// of the field applied. Values that are the no data value of the field are NaN. None when
+    /// there is no such field, or when it is undocumented or an array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use laszy::{HagParams, PointCloudBuilder};
+    /// let mut builder = PointCloudBuilder::from_file("test.las").unwrap();
+    /// builder.with_height_above_ground(HagParams::default());
+    /// let cloud = builder.to_cloud().unwrap();
+    /// let heights = cloud.extra_bytes("HeightAboveGround").unwrap();
+    /// assert_eq!(heights.len(), cloud.len());
+    /// ```
+    pub fn extra_bytes(&self, name: &str) -> Option<Vec<f64>> {
+        // A field added by the builder comes after a field of the input with the same name
+        let position = self
+            .extra_bytes
+            .iter()
+            .rposition(|field| field.name() == name)?;
+        let field = &self.extra_bytes[position];
+        if !field.is_number() {
+            return None;
         }
-        pb.finish_with_message("done");
-        Ok(())
+        let offset: usize = self.extra_bytes[..position].iter().map(|f| f.size()).sum();
+        let size = field.size();
+        let values = self
+            .points
+            .iter()
+            .map(|point| {
+                point
+                    .extra_bytes
+                    .get(offset..offset + size)
+                    .and_then(|bytes| field.value(bytes))
+                    .unwrap_or(f64::NAN)
+            })
+            .collect();
+        Some(values)
+    }
+
+    /// Describe the extra bytes of the points with `fields`.
+    pub(crate) fn set_extra_bytes(&mut self, fields: Vec<ExtraBytesField>) {
+        self.extra_bytes = fields;
+    }
+
+    pub fn to_file(&self, _filepath: &str) -> Result<(), LaszyError> {
+        Err(LaszyError::NotImplemented(
+            "PointCloud::to_file, use PointCloudBuilder::to_file instead".to_string(),
+        ))
+    }
+}
+
+impl Default for PointCloud {
+    fn default() -> Self {
+        Self::new()
     }
 }
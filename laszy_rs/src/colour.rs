@@ -0,0 +1,115 @@
+//! Colours of the points from a georeferenced RGB image, such as an orthophoto.
+
+use crate::raster::{self, ImageWindow, RasterFormat};
+use crate::LaszyError;
+use las::{Color, Point};
+
+/// Parameters of the colourisation of the points with the red, green and blue bands of a GeoTIFF
+/// orthophoto at their x and y. Points get the colour of the cell they are in. The bands must be
+/// 8 or 16 bit unsigned integers, 8 bit values are scaled to 16 bits by multiplying them by 257,
+/// so that 255 becomes 65535. Points outside the image or in cells with its NODATA value keep
+/// their colour, or are black when the input has no colour.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::ColourParams;
+/// // An orthophoto with the bands in the order blue, green, red
+/// let params = ColourParams::new("orthophoto.tif").with_bands(3, 2, 1);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColourParams {
+    /// Path of the GeoTIFF, which must be in the coordinate reference system of the points.
+    pub path: String,
+    /// Numbers of the red, green and blue bands of the GeoTIFF, starting at 1.
+    pub bands: [usize; 3],
+}
+
+impl ColourParams {
+    pub fn new(path: &str) -> Self {
+        ColourParams {
+            path: path.to_string(),
+            bands: [1, 2, 3],
+        }
+    }
+
+    pub fn with_bands(mut self, red: usize, green: usize, blue: usize) -> Self {
+        self.bands = [red, green, blue];
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if RasterFormat::from_path(&self.path)? != RasterFormat::GeoTiff {
+            return Err(LaszyError::InvalidFileExtension(format!(
+                "{}, the image must be a GeoTIFF ending in .tif or .tiff",
+                self.path
+            )));
+        }
+        if let Some(band) = self.bands.iter().find(|band| **band == 0) {
+            return Err(LaszyError::invalid_parameter(
+                "bands",
+                band,
+                "the numbers of the bands start at 1",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Colours the points with the cells of the image in the area of the points.
+pub(crate) struct Colouriser {
+    image: ImageWindow,
+    /// Indices of the red, green and blue bands in the values of a cell.
+    bands: [usize; 3],
+    /// Factor that scales the values of the image to 16 bits.
+    scale: u16,
+}
+
+impl Colouriser {
+    /// Read the cells of the image of `params` within `lower_left` to `upper_right`.
+    pub fn new(
+        params: &ColourParams,
+        lower_left: (f64, f64),
+        upper_right: (f64, f64),
+    ) -> Result<Self, LaszyError> {
+        let image = raster::read_image_window(&params.path, lower_left, upper_right)?;
+        if let Some(band) = params.bands.iter().find(|band| **band > image.bands) {
+            return Err(LaszyError::invalid_parameter(
+                "bands",
+                band,
+                format!("the image has {} bands", image.bands),
+            ));
+        }
+        let scale = if image.bits_per_sample == 8 { 257 } else { 1 };
+        log::info!(
+            "Read {} by {} cells of {}",
+            image.grid.columns,
+            image.grid.rows,
+            params.path
+        );
+        Ok(Colouriser {
+            image,
+            bands: params.bands.map(|band| band - 1),
+            scale,
+        })
+    }
+
+    /// Give `point` the colour of the cell it is in. Returns false when it is outside the image or
+    /// the cell has no data, in which case it keeps its colour, or becomes black without one.
+    pub fn apply(&self, point: &mut Point) -> bool {
+        let values = self.image.cell_values(point.x, point.y).and_then(|cell| {
+            let values = self.bands.map(|band| cell[band]);
+            let nodata = self.image.nodata;
+            let is_nodata = values.iter().all(|value| Some(*value as f64) == nodata);
+            (!is_nodata).then_some(values)
+        });
+        let Some([red, green, blue]) = values else {
+            point.color.get_or_insert(Color::new(0, 0, 0));
+            return false;
+        };
+        let scale = |value: u16| value.saturating_mul(self.scale);
+        point.color = Some(Color::new(scale(red), scale(green), scale(blue)));
+        true
+    }
+}
@@ -1,4 +1,4 @@
-use crate::Point;
+use crate::{LaszyError, Point};
 
 #[derive(Default)]
 pub enum CroppingMethod {
@@ -17,5 +17,35 @@ impl CroppingMethod {
             }
         }
     }
-}
 
+    /// Check that the corners of the bounding box are in the right order and that the box
+    /// overlaps with `bounds`, the bounds of the file that will be cropped.
+    pub fn validate(&self, bounds: &las::Bounds) -> Result<(), LaszyError> {
+        let CroppingMethod::BoundingBox { lower_left, upper_right } = self else {
+            return Ok(());
+        };
+        let corners = format!("{lower_left:?}, {upper_right:?}");
+        if !(lower_left.0 < upper_right.0 && lower_left.1 < upper_right.1) {
+            return Err(LaszyError::invalid_parameter(
+                "crop",
+                corners,
+                "lower_left must be below and to the left of upper_right",
+            ));
+        }
+        if upper_right.0 < bounds.min.x
+            || lower_left.0 > bounds.max.x
+            || upper_right.1 < bounds.min.y
+            || lower_left.1 > bounds.max.y
+        {
+            return Err(LaszyError::invalid_parameter(
+                "crop",
+                corners,
+                format!(
+                    "does not intersect the bounds of the file, ({}, {}), ({}, {})",
+                    bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
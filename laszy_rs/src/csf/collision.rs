@@ -0,0 +1,103 @@
+use crate::LaszyError;
+
+/// How the height the cloth collides with is taken from the points in a cloth cell. Every point
+/// belongs to the cell of the cloth particle closest to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CollisionHeight {
+    /// Height of the point closest to the particle, as in the reference implementation. Uses the
+    /// least memory, but a single noise point can pin the cloth.
+    #[default]
+    Nearest,
+    /// Height of the lowest point in the cell.
+    Lowest,
+    /// Height at a percentile of the points in the cell, for example 0.05 for the height that 5%
+    /// of the points are below. Keeps the height of every point in memory during the simulation.
+    Percentile { percent: f64 },
+    /// Median height of the `k` lowest points in the cell. Robust against up to `k / 2` low noise
+    /// points per cell, while keeping only `k` heights per cell in memory.
+    MedianOfLowest { k: usize },
+}
+
+impl CollisionHeight {
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        match self {
+            CollisionHeight::Percentile { percent } if !(0.0..=1.0).contains(percent) => {
+                Err(LaszyError::invalid_parameter(
+                    "Percentile { percent }",
+                    percent,
+                    "must be between 0.0 and 1.0",
+                ))
+            }
+            CollisionHeight::MedianOfLowest { k: 0 } => Err(LaszyError::invalid_parameter(
+                "MedianOfLowest { k }",
+                0,
+                "must be at least 1",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The heights of the points in one cloth cell that are needed for its collision height.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CellHeights {
+    /// Squared horizontal distance to the particle of the point in `height`, for `Nearest`.
+    nearest_distance: Option<f64>,
+    /// Height for `Nearest` and `Lowest`.
+    height: Option<f64>,
+    /// Sorted heights for `Percentile` and `MedianOfLowest`.
+    heights: Vec<f64>,
+}
+
+impl CellHeights {
+    /// Add a point at squared horizontal `distance` from the particle with height `z`.
+    pub fn add(&mut self, strategy: CollisionHeight, distance: f64, z: f64) {
+        match strategy {
+            CollisionHeight::Nearest => {
+                if self
+                    .nearest_distance
+                    .is_none_or(|nearest| distance < nearest)
+                {
+                    self.nearest_distance = Some(distance);
+                    self.height = Some(z);
+                }
+            }
+            CollisionHeight::Lowest => {
+                self.height = Some(self.height.map_or(z, |lowest| lowest.min(z)));
+            }
+            CollisionHeight::Percentile { .. } => {
+                self.heights.push(z);
+            }
+            CollisionHeight::MedianOfLowest { k } => {
+                let index = self.heights.partition_point(|height| *height <= z);
+                if index < k {
+                    self.heights.insert(index, z);
+                    self.heights.truncate(k);
+                }
+            }
+        }
+    }
+
+    /// The collision height of the cell, or None when no point fell in the cell.
+    pub fn collision_height(&mut self, strategy: CollisionHeight) -> Option<f64> {
+        match strategy {
+            CollisionHeight::Nearest | CollisionHeight::Lowest => self.height,
+            CollisionHeight::Percentile { percent } => {
+                if self.heights.is_empty() {
+                    return None;
+                }
+                self.heights.sort_by(f64::total_cmp);
+                let index = (percent * (self.heights.len() - 1) as f64).round() as usize;
+                Some(self.heights[index])
+            }
+            CollisionHeight::MedianOfLowest { .. } => {
+                let count = self.heights.len();
+                match count {
+                    0 => None,
+                    _ if count % 2 == 1 => Some(self.heights[count / 2]),
+                    _ => Some((self.heights[count / 2 - 1] + self.heights[count / 2]) / 2.0),
+                }
+            }
+        }
+    }
+}
@@ -1,2 +1,43 @@
+pub mod collision;
+pub mod params;
 mod particle;
+pub mod physics;
 pub mod surface;
+pub(crate) mod tiled;
+
+use crate::raster::RasterSource;
+use crate::LaszyError;
+use las::Point;
+use surface::ClothSurface;
+use tiled::TiledCloth;
+
+/// A simulated cloth, either of the whole extent at once or in tiles.
+pub(crate) enum Cloth {
+    Surface(ClothSurface),
+    Tiled(TiledCloth),
+}
+
+impl Cloth {
+    pub fn is_ground_point(&self, point: &Point) -> Result<bool, LaszyError> {
+        match self {
+            Cloth::Surface(cloth) => Ok(cloth.is_ground_point(point)),
+            Cloth::Tiled(cloth) => cloth.is_ground_point(point),
+        }
+    }
+
+    /// Height of the cloth below a point, or None when the point is outside the simulated cloth.
+    pub fn height_at(&self, point: &Point) -> Result<Option<f64>, LaszyError> {
+        match self {
+            Cloth::Surface(cloth) => Ok(cloth.height_at(point)),
+            Cloth::Tiled(cloth) => cloth.height_at(point),
+        }
+    }
+
+    /// The heights of the cloth as a raster with a cell centred on each particle.
+    pub fn raster_source(&mut self) -> Box<dyn RasterSource + '_> {
+        match self {
+            Cloth::Surface(cloth) => Box::new(cloth),
+            Cloth::Tiled(cloth) => Box::new(cloth.raster_source()),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::csf::collision::CollisionHeight;
+use crate::csf::physics::ClothPhysics;
+use crate::LaszyError;
+
+/// Parameters of the Cloth Simulation Filter (CSF). Start from the defaults or from one of the
+/// presets and change single parameters with the `with_*` methods.
+///
+/// The presets mirror the scene types of the CSF plugin of CloudCompare: `flat` for flat terrain
+/// with a rigid cloth, `relief` for gentle relief and `steep_slope` for steep terrain with a soft
+/// cloth and slope smoothing. `relief` is the default.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::CsfParams;
+/// let params = CsfParams::steep_slope()
+///     .with_cloth_resolution(2.0)
+///     .with_classification_threshold(0.3);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CsfParams {
+    /// Value between 0.0 and 1.0. When 0.0, the cloth surface filter will classify all points as
+    /// ground. When 1.0, the cloth is at maximum rigidity and will classify points as ground in a
+    /// strict manner.
+    pub rigidness: f64,
+    /// Distance in meters between the cloth surface points.
+    pub cloth_resolution: f64,
+    /// If the largest amount any particle moved during an iteration is less than this value in
+    /// meters, the simulation will stop.
+    pub simulation_threshold: f64,
+    /// The maximum distance in meters between a point and the cloth surface for the point to be
+    /// classified as ground.
+    pub classification_threshold: f64,
+    /// How the height the cloth collides with is taken from the points in a cloth cell.
+    pub collision_height: CollisionHeight,
+    /// Physical parameters of the cloth simulation.
+    pub physics: ClothPhysics,
+    /// Simulate the cloth in overlapping tiles instead of at once, to limit memory usage for large
+    /// extents. None simulates the whole extent at once.
+    pub tiling: Option<CsfTiling>,
+}
+
+/// Tiling of the cloth simulation for extents that don't fit in memory at once. Every tile is
+/// simulated on its own, including a buffer of the surrounding tiles, so the cloth at the edge of
+/// a tile is pulled by the terrain around it. Only the core of each tile is kept.
+///
+/// Memory usage of the simulation depends on the size of a tile with its buffer instead of the
+/// whole extent. The points and simulated tiles are kept in temporary files in the directory of
+/// `std::env::temp_dir`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CsfTiling {
+    /// Width and height in meters of the core of a tile, rounded to whole cloth cells.
+    pub tile_size: f64,
+    /// Distance in meters the simulation of a tile extends beyond its core. Should be larger than
+    /// the largest object that is not ground, such as a building, for seamless results.
+    pub buffer: f64,
+}
+
+impl CsfTiling {
+    pub fn new(tile_size: f64, buffer: f64) -> Self {
+        CsfTiling { tile_size, buffer }
+    }
+}
+
+impl Default for CsfParams {
+    fn default() -> Self {
+        CsfParams::relief()
+    }
+}
+
+impl CsfParams {
+    /// Preset for flat terrain, such as urban areas: a rigid cloth without slope smoothing.
+    pub fn flat() -> Self {
+        CsfParams {
+            rigidness: 0.9,
+            physics: ClothPhysics {
+                slope_smoothing: false,
+                ..ClothPhysics::default()
+            },
+            ..CsfParams::relief()
+        }
+    }
+
+    /// Preset for terrain with gentle relief.
+    pub fn relief() -> Self {
+        CsfParams {
+            rigidness: 0.5,
+            cloth_resolution: 5.0,
+            simulation_threshold: 0.1,
+            classification_threshold: 0.5,
+            collision_height: CollisionHeight::Nearest,
+            tiling: None,
+            physics: ClothPhysics::default(),
+        }
+    }
+
+    /// Preset for steep slopes, such as mountains: a soft cloth with slope smoothing.
+    pub fn steep_slope() -> Self {
+        CsfParams {
+            rigidness: 0.2,
+            physics: ClothPhysics {
+                slope_smoothing: true,
+                ..ClothPhysics::default()
+            },
+            ..CsfParams::relief()
+        }
+    }
+
+    pub fn with_rigidness(mut self, rigidness: f64) -> Self {
+        self.rigidness = rigidness;
+        self
+    }
+
+    pub fn with_cloth_resolution(mut self, cloth_resolution: f64) -> Self {
+        self.cloth_resolution = cloth_resolution;
+        self
+    }
+
+    pub fn with_simulation_threshold(mut self, simulation_threshold: f64) -> Self {
+        self.simulation_threshold = simulation_threshold;
+        self
+    }
+
+    pub fn with_classification_threshold(mut self, classification_threshold: f64) -> Self {
+        self.classification_threshold = classification_threshold;
+        self
+    }
+
+    pub fn with_collision_height(mut self, collision_height: CollisionHeight) -> Self {
+        self.collision_height = collision_height;
+        self
+    }
+
+    pub fn with_physics(mut self, physics: ClothPhysics) -> Self {
+        self.physics = physics;
+        self
+    }
+
+    /// Simulate the cloth in tiles of `tile_size` meters with a `buffer` in meters around each tile.
+    pub fn with_tiling(mut self, tile_size: f64, buffer: f64) -> Self {
+        self.tiling = Some(CsfTiling::new(tile_size, buffer));
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.physics.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn with_slope_smoothing(mut self, slope_smoothing: bool) -> Self {
+        self.physics.slope_smoothing = slope_smoothing;
+        self
+    }
+
+    /// Check that all parameters are in their valid range.
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(0.0..=1.0).contains(&self.rigidness) {
+            return Err(LaszyError::invalid_parameter(
+                "rigidness",
+                self.rigidness,
+                "must be between 0.0 and 1.0",
+            ));
+        }
+        if !(self.cloth_resolution > 0.0 && self.cloth_resolution.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "cloth_resolution",
+                self.cloth_resolution,
+                "must be larger than 0.0",
+            ));
+        }
+        if !(self.simulation_threshold > 0.0 && self.simulation_threshold.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "simulation_threshold",
+                self.simulation_threshold,
+                "must be larger than 0.0",
+            ));
+        }
+        if !(self.classification_threshold >= 0.0 && self.classification_threshold.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "classification_threshold",
+                self.classification_threshold,
+                "must be 0.0 or larger",
+            ));
+        }
+        self.collision_height.validate()?;
+        if let Some(tiling) = &self.tiling {
+            if !(tiling.tile_size >= self.cloth_resolution && tiling.tile_size.is_finite()) {
+                return Err(LaszyError::invalid_parameter(
+                    "tile_size",
+                    tiling.tile_size,
+                    "must be at least the cloth resolution",
+                ));
+            }
+            if !(tiling.buffer >= 0.0 && tiling.buffer.is_finite()) {
+                return Err(LaszyError::invalid_parameter(
+                    "buffer",
+                    tiling.buffer,
+                    "must be 0.0 or larger",
+                ));
+            }
+        }
+        self.physics.validate()
+    }
+}
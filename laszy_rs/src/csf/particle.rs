@@ -1,14 +1,14 @@
-use std::cell::Cell;
+/// Fraction of its velocity a particle loses each iteration, as in the reference implementation.
+const DAMPING: f64 = 0.01;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Particle {
     pub x: f64,
     pub y: f64,
-    pub z: Cell<f64>,
+    pub z: f64,
     pub max_z: f64,
     pub prev_z: f64,
-    pub is_moveable: Cell<bool>,
-    pub closest_pt_distance: f64,
+    pub is_moveable: bool,
 }
 
 impl Particle {
@@ -16,61 +16,64 @@ impl Particle {
         Particle {
             x,
             y,
-            z: Cell::new(z),
+            z,
             prev_z: z,
             max_z,
-            is_moveable: Cell::new(true),
-            closest_pt_distance: f64::MAX,
+            is_moveable: true,
         }
     }
 
-    pub fn apply_force(&self, rigidness: f64, neighbours: Vec<&Particle>, displacement: f64) {
-        self.apply_internal_force(rigidness, neighbours);
-        self.apply_external_force(displacement);
-        if self.z.get() > self.max_z {
-            self.z.set(self.max_z);
-            self.is_moveable.set(false);
+    /// Verlet integration step. The particle keeps the damped movement of the previous iteration
+    /// and is moved `displacement` further by gravity.
+    pub fn apply_external_force(&mut self, displacement: f64) {
+        let velocity = self.z - self.prev_z;
+        self.prev_z = self.z;
+        if self.is_moveable {
+            self.z += velocity * (1.0 - DAMPING) + displacement;
+            self.collide();
         }
     }
 
-    fn apply_internal_force(&self, rigidness: f64, neighbours: Vec<&Particle>) {
-        for neighbour in neighbours {
-            let mut ztransform = self.z_difference(neighbour);
+    /// Move the particle towards its neighbours. The neighbours are given as `(z, is_moveable)`
+    /// pairs, taken from the state before this update.
+    pub fn apply_internal_force(
+        &mut self,
+        rigidness: f64,
+        neighbours: impl IntoIterator<Item = (f64, bool)>,
+    ) {
+        if !self.is_moveable {
+            return;
+        }
+        for (neighbour_z, neighbour_is_moveable) in neighbours {
+            let mut ztransform = self.z - neighbour_z;
 
-            // Divide by 2.0 because we are applying the force to both particles
-            if self.is_moveable.get() && neighbour.is_moveable.get() {
+            // Divide by 2.0 when both particles can move, the neighbour moves the other half when
+            // it is updated itself
+            if neighbour_is_moveable {
                 ztransform /= 2.0;
             }
-            if neighbour.is_moveable.get() {
-                neighbour.z.set(neighbour.z.get() + ztransform * rigidness);
-            }
-            if self.is_moveable.get() {
-                self.z.set(self.z.get() - ztransform * rigidness);
-            }
+            self.z -= ztransform * rigidness;
         }
+        self.collide();
     }
 
-    fn apply_external_force(&self, displacement: f64) {
-        if self.is_moveable.get() {
-            self.z.set(self.z.get() + displacement);
+    /// Stop the particle at the height of the points.
+    fn collide(&mut self) {
+        if self.z > self.max_z {
+            self.z = self.max_z;
+            self.is_moveable = false;
         }
     }
 
-    pub fn z_difference(&self, other: &Particle) -> f64 {
-        self.z.get() - other.z.get()
+    /// Place the particle on the points and stop it from moving.
+    pub fn snap_to_max_z(&mut self) {
+        self.z = self.max_z;
+        self.is_moveable = false;
     }
 }
 
 impl Default for Particle {
     fn default() -> Self {
-        Particle {
-            x: 0.0,
-            y: 0.0,
-            z: Cell::new(0.0),
-            prev_z: 0.0,
-            max_z: 0.0,
-            is_moveable: Cell::new(true),
-            closest_pt_distance: f64::MAX,
-        }
+        Particle::new(0.0, 0.0, 0.0, 0.0)
     }
 }
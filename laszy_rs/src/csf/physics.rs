@@ -0,0 +1,72 @@
+use crate::LaszyError;
+
+/// Physical parameters of the cloth simulation, following the Cloth Simulation Filter of
+/// Zhang et al. (2016). The defaults are the defaults of the reference implementation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClothPhysics {
+    /// Time step of one iteration. Smaller steps move the cloth slower, but more precisely.
+    pub time_step: f64,
+    /// Acceleration of the particles towards the points. Per iteration a free particle moves
+    /// `gravity * time_step²` further, on top of its damped movement of the previous iteration.
+    pub gravity: f64,
+    /// The simulation stops after this many iterations, even when the simulation threshold was not
+    /// reached.
+    pub max_iterations: usize,
+    /// After the simulation, snap parts of the cloth that hang just above steep slopes onto the
+    /// points below them.
+    pub slope_smoothing: bool,
+    /// Maximum distance in meters between a particle and its collision height for slope smoothing
+    /// to snap it onto the points.
+    pub slope_smoothing_threshold: f64,
+}
+
+impl Default for ClothPhysics {
+    fn default() -> Self {
+        ClothPhysics {
+            time_step: 0.65,
+            gravity: 0.2,
+            max_iterations: 500,
+            slope_smoothing: false,
+            slope_smoothing_threshold: 0.3,
+        }
+    }
+}
+
+impl ClothPhysics {
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.time_step > 0.0 && self.time_step.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "time_step",
+                self.time_step,
+                "must be larger than 0.0",
+            ));
+        }
+        if !(self.gravity > 0.0 && self.gravity.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "gravity",
+                self.gravity,
+                "must be larger than 0.0",
+            ));
+        }
+        if self.max_iterations == 0 {
+            return Err(LaszyError::invalid_parameter(
+                "max_iterations",
+                self.max_iterations,
+                "must be at least 1",
+            ));
+        }
+        if !(self.slope_smoothing_threshold >= 0.0 && self.slope_smoothing_threshold.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "slope_smoothing_threshold",
+                self.slope_smoothing_threshold,
+                "must be 0.0 or larger",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Distance a free particle is moved by gravity in one iteration.
+    pub(crate) fn displacement(&self) -> f64 {
+        self.gravity * self.time_step * self.time_step
+    }
+}
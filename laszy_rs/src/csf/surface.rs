@@ -1,203 +1,360 @@
+use crate::cancellation::CancellationToken;
+use crate::csf::collision::CellHeights;
+use crate::csf::params::CsfParams;
 use crate::csf::particle::Particle;
-use indicatif::ProgressStyle;
+use crate::progress::ProgressReporter;
+use crate::raster::{RasterGrid, RasterSource};
+use crate::LaszyError;
 use kdtree::distance::squared_euclidean;
 use kdtree::KdTree;
 use las::Point;
-use ndarray::Array2;
-use std::io::prelude::*;
+use ndarray::{Array2, Zip};
 
 pub struct ClothSurface {
     pub particles: Array2<Particle>,
-    pub simulation_threshold: f64,
-    pub classification_threshold: f64,
-    pub rigidness: f64,
-    pub displacement: f64,
+    pub params: CsfParams,
+    /// Heights of the points per particle, until the collision heights are set.
+    cells: Array2<CellHeights>,
     bounds: ((f64, f64), (f64, f64)),
-    cell_resolution: f64,
 }
 
 impl ClothSurface {
-    pub fn initialize<'a>(
+    pub fn initialize(
         lower_left: (f64, f64),
         upper_right: (f64, f64),
-        cell_resolution: f64,
-        simulation_threshold: f64,
-        classification_threshold: f64,
-        rigidness: f64,
+        params: CsfParams,
         top_z: f64,
     ) -> ClothSurface {
-        let rows = ((upper_right.1 - lower_left.1) / cell_resolution).ceil() as usize;
-        let columns = ((upper_right.0 - lower_left.0) / cell_resolution).ceil() as usize;
-        let mut particles: Array2<Particle> = Array2::default((rows, columns));
-        for i in 0..rows {
-            for j in 0..columns {
-                let x = lower_left.0 + cell_resolution * j as f64;
-                let y = lower_left.1 + cell_resolution * i as f64;
-                particles[[i, j]].x = x;
-                particles[[i, j]].y = y;
-                particles[[i, j]].z.set(top_z);
-            }
-        }
+        let (rows, columns) = grid_shape(lower_left, upper_right, params.cloth_resolution);
+        ClothSurface::with_shape(lower_left, rows, columns, params, top_z)
+    }
+
+    /// Create a cloth surface of `rows` by `columns` particles, with the first particle at
+    /// `lower_left`.
+    pub fn with_shape(
+        lower_left: (f64, f64),
+        rows: usize,
+        columns: usize,
+        params: CsfParams,
+        top_z: f64,
+    ) -> ClothSurface {
+        let cell_resolution = params.cloth_resolution;
+        let particles = Array2::from_shape_fn((rows, columns), |(i, j)| {
+            let x = lower_left.0 + cell_resolution * j as f64;
+            let y = lower_left.1 + cell_resolution * i as f64;
+            Particle::new(x, y, top_z, 0.0)
+        });
         let upper_right_corrected = (
             lower_left.0 + cell_resolution * (columns - 1) as f64,
             lower_left.1 + cell_resolution * (rows - 1) as f64,
         );
         ClothSurface {
             particles,
-            simulation_threshold,
-            classification_threshold,
-            rigidness,
-            displacement: 0.05,
+            cells: Array2::default((rows, columns)),
+            params,
             bounds: (lower_left, upper_right_corrected),
-            cell_resolution,
         }
     }
 
     pub fn is_ground_point(&self, point: &Point) -> bool {
-        let particle = match self.get_closest_cell(point) {
-            Some((row, column)) => &self.particles[[row, column]],
-            None => return false,
-        };
-        let distance = (point.z - particle.z.get()).abs();
-        distance < self.classification_threshold
+        match self.height_at(point) {
+            Some(height) => (point.z - height).abs() < self.params.classification_threshold,
+            None => false,
+        }
     }
 
-    fn iterate(&mut self) -> f64 {
-        for i in 0..self.particles.nrows() {
-            for j in 0..self.particles.ncols() {
-                let neighbours = self.get_neighbours(i, j);
-                self.particles[[i, j]].apply_force(self.rigidness, neighbours, self.displacement);
-            }
-        }
-        let mut max_distance = 0.0;
-        for i in 0..self.particles.nrows() {
-            for j in 0..self.particles.ncols() {
-                let mut particle = &mut self.particles[[i, j]];
-                let distance = (particle.z.get() - particle.prev_z).abs();
-                if distance > max_distance {
-                    max_distance = distance;
+    /// Height of the particle closest to the point, or None when the point is outside the cloth.
+    pub fn height_at(&self, point: &Point) -> Option<f64> {
+        let (row, column) = self.get_closest_cell(point)?;
+        Some(self.particles[[row, column]].z)
+    }
+
+    /// Move all particles once, and return the largest distance any particle moved and whether
+    /// any particle has reached the points.
+    ///
+    /// First gravity moves all particles, then the particles are pulled towards their neighbours in
+    /// a red-black (checkerboard) order. All neighbours of a particle have the other colour, so the
+    /// particles of one colour are updated in parallel from a snapshot of the other colour, taken
+    /// in `state`. The result is the same for any amount of threads.
+    fn iterate(&mut self, state: &mut Array2<(f64, bool)>) -> (f64, bool) {
+        let rigidness = self.params.rigidness;
+        let displacement = self.params.physics.displacement();
+        Zip::from(&mut self.particles)
+            .par_for_each(|particle| particle.apply_external_force(displacement));
+        for colour in 0..2 {
+            Zip::from(&mut *state)
+                .and(&self.particles)
+                .par_for_each(|state, p| *state = (p.z, p.is_moveable));
+            Zip::indexed(&mut self.particles).par_for_each(|(i, j), particle| {
+                if (i + j) % 2 == colour {
+                    particle.apply_internal_force(rigidness, neighbours(state, i, j));
                 }
-                particle.prev_z = particle.z.get();
-            }
+            });
         }
-        max_distance
+        Zip::from(&self.particles).par_fold(
+            || (0.0_f64, false),
+            |(max_distance, collided), particle| {
+                (
+                    max_distance.max((particle.z - particle.prev_z).abs()),
+                    collided || !particle.is_moveable,
+                )
+            },
+            |a, b| (a.0.max(b.0), a.1 || b.1),
+        )
     }
 
-    pub fn simulate(&mut self) {
+    /// Run the simulation until no particle moves more than the simulation threshold, or until the
+    /// maximum amount of iterations is reached. The threshold is only checked once the cloth has
+    /// reached the points, as a cloth in free fall moves little in the first iterations.
+    pub fn simulate(
+        &mut self,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<(), LaszyError> {
         let mut iteration = 0;
         let mut max_distance = f64::INFINITY;
-        let spinner = indicatif::ProgressBar::new_spinner();
-        while max_distance > self.simulation_threshold {
-            spinner.set_message(format!(
+        let max_iterations = self.params.physics.max_iterations;
+        progress.start("Simulating cloth surface...", Some(max_iterations as u64));
+        let mut state = Array2::default(self.particles.dim());
+        while iteration < max_iterations {
+            cancellation.check()?;
+            progress.message(&format!(
                 "Simulation threshold {} (meters) not reached, currently at {:.3}",
-                self.simulation_threshold, max_distance
+                self.params.simulation_threshold, max_distance
             ));
-            spinner.inc(1);
-            max_distance = self.iterate();
+            progress.advance(1);
+            let (distance, collided) = self.iterate(&mut state);
+            max_distance = distance;
             iteration += 1;
+            if collided && max_distance <= self.params.simulation_threshold {
+                break;
+            }
         }
-        spinner.finish_with_message(format!("Simulation finished with {} iterations", iteration));
-    }
-
-    fn get_neighbours(&self, i: usize, j: usize) -> Vec<&Particle> {
-        let mut neighbours = Vec::new();
-        let rows = self.particles.nrows();
-        let columns = self.particles.ncols();
-        if i > 0 {
-            neighbours.push(&self.particles[[i - 1, j]]);
-        }
-        if i < rows - 1 {
-            neighbours.push(&self.particles[[i + 1, j]]);
-        }
-        if j > 0 {
-            neighbours.push(&self.particles[[i, j - 1]]);
+        if max_distance > self.params.simulation_threshold {
+            log::warn!(
+                "Simulation threshold {} (meters) not reached after {iteration} iterations, \
+                 largest movement in the last iteration was {max_distance:.3}",
+                self.params.simulation_threshold
+            );
         }
-        if j < columns - 1 {
-            neighbours.push(&self.particles[[i, j + 1]]);
+        if self.params.physics.slope_smoothing {
+            self.smooth_slopes(self.params.physics.slope_smoothing_threshold);
         }
-
-        neighbours
+        progress.finish(&format!("Simulation finished with {iteration} iterations"));
+        Ok(())
     }
 
-    pub fn to_asc(&self, filename: &str) {
-        let mut file = std::fs::File::create(filename).unwrap();
-        let mut header = String::new();
-        header.push_str("ncols ");
-        header.push_str(&self.particles.ncols().to_string());
-        header.push('\n');
-        header.push_str("nrows ");
-        header.push_str(&self.particles.nrows().to_string());
-        header.push('\n');
-        header.push_str("xllcorner ");
-        header.push_str(&self.particles[[0, 0]].x.to_string());
-        header.push('\n');
-        header.push_str("yllcorner ");
-        header.push_str(&self.particles[[0, 0]].y.to_string());
-        header.push('\n');
-        header.push_str("cellsize ");
-        header.push_str(&((self.particles[[0, 1]].x - self.particles[[0, 0]].x).to_string()));
-        header.push('\n');
-        header.push_str("NODATA_value ");
-        header.push_str(&(-9999.0).to_string());
-        header.push('\n');
-        file.write_all(header.as_bytes()).unwrap();
-        for i in 0..self.particles.nrows() {
-            let mut line = String::new();
-            for j in 0..self.particles.ncols() {
-                line.push_str(&self.particles[[i, j]].z.get().to_string());
-                line.push_str(" ");
+    /// Post-processing of the reference implementation for steep slopes. Large connected parts of
+    /// the cloth that are still moving are hanging above the points. Starting from their edges
+    /// with the cloth that did reach the points, particles closer than `threshold` to their
+    /// collision height are snapped onto the points.
+    fn smooth_slopes(&mut self, threshold: f64) {
+        // Smaller moving parts are left alone, these are usually small pits in the surface
+        const MIN_COMPONENT_SIZE: usize = 50;
+        let (rows, columns) = self.particles.dim();
+        let mut visited = Array2::from_elem((rows, columns), false);
+        for start_i in 0..rows {
+            for start_j in 0..columns {
+                if visited[[start_i, start_j]] || !self.particles[[start_i, start_j]].is_moveable {
+                    continue;
+                }
+                // Collect the connected part of the cloth that is still moving
+                let mut component = vec![(start_i, start_j)];
+                visited[[start_i, start_j]] = true;
+                let mut next = 0;
+                while next < component.len() {
+                    let (i, j) = component[next];
+                    next += 1;
+                    for (ni, nj) in neighbour_indices(rows, columns, i, j) {
+                        if !visited[[ni, nj]] && self.particles[[ni, nj]].is_moveable {
+                            visited[[ni, nj]] = true;
+                            component.push((ni, nj));
+                        }
+                    }
+                }
+                if component.len() <= MIN_COMPONENT_SIZE {
+                    continue;
+                }
+
+                let is_close =
+                    |particle: &Particle| (particle.max_z - particle.z).abs() < threshold;
+                let mut queue: Vec<(usize, usize)> = component
+                    .iter()
+                    .copied()
+                    .filter(|&(i, j)| {
+                        is_close(&self.particles[[i, j]])
+                            && neighbour_indices(rows, columns, i, j)
+                                .any(|(ni, nj)| !self.particles[[ni, nj]].is_moveable)
+                    })
+                    .collect();
+                for &(i, j) in &queue {
+                    self.particles[[i, j]].snap_to_max_z();
+                }
+                while let Some((i, j)) = queue.pop() {
+                    for (ni, nj) in neighbour_indices(rows, columns, i, j) {
+                        let neighbour = &mut self.particles[[ni, nj]];
+                        if neighbour.is_moveable && is_close(neighbour) {
+                            neighbour.snap_to_max_z();
+                            queue.push((ni, nj));
+                        }
+                    }
+                }
             }
-            file.write_all(line.as_bytes()).unwrap();
         }
     }
 
-    pub fn set_max_z_if_closest_to_particle(&mut self, point: &Point) {
-        let particle = match self.get_closest_cell(point) {
-            Some((row, col)) => &mut self.particles[[row, col]],
-            None => return,
-        };
+    /// Add a point to the cell of the particle closest to it, for the collision heights.
+    pub fn add_point(&mut self, point: &Point) {
+        if let Some((row, column)) = self.get_closest_cell(point) {
+            self.add_point_to_cell(row, column, point);
+        }
+    }
 
+    /// Add a point to the cell of the particle at `row`, `column`.
+    pub(crate) fn add_point_to_cell(&mut self, row: usize, column: usize, point: &Point) {
+        let particle = &self.particles[[row, column]];
         let distance = (particle.x - point.x).powi(2) + (particle.y - point.y).powi(2);
-        if distance < particle.closest_pt_distance {
-            particle.closest_pt_distance = distance;
-            particle.max_z = point.z;
+        self.cells[[row, column]].add(self.params.collision_height, distance, point.z);
+    }
+
+    /// Set the collision height of every particle from the points added to its cell. Particles
+    /// without points in their cell get the collision height of the closest particle that has
+    /// points.
+    pub fn set_collision_heights(&mut self) -> Result<(), LaszyError> {
+        let strategy = self.params.collision_height;
+        let mut cells = std::mem::take(&mut self.cells);
+        let heights = cells.map_mut(|cell| cell.collision_height(strategy));
+        drop(cells);
+
+        let mut kd = KdTree::new(2);
+        for (particle, height) in self.particles.iter().zip(&heights) {
+            if let Some(height) = height {
+                kd.add([particle.x, particle.y], *height).map_err(|e| {
+                    LaszyError::invalid_parameter(
+                        "crop",
+                        format!("({}, {})", particle.x, particle.y),
+                        format!("cloth particle has an invalid position: {e:?}"),
+                    )
+                })?;
+            }
+        }
+        if kd.size() == 0 {
+            return Err(LaszyError::EmptyCloud(
+                "None of the included points fall on the cloth surface".to_string(),
+            ));
         }
+        for (particle, height) in self.particles.iter_mut().zip(&heights) {
+            particle.max_z = match height {
+                Some(height) => *height,
+                None => {
+                    let closest = kd
+                        .nearest(&[particle.x, particle.y], 1, &squared_euclidean)
+                        .map_err(|e| {
+                            LaszyError::invalid_parameter(
+                                "crop",
+                                format!("({}, {})", particle.x, particle.y),
+                                format!("cloth particle has an invalid position: {e:?}"),
+                            )
+                        })?;
+                    match closest.first() {
+                        Some((_, height)) => **height,
+                        None => continue,
+                    }
+                }
+            };
+        }
+        Ok(())
     }
 
+    /// Row and column of the particle closest to the point.
     fn get_closest_cell(&self, point: &Point) -> Option<(usize, usize)> {
         let ll = self.bounds.0;
         let ur = self.bounds.1;
-        let x = point.x;
-        let y = point.y;
-        let cell_resolution = self.cell_resolution;
-        let col = ((x - ll.0) / cell_resolution).floor() as usize;
-        let row = ((ur.1 - y) / cell_resolution).ceil() as usize;
-        if row >= self.particles.nrows() || col >= self.particles.ncols() {
-            println!(
-                "WARNING: Point ({}, {}) outside of cloth surface with ll: ({}, {}), ur ({}, {})",
-                point.x, point.y, ll.0, ll.1, ur.0, ur.1
+        let cell_resolution = self.params.cloth_resolution;
+        let col = ((point.x - ll.0) / cell_resolution).round();
+        let row = ((point.y - ll.1) / cell_resolution).round();
+        if row < 0.0
+            || col < 0.0
+            || row as usize >= self.particles.nrows()
+            || col as usize >= self.particles.ncols()
+        {
+            log::warn!(
+                "Point ({}, {}) outside of cloth surface with ll: ({}, {}), ur ({}, {})",
+                point.x,
+                point.y,
+                ll.0,
+                ll.1,
+                ur.0,
+                ur.1
             );
             return None;
         }
-        Some((row, col))
+        Some((row as usize, col as usize))
     }
+}
 
-    pub fn fix_zero_max_heights(&mut self) {
-        // Assign max_z of closest particle that has non-zero max_z to all particles with max_z = 0
-        let mut kd = KdTree::new(2);
-        for particle in &self.particles {
-            if particle.max_z != 0.0 {
-                kd.add([particle.x.clone(), particle.y.clone()], particle.max_z)
-                    .unwrap();
-            }
+/// The heights of the particles, with a cell centred on each particle.
+impl RasterSource for ClothSurface {
+    fn grid(&self) -> RasterGrid {
+        let resolution = self.params.cloth_resolution;
+        let lower_left = self.bounds.0;
+        RasterGrid {
+            lower_left: (
+                lower_left.0 - resolution / 2.0,
+                lower_left.1 - resolution / 2.0,
+            ),
+            cell_size: resolution,
+            rows: self.particles.nrows(),
+            columns: self.particles.ncols(),
         }
-        for particle in &mut self.particles {
-            if particle.max_z == 0.0 {
-                let closest = kd
-                    .nearest(&[particle.x, particle.y], 1, &squared_euclidean)
-                    .unwrap();
-                particle.max_z = *closest[0].1;
-            }
+    }
+
+    fn read_row(&mut self, row: usize, values: &mut [f64]) -> Result<(), LaszyError> {
+        // Rows of the raster start in the north, rows of the particles in the south
+        let particles = self.particles.row(self.particles.nrows() - 1 - row);
+        for (value, particle) in values.iter_mut().zip(particles) {
+            *value = particle.z;
         }
+        Ok(())
     }
 }
+
+/// Rows and columns of a grid of particles with a spacing of `cell_resolution`, from `lower_left`
+/// up to or just beyond `upper_right`.
+pub(crate) fn grid_shape(
+    lower_left: (f64, f64),
+    upper_right: (f64, f64),
+    cell_resolution: f64,
+) -> (usize, usize) {
+    // One extra row and column, so the last particles lie on or beyond the upper right corner
+    let rows = ((upper_right.1 - lower_left.1) / cell_resolution).ceil() as usize + 1;
+    let columns = ((upper_right.0 - lower_left.0) / cell_resolution).ceil() as usize + 1;
+    (rows, columns)
+}
+
+/// Row and column of the direct neighbours of row `i`, column `j` in a grid of `rows` by `columns`.
+pub(crate) fn neighbour_indices(
+    rows: usize,
+    columns: usize,
+    i: usize,
+    j: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    [
+        (i > 0).then(|| (i - 1, j)),
+        (i + 1 < rows).then(|| (i + 1, j)),
+        (j > 0).then(|| (i, j - 1)),
+        (j + 1 < columns).then(|| (i, j + 1)),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// The `(z, is_moveable)` state of the direct neighbours of the particle at row `i`, column `j`.
+fn neighbours(
+    state: &Array2<(f64, bool)>,
+    i: usize,
+    j: usize,
+) -> impl Iterator<Item = (f64, bool)> + '_ {
+    let (rows, columns) = state.dim();
+    neighbour_indices(rows, columns, i, j).map(|index| state[index])
+}
@@ -0,0 +1,426 @@
+use crate::cancellation::CancellationToken;
+use crate::csf::params::{CsfParams, CsfTiling};
+use crate::csf::surface::{grid_shape, ClothSurface};
+use crate::error::ErrorContext;
+use crate::output::TempDir;
+use crate::progress::{ProgressReporter, SilentReporter};
+use crate::raster::{RasterGrid, RasterSource};
+use crate::LaszyError;
+use las::Point;
+use ndarray::{s, Array2};
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Bytes of points buffered per tile before they are appended to the file of the tile.
+const BUCKET_BUFFER_SIZE: usize = 16 * 1024;
+/// Amount of simulated tiles kept in memory while classifying points.
+const TILE_CACHE_SIZE: usize = 16;
+/// Bytes of a point in the file of a tile: x, y and z as little endian f64.
+const POINT_SIZE: usize = 24;
+
+/// Cloth simulation of a large extent in overlapping tiles, see `CsfTiling`.
+///
+/// All tiles share the particle grid of the whole extent, so the cores of the tiles fit together
+/// without gaps or overlap. Points are first sorted into a file per tile, including the points in
+/// the buffer of a tile. Each tile is then simulated on its own and the heights of the particles in
+/// its core are written to another file, which is read back when points are classified.
+pub(crate) struct TiledCloth {
+    layout: TileLayout,
+    params: CsfParams,
+    dir: TempDir,
+    buckets: Vec<Vec<u8>>,
+    /// Whether a tile has been simulated, tiles without any points are not.
+    simulated: Vec<bool>,
+    /// Recently used tile heights, the most recently used last.
+    cache: RefCell<Vec<(usize, Array2<f64>)>>,
+}
+
+impl TiledCloth {
+    pub fn new(
+        lower_left: (f64, f64),
+        upper_right: (f64, f64),
+        params: CsfParams,
+        tiling: CsfTiling,
+    ) -> Result<Self, LaszyError> {
+        let layout = TileLayout::new(lower_left, upper_right, params.cloth_resolution, tiling);
+        let tile_count = layout.tile_count();
+        log::info!(
+            "Splitting the cloth of {} by {} particles into {tile_count} tiles",
+            layout.columns,
+            layout.rows
+        );
+        Ok(TiledCloth {
+            layout,
+            params,
+            dir: TempDir::new("laszy-csf")?,
+            buckets: vec![Vec::new(); tile_count],
+            simulated: vec![false; tile_count],
+            cache: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Add a point to every tile it falls in, including the buffers of the tiles.
+    pub fn add_point(&mut self, point: &Point) -> Result<(), LaszyError> {
+        let Some((row, column)) = self.layout.cell(point.x, point.y) else {
+            return Ok(());
+        };
+        for tile in self.layout.tiles_containing(row, column) {
+            let bucket = &mut self.buckets[tile];
+            for value in [point.x, point.y, point.z] {
+                bucket.extend_from_slice(&value.to_le_bytes());
+            }
+            if bucket.len() >= BUCKET_BUFFER_SIZE {
+                self.flush_bucket(tile)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_bucket(&mut self, tile: usize) -> Result<(), LaszyError> {
+        let path = self.points_path(tile);
+        let path_name = path.to_string_lossy();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .in_file(&path_name)?;
+        file.write_all(&self.buckets[tile]).in_file(&path_name)?;
+        self.buckets[tile].clear();
+        Ok(())
+    }
+
+    /// Simulate all tiles one after another. Only one tile is in memory at a time.
+    pub fn simulate(
+        &mut self,
+        top_z: f64,
+        progress: &dyn ProgressReporter,
+        cancellation: &CancellationToken,
+    ) -> Result<(), LaszyError> {
+        for tile in 0..self.buckets.len() {
+            if !self.buckets[tile].is_empty() {
+                self.flush_bucket(tile)?;
+            }
+        }
+        self.buckets = Vec::new();
+
+        let tile_count = self.layout.tile_count();
+        progress.start("Simulating CSF tiles...", Some(tile_count as u64));
+        for tile in 0..tile_count {
+            cancellation.check()?;
+            progress.advance(1);
+            let points = self.take_points(tile)?;
+            if points.is_empty() {
+                continue;
+            }
+            let (rows, columns) = self.layout.buffered(tile);
+            let lower_left = self.layout.position(rows.start, columns.start);
+            let mut cloth =
+                ClothSurface::with_shape(lower_left, rows.len(), columns.len(), self.params, top_z);
+            // The cell of a point is taken from the grid of the whole extent, to get the same cell
+            // as when the point was sorted into the tile
+            for point in &points {
+                if let Some((row, column)) = self.layout.cell(point.x, point.y) {
+                    cloth.add_point_to_cell(row - rows.start, column - columns.start, point);
+                }
+            }
+            drop(points);
+            cloth.set_collision_heights()?;
+            cloth.simulate(&SilentReporter, cancellation)?;
+
+            let (core_rows, core_columns) = self.layout.core(tile);
+            let core = cloth.particles.slice(s![
+                core_rows.start - rows.start..core_rows.end - rows.start,
+                core_columns.start - columns.start..core_columns.end - columns.start
+            ]);
+            let path = self.heights_path(tile);
+            let path_name = path.to_string_lossy();
+            let mut file = BufWriter::new(File::create(&path).in_file(&path_name)?);
+            for particle in core.iter() {
+                file.write_all(&particle.z.to_le_bytes())
+                    .in_file(&path_name)?;
+            }
+            file.flush().in_file(&path_name)?;
+            self.simulated[tile] = true;
+        }
+        let simulated = self
+            .simulated
+            .iter()
+            .filter(|simulated| **simulated)
+            .count();
+        progress.finish(&format!(
+            "Simulated {simulated} of {tile_count} tiles, the other tiles have no points"
+        ));
+        Ok(())
+    }
+
+    /// Read and remove the points of a tile.
+    fn take_points(&self, tile: usize) -> Result<Vec<Point>, LaszyError> {
+        let path = self.points_path(tile);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let path_name = path.to_string_lossy();
+        let mut bytes = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .in_file(&path_name)?;
+        std::fs::remove_file(&path).in_file(&path_name)?;
+        Ok(bytes
+            .chunks_exact(POINT_SIZE)
+            .map(|chunk| {
+                let value = |i: usize| {
+                    let mut value = [0; 8];
+                    value.copy_from_slice(&chunk[i * 8..(i + 1) * 8]);
+                    f64::from_le_bytes(value)
+                };
+                Point {
+                    x: value(0),
+                    y: value(1),
+                    z: value(2),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+
+    pub fn is_ground_point(&self, point: &Point) -> Result<bool, LaszyError> {
+        Ok(match self.height_at(point)? {
+            Some(height) => (point.z - height).abs() < self.params.classification_threshold,
+            None => false,
+        })
+    }
+
+    /// Height of the cloth at the particle closest to the point, or None when the point is outside
+    /// the cloth or in a tile that wasn't simulated.
+    pub fn height_at(&self, point: &Point) -> Result<Option<f64>, LaszyError> {
+        let Some((row, column)) = self.layout.cell(point.x, point.y) else {
+            return Ok(None);
+        };
+        let tile = self.layout.tile_of(row, column);
+        if !self.simulated[tile] {
+            return Ok(None);
+        }
+        let (core_rows, core_columns) = self.layout.core(tile);
+        let mut cache = self.cache.borrow_mut();
+        let position = match cache.iter().position(|(cached, _)| *cached == tile) {
+            Some(position) => position,
+            None => {
+                if cache.len() == TILE_CACHE_SIZE {
+                    cache.remove(0);
+                }
+                let mut heights = Array2::zeros((core_rows.len(), core_columns.len()));
+                let path = self.heights_path(tile);
+                let mut file = BufReader::new(File::open(&path).in_file(&path.to_string_lossy())?);
+                read_heights(&mut file, heights.as_slice_mut().unwrap_or_default())
+                    .in_file(&path.to_string_lossy())?;
+                cache.push((tile, heights));
+                cache.len() - 1
+            }
+        };
+        let entry = cache.remove(position);
+        let height = entry.1[[row - core_rows.start, column - core_columns.start]];
+        cache.push(entry);
+        Ok(Some(height))
+    }
+
+    /// The heights of the cloth as a raster, read one row at a time from the files of the tiles.
+    /// Cells in tiles without points have no data.
+    pub fn raster_source(&self) -> TiledRasterSource<'_> {
+        TiledRasterSource {
+            cloth: self,
+            tile_row: None,
+            tiles: Vec::new(),
+        }
+    }
+
+    fn points_path(&self, tile: usize) -> PathBuf {
+        self.dir.path().join(format!("tile-{tile}.points"))
+    }
+
+    fn heights_path(&self, tile: usize) -> PathBuf {
+        self.dir.path().join(format!("tile-{tile}.heights"))
+    }
+}
+
+/// Reads the rows of a tiled cloth, keeping the height files of the current row of tiles open.
+pub(crate) struct TiledRasterSource<'a> {
+    cloth: &'a TiledCloth,
+    tile_row: Option<usize>,
+    tiles: Vec<Option<BufReader<File>>>,
+}
+
+impl TiledRasterSource<'_> {
+    fn open_tile_row(&mut self, tile_row: usize) -> Result<(), LaszyError> {
+        let tile_columns = self.cloth.layout.tile_columns();
+        self.tiles = (0..tile_columns)
+            .map(|tile_column| {
+                let tile = tile_row * tile_columns + tile_column;
+                if !self.cloth.simulated[tile] {
+                    return Ok(None);
+                }
+                let path = self.cloth.heights_path(tile);
+                let file = File::open(&path).in_file(&path.to_string_lossy())?;
+                Ok(Some(BufReader::new(file)))
+            })
+            .collect::<Result<_, LaszyError>>()?;
+        self.tile_row = Some(tile_row);
+        Ok(())
+    }
+}
+
+impl RasterSource for TiledRasterSource<'_> {
+    fn grid(&self) -> RasterGrid {
+        let layout = &self.cloth.layout;
+        let half_cell = layout.cell_resolution / 2.0;
+        RasterGrid {
+            lower_left: (
+                layout.lower_left.0 - half_cell,
+                layout.lower_left.1 - half_cell,
+            ),
+            cell_size: layout.cell_resolution,
+            rows: layout.rows,
+            columns: layout.columns,
+        }
+    }
+
+    fn read_row(&mut self, row: usize, values: &mut [f64]) -> Result<(), LaszyError> {
+        let layout = self.cloth.layout;
+        // Rows of the raster start in the north, rows of the particle grid in the south
+        let row = layout.rows - 1 - row;
+        let tile_row = row / layout.tile_cells;
+        if self.tile_row != Some(tile_row) {
+            self.open_tile_row(tile_row)?;
+        }
+        for (tile_column, tile_file) in self.tiles.iter_mut().enumerate() {
+            let tile = tile_row * layout.tile_columns() + tile_column;
+            let (core_rows, core_columns) = layout.core(tile);
+            let values = &mut values[core_columns.clone()];
+            match tile_file {
+                Some(tile_file) => {
+                    let offset = (row - core_rows.start) * core_columns.len() * 8;
+                    tile_file.seek(SeekFrom::Start(offset as u64))?;
+                    read_heights(tile_file, values)?;
+                }
+                None => values.fill(f64::NAN),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_heights(file: &mut impl Read, heights: &mut [f64]) -> std::io::Result<()> {
+    let mut bytes = vec![0; heights.len() * 8];
+    file.read_exact(&mut bytes)?;
+    for (height, chunk) in heights.iter_mut().zip(bytes.chunks_exact(8)) {
+        let mut value = [0; 8];
+        value.copy_from_slice(chunk);
+        *height = f64::from_le_bytes(value);
+    }
+    Ok(())
+}
+
+/// Division of the particle grid of the whole extent into tiles of `tile_cells` by `tile_cells`
+/// particles, with a buffer of `buffer_cells` particles around each tile.
+#[derive(Clone, Copy, Debug)]
+struct TileLayout {
+    lower_left: (f64, f64),
+    cell_resolution: f64,
+    rows: usize,
+    columns: usize,
+    tile_cells: usize,
+    buffer_cells: usize,
+}
+
+impl TileLayout {
+    fn new(
+        lower_left: (f64, f64),
+        upper_right: (f64, f64),
+        cell_resolution: f64,
+        tiling: CsfTiling,
+    ) -> Self {
+        let (rows, columns) = grid_shape(lower_left, upper_right, cell_resolution);
+        TileLayout {
+            lower_left,
+            cell_resolution,
+            rows,
+            columns,
+            tile_cells: ((tiling.tile_size / cell_resolution).round() as usize).max(1),
+            buffer_cells: (tiling.buffer / cell_resolution).ceil() as usize,
+        }
+    }
+
+    fn tile_rows(&self) -> usize {
+        self.rows.div_ceil(self.tile_cells)
+    }
+
+    fn tile_columns(&self) -> usize {
+        self.columns.div_ceil(self.tile_cells)
+    }
+
+    fn tile_count(&self) -> usize {
+        self.tile_rows() * self.tile_columns()
+    }
+
+    /// Position of the particle at `row`, `column` of the grid.
+    fn position(&self, row: usize, column: usize) -> (f64, f64) {
+        (
+            self.lower_left.0 + self.cell_resolution * column as f64,
+            self.lower_left.1 + self.cell_resolution * row as f64,
+        )
+    }
+
+    /// Row and column of the particle closest to `x`, `y`, or None outside the grid.
+    fn cell(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        let column = ((x - self.lower_left.0) / self.cell_resolution).round();
+        let row = ((y - self.lower_left.1) / self.cell_resolution).round();
+        if row < 0.0 || column < 0.0 {
+            return None;
+        }
+        let (row, column) = (row as usize, column as usize);
+        (row < self.rows && column < self.columns).then_some((row, column))
+    }
+
+    /// The tile whose core contains the particle at `row`, `column`.
+    fn tile_of(&self, row: usize, column: usize) -> usize {
+        (row / self.tile_cells) * self.tile_columns() + column / self.tile_cells
+    }
+
+    /// Rows and columns of the particles in the core of a tile.
+    fn core(&self, tile: usize) -> (Range<usize>, Range<usize>) {
+        let tile_row = tile / self.tile_columns();
+        let tile_column = tile % self.tile_columns();
+        let range = |index: usize, length: usize| {
+            index * self.tile_cells..((index + 1) * self.tile_cells).min(length)
+        };
+        (range(tile_row, self.rows), range(tile_column, self.columns))
+    }
+
+    /// Rows and columns of the particles in a tile including its buffer.
+    fn buffered(&self, tile: usize) -> (Range<usize>, Range<usize>) {
+        let (rows, columns) = self.core(tile);
+        let buffer = |range: Range<usize>, length: usize| {
+            range.start.saturating_sub(self.buffer_cells)
+                ..(range.end + self.buffer_cells).min(length)
+        };
+        (buffer(rows, self.rows), buffer(columns, self.columns))
+    }
+
+    /// All tiles that contain the particle at `row`, `column` in their core or buffer.
+    fn tiles_containing(&self, row: usize, column: usize) -> impl Iterator<Item = usize> {
+        let tiles = |index: usize, tile_count: usize| {
+            let first = index.saturating_sub(self.buffer_cells) / self.tile_cells;
+            let last = ((index + self.buffer_cells) / self.tile_cells).min(tile_count - 1);
+            first..=last
+        };
+        let tile_columns = self.tile_columns();
+        let column_tiles = tiles(column, tile_columns);
+        tiles(row, self.tile_rows()).flat_map(move |tile_row| {
+            column_tiles
+                .clone()
+                .map(move |tile_column| tile_row * tile_columns + tile_column)
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use crate::dtm::fill_voids;
+use crate::raster::{MemoryRaster, RasterGrid};
+use crate::LaszyError;
+use las::Point;
+use ndarray::Zip;
+
+/// Parameters of a DSM (Digital Surface Model), the height of the highest point in each cell.
+/// Also used for the DSM of a canopy height model.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::DsmParams;
+/// let params = DsmParams::default().with_resolution(0.5).with_pit_free(true);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DsmParams {
+    /// Cell size of the DSM.
+    pub resolution: f64,
+    /// Fill cells without points that are enclosed by cells with points, and raise pits: cells
+    /// that are more than `pit_threshold` below the median of the cells around them. Pits are
+    /// common in canopy, where a laser pulse went deep into a tree before its first return.
+    pub pit_free: bool,
+    /// Distance below the median of the cells around it from which a cell is a pit.
+    pub pit_threshold: f64,
+}
+
+impl Default for DsmParams {
+    fn default() -> Self {
+        DsmParams {
+            resolution: 1.0,
+            pit_free: false,
+            pit_threshold: 1.0,
+        }
+    }
+}
+
+impl DsmParams {
+    pub fn with_resolution(mut self, resolution: f64) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn with_pit_free(mut self, pit_free: bool) -> Self {
+        self.pit_free = pit_free;
+        self
+    }
+
+    pub fn with_pit_threshold(mut self, pit_threshold: f64) -> Self {
+        self.pit_threshold = pit_threshold;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.resolution > 0.0 && self.resolution.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "resolution",
+                self.resolution,
+                "must be larger than 0.0",
+            ));
+        }
+        if self.pit_threshold.is_nan() || self.pit_threshold < 0.0 {
+            return Err(LaszyError::invalid_parameter(
+                "pit_threshold",
+                self.pit_threshold,
+                "must be at least 0.0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A DSM that is built up one point at a time, keeping the highest point per cell.
+pub(crate) struct SurfaceModel {
+    raster: MemoryRaster,
+}
+
+impl SurfaceModel {
+    pub fn new(grid: RasterGrid) -> Self {
+        SurfaceModel {
+            raster: MemoryRaster::empty(grid),
+        }
+    }
+
+    pub fn add_point(&mut self, point: &Point) {
+        if let Some(cell) = self.raster.grid.cell(point.x, point.y) {
+            let height = &mut self.raster.values[cell];
+            if height.is_nan() || point.z > *height {
+                *height = point.z;
+            }
+        }
+    }
+
+    /// The DSM after all points are added.
+    pub fn finish(mut self, params: &DsmParams) -> MemoryRaster {
+        if params.pit_free {
+            fill_voids(&mut self.raster.values);
+            raise_pits(&mut self.raster, params.pit_threshold);
+        }
+        self.raster
+    }
+}
+
+/// Raise cells that are more than `threshold` below the median of the cells with data around them
+/// to that median. Cells with fewer than 5 neighbours with data, such as at the edge of the data,
+/// are left as they are.
+fn raise_pits(raster: &mut MemoryRaster, threshold: f64) {
+    let (rows, columns) = raster.values.dim();
+    let original = raster.values.clone();
+    let mut raised = 0_usize;
+    let mut neighbours = Vec::with_capacity(8);
+    for ((row, column), value) in raster.values.indexed_iter_mut() {
+        if value.is_nan() {
+            continue;
+        }
+        neighbours.clear();
+        for i in row.saturating_sub(1)..(row + 2).min(rows) {
+            for j in column.saturating_sub(1)..(column + 2).min(columns) {
+                let neighbour = original[[i, j]];
+                if (i, j) != (row, column) && !neighbour.is_nan() {
+                    neighbours.push(neighbour);
+                }
+            }
+        }
+        if neighbours.len() < 5 {
+            continue;
+        }
+        neighbours.sort_by(f64::total_cmp);
+        let count = neighbours.len();
+        let median = match count % 2 {
+            1 => neighbours[count / 2],
+            _ => (neighbours[count / 2 - 1] + neighbours[count / 2]) / 2.0,
+        };
+        if *value < median - threshold {
+            *value = median;
+            raised += 1;
+        }
+    }
+    log::info!("Raised {raised} pits in the DSM");
+}
+
+/// The canopy height model of a DSM and a DTM of the same grid: the height of the surface above
+/// the ground. Surfaces below the ground, such as from noise, get a height of 0.
+pub(crate) fn canopy_heights(mut dsm: MemoryRaster, dtm: &MemoryRaster) -> MemoryRaster {
+    Zip::from(&mut dsm.values)
+        .and(&dtm.values)
+        .for_each(|surface, ground| {
+            // f64::max would turn cells without data into 0
+            let height = *surface - ground;
+            *surface = if height < 0.0 { 0.0 } else { height };
+        });
+    dsm
+}
@@ -0,0 +1,383 @@
+use crate::cancellation::CancellationToken;
+use crate::csf::surface::neighbour_indices;
+use crate::progress::ProgressReporter;
+use crate::raster::{MemoryRaster, RasterGrid};
+use crate::LaszyError;
+use kdtree::distance::squared_euclidean;
+use kdtree::KdTree;
+use ndarray::{Array2, Axis};
+use rayon::prelude::*;
+use spade::{DelaunayTriangulation, FloatTriangulation, HasPosition, Point2, Triangulation};
+use std::collections::VecDeque;
+
+/// Amount of cells around a void that are used to fill a cell in the void.
+const VOID_FILL_NEIGHBOURS: usize = 8;
+
+/// How the height of a DTM cell is interpolated from the ground points around its centre.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    /// Inverse distance weighting of the `neighbours` closest ground points, with weights of
+    /// `1 / distance ^ power`. Also gives heights outside the convex hull of the ground points.
+    Idw { power: f64, neighbours: usize },
+    /// Linear interpolation in the Delaunay triangulation of the ground points.
+    #[default]
+    Tin,
+    /// Natural neighbour (Sibson) interpolation, smoother than `Tin` but slower.
+    NaturalNeighbour,
+}
+
+impl Interpolation {
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        match self {
+            Interpolation::Idw { power, .. } if !(*power > 0.0 && power.is_finite()) => Err(
+                LaszyError::invalid_parameter("Idw { power }", power, "must be larger than 0.0"),
+            ),
+            Interpolation::Idw { neighbours: 0, .. } => Err(LaszyError::invalid_parameter(
+                "Idw { neighbours }",
+                0,
+                "must be at least 1",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parameters of a DTM interpolated from ground points.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{DtmParams, Interpolation};
+/// let params = DtmParams::default()
+///     .with_resolution(0.5)
+///     .with_interpolation(Interpolation::NaturalNeighbour)
+///     .with_max_distance(10.0);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DtmParams {
+    /// Cell size of the DTM.
+    pub resolution: f64,
+    pub interpolation: Interpolation,
+    /// Cells further than this from the closest ground point get no data, or are filled as a void.
+    pub max_distance: Option<f64>,
+    /// Fill voids in the DTM, such as under buildings or water, from the cells around them. Only
+    /// areas without data that are fully enclosed by cells with data are voids, the area outside
+    /// of the ground points stays without data.
+    pub fill_voids: bool,
+}
+
+impl Default for DtmParams {
+    fn default() -> Self {
+        DtmParams {
+            resolution: 1.0,
+            interpolation: Interpolation::default(),
+            max_distance: None,
+            fill_voids: true,
+        }
+    }
+}
+
+impl DtmParams {
+    pub fn with_resolution(mut self, resolution: f64) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f64) -> Self {
+        self.max_distance = Some(max_distance);
+        self
+    }
+
+    pub fn with_fill_voids(mut self, fill_voids: bool) -> Self {
+        self.fill_voids = fill_voids;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.resolution > 0.0 && self.resolution.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "resolution",
+                self.resolution,
+                "must be larger than 0.0",
+            ));
+        }
+        if let Some(max_distance) = self.max_distance {
+            if max_distance.is_nan() || max_distance <= 0.0 {
+                return Err(LaszyError::invalid_parameter(
+                    "max_distance",
+                    max_distance,
+                    "must be larger than 0.0",
+                ));
+            }
+        }
+        self.interpolation.validate()
+    }
+}
+
+/// A ground point in the triangulation.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GroundVertex {
+    pub position: Point2<f64>,
+    pub z: f64,
+}
+
+impl HasPosition for GroundVertex {
+    type Scalar = f64;
+
+    fn position(&self) -> Point2<f64> {
+        self.position
+    }
+}
+
+/// The Delaunay triangulation of ground points, as x, y and z.
+fn triangulate(ground: Vec<[f64; 3]>) -> Result<DelaunayTriangulation<GroundVertex>, LaszyError> {
+    let vertices = ground
+        .into_iter()
+        .map(|[x, y, z]| GroundVertex {
+            position: Point2::new(x, y),
+            z,
+        })
+        .collect();
+    DelaunayTriangulation::bulk_load(vertices).map_err(|e| {
+        LaszyError::Triangulation(format!("The ground points can't be triangulated: {e:?}"))
+    })
+}
+
+/// A TIN (Triangulated Irregular Network) of ground points, to get the height of the ground at any
+/// position.
+pub(crate) struct GroundTin {
+    triangulation: DelaunayTriangulation<GroundVertex>,
+}
+
+impl GroundTin {
+    /// The TIN of the `ground` points, as x, y and z.
+    pub fn new(ground: Vec<[f64; 3]>) -> Result<Self, LaszyError> {
+        Ok(GroundTin {
+            triangulation: triangulate(ground)?,
+        })
+    }
+
+    /// Height of the ground at `x`, `y`, linearly interpolated in the triangle containing it.
+    /// Outside the TIN the height of the closest ground point is used.
+    pub fn height_at(&self, x: f64, y: f64) -> Option<f64> {
+        let position = Point2::new(x, y);
+        self.triangulation
+            .barycentric()
+            .interpolate(|vertex| vertex.data().z, position)
+            .or_else(|| {
+                let nearest = self.triangulation.nearest_neighbor(position)?;
+                Some(nearest.data().z)
+            })
+    }
+}
+
+/// The ground points of a DTM, indexed for the interpolation.
+enum GroundModel {
+    Triangulation(DelaunayTriangulation<GroundVertex>),
+    /// The ground points of inverse distance weighting, with its `power` and amount of
+    /// `neighbours`.
+    Index {
+        index: KdTree<f64, f64, [f64; 2]>,
+        power: f64,
+        neighbours: usize,
+    },
+}
+
+/// Interpolate the DTM of `grid` from the `ground` points, as x, y and z.
+pub(crate) fn interpolate(
+    ground: Vec<[f64; 3]>,
+    grid: RasterGrid,
+    params: &DtmParams,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<MemoryRaster, LaszyError> {
+    let model = match params.interpolation {
+        Interpolation::Tin | Interpolation::NaturalNeighbour => {
+            GroundModel::Triangulation(triangulate(ground)?)
+        }
+        Interpolation::Idw { power, neighbours } => {
+            let mut index = KdTree::new(2);
+            for [x, y, z] in ground {
+                index
+                    .add([x, y], z)
+                    .map_err(|e| LaszyError::InvalidPoint(format!("ground point: {e}")))?;
+            }
+            GroundModel::Index {
+                index,
+                power,
+                neighbours,
+            }
+        }
+    };
+
+    let mut raster = MemoryRaster::empty(grid);
+    let max_distance = params.max_distance.unwrap_or(f64::INFINITY);
+    progress.start("Interpolating DTM...", Some(grid.rows as u64));
+    raster
+        .values
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .try_for_each(|(row, mut values)| {
+            cancellation.check()?;
+            match &model {
+                GroundModel::Triangulation(triangulation) => {
+                    let barycentric = triangulation.barycentric();
+                    let natural_neighbour = triangulation.natural_neighbor();
+                    let height =
+                        |vertex: spade::handles::VertexHandle<GroundVertex>| vertex.data().z;
+                    for (column, value) in values.iter_mut().enumerate() {
+                        let (x, y) = grid.cell_centre(row, column);
+                        let position = Point2::new(x, y);
+                        let within_distance =
+                            triangulation
+                                .nearest_neighbor(position)
+                                .is_some_and(|nearest| {
+                                    nearest.position().distance_2(position) <= max_distance.powi(2)
+                                });
+                        if !within_distance {
+                            continue;
+                        }
+                        let interpolated = match params.interpolation {
+                            Interpolation::NaturalNeighbour => {
+                                natural_neighbour.interpolate(height, position)
+                            }
+                            _ => barycentric.interpolate(height, position),
+                        };
+                        *value = interpolated.unwrap_or(f64::NAN);
+                    }
+                }
+                GroundModel::Index {
+                    index,
+                    power,
+                    neighbours,
+                } => {
+                    for (column, value) in values.iter_mut().enumerate() {
+                        let (x, y) = grid.cell_centre(row, column);
+                        let nearest = index
+                            .nearest(&[x, y], *neighbours, &squared_euclidean)
+                            .unwrap_or_default();
+                        let nearest = nearest
+                            .iter()
+                            .map(|(distance, z)| (distance.sqrt(), **z))
+                            .filter(|(distance, _)| *distance <= max_distance);
+                        *value = inverse_distance_weighted(nearest, *power);
+                    }
+                }
+            }
+            progress.advance(1);
+            Ok::<(), LaszyError>(())
+        })?;
+    progress.finish(&format!(
+        "Interpolated a DTM of {} by {} cells",
+        grid.columns, grid.rows
+    ));
+
+    if params.fill_voids {
+        fill_voids(&mut raster.values);
+    }
+    Ok(raster)
+}
+
+/// Weighted average of values with weights of `1 / distance ^ power`, or NaN without any values.
+/// A value at distance 0 is returned as is.
+fn inverse_distance_weighted(values: impl Iterator<Item = (f64, f64)>, power: f64) -> f64 {
+    let mut weighted_sum = 0.0;
+    let mut total_weight = 0.0;
+    for (distance, value) in values {
+        if distance == 0.0 {
+            return value;
+        }
+        let weight = distance.powf(-power);
+        weighted_sum += weight * value;
+        total_weight += weight;
+    }
+    match total_weight > 0.0 {
+        true => weighted_sum / total_weight,
+        false => f64::NAN,
+    }
+}
+
+/// Fill the voids in `values`, areas of NaN cells that aren't connected to the edge of the raster
+/// through other NaN cells. Each void cell gets the inverse distance weighted value of the closest
+/// cells with data along the edges of the voids.
+pub(crate) fn fill_voids(values: &mut Array2<f64>) {
+    let (rows, columns) = values.dim();
+    // Flood fill the cells without data from the edge of the raster, the rest are voids
+    let mut outside = Array2::from_elem((rows, columns), false);
+    let mut queue: VecDeque<(usize, usize)> = (0..rows)
+        .flat_map(|row| [(row, 0), (row, columns - 1)])
+        .chain((0..columns).flat_map(|column| [(0, column), (rows - 1, column)]))
+        .collect();
+    while let Some((row, column)) = queue.pop_front() {
+        if outside[[row, column]] || !values[[row, column]].is_nan() {
+            continue;
+        }
+        outside[[row, column]] = true;
+        queue.extend(neighbour_indices(rows, columns, row, column));
+    }
+    let filled = fill_cells(values, |row, column| !outside[[row, column]]);
+    if filled > 0 {
+        log::info!("Filled {filled} cells in voids of the DTM");
+    }
+}
+
+/// Fill all NaN cells of `values` the same way as voids, including those along the edge of the
+/// raster.
+pub(crate) fn fill_all(values: &mut Array2<f64>) {
+    fill_cells(values, |_, _| true);
+}
+
+/// Fill the NaN cells for which `is_target` is true with the inverse distance weighted value of the
+/// closest cells with data next to them, and return the amount of filled cells.
+fn fill_cells(values: &mut Array2<f64>, is_target: impl Fn(usize, usize) -> bool) -> usize {
+    let (rows, columns) = values.dim();
+    let is_void = |row: usize, column: usize, values: &Array2<f64>| {
+        values[[row, column]].is_nan() && is_target(row, column)
+    };
+    let mut edges = KdTree::new(2);
+    let mut voids = Vec::new();
+    for ((row, column), value) in values.indexed_iter() {
+        if is_void(row, column, values) {
+            voids.push((row, column));
+        } else if !value.is_nan()
+            && neighbour_indices(rows, columns, row, column)
+                .any(|(row, column)| is_void(row, column, values))
+        {
+            // Rows and columns are far below the precision of f64, so adding them can't fail
+            let _ = edges.add([row as f64, column as f64], *value);
+        }
+    }
+    if voids.is_empty() {
+        return 0;
+    }
+    let filled: Vec<f64> = voids
+        .par_iter()
+        .map(|(row, column)| {
+            let nearest = edges
+                .nearest(
+                    &[*row as f64, *column as f64],
+                    VOID_FILL_NEIGHBOURS,
+                    &squared_euclidean,
+                )
+                .unwrap_or_default();
+            inverse_distance_weighted(
+                nearest
+                    .into_iter()
+                    .map(|(distance, value)| (distance.sqrt(), *value)),
+                2.0,
+            )
+        })
+        .collect();
+    let count = voids.len();
+    for ((row, column), value) in voids.into_iter().zip(filled) {
+        values[[row, column]] = value;
+    }
+    count
+}
@@ -0,0 +1,184 @@
+use crate::cancellation::CancellationToken;
+use crate::progress::ProgressReporter;
+use crate::tiles::{self, PointId, PointSet, PointTiles, Tile};
+use crate::LaszyError;
+use kdtree::distance::squared_euclidean;
+use rayon::prelude::*;
+
+/// What happens to duplicate points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Leave the duplicates out, before the thinning.
+    #[default]
+    Remove,
+    /// Keep the duplicates with their withheld flag set.
+    Withhold,
+}
+
+/// Parameters of the duplicate point detection. A point is a duplicate when it is within the
+/// tolerance of a point that comes before it in the input, in the same or an earlier file, so the
+/// first of the copies of a point is kept.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{DuplicateAction, DuplicateParams};
+/// let params = DuplicateParams::default()
+///     .with_tolerance(0.005)
+///     .with_same_gps_time(true)
+///     .with_action(DuplicateAction::Withhold);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DuplicateParams {
+    /// Largest distance in 3D between duplicate points. With 0.0 only points with exactly the
+    /// same x, y and z are duplicates.
+    pub tolerance: f64,
+    /// Only points with the same GPS time are duplicates.
+    pub same_gps_time: bool,
+    pub action: DuplicateAction,
+}
+
+impl DuplicateParams {
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_same_gps_time(mut self, same_gps_time: bool) -> Self {
+        self.same_gps_time = same_gps_time;
+        self
+    }
+
+    pub fn with_action(mut self, action: DuplicateAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.tolerance >= 0.0 && self.tolerance.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "tolerance",
+                self.tolerance,
+                "must be 0.0 or larger",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The duplicate points found by the duplicate detection.
+#[derive(Debug)]
+pub(crate) struct DuplicatePoints {
+    points: PointSet,
+    action: DuplicateAction,
+}
+
+impl DuplicatePoints {
+    pub fn is_duplicate(&self, id: PointId) -> bool {
+        self.points.contains(id)
+    }
+
+    /// Whether the point is a duplicate that is left out, before the thinning.
+    pub fn is_removed(&self, id: PointId) -> bool {
+        self.action == DuplicateAction::Remove && self.is_duplicate(id)
+    }
+}
+
+/// Find the duplicate points in the `tiles`, going through them one at a time. A point is a
+/// duplicate of a point before it that is kept, so of a chain of points that are each within the
+/// tolerance of the next only every other point is a duplicate. Whether the buffer points of a
+/// tile are kept is found in their own tile, so tiles that depend on their buffer points are
+/// checked again with those until nothing changes.
+pub(crate) fn find(
+    mut tiles: PointTiles,
+    params: &DuplicateParams,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<DuplicatePoints, LaszyError> {
+    tiles.finish()?;
+    let mut duplicates = PointSet::default();
+    let tile_count = tiles.tile_count();
+    progress.start("Finding duplicates in tiles...", Some(tile_count as u64));
+    let mut dependent = Vec::new();
+    for tile_index in 0..tile_count {
+        cancellation.check()?;
+        progress.advance(1);
+        let tile = tiles.read_tile(tile_index)?;
+        let (_, depends_on_buffer) = find_in_tile(&tile, params, &mut duplicates, false)?;
+        if depends_on_buffer {
+            dependent.push(tile_index);
+        }
+    }
+    let mut is_changed = !dependent.is_empty();
+    while is_changed {
+        is_changed = false;
+        for tile_index in &dependent {
+            cancellation.check()?;
+            let tile = tiles.read_tile(*tile_index)?;
+            let (changed, _) = find_in_tile(&tile, params, &mut duplicates, true)?;
+            is_changed |= changed;
+        }
+    }
+    progress.finish(&format!("Found {} duplicate points", duplicates.len()));
+    Ok(DuplicatePoints {
+        points: duplicates,
+        action: params.action,
+    })
+}
+
+/// Find the duplicates among the core points of `tile` and set them in `duplicates`. The buffer
+/// points are kept when their tile did not put them in `duplicates` with `use_found`, or else when they
+/// are kept within this tile. Returns whether any core point changed, and whether any core point
+/// depends on a buffer point.
+fn find_in_tile(
+    tile: &Tile,
+    params: &DuplicateParams,
+    duplicates: &mut PointSet,
+    use_found: bool,
+) -> Result<(bool, bool), LaszyError> {
+    let max_distance = params.tolerance * params.tolerance;
+    let index = tile.kdtree()?;
+    // The points before each point within the tolerance
+    let before = (0..tile.points.len())
+        .into_par_iter()
+        .map(|i| {
+            let point = &tile.points[i];
+            let within = index
+                .within(&point.xyz, max_distance, &squared_euclidean)
+                .map_err(tiles::invalid_point)?;
+            Ok(within
+                .into_iter()
+                .map(|(_, j)| *j)
+                .filter(|j| {
+                    let other = &tile.points[*j];
+                    other.id < point.id
+                        && (!params.same_gps_time || other.gps_time == point.gps_time)
+                })
+                .collect::<Vec<_>>())
+        })
+        .collect::<Result<Vec<_>, LaszyError>>()?;
+    let mut order: Vec<usize> = (0..tile.points.len()).collect();
+    order.sort_unstable_by_key(|i| tile.points[*i].id);
+    let mut is_duplicate = vec![false; tile.points.len()];
+    let (mut is_changed, mut depends_on_buffer) = (false, false);
+    for i in order {
+        let id = tile.points[i].id;
+        if !tile.is_core[i] && use_found {
+            is_duplicate[i] = duplicates.contains(id);
+            continue;
+        }
+        is_duplicate[i] = before[i].iter().any(|j| !is_duplicate[*j]);
+        if tile.is_core[i] {
+            depends_on_buffer |= before[i].iter().any(|j| !tile.is_core[*j]);
+            if is_duplicate[i] != duplicates.contains(id) {
+                is_changed = true;
+                match is_duplicate[i] {
+                    true => duplicates.insert(id),
+                    false => duplicates.remove(id),
+                }
+            }
+        }
+    }
+    Ok((is_changed, depends_on_buffer))
+}
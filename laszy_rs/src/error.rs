@@ -3,21 +3,146 @@ use std::io;
 
 #[derive(Debug, Display)]
 pub enum LaszyError {
-    IoError(io::Error),
-    LasError(String),
-    LaszyError(String),
+    /// Reading or writing a file failed.
+    #[display(fmt = "I/O error{}: {}", "location(path, &None)", source)]
+    IoError {
+        path: Option<String>,
+        source: io::Error,
+    },
+    /// The las crate could not read or write a file, or a point in it.
+    #[display(fmt = "LAS error{}: {}", "location(path, point_index)", message)]
+    LasError {
+        path: Option<String>,
+        point_index: Option<u64>,
+        message: String,
+    },
+    /// A builder or algorithm parameter has a value that can't be used.
+    #[display(fmt = "Invalid value {} for {}: {}", value, parameter, reason)]
+    InvalidParameter {
+        parameter: &'static str,
+        value: String,
+        reason: String,
+    },
+    /// The requested method exists in the API, but is not implemented yet.
+    #[display(fmt = "{} is not implemented yet", _0)]
+    NotImplemented(String),
+    #[display(fmt = "Empty cloud: {}", _0)]
     EmptyCloud(String),
+    /// A point can't be used, for example because its coordinates are not finite.
+    #[display(fmt = "Invalid point: {}", _0)]
+    InvalidPoint(String),
+    /// Points can't be triangulated into a TIN, for example because their coordinates are too
+    /// large.
+    #[display(fmt = "Triangulation failed: {}", _0)]
+    Triangulation(String),
+    #[display(fmt = "Invalid file extension: {}", _0)]
     InvalidFileExtension(String),
+    #[display(fmt = "Cancelled")]
+    Cancelled,
+    #[display(fmt = "Output file {} already exists", _0)]
+    OutputExists(String),
+}
+
+impl LaszyError {
+    pub(crate) fn invalid_parameter(
+        parameter: &'static str,
+        value: impl ToString,
+        reason: impl ToString,
+    ) -> Self {
+        LaszyError::InvalidParameter {
+            parameter,
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Add the file the error occurred in, unless the error already knows its file.
+    fn with_path(self, file: &str) -> Self {
+        match self {
+            LaszyError::IoError { path: None, source } => LaszyError::IoError {
+                path: Some(file.to_string()),
+                source,
+            },
+            LaszyError::LasError {
+                path: None,
+                point_index,
+                message,
+            } => LaszyError::LasError {
+                path: Some(file.to_string()),
+                point_index,
+                message,
+            },
+            other => other,
+        }
+    }
+
+    /// Add the index of the point in its file the error occurred at.
+    fn with_point_index(self, index: u64) -> Self {
+        match self {
+            LaszyError::LasError {
+                path,
+                point_index: None,
+                message,
+            } => LaszyError::LasError {
+                path,
+                point_index: Some(index),
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+fn location(path: &Option<String>, point_index: &Option<u64>) -> String {
+    match (path, point_index) {
+        (Some(path), Some(index)) => format!(" in {path} at point {index}"),
+        (Some(path), None) => format!(" in {path}"),
+        (None, Some(index)) => format!(" at point {index}"),
+        (None, None) => String::new(),
+    }
+}
+
+impl std::error::Error for LaszyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LaszyError::IoError { source, .. } => Some(source),
+            _ => None,
+        }
+    }
 }
 
 impl From<las::Error> for LaszyError {
     fn from(error: las::Error) -> Self {
-        LaszyError::LasError(error.to_string())
+        LaszyError::LasError {
+            path: None,
+            point_index: None,
+            message: error.to_string(),
+        }
     }
 }
 
 impl From<io::Error> for LaszyError {
     fn from(error: io::Error) -> Self {
-        LaszyError::IoError(error)
+        LaszyError::IoError {
+            path: None,
+            source: error,
+        }
+    }
+}
+
+/// Adds the file and point an error occurred at to results of the las crate and std::io.
+pub(crate) trait ErrorContext<T> {
+    fn in_file(self, path: &str) -> Result<T, LaszyError>;
+
+    fn at_point(self, path: &str, point_index: u64) -> Result<T, LaszyError>;
+}
+
+impl<T, E: Into<LaszyError>> ErrorContext<T> for Result<T, E> {
+    fn in_file(self, path: &str) -> Result<T, LaszyError> {
+        self.map_err(|e| e.into().with_path(path))
+    }
+
+    fn at_point(self, path: &str, point_index: u64) -> Result<T, LaszyError> {
+        self.map_err(|e| e.into().with_path(path).with_point_index(point_index))
     }
 }
@@ -0,0 +1,214 @@
+//! Extra bytes of LAS points: fields after the standard attributes of each point, described by the
+//! "LASF_Spec" VLR with record id 4.
+
+use las::Vlr;
+
+const EXTRA_BYTES_USER_ID: &str = "LASF_Spec";
+const EXTRA_BYTES_RECORD: u16 = 4;
+/// Size of the description of one field in the VLR.
+const DESCRIPTOR_SIZE: usize = 192;
+/// Data type of bytes without a known meaning, of which the options give the amount.
+const UNDOCUMENTED_TYPE: u8 = 0;
+const DOUBLE_TYPE: u8 = 10;
+/// Option bits that mark the no data value, the scale and the offset as set.
+const NO_DATA_BIT: u8 = 1;
+const SCALE_BIT: u8 = 1 << 3;
+const OFFSET_BIT: u8 = 1 << 4;
+/// Where the name, the no data value, the minimum and maximum, the scale, the offset and the
+/// description of a field are in its descriptor.
+const NAME: std::ops::Range<usize> = 4..36;
+const NO_DATA: usize = 40;
+const MIN_MAX: std::ops::Range<usize> = 64..112;
+const SCALE: usize = 112;
+const OFFSET: usize = 136;
+const DESCRIPTION: std::ops::Range<usize> = 160..192;
+
+/// Description of a field in the extra bytes of the points, kept as its descriptor in the extra
+/// bytes VLR so the fields of the input are written unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ExtraBytesField {
+    descriptor: [u8; DESCRIPTOR_SIZE],
+}
+
+impl ExtraBytesField {
+    fn new(data_type: u8, options: u8, name: &str, description: &str) -> Self {
+        let mut descriptor = [0; DESCRIPTOR_SIZE];
+        descriptor[2] = data_type;
+        descriptor[3] = options;
+        write_string(&mut descriptor[NAME], name);
+        write_string(&mut descriptor[DESCRIPTION], description);
+        ExtraBytesField { descriptor }
+    }
+
+    /// A 64 bit float field.
+    pub fn double(name: &str, description: &str, no_data: Option<f64>) -> Self {
+        let options = if no_data.is_some() { NO_DATA_BIT } else { 0 };
+        let mut field = Self::new(DOUBLE_TYPE, options, name, description);
+        if let Some(no_data) = no_data {
+            field.descriptor[NO_DATA..NO_DATA + 8].copy_from_slice(&no_data.to_le_bytes());
+        }
+        field
+    }
+
+    /// Fields for `bytes` bytes without a known meaning, such as extra bytes of the input that
+    /// aren't described. A field has at most 255 bytes.
+    pub fn undocumented(bytes: u16) -> Vec<Self> {
+        let mut fields = Vec::new();
+        let mut remaining = bytes;
+        while remaining > 0 {
+            let size = remaining.min(u8::MAX as u16);
+            let name = format!("undocumented {}", fields.len() + 1);
+            fields.push(Self::new(UNDOCUMENTED_TYPE, size as u8, &name, ""));
+            remaining -= size;
+        }
+        fields
+    }
+
+    /// The fields of the `extra_bytes` bytes of the points of a file with `header`, as described
+    /// by its extra bytes VLR. Bytes that aren't described become undocumented fields.
+    pub fn from_header(header: &las::Header, extra_bytes: u16) -> Vec<Self> {
+        let vlr =
+            header.vlrs().iter().chain(header.evlrs()).find(|vlr| {
+                vlr.user_id == EXTRA_BYTES_USER_ID && vlr.record_id == EXTRA_BYTES_RECORD
+            });
+        let mut fields: Vec<Self> = vlr
+            .map(|vlr| {
+                vlr.data
+                    .chunks_exact(DESCRIPTOR_SIZE)
+                    .map(|bytes| ExtraBytesField {
+                        descriptor: bytes.try_into().expect("descriptor size"),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let described: usize = fields.iter().map(ExtraBytesField::size).sum();
+        if described > extra_bytes as usize {
+            log::warn!(
+                "The extra bytes VLR describes {described} bytes, but the points have \
+                 {extra_bytes}, the extra bytes are read as undocumented"
+            );
+            return Self::undocumented(extra_bytes);
+        }
+        fields.extend(Self::undocumented(extra_bytes - described as u16));
+        fields
+    }
+
+    pub fn name(&self) -> String {
+        read_string(&self.descriptor[NAME])
+    }
+
+    /// Whether the field stores its values the same way as `other`, which may only differ in the
+    /// minimum and maximum of the values of its file.
+    pub fn same_layout(&self, other: &ExtraBytesField) -> bool {
+        let without_min_max = |field: &ExtraBytesField| {
+            let mut descriptor = field.descriptor;
+            descriptor[MIN_MAX].fill(0);
+            descriptor
+        };
+        without_min_max(self) == without_min_max(other)
+    }
+
+    fn data_type(&self) -> u8 {
+        self.descriptor[2]
+    }
+
+    fn options(&self) -> u8 {
+        self.descriptor[3]
+    }
+
+    /// Amount of bytes of the field in each point.
+    pub fn size(&self) -> usize {
+        match self.data_type() {
+            UNDOCUMENTED_TYPE => self.options() as usize,
+            data_type => {
+                // Types 11 to 30 are deprecated arrays of 2 and 3 values of types 1 to 10
+                let (value_type, values) = match data_type {
+                    1..=10 => (data_type, 1),
+                    11..=20 => (data_type - 10, 2),
+                    _ => (data_type - 20, 3),
+                };
+                value_size(value_type) * values
+            }
+        }
+    }
+
+    /// Whether the field holds a single number per point, which it has a value of.
+    pub fn is_number(&self) -> bool {
+        (1..=DOUBLE_TYPE).contains(&self.data_type())
+    }
+
+    fn option_value(&self, bit: u8, at: usize) -> Option<f64> {
+        (self.options() & bit != 0)
+            .then(|| f64::from_le_bytes(self.descriptor[at..at + 8].try_into().expect("8 bytes")))
+    }
+
+    /// The value of the field in the `bytes` of the field of a point, with its scale and offset
+    /// applied, or NaN when it is the no data value. None for undocumented fields and the
+    /// deprecated arrays.
+    pub fn value(&self, bytes: &[u8]) -> Option<f64> {
+        let raw: [u8; 8] = std::array::from_fn(|i| bytes.get(i).copied().unwrap_or(0));
+        let no_data = &self.descriptor[NO_DATA..NO_DATA + 8];
+        let size = value_size(self.data_type());
+        let value = match self.data_type() {
+            1 => raw[0] as f64,
+            2 => raw[0] as i8 as f64,
+            3 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            4 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            5 => u32::from_le_bytes(raw[..4].try_into().expect("4 bytes")) as f64,
+            6 => i32::from_le_bytes(raw[..4].try_into().expect("4 bytes")) as f64,
+            7 => u64::from_le_bytes(raw) as f64,
+            8 => i64::from_le_bytes(raw) as f64,
+            9 => f32::from_le_bytes(raw[..4].try_into().expect("4 bytes")) as f64,
+            DOUBLE_TYPE => f64::from_le_bytes(raw),
+            _ => return None,
+        };
+        // The no data value is stored in the type of the field, widened to 8 bytes
+        let is_no_data = self.options() & NO_DATA_BIT != 0
+            && match self.data_type() {
+                9 => f64::from_le_bytes(no_data.try_into().expect("8 bytes")) == value,
+                DOUBLE_TYPE => no_data == raw,
+                _ => no_data[..size] == raw[..size],
+            };
+        if is_no_data {
+            return Some(f64::NAN);
+        }
+        let scale = self.option_value(SCALE_BIT, SCALE).unwrap_or(1.0);
+        let offset = self.option_value(OFFSET_BIT, OFFSET).unwrap_or(0.0);
+        Some(value * scale + offset)
+    }
+}
+
+/// Size in bytes of a value of the data types 1 to 10.
+fn value_size(data_type: u8) -> usize {
+    match data_type {
+        1 | 2 => 1,
+        3 | 4 => 2,
+        5 | 6 | 9 => 4,
+        _ => 8,
+    }
+}
+
+/// Write `value` to `field`, cut off or padded with zeros to its length.
+fn write_string(field: &mut [u8], value: &str) {
+    let length = value.len().min(field.len());
+    field[..length].copy_from_slice(&value.as_bytes()[..length]);
+}
+
+/// The string in `field`, up to the first zero.
+fn read_string(field: &[u8]) -> String {
+    let length = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..length]).to_string()
+}
+
+/// The VLR describing the extra bytes of the points, in the order of `fields`.
+pub(crate) fn extra_bytes_vlr(fields: &[ExtraBytesField]) -> Vlr {
+    Vlr {
+        user_id: EXTRA_BYTES_USER_ID.to_string(),
+        record_id: EXTRA_BYTES_RECORD,
+        description: "Extra bytes".to_string(),
+        data: fields.iter().flat_map(|field| field.descriptor).collect(),
+    }
+}
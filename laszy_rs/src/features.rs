@@ -0,0 +1,311 @@
+//! Geometric features of the points, such as their normal and planarity, from the principal
+//! components of the neighbourhood of each point.
+
+use crate::cancellation::CancellationToken;
+use crate::error::ErrorContext;
+use crate::extra_bytes::ExtraBytesField;
+use crate::geometry::LocalShape;
+use crate::output::TempDir;
+use crate::progress::ProgressReporter;
+use crate::tiles::{self, PointId, PointTiles};
+use crate::LaszyError;
+use kdtree::distance::squared_euclidean;
+use rayon::prelude::*;
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Distance in meters around a tile within which the nearest neighbours of its points are found.
+const KNN_BUFFER: f64 = 10.0;
+/// Amount of values of the features of a point: the x, y and z of the normal, followed by the
+/// `GeometricFeature`s.
+pub(crate) const FEATURE_COUNT: usize = 8;
+/// Name and description of the extra bytes field of each value of the features.
+const FEATURE_FIELDS: [(&str, &str); FEATURE_COUNT] = [
+    ("NormalX", "X of the unit normal"),
+    ("NormalY", "Y of the unit normal"),
+    ("NormalZ", "Z of the unit normal"),
+    ("Curvature", "Change of curvature"),
+    ("Planarity", "Planarity"),
+    ("Linearity", "Linearity"),
+    ("Sphericity", "Sphericity"),
+    ("Verticality", "Verticality"),
+];
+/// Bytes of the features of a point in the file of its input file: a byte that is 1 when the
+/// features are computed, followed by the values as little endian f64.
+const RECORD_SIZE: usize = 1 + FEATURE_COUNT * 8;
+
+/// The neighbours of a point its features are computed from, including the point itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Neighbourhood {
+    /// The `k` nearest points.
+    Knn { k: usize },
+    /// The points within `radius` meters in 3D.
+    Radius { radius: f64 },
+}
+
+impl Default for Neighbourhood {
+    fn default() -> Self {
+        Neighbourhood::Knn { k: 10 }
+    }
+}
+
+impl Neighbourhood {
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        match *self {
+            Neighbourhood::Knn { k } if k < 3 => {
+                Err(LaszyError::invalid_parameter("k", k, "must be at least 3"))
+            }
+            Neighbourhood::Radius { radius } if !(radius > 0.0 && radius.is_finite()) => Err(
+                LaszyError::invalid_parameter("radius", radius, "must be larger than 0.0"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Distance around a tile within which the neighbours of its points are.
+    pub(crate) fn buffer(&self) -> f64 {
+        match *self {
+            Neighbourhood::Knn { .. } => KNN_BUFFER,
+            Neighbourhood::Radius { radius } => radius,
+        }
+    }
+}
+
+/// A geometric feature of a point and its neighbours, besides the normal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeometricFeature {
+    /// The change of curvature, the smallest eigenvalue divided by the sum of the eigenvalues of
+    /// the covariance matrix, from 0.0 for a plane to 1/3 for points scattered in all directions.
+    Curvature,
+    /// How much the points lie in a plane, from 0.0 to 1.0.
+    Planarity,
+    /// How much the points lie on a line, from 0.0 to 1.0.
+    Linearity,
+    /// How much the points are scattered in all directions, from 0.0 to 1.0.
+    Sphericity,
+    /// How vertical the plane through the points is, from 0.0 for a horizontal plane to 1.0 for a
+    /// vertical one.
+    Verticality,
+}
+
+impl GeometricFeature {
+    /// Name of the extra bytes field of the feature.
+    pub fn name(&self) -> &'static str {
+        FEATURE_FIELDS[self.index()].0
+    }
+
+    /// Index of the feature in the values of the features of a point.
+    fn index(&self) -> usize {
+        match self {
+            GeometricFeature::Curvature => 3,
+            GeometricFeature::Planarity => 4,
+            GeometricFeature::Linearity => 5,
+            GeometricFeature::Sphericity => 6,
+            GeometricFeature::Verticality => 7,
+        }
+    }
+}
+
+/// Names of the extra bytes fields of the x, y and z of the normal.
+pub(crate) const NORMAL_FIELDS: [&str; 3] = [
+    FEATURE_FIELDS[0].0,
+    FEATURE_FIELDS[1].0,
+    FEATURE_FIELDS[2].0,
+];
+
+/// The extra bytes fields the features of the points are stored in, 64 bit floats that are NaN
+/// for points without features.
+pub(crate) fn extra_bytes_fields() -> Vec<ExtraBytesField> {
+    FEATURE_FIELDS
+        .iter()
+        .map(|(name, description)| ExtraBytesField::double(name, description, None))
+        .collect()
+}
+
+/// The values of the features of the points with `shape`.
+fn feature_values(shape: &LocalShape) -> [f64; FEATURE_COUNT] {
+    let [x, y, z] = shape.normal();
+    [
+        x,
+        y,
+        z,
+        shape.curvature(),
+        shape.planarity(),
+        shape.linearity(),
+        shape.sphericity(),
+        shape.verticality(),
+    ]
+}
+
+/// The features of the points, in a temporary file per input file at the index of each point.
+pub(crate) struct FeatureStore {
+    dir: TempDir,
+    files: HashMap<u32, File>,
+}
+
+impl FeatureStore {
+    fn new() -> Result<Self, LaszyError> {
+        Ok(FeatureStore {
+            dir: TempDir::new("laszy-features")?,
+            files: HashMap::new(),
+        })
+    }
+
+    fn path(&self, file_index: u32) -> PathBuf {
+        self.dir.path().join(format!("features-{file_index}.bin"))
+    }
+
+    /// Write the features of points of the same file with consecutive indices, starting at `id`.
+    fn write_run(&mut self, id: PointId, records: &[u8]) -> Result<(), LaszyError> {
+        let path = self.path(id.0);
+        let path_name = path.to_string_lossy().to_string();
+        let file = match self.files.entry(id.0) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .truncate(true)
+                    .write(true)
+                    .open(&path)
+                    .in_file(&path_name)?,
+            ),
+        };
+        file.seek(SeekFrom::Start(id.1 * RECORD_SIZE as u64))
+            .and_then(|_| file.write_all(records))
+            .in_file(&path_name)
+    }
+
+    /// Write the features of the points, sorted by where they are in the input.
+    fn write(
+        &mut self,
+        mut features: Vec<(PointId, [f64; FEATURE_COUNT])>,
+    ) -> Result<(), LaszyError> {
+        features.sort_by_key(|(id, _)| *id);
+        let mut start = None;
+        let mut records = Vec::new();
+        for (i, (id, values)) in features.iter().enumerate() {
+            let follows = i > 0 && features[i - 1].0 == (id.0, id.1.wrapping_sub(1));
+            if !follows {
+                if let Some(start) = start {
+                    self.write_run(start, &records)?;
+                }
+                start = Some(*id);
+                records.clear();
+            }
+            records.push(1);
+            for value in values {
+                records.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if let Some(start) = start {
+            self.write_run(start, &records)?;
+        }
+        Ok(())
+    }
+
+    /// Read the features back in the order of the points in the input.
+    pub fn reader(mut self) -> FeatureReader {
+        self.files.clear();
+        FeatureReader {
+            store: self,
+            file: None,
+        }
+    }
+}
+
+/// Reads the features of the points from a `FeatureStore`, in the order of the input.
+pub(crate) struct FeatureReader {
+    store: FeatureStore,
+    /// The index of the input file being read, with the file of its features, if there is one,
+    /// and the position in it.
+    file: Option<(u32, Option<BufReader<File>>, u64)>,
+}
+
+impl FeatureReader {
+    /// The values of the features of a point, NaN when they are not computed. The points of a file
+    /// must be read with increasing indices.
+    pub fn read(&mut self, id: PointId) -> Result<[f64; FEATURE_COUNT], LaszyError> {
+        let path = self.store.path(id.0);
+        let path_name = path.to_string_lossy();
+        if self.file.as_ref().is_none_or(|(index, ..)| *index != id.0) {
+            let file = match File::open(&path) {
+                Ok(file) => Some(BufReader::new(file)),
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                Err(e) => return Err(e).in_file(&path_name),
+            };
+            self.file = Some((id.0, file, 0));
+        }
+        let missing = [f64::NAN; FEATURE_COUNT];
+        let Some((_, Some(file), position)) = self.file.as_mut() else {
+            return Ok(missing);
+        };
+        let offset = id.1 * RECORD_SIZE as u64;
+        file.seek_relative(offset as i64 - *position as i64)
+            .in_file(&path_name)?;
+        let mut record = [0; RECORD_SIZE];
+        match file.read_exact(&mut record) {
+            Ok(()) => *position = offset + RECORD_SIZE as u64,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                // Past the last point with features, the position is no longer known
+                file.rewind().in_file(&path_name)?;
+                *position = 0;
+                return Ok(missing);
+            }
+            Err(e) => return Err(e).in_file(&path_name),
+        }
+        if record[0] == 0 {
+            return Ok(missing);
+        }
+        Ok(std::array::from_fn(|i| {
+            f64::from_le_bytes(record[1 + i * 8..9 + i * 8].try_into().expect("8 bytes"))
+        }))
+    }
+}
+
+/// Compute the features of the core points of the `tiles` from their `neighbourhood`, going
+/// through the tiles one at a time. Points with fewer than 3 neighbours get no features.
+pub(crate) fn compute(
+    mut tiles: PointTiles,
+    neighbourhood: Neighbourhood,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<FeatureStore, LaszyError> {
+    tiles.finish()?;
+    let mut store = FeatureStore::new()?;
+    let tile_count = tiles.tile_count();
+    let mut count = 0_usize;
+    progress.start("Computing features in tiles...", Some(tile_count as u64));
+    for tile_index in 0..tile_count {
+        cancellation.check()?;
+        progress.advance(1);
+        let tile = tiles.take_tile(tile_index)?;
+        let index = tile.kdtree()?;
+        let features = (0..tile.points.len())
+            .into_par_iter()
+            .filter(|i| tile.is_core[*i])
+            .map(|i| {
+                let point = &tile.points[i];
+                let neighbours = match neighbourhood {
+                    Neighbourhood::Knn { k } => index.nearest(&point.xyz, k, &squared_euclidean),
+                    Neighbourhood::Radius { radius } => {
+                        index.within(&point.xyz, radius * radius, &squared_euclidean)
+                    }
+                }
+                .map_err(tiles::invalid_point)?;
+                let neighbours: Vec<[f64; 3]> = neighbours
+                    .iter()
+                    .map(|(_, j)| tile.points[**j].xyz)
+                    .collect();
+                let shape = LocalShape::new(&neighbours);
+                Ok(shape.map(|shape| (point.id, feature_values(&shape))))
+            })
+            .collect::<Result<Vec<_>, LaszyError>>()?;
+        let features: Vec<_> = features.into_iter().flatten().collect();
+        count += features.len();
+        store.write(features)?;
+    }
+    progress.finish(&format!("Computed the features of {count} points"));
+    Ok(store)
+}
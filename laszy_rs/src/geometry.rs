@@ -0,0 +1,145 @@
+//! The local shape of the neighbourhood of a point, from a principal component analysis of the
+//! positions of its neighbours.
+
+/// Amount of Jacobi sweeps after which the eigenvalues of a 3 by 3 matrix have converged.
+const JACOBI_SWEEPS: usize = 16;
+
+/// The principal components of a set of points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LocalShape {
+    pub centroid: [f64; 3],
+    /// Eigenvalues of the covariance matrix, from largest to smallest.
+    pub eigenvalues: [f64; 3],
+    /// Unit eigenvectors of the eigenvalues.
+    pub eigenvectors: [[f64; 3]; 3],
+}
+
+impl LocalShape {
+    /// The shape of `points`, or None with fewer than 3 points.
+    pub fn new(points: &[[f64; 3]]) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let count = points.len() as f64;
+        let mut centroid = [0.0; 3];
+        for point in points {
+            for (centre, value) in centroid.iter_mut().zip(point) {
+                *centre += value / count;
+            }
+        }
+        let mut covariance = [[0.0; 3]; 3];
+        for point in points {
+            let d = [
+                point[0] - centroid[0],
+                point[1] - centroid[1],
+                point[2] - centroid[2],
+            ];
+            for (row, d_row) in covariance.iter_mut().zip(d) {
+                for (value, d_column) in row.iter_mut().zip(d) {
+                    *value += d_row * d_column / count;
+                }
+            }
+        }
+        let (eigenvalues, eigenvectors) = symmetric_eigen(covariance);
+        Some(LocalShape {
+            centroid,
+            eigenvalues,
+            eigenvectors,
+        })
+    }
+
+    /// Unit normal of the plane through the points, pointing up.
+    pub fn normal(&self) -> [f64; 3] {
+        let normal = self.eigenvectors[2];
+        if normal[2] < 0.0 {
+            normal.map(|value| -value)
+        } else {
+            normal
+        }
+    }
+
+    /// How much the points lie on a line, from 0.0 to 1.0.
+    pub fn linearity(&self) -> f64 {
+        let [l1, l2, _] = self.eigenvalues;
+        ratio(l1 - l2, l1)
+    }
+
+    /// How much the points lie in a plane, from 0.0 to 1.0.
+    pub fn planarity(&self) -> f64 {
+        let [l1, l2, l3] = self.eigenvalues;
+        ratio(l2 - l3, l1)
+    }
+
+    /// How much the points are scattered in all directions, from 0.0 to 1.0.
+    pub fn sphericity(&self) -> f64 {
+        let [l1, _, l3] = self.eigenvalues;
+        ratio(l3, l1)
+    }
+
+    /// The change of curvature, the share of the variance of the points along their normal, from
+    /// 0.0 for a plane to 1/3 for points scattered in all directions.
+    pub fn curvature(&self) -> f64 {
+        let [l1, l2, l3] = self.eigenvalues;
+        ratio(l3, l1 + l2 + l3)
+    }
+
+    /// How vertical the plane through the points is, from 0.0 for a horizontal plane to 1.0 for a
+    /// vertical one.
+    pub fn verticality(&self) -> f64 {
+        1.0 - self.normal()[2].abs()
+    }
+
+    /// Root mean square of the distances of the points to the plane through them.
+    pub fn roughness(&self) -> f64 {
+        self.eigenvalues[2].sqrt()
+    }
+}
+
+/// `numerator` divided by `denominator`, or 0.0 when all points are the same.
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0.0 {
+        numerator / denominator
+    } else {
+        0.0
+    }
+}
+
+/// Eigenvalues from largest to smallest and unit eigenvectors of a symmetric 3 by 3 matrix, with
+/// cyclic Jacobi rotations.
+pub(crate) fn symmetric_eigen(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..JACOBI_SWEEPS {
+        let off_diagonal = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+        let diagonal = a[0][0].powi(2) + a[1][1].powi(2) + a[2][2].powi(2);
+        if off_diagonal <= f64::EPSILON * f64::EPSILON * diagonal {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in &mut a {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in &mut v {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+        }
+    }
+    let mut order = [0, 1, 2];
+    order.sort_by(|i, j| a[*j][*j].total_cmp(&a[*i][*i]));
+    let values = order.map(|i| a[i][i].max(0.0));
+    let vectors = order.map(|i| [v[0][i], v[1][i], v[2][i]]);
+    (values, vectors)
+}
@@ -0,0 +1,73 @@
+//! Ground filters that classify points as ground: the cloth simulation of `csf`, the
+//! morphological filters SMRF and PMF, and the progressive TIN densification.
+
+pub(crate) mod morphology;
+pub mod pmf;
+pub mod ptd;
+pub mod smrf;
+
+use crate::csf::params::CsfParams;
+use crate::csf::Cloth;
+use crate::LaszyError;
+use las::point::Classification;
+use las::Point;
+pub(crate) use morphology::{MinimumSurface, MorphologicalGround};
+use pmf::PmfParams;
+use ptd::{DensifiedTin, PtdParams};
+use smrf::SmrfParams;
+
+/// The ground filter of a ground reclassification.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum GroundFilter {
+    Csf(CsfParams),
+    Smrf(SmrfParams),
+    Pmf(PmfParams),
+    Ptd(PtdParams),
+}
+
+impl GroundFilter {
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        match self {
+            GroundFilter::Csf(params) => params.validate(),
+            GroundFilter::Smrf(params) => params.validate(),
+            GroundFilter::Pmf(params) => params.validate(),
+            GroundFilter::Ptd(params) => params.validate(),
+        }
+    }
+}
+
+/// A ground filter that has been run, ready to classify points.
+pub(crate) enum GroundClassifier {
+    Cloth(Cloth),
+    Morphological(MorphologicalGround),
+    Densified(DensifiedTin),
+}
+
+impl GroundClassifier {
+    pub fn is_ground_point(&self, point: &Point) -> Result<bool, LaszyError> {
+        match self {
+            GroundClassifier::Cloth(cloth) => cloth.is_ground_point(point),
+            GroundClassifier::Morphological(ground) => Ok(ground.is_ground_point(point)),
+            GroundClassifier::Densified(tin) => Ok(tin.is_ground_point(point)),
+        }
+    }
+
+    /// Classify a point as ground when the filter finds it is ground. Points that were ground but
+    /// aren't according to the filter become unclassified, other classes are kept.
+    pub fn reclassify(&self, point: &mut Point) -> Result<(), LaszyError> {
+        if self.is_ground_point(point)? {
+            point.classification = Classification::Ground;
+        } else if point.classification == Classification::Ground {
+            point.classification = Classification::Unclassified;
+        }
+        Ok(())
+    }
+
+    /// The simulated cloth, when the filter is CSF.
+    pub fn cloth(&self) -> Option<&Cloth> {
+        match self {
+            GroundClassifier::Cloth(cloth) => Some(cloth),
+            GroundClassifier::Morphological(_) | GroundClassifier::Densified(_) => None,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Grid operations shared by the morphological ground filters, SMRF and PMF. The filters work on
+//! a grid of the lowest point per cell, and remove the cells that stick out of the terrain after
+//! a morphological opening with growing windows.
+
+use crate::dtm::fill_all;
+use crate::raster::{MemoryRaster, RasterGrid};
+use las::Point;
+use ndarray::{Array2, ArrayViewMut1, Axis, Zip};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
+/// A grid with the lowest point per cell, built up one point at a time.
+pub(crate) struct MinimumSurface {
+    raster: MemoryRaster,
+}
+
+impl MinimumSurface {
+    pub fn new(grid: RasterGrid) -> Self {
+        MinimumSurface {
+            raster: MemoryRaster::empty(grid),
+        }
+    }
+
+    pub fn add_point(&mut self, point: &Point) {
+        if let Some(cell) = self.raster.grid.cell(point.x, point.y) {
+            let height = &mut self.raster.values[cell];
+            if height.is_nan() || point.z < *height {
+                *height = point.z;
+            }
+        }
+    }
+
+    /// The surface after all points are added, with the cells without points filled from the
+    /// cells around them.
+    pub fn finish(mut self) -> MemoryRaster {
+        fill_all(&mut self.raster.values);
+        self.raster
+    }
+}
+
+/// Morphological opening, an erosion followed by a dilation, with a square window that reaches
+/// `radius` cells around each cell. It removes objects that are smaller than the window while
+/// keeping the shape of larger terrain features.
+pub(crate) fn opening(values: &Array2<f64>, radius: usize) -> Array2<f64> {
+    let eroded = filter(values, radius, true);
+    filter(&eroded, radius, false)
+}
+
+/// The lowest or highest value in a square window around each cell. A square window is separable,
+/// so the rows and columns are filtered one after the other.
+fn filter(values: &Array2<f64>, radius: usize, lowest: bool) -> Array2<f64> {
+    let mut filtered = values.clone();
+    for axis in [Axis(0), Axis(1)] {
+        filtered
+            .axis_iter_mut(axis)
+            .into_par_iter()
+            .for_each(|lane| sliding_extreme(lane, radius, lowest));
+    }
+    filtered
+}
+
+/// Replace each value in `lane` by the lowest or highest value within `radius` of it, using a
+/// monotonic queue so the window size doesn't matter for the speed.
+fn sliding_extreme(mut lane: ArrayViewMut1<f64>, radius: usize, lowest: bool) {
+    let values = lane.to_vec();
+    let replaces = |new: f64, old: f64| if lowest { new <= old } else { new >= old };
+    let mut window: VecDeque<usize> = VecDeque::new();
+    let mut next = 0;
+    for (i, value) in lane.iter_mut().enumerate() {
+        while next < values.len() && next <= i + radius {
+            while window
+                .back()
+                .is_some_and(|back| replaces(values[next], values[*back]))
+            {
+                window.pop_back();
+            }
+            window.push_back(next);
+            next += 1;
+        }
+        while window.front().is_some_and(|front| front + radius < i) {
+            window.pop_front();
+        }
+        *value = values[window[0]];
+    }
+}
+
+/// The minimum surface without the cells of objects, filled from the ground cells around them.
+pub(crate) fn ground_surface(minimum: &MemoryRaster, is_object: &Array2<bool>) -> MemoryRaster {
+    let mut values = minimum.values.clone();
+    Zip::from(&mut values)
+        .and(is_object)
+        .for_each(|value, is_object| {
+            if *is_object {
+                *value = f64::NAN;
+            }
+        });
+    fill_all(&mut values);
+    MemoryRaster {
+        grid: minimum.grid,
+        values,
+    }
+}
+
+/// Slope of each cell as rise over run, from the differences with the cells next to it.
+pub(crate) fn slopes(raster: &MemoryRaster) -> Array2<f64> {
+    let (rows, columns) = raster.values.dim();
+    let values = &raster.values;
+    let cell_size = raster.grid.cell_size;
+    // The difference over the neighbours on both sides, or one side at the edge
+    let gradient = |before: f64, after: f64, cells: usize| match cells {
+        0 => 0.0,
+        cells => (after - before) / (cells as f64 * cell_size),
+    };
+    Array2::from_shape_fn((rows, columns), |(row, column)| {
+        let (left, right) = (column.saturating_sub(1), (column + 1).min(columns - 1));
+        let (up, down) = (row.saturating_sub(1), (row + 1).min(rows - 1));
+        let dx = gradient(values[[row, left]], values[[row, right]], right - left);
+        let dy = gradient(values[[up, column]], values[[down, column]], down - up);
+        dx.hypot(dy)
+    })
+}
+
+/// The ground surface of a morphological filter, with the distance a point may be from it per
+/// cell to be ground.
+pub(crate) struct MorphologicalGround {
+    surface: MemoryRaster,
+    thresholds: Array2<f64>,
+}
+
+impl MorphologicalGround {
+    pub fn new(surface: MemoryRaster, thresholds: Array2<f64>) -> Self {
+        MorphologicalGround {
+            surface,
+            thresholds,
+        }
+    }
+
+    /// Whether the point is within the threshold of its cell from the bilinearly interpolated
+    /// ground surface.
+    pub fn is_ground_point(&self, point: &Point) -> bool {
+        let (Some(height), Some(cell)) = (
+            self.surface.value_at(point.x, point.y),
+            self.surface.grid.cell(point.x, point.y),
+        ) else {
+            return false;
+        };
+        (point.z - height).abs() <= self.thresholds[cell]
+    }
+}
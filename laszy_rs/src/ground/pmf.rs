@@ -0,0 +1,171 @@
+use crate::cancellation::CancellationToken;
+use crate::ground::morphology::{ground_surface, opening, slopes, MorphologicalGround};
+use crate::progress::ProgressReporter;
+use crate::raster::MemoryRaster;
+use crate::LaszyError;
+use ndarray::{Array2, Zip};
+
+/// Parameters of the Progressive Morphological Filter (PMF) of Zhang et al. (2003). The defaults
+/// are those of PDAL.
+///
+/// The filter opens the grid of the lowest points with windows that grow up to `max_window`. A
+/// cell is an object when it is higher than the opened grid by more than the elevation difference
+/// threshold of the window, which grows with the window size and the slope. Points are ground when
+/// they are within `initial_distance`, plus the rise of the terrain within a cell, of the surface
+/// interpolated from the remaining cells.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::PmfParams;
+/// let params = PmfParams::default().with_max_window(20.0).with_exponential(false);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PmfParams {
+    /// Cell size in meters of the grid of the lowest points.
+    pub cell_size: f64,
+    /// Size in meters of the largest window, which should be larger than the largest object, such
+    /// as a building.
+    pub max_window: f64,
+    /// Slope as rise over run of the terrain, used to grow the elevation difference threshold.
+    pub slope: f64,
+    /// Elevation difference threshold in meters of the first window.
+    pub initial_distance: f64,
+    /// Largest elevation difference threshold in meters.
+    pub max_distance: f64,
+    /// Grow the windows exponentially, with sizes of 3, 5, 9, 17 and so on cells, instead of
+    /// linearly with sizes of 3, 5, 7 and so on cells.
+    pub exponential: bool,
+}
+
+impl Default for PmfParams {
+    fn default() -> Self {
+        PmfParams {
+            cell_size: 1.0,
+            max_window: 33.0,
+            slope: 1.0,
+            initial_distance: 0.15,
+            max_distance: 2.5,
+            exponential: true,
+        }
+    }
+}
+
+impl PmfParams {
+    pub fn with_cell_size(mut self, cell_size: f64) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    pub fn with_max_window(mut self, max_window: f64) -> Self {
+        self.max_window = max_window;
+        self
+    }
+
+    pub fn with_slope(mut self, slope: f64) -> Self {
+        self.slope = slope;
+        self
+    }
+
+    pub fn with_initial_distance(mut self, initial_distance: f64) -> Self {
+        self.initial_distance = initial_distance;
+        self
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f64) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn with_exponential(mut self, exponential: bool) -> Self {
+        self.exponential = exponential;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.cell_size > 0.0 && self.cell_size.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "cell_size",
+                self.cell_size,
+                "must be larger than 0.0",
+            ));
+        }
+        if !(self.max_window >= 3.0 * self.cell_size && self.max_window.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "max_window",
+                self.max_window,
+                "must be at least 3 cells",
+            ));
+        }
+        for (parameter, value) in [
+            ("slope", self.slope),
+            ("initial_distance", self.initial_distance),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(LaszyError::invalid_parameter(
+                    parameter,
+                    value,
+                    "must be 0.0 or larger",
+                ));
+            }
+        }
+        if !(self.max_distance >= self.initial_distance && self.max_distance.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "max_distance",
+                self.max_distance,
+                "must be at least the initial distance",
+            ));
+        }
+        Ok(())
+    }
+
+    /// The radii in cells of the windows, which have a size of twice the radius plus one.
+    fn window_radii(&self) -> Vec<usize> {
+        let max_size = (self.max_window / self.cell_size).floor() as usize;
+        (0..)
+            .map(|k| match self.exponential {
+                true => 1_usize << k,
+                false => k + 1,
+            })
+            .take_while(|radius| 2 * radius < max_size)
+            .collect()
+    }
+}
+
+/// Filter the ground from the grid of the lowest points.
+pub(crate) fn filter(
+    minimum: MemoryRaster,
+    params: &PmfParams,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<MorphologicalGround, LaszyError> {
+    let cell_size = minimum.grid.cell_size;
+    let radii = params.window_radii();
+    let mut is_object = Array2::from_elem(minimum.values.dim(), false);
+    let mut last = minimum.values.clone();
+    let mut threshold = params.initial_distance;
+    progress.start("Filtering ground with PMF...", Some(radii.len() as u64));
+    for (k, radius) in radii.iter().enumerate() {
+        cancellation.check()?;
+        if k > 0 {
+            let growth = 2 * (radius - radii[k - 1]);
+            threshold = (params.slope * growth as f64 * cell_size + params.initial_distance)
+                .min(params.max_distance);
+        }
+        let opened = opening(&last, *radius);
+        Zip::from(&mut is_object)
+            .and(&last)
+            .and(&opened)
+            .for_each(|is_object, last, opened| *is_object |= last - opened > threshold);
+        last = opened;
+        progress.advance(1);
+    }
+    let objects = is_object.iter().filter(|is_object| **is_object).count();
+    progress.finish(&format!("Removed {objects} cells of objects"));
+
+    let surface = ground_surface(&minimum, &is_object);
+    let thresholds = slopes(&surface)
+        .mapv(|slope| (params.initial_distance + slope * cell_size).min(params.max_distance));
+    Ok(MorphologicalGround::new(surface, thresholds))
+}
@@ -0,0 +1,386 @@
+use crate::cancellation::CancellationToken;
+use crate::csf::surface::neighbour_indices;
+use crate::dtm::GroundVertex;
+use crate::progress::ProgressReporter;
+use crate::raster::RasterGrid;
+use crate::LaszyError;
+use las::Point;
+use ndarray::Array2;
+use rayon::prelude::*;
+use spade::handles::{FixedFaceHandle, FixedVertexHandle, InnerTag};
+use spade::{DelaunayTriangulation, Point2, PositionInTriangulation, Triangulation};
+use std::collections::HashMap;
+
+/// Parameters of the progressive TIN densification (PTD) of Axelsson (2000), the ground filter of
+/// TerraScan and of many national mapping agencies. The defaults are those of TerraScan.
+///
+/// The filter seeds a TIN with the lowest point of each cell of `max_building_size`, and then
+/// repeatedly adds the points that are close to a triangle of the TIN. A point is close when its
+/// distance to the plane of the triangle is at most `iteration_distance`, and the angles between
+/// that plane and the lines from the point to the corners of the triangle are at most
+/// `iteration_angle`. Each round adds at most one point per triangle, the closest one, and the
+/// filter stops when no more points are close. The points in the TIN are ground.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::PtdParams;
+/// let params = PtdParams::default()
+///     .with_max_building_size(40.0)
+///     .with_iteration_angle(8.0);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PtdParams {
+    /// Size in meters of the largest building, the cell size of the grid of seed points. Each cell
+    /// should have some ground, so the grid must be coarser than the largest object.
+    pub max_building_size: f64,
+    /// Steepest slope of the terrain in degrees. Seeds that are higher than the seed in a cell
+    /// next to them by a steeper slope are not used.
+    pub terrain_angle: f64,
+    /// Largest angle in degrees between a triangle and the lines from a point to its corners for
+    /// the point to be added.
+    pub iteration_angle: f64,
+    /// Largest distance in meters from a point to the plane of a triangle for the point to be
+    /// added.
+    pub iteration_distance: f64,
+}
+
+impl Default for PtdParams {
+    fn default() -> Self {
+        PtdParams {
+            max_building_size: 60.0,
+            terrain_angle: 88.0,
+            iteration_angle: 6.0,
+            iteration_distance: 1.4,
+        }
+    }
+}
+
+impl PtdParams {
+    pub fn with_max_building_size(mut self, max_building_size: f64) -> Self {
+        self.max_building_size = max_building_size;
+        self
+    }
+
+    pub fn with_terrain_angle(mut self, terrain_angle: f64) -> Self {
+        self.terrain_angle = terrain_angle;
+        self
+    }
+
+    pub fn with_iteration_angle(mut self, iteration_angle: f64) -> Self {
+        self.iteration_angle = iteration_angle;
+        self
+    }
+
+    pub fn with_iteration_distance(mut self, iteration_distance: f64) -> Self {
+        self.iteration_distance = iteration_distance;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.max_building_size > 0.0 && self.max_building_size.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "max_building_size",
+                self.max_building_size,
+                "must be larger than 0.0",
+            ));
+        }
+        for (parameter, value) in [
+            ("terrain_angle", self.terrain_angle),
+            ("iteration_angle", self.iteration_angle),
+        ] {
+            if !(value > 0.0 && value <= 90.0) {
+                return Err(LaszyError::invalid_parameter(
+                    parameter,
+                    value,
+                    "must be larger than 0.0 and at most 90.0 degrees",
+                ));
+            }
+        }
+        if !(self.iteration_distance >= 0.0 && self.iteration_distance.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "iteration_distance",
+                self.iteration_distance,
+                "must be 0.0 or larger",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Where a point is relative to the TIN.
+enum Position {
+    /// Outside the TIN, which can't tell whether the point is ground.
+    Outside,
+    /// At the same x and y as a point of the TIN, `dz` above or below it.
+    OnVertex { dz: f64 },
+    /// Above or below a triangle, at `distance` from its plane.
+    InTriangle {
+        face: FixedFaceHandle<InnerTag>,
+        corners: [FixedVertexHandle; 3],
+        distance: f64,
+        is_close: bool,
+    },
+}
+
+/// A point that may be added to the TIN.
+struct Candidate {
+    point: usize,
+    /// A vertex near the point, where the next search for its triangle starts.
+    hint: Option<FixedVertexHandle>,
+    /// The corners of the triangle the point was too far from in the last round. While the
+    /// triangle is in the TIN the point stays too far from it.
+    too_far_from: Option<[FixedVertexHandle; 3]>,
+    /// Whether the point can never be added, as it is outside the TIN or at a vertex.
+    is_excluded: bool,
+}
+
+/// The densified TIN of the ground points.
+pub(crate) struct DensifiedTin {
+    triangulation: DelaunayTriangulation<GroundVertex>,
+    params: PtdParams,
+}
+
+impl DensifiedTin {
+    /// The position of a point relative to the TIN. The search for its triangle starts at `hint`
+    /// when there is one, which is then set to a vertex of the triangle for the next search.
+    fn position(&self, [x, y, z]: [f64; 3], hint: &mut Option<FixedVertexHandle>) -> Position {
+        let location = match hint {
+            Some(hint) => self
+                .triangulation
+                .locate_with_hint(Point2::new(x, y), *hint),
+            None => self.triangulation.locate(Point2::new(x, y)),
+        };
+        let face = match location {
+            PositionInTriangulation::OnVertex(vertex) => {
+                *hint = Some(vertex);
+                let dz = z - self.triangulation.vertex(vertex).data().z;
+                return Position::OnVertex { dz };
+            }
+            PositionInTriangulation::OnFace(face) => self.triangulation.face(face),
+            PositionInTriangulation::OnEdge(edge) => {
+                let edge = self.triangulation.directed_edge(edge);
+                *hint = Some(edge.from().fix());
+                match edge
+                    .face()
+                    .as_inner()
+                    .or_else(|| edge.rev().face().as_inner())
+                {
+                    Some(face) => face,
+                    None => return Position::Outside,
+                }
+            }
+            PositionInTriangulation::OutsideOfConvexHull(_)
+            | PositionInTriangulation::NoTriangulation => return Position::Outside,
+        };
+        *hint = Some(face.vertices()[0].fix());
+        let corners = face.vertices().map(|vertex| {
+            let vertex = vertex.data();
+            [vertex.position.x, vertex.position.y, vertex.z]
+        });
+        let distance = plane_distance(&corners, [x, y, z]).unwrap_or(f64::INFINITY);
+        let max_angle = corners
+            .iter()
+            .map(|corner| {
+                let length = corner
+                    .iter()
+                    .zip([x, y, z])
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum();
+                match f64::sqrt(length) {
+                    0.0 => 0.0,
+                    length => (distance / length).min(1.0).asin().to_degrees(),
+                }
+            })
+            .fold(0.0, f64::max);
+        Position::InTriangle {
+            face: face.fix(),
+            corners: face.vertices().map(|vertex| vertex.fix()),
+            distance,
+            is_close: distance <= self.params.iteration_distance
+                && max_angle <= self.params.iteration_angle,
+        }
+    }
+
+    /// Whether the corners, in counterclockwise order, are a triangle of the TIN.
+    fn is_triangle(&self, [a, b, c]: [FixedVertexHandle; 3]) -> bool {
+        self.triangulation
+            .get_edge_from_neighbors(a, b)
+            .is_some_and(|edge| !edge.face().is_outer() && edge.next().to().fix() == c)
+    }
+
+    /// Whether the point is ground: a point of the TIN, or close enough to it to be added. The
+    /// densification stops when none of its points is close enough, so for those points this
+    /// gives exactly the points of the TIN.
+    pub fn is_ground_point(&self, point: &Point) -> bool {
+        match self.position([point.x, point.y, point.z], &mut None) {
+            Position::Outside => false,
+            Position::OnVertex { dz } => dz.abs() <= self.params.iteration_distance,
+            Position::InTriangle { is_close, .. } => is_close,
+        }
+    }
+}
+
+/// Distance from `point` to the plane through `corners`, or None when the corners are in a
+/// vertical plane.
+fn plane_distance(corners: &[[f64; 3]; 3], point: [f64; 3]) -> Option<f64> {
+    let [a, b, c] = corners;
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let normal = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let length = normal.iter().map(|n| n * n).sum::<f64>().sqrt();
+    if length == 0.0 || normal[2] == 0.0 {
+        return None;
+    }
+    let offset: f64 = (0..3).map(|i| normal[i] * (point[i] - a[i])).sum();
+    Some(offset.abs() / length)
+}
+
+/// The lowest point of each cell of `grid`, leaving out the cells whose lowest point is higher
+/// than that of a cell next to it by a slope steeper than `terrain_angle`.
+fn seeds(points: &[[f64; 3]], grid: &RasterGrid, terrain_angle: f64) -> Vec<usize> {
+    let mut lowest: Array2<Option<usize>> = Array2::from_elem((grid.rows, grid.columns), None);
+    for (i, [x, y, z]) in points.iter().enumerate() {
+        if let Some(cell) = grid.cell(*x, *y) {
+            if lowest[cell].is_none_or(|lowest| *z < points[lowest][2]) {
+                lowest[cell] = Some(i);
+            }
+        }
+    }
+    let max_slope = terrain_angle.to_radians().tan();
+    let is_too_steep = |seed: [f64; 3], neighbour: [f64; 3]| {
+        let run = (seed[0] - neighbour[0]).hypot(seed[1] - neighbour[1]);
+        seed[2] - neighbour[2] > max_slope * run
+    };
+    lowest
+        .indexed_iter()
+        .filter_map(|((row, column), seed)| {
+            let seed = (*seed)?;
+            let is_steep = neighbour_indices(grid.rows, grid.columns, row, column)
+                .filter_map(|cell| lowest[cell])
+                .any(|neighbour| is_too_steep(points[seed], points[neighbour]));
+            (!is_steep).then_some(seed)
+        })
+        .collect()
+}
+
+/// Densify the TIN of the ground of `points`, as x, y and z, in the extent between `lower_left`
+/// and `upper_right`.
+pub(crate) fn filter(
+    points: Vec<[f64; 3]>,
+    (lower_left, upper_right): ((f64, f64), (f64, f64)),
+    params: &PtdParams,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<DensifiedTin, LaszyError> {
+    let grid = RasterGrid::covering(lower_left, upper_right, params.max_building_size);
+    let seeds = seeds(&points, &grid, params.terrain_angle);
+    if seeds.is_empty() {
+        return Err(LaszyError::EmptyCloud(
+            "There are no points to seed the TIN densification with.".to_string(),
+        ));
+    }
+    let vertex = |[x, y, z]: [f64; 3]| GroundVertex {
+        position: Point2::new(x, y),
+        z,
+    };
+    let mut vertices: Vec<GroundVertex> = seeds.iter().map(|seed| vertex(points[*seed])).collect();
+    // Corners at the height of the closest seed, so that the TIN covers all points
+    let (left, bottom) = grid.lower_left;
+    let (right, top) = (
+        left + grid.columns as f64 * grid.cell_size,
+        bottom + grid.rows as f64 * grid.cell_size,
+    );
+    for (x, y) in [(left, bottom), (right, bottom), (left, top), (right, top)] {
+        let closest = seeds
+            .iter()
+            .map(|seed| points[*seed])
+            .min_by(|a, b| {
+                let distance = |p: &[f64; 3]| (p[0] - x).hypot(p[1] - y);
+                distance(a).total_cmp(&distance(b))
+            })
+            .expect("there are seeds");
+        vertices.push(vertex([x, y, closest[2]]));
+    }
+    let mut tin = DensifiedTin {
+        triangulation: DelaunayTriangulation::bulk_load(vertices).map_err(|e| {
+            LaszyError::Triangulation(format!("The seed points can't be triangulated: {e:?}"))
+        })?,
+        params: *params,
+    };
+
+    progress.start("Densifying the ground TIN...", None);
+    let mut is_candidate = vec![true; points.len()];
+    for seed in &seeds {
+        is_candidate[*seed] = false;
+    }
+    let mut candidates: Vec<Candidate> = (0..points.len())
+        .filter(|i| is_candidate[*i])
+        .map(|point| Candidate {
+            point,
+            hint: None,
+            too_far_from: None,
+            is_excluded: false,
+        })
+        .collect();
+    let mut iterations = 0;
+    loop {
+        cancellation.check()?;
+        let close: Vec<(usize, f64, usize)> = candidates
+            .par_iter_mut()
+            .filter_map(|candidate| {
+                if candidate
+                    .too_far_from
+                    .is_some_and(|corners| tin.is_triangle(corners))
+                {
+                    return None;
+                }
+                match tin.position(points[candidate.point], &mut candidate.hint) {
+                    Position::InTriangle {
+                        face,
+                        distance,
+                        is_close: true,
+                        ..
+                    } => return Some((face.index(), distance, candidate.point)),
+                    Position::InTriangle { corners, .. } => candidate.too_far_from = Some(corners),
+                    Position::Outside | Position::OnVertex { .. } => candidate.is_excluded = true,
+                }
+                None
+            })
+            .collect();
+        // Only the closest point of each triangle is added in a round
+        let mut closest: HashMap<usize, (f64, usize)> = HashMap::new();
+        for (face, distance, i) in close {
+            let best = closest.entry(face).or_insert((distance, i));
+            if (distance, i) < *best {
+                *best = (distance, i);
+            }
+        }
+        if closest.is_empty() {
+            break;
+        }
+        // Added in a fixed order, as the triangulation of cocircular points depends on it
+        let mut added: Vec<usize> = closest.into_values().map(|(_, i)| i).collect();
+        added.sort_unstable();
+        for i in added {
+            tin.triangulation
+                .insert(vertex(points[i]))
+                .map_err(|e| LaszyError::Triangulation(format!("A point can't be added: {e:?}")))?;
+            is_candidate[i] = false;
+        }
+        candidates.retain(|candidate| is_candidate[candidate.point] && !candidate.is_excluded);
+        iterations += 1;
+        progress.advance(1);
+    }
+    // Without the corners
+    let ground = tin.triangulation.num_vertices() - 4;
+    progress.finish(&format!(
+        "Densified the ground TIN to {ground} points in {iterations} iterations"
+    ));
+    Ok(tin)
+}
@@ -0,0 +1,137 @@
+use crate::cancellation::CancellationToken;
+use crate::ground::morphology::{ground_surface, opening, slopes, MorphologicalGround};
+use crate::progress::ProgressReporter;
+use crate::raster::MemoryRaster;
+use crate::LaszyError;
+use ndarray::{Array2, Zip};
+
+/// Parameters of the Simple Morphological Filter (SMRF) of Pingel et al. (2013). The defaults are
+/// those of PDAL, which work well for urban areas.
+///
+/// The filter opens the grid of the lowest points with windows growing to `window`, and removes
+/// cells that stick out more than the slope allows. Points are ground when they are close to the
+/// surface interpolated from the remaining cells, where steeper cells allow a larger distance.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::SmrfParams;
+/// let params = SmrfParams::default().with_window(25.0).with_slope(0.2);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmrfParams {
+    /// Cell size in meters of the grid of the lowest points.
+    pub cell_size: f64,
+    /// Slope as rise over run that terrain can have, larger slopes are objects.
+    pub slope: f64,
+    /// Radius in meters of the largest window, which should be larger than the largest object,
+    /// such as a building.
+    pub window: f64,
+    /// Distance in meters a point may be from the ground surface on flat terrain to be ground.
+    pub elevation_threshold: f64,
+    /// Factor of the slope of the ground surface added to the elevation threshold.
+    pub elevation_scaler: f64,
+}
+
+impl Default for SmrfParams {
+    fn default() -> Self {
+        SmrfParams {
+            cell_size: 1.0,
+            slope: 0.15,
+            window: 18.0,
+            elevation_threshold: 0.5,
+            elevation_scaler: 1.25,
+        }
+    }
+}
+
+impl SmrfParams {
+    pub fn with_cell_size(mut self, cell_size: f64) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    pub fn with_slope(mut self, slope: f64) -> Self {
+        self.slope = slope;
+        self
+    }
+
+    pub fn with_window(mut self, window: f64) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_elevation_threshold(mut self, elevation_threshold: f64) -> Self {
+        self.elevation_threshold = elevation_threshold;
+        self
+    }
+
+    pub fn with_elevation_scaler(mut self, elevation_scaler: f64) -> Self {
+        self.elevation_scaler = elevation_scaler;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.cell_size > 0.0 && self.cell_size.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "cell_size",
+                self.cell_size,
+                "must be larger than 0.0",
+            ));
+        }
+        if !(self.window >= self.cell_size && self.window.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "window",
+                self.window,
+                "must be at least the cell size",
+            ));
+        }
+        for (parameter, value) in [
+            ("slope", self.slope),
+            ("elevation_threshold", self.elevation_threshold),
+            ("elevation_scaler", self.elevation_scaler),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(LaszyError::invalid_parameter(
+                    parameter,
+                    value,
+                    "must be 0.0 or larger",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Filter the ground from the grid of the lowest points.
+pub(crate) fn filter(
+    minimum: MemoryRaster,
+    params: &SmrfParams,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<MorphologicalGround, LaszyError> {
+    let cell_size = minimum.grid.cell_size;
+    let max_radius = (params.window / cell_size).ceil() as usize;
+    let mut is_object = Array2::from_elem(minimum.values.dim(), false);
+    let mut last = minimum.values.clone();
+    progress.start("Filtering ground with SMRF...", Some(max_radius as u64));
+    for radius in 1..=max_radius {
+        cancellation.check()?;
+        let opened = opening(&last, radius);
+        let threshold = params.slope * radius as f64 * cell_size;
+        Zip::from(&mut is_object)
+            .and(&last)
+            .and(&opened)
+            .for_each(|is_object, last, opened| *is_object |= last - opened > threshold);
+        last = opened;
+        progress.advance(1);
+    }
+    let objects = is_object.iter().filter(|is_object| **is_object).count();
+    progress.finish(&format!("Removed {objects} cells of objects"));
+
+    let surface = ground_surface(&minimum, &is_object);
+    let thresholds =
+        slopes(&surface).mapv(|slope| params.elevation_threshold + params.elevation_scaler * slope);
+    Ok(MorphologicalGround::new(surface, thresholds))
+}
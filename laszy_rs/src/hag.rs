@@ -0,0 +1,118 @@
+use crate::csf::Cloth;
+use crate::dtm::GroundTin;
+use crate::extra_bytes::ExtraBytesField;
+use crate::raster::{MemoryRaster, RasterFormat};
+use crate::LaszyError;
+use las::Point;
+
+/// Name of the extra bytes field with the height above ground, as used by PDAL.
+pub(crate) const HAG_FIELD_NAME: &str = "HeightAboveGround";
+/// Height above ground stored for points without ground below them.
+pub(crate) const HAG_NO_DATA: f64 = -9999.0;
+
+/// The ground surface the height above ground of the points is computed from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum GroundSurface {
+    /// The simulated cloth of the CSF ground reclassification, which must be configured.
+    Cloth,
+    /// A TIN of the ground points: the points classified as ground by the ground reclassification
+    /// when one is configured, or else the points classified as ground in the file. Outside the
+    /// TIN the height of the closest ground point is used.
+    #[default]
+    Tin,
+    /// A DTM raster, as an ESRI ASCII grid (.asc) or a GeoTIFF (.tif or .tiff), that is
+    /// bilinearly interpolated. It should have the same coordinate reference system as the points.
+    Dtm { path: String },
+}
+
+/// Parameters of the height above ground (HAG) normalisation, which computes the height of each
+/// point above the ground surface below it.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{GroundSurface, HagParams};
+/// let params = HagParams::default()
+///     .with_surface(GroundSurface::Dtm { path: "dtm.tif".to_string() })
+///     .with_replace_z(true);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HagParams {
+    pub surface: GroundSurface,
+    /// Replace the z of the points with their height above ground, instead of storing it in a
+    /// "HeightAboveGround" extra bytes field. Points without ground below them are then left out,
+    /// where the extra bytes field would have the NODATA value -9999.
+    pub replace_z: bool,
+}
+
+impl HagParams {
+    pub fn with_surface(mut self, surface: GroundSurface) -> Self {
+        self.surface = surface;
+        self
+    }
+
+    pub fn with_replace_z(mut self, replace_z: bool) -> Self {
+        self.replace_z = replace_z;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if let GroundSurface::Dtm { path } = &self.surface {
+            RasterFormat::from_path(path)?;
+        }
+        Ok(())
+    }
+
+    /// The extra bytes field the height above ground is stored in, unless it replaces z.
+    pub(crate) fn extra_bytes_field(&self) -> Option<ExtraBytesField> {
+        (!self.replace_z).then(|| {
+            ExtraBytesField::double(HAG_FIELD_NAME, "Height above ground", Some(HAG_NO_DATA))
+        })
+    }
+}
+
+/// The ground surface of a height above ground normalisation, ready to use.
+pub(crate) enum GroundHeights<'a> {
+    Cloth(&'a Cloth),
+    Tin(GroundTin),
+    Dtm(MemoryRaster),
+}
+
+impl GroundHeights<'_> {
+    fn height_at(&self, point: &Point) -> Result<Option<f64>, LaszyError> {
+        match self {
+            GroundHeights::Cloth(cloth) => cloth.height_at(point),
+            GroundHeights::Tin(tin) => Ok(tin.height_at(point.x, point.y)),
+            GroundHeights::Dtm(dtm) => Ok(dtm.value_at(point.x, point.y)),
+        }
+    }
+
+    /// Height of `point` above the ground, or None when there is no ground below it.
+    pub fn height_above(&self, point: &Point) -> Result<Option<f64>, LaszyError> {
+        Ok(self.height_at(point)?.map(|ground| point.z - ground))
+    }
+}
+
+/// Normalises the heights of points with a ground surface.
+pub(crate) struct HagStage<'a> {
+    pub heights: GroundHeights<'a>,
+    pub replace_z: bool,
+}
+
+impl HagStage<'_> {
+    /// Store the height above ground of `point` in its extra bytes, or replace its z with it.
+    /// Returns false when z should be replaced but there is no ground below the point, in which
+    /// case the point should be left out.
+    pub fn apply(&self, point: &mut Point) -> Result<bool, LaszyError> {
+        let height = self.heights.height_above(point)?;
+        match (height, self.replace_z) {
+            (Some(height), true) => point.z = height,
+            (None, true) => return Ok(false),
+            (height, false) => point
+                .extra_bytes
+                .extend(height.unwrap_or(HAG_NO_DATA).to_le_bytes()),
+        }
+        Ok(true)
+    }
+}
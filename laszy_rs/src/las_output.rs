@@ -0,0 +1,175 @@
+//! The point format and LAS version of LAS/LAZ outputs, and the conversion of the points to them.
+
+use crate::LaszyError;
+use las::point::{Classification, Format};
+use las::{Color, Point, Version};
+
+/// Highest number of returns of the point formats 0 to 5.
+const LEGACY_MAX_RETURNS: u8 = 7;
+/// Highest class of the point formats 0 to 5.
+const LEGACY_MAX_CLASS: u8 = 31;
+
+/// Options for writing LAS/LAZ outputs. By default the point format and LAS version of the input
+/// are kept.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::LasOptions;
+/// // Upgrade legacy files to LAS 1.4 with point format 6
+/// let options = LasOptions::default().with_point_format(6).with_version(1, 4);
+/// assert!(options.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LasOptions {
+    /// Point format from 0 to 10 of the output. Fields of the input that the format doesn't have
+    /// are dropped with a warning, fields that the input doesn't have get default values: a GPS
+    /// time of 0.0, black colour and a NIR of 0. The formats 0 to 5 don't support return numbers
+    /// above 7, which become 7, and classes above 31, which become unclassified (1). When None,
+    /// the format of the input is kept.
+    pub point_format: Option<u8>,
+    /// Major and minor LAS version of the output, from 1.0 to 1.4, which must support the point
+    /// format. When None, the version of the input is kept, or raised to the lowest version that
+    /// supports the point format.
+    pub version: Option<(u8, u8)>,
+}
+
+impl LasOptions {
+    pub fn with_point_format(mut self, point_format: u8) -> Self {
+        self.point_format = Some(point_format);
+        self
+    }
+
+    pub fn with_version(mut self, major: u8, minor: u8) -> Self {
+        self.version = Some((major, minor));
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if let Some(point_format) = self.point_format {
+            if point_format > 10 {
+                return Err(LaszyError::invalid_parameter(
+                    "point_format",
+                    point_format,
+                    "must be between 0 and 10",
+                ));
+            }
+        }
+        if let Some((major, minor)) = self.version {
+            if major != 1 || minor > 4 {
+                return Err(LaszyError::invalid_parameter(
+                    "version",
+                    format!("{major}.{minor}"),
+                    "must be between 1.0 and 1.4",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The point format of the output for the `input` format, with the extra bytes of the input.
+    pub(crate) fn point_format(&self, input: &Format) -> Result<Format, LaszyError> {
+        let Some(number) = self.point_format else {
+            return Ok(*input);
+        };
+        let mut format = Format::new(number)?;
+        format.extra_bytes = input.extra_bytes;
+        Ok(format)
+    }
+
+    /// The LAS version of the output with the point `format`, for an input with version `input`.
+    pub(crate) fn version(&self, input: Version, format: Format) -> Result<Version, LaszyError> {
+        let Some((major, minor)) = self.version else {
+            let minimum = (0..=4)
+                .map(|minor| Version::new(1, minor))
+                .find(|version| version.supports_point_format(format))
+                .unwrap_or(Version::new(1, 4));
+            return Ok(input.max(minimum));
+        };
+        let version = Version::new(major, minor);
+        if !version.supports_point_format(format) {
+            return Err(LaszyError::invalid_parameter(
+                "version",
+                version,
+                format!("doesn't support {format}, use 1.4 or another point format"),
+            ));
+        }
+        Ok(version)
+    }
+}
+
+/// The point format with colour that has the fields of `format`, such as 2 for format 0 and 7 for
+/// format 6.
+pub(crate) fn with_colour(format: &Format) -> Result<Format, LaszyError> {
+    let number = match format.to_u8()? {
+        0 => 2,
+        1 => 3,
+        4 => 5,
+        6 => 7,
+        9 => 10,
+        number => number,
+    };
+    let mut coloured = Format::new(number)?;
+    coloured.extra_bytes = format.extra_bytes;
+    Ok(coloured)
+}
+
+/// Names of the fields of points with the `input` format that points with the `output` format
+/// don't have.
+pub(crate) fn dropped_fields(input: &Format, output: &Format) -> Vec<&'static str> {
+    [
+        (input.has_gps_time && !output.has_gps_time, "GPS time"),
+        (input.has_color && !output.has_color, "colour"),
+        (input.has_nir && !output.has_nir, "NIR"),
+        (input.has_waveform && !output.has_waveform, "waveform"),
+        (input.is_extended && !output.is_extended, "scanner channel"),
+    ]
+    .into_iter()
+    .filter_map(|(dropped, name)| dropped.then_some(name))
+    .collect()
+}
+
+/// Converts points to a point format. The las crate reads a GPS time or NIR of 0 as None, so the
+/// points of the input need this even when the format is kept.
+pub(crate) struct PointConversion {
+    format: Format,
+}
+
+impl PointConversion {
+    pub fn new(format: &Format) -> Self {
+        PointConversion { format: *format }
+    }
+
+    /// Drop the fields of `point` that the format doesn't have, and give the fields it misses a
+    /// default value. Returns false when a return number or class had to be changed, because the
+    /// point formats 0 to 5 don't support returns above 7 and classes above 31.
+    pub fn convert(&self, point: &mut Point) -> bool {
+        let format = &self.format;
+        point.gps_time = format.has_gps_time.then(|| point.gps_time.unwrap_or(0.0));
+        point.color = format
+            .has_color
+            .then(|| point.color.unwrap_or(Color::new(0, 0, 0)));
+        point.nir = format.has_nir.then(|| point.nir.unwrap_or(0));
+        if !format.has_waveform {
+            point.waveform = None;
+        } else if point.waveform.is_none() {
+            point.waveform = Some(Default::default());
+        }
+        if format.is_extended {
+            return true;
+        }
+        point.scanner_channel = 0;
+        let mut kept = true;
+        if point.return_number > LEGACY_MAX_RETURNS || point.number_of_returns > LEGACY_MAX_RETURNS
+        {
+            point.return_number = point.return_number.min(LEGACY_MAX_RETURNS);
+            point.number_of_returns = point.number_of_returns.min(LEGACY_MAX_RETURNS);
+            kept = false;
+        }
+        if u8::from(point.classification) > LEGACY_MAX_CLASS {
+            point.classification = Classification::Unclassified;
+            kept = false;
+        }
+        kept
+    }
+}
@@ -7,7 +7,7 @@
 ///
 /// # Examples
 /// ```
-/// use laszy::{PointCloudBuilder, ThinningMethod, CroppingMethod};
+/// use laszy::{PointCloudBuilder, ThinningMethod, CroppingMethod, CsfParams};
 /// let cloud = PointCloudBuilder::from_file(&"test.las".to_string())
 ///     .unwrap()
 ///     .with_crop(CroppingMethod::BoundingBox {
@@ -15,31 +15,88 @@
 ///         upper_right: (183_564.09, 332_424.13),
 ///     })
 ///     .with_thinning(ThinningMethod::EveryNth { nth: 40 })
-///     .with_csf_ground_reclassification(0.5, 5.0, 0.1, 1.0)
+///     .with_csf_ground_reclassification(CsfParams::default())
 ///     .to_cloud()
 ///     .unwrap();
 /// ```
 ///
 /// # Features
-/// - Read LAS/LAZ files
-/// - Write LAS/LAZ files
+/// - Read LAS/LAZ files, or several overlapping files as one
+/// - Write LAS/LAZ files, optionally converted to another point format or LAS version
+/// - Split the points over a regular grid of LAS/LAZ tiles, with an optional buffer
 /// - Crop point clouds
 /// - Thin point clouds using a variety of methods
 /// - Reclassify ground points using the CSF (Cloth Simulation Filter) method
+/// - Reclassify ground points using the SMRF or PMF morphological filters
+/// - Reclassify ground points using progressive TIN densification
+/// - Classify the other points as vegetation by their height above ground, or as buildings
+/// - Remove or withhold duplicate points, also across files
+/// - Classify or remove noise with statistical, radius or isolated point outlier filters
+/// - Interpolate DTMs from ground points with IDW, TIN or natural neighbour interpolation
+/// - Create DSMs and canopy height models
+/// - Normalise heights to the height above ground from the CSF cloth, a TIN or a DTM
+/// - Colour the points from an RGB orthophoto
+/// - Compute normals, curvature, planarity and other geometric features of the points
+/// - Keep the extra bytes of the input and read them by name, next to the fields the builder adds
+/// - Aggregate point attributes per cell into rasters, such as density or intensity images
+/// - Write DTMs as ESRI ASCII grids or GeoTIFFs
+/// - Cancel long running operations from another thread
+mod aggregation;
 mod builder;
+mod cancellation;
 mod cloud;
+mod colour;
 mod cropping;
 mod csf;
+mod dsm;
+mod dtm;
+mod duplicates;
 mod error;
+mod extra_bytes;
+mod features;
+mod geometry;
+mod ground;
+mod hag;
+mod las_output;
 mod metadata;
+mod noise;
+mod objects;
+mod output;
+mod progress;
+mod raster;
 #[cfg(test)]
 mod tests;
 mod thinning;
+mod tiles;
+mod tiling;
 
+pub use aggregation::{Aggregation, Attribute, Reducer};
 pub use builder::PointCloudBuilder;
+pub use cancellation::CancellationToken;
 pub use cloud::PointCloud;
+pub use colour::ColourParams;
 pub use cropping::CroppingMethod;
+pub use csf::collision::CollisionHeight;
+pub use csf::params::{CsfParams, CsfTiling};
+pub use csf::physics::ClothPhysics;
+pub use dsm::DsmParams;
+pub use dtm::{DtmParams, Interpolation};
+pub use duplicates::{DuplicateAction, DuplicateParams};
 pub use error::LaszyError;
+pub use features::{GeometricFeature, Neighbourhood};
+pub use ground::pmf::PmfParams;
+pub use ground::ptd::PtdParams;
+pub use ground::smrf::SmrfParams;
+pub use hag::{GroundSurface, HagParams};
 pub use las::Point;
+pub use las_output::LasOptions;
 pub use metadata::Metadata;
+pub use noise::{NoiseAction, NoiseFilter, OutlierMethod};
+pub use objects::ObjectParams;
+pub use output::OverwritePolicy;
+#[cfg(feature = "indicatif")]
+pub use progress::IndicatifReporter;
+pub use progress::{LogReporter, ProgressReporter, SilentReporter};
+pub use raster::{RasterCompression, RasterOptions};
 pub use thinning::ThinningMethod;
+pub use tiling::TileParams;
@@ -1,7 +1,15 @@
+use crate::extra_bytes::ExtraBytesField;
+use crate::raster::GeoKeys;
+use crate::LaszyError;
+
 pub struct Metadata {
     point_count: u64,
     bounds: las::Bounds,
     point_format: las::point::Format,
+    version: las::Version,
+    geo_keys: Option<GeoKeys>,
+    /// The fields in the extra bytes of the points.
+    extra_bytes: Vec<ExtraBytesField>,
     // fIXME transforms: Vector<Transform>,
 }
 
@@ -9,11 +17,17 @@ impl Metadata {
     pub fn from_las_header(header: &las::Header) -> Self {
         let point_count = header.number_of_points();
         let bounds = header.bounds();
-        let point_format = header.point_format().clone();
+        let point_format = *header.point_format();
+        let version = header.version();
+        let geo_keys = GeoKeys::from_las_header(header);
+        let extra_bytes = ExtraBytesField::from_header(header, point_format.extra_bytes);
         Metadata {
             point_count,
             bounds,
             point_format,
+            version,
+            geo_keys,
+            extra_bytes,
         }
     }
 
@@ -34,4 +48,69 @@ impl Metadata {
     pub fn point_format(&self) -> &las::point::Format {
         &self.point_format
     }
+
+    /// The LAS version of the file, or the highest version of the files.
+    pub fn version(&self) -> las::Version {
+        self.version
+    }
+
+    /// Names of the fields in the extra bytes of the points, in the order of the bytes. Bytes that
+    /// the file doesn't describe are called "undocumented 1", "undocumented 2" and so on.
+    pub fn extra_bytes_names(&self) -> Vec<String> {
+        self.extra_bytes.iter().map(ExtraBytesField::name).collect()
+    }
+
+    /// Add the points and bounds of another file at `path`, which must have the same point format
+    /// and extra bytes. The coordinate reference system of this file is kept.
+    pub(crate) fn merge(&mut self, other: &Metadata, path: &str) -> Result<(), LaszyError> {
+        if other.point_format != self.point_format {
+            return Err(LaszyError::invalid_parameter(
+                "filepaths",
+                path,
+                format!(
+                    "has point format {}, where the first file has point format {}",
+                    other.point_format, self.point_format
+                ),
+            ));
+        }
+        let same_extra_bytes = other.extra_bytes.len() == self.extra_bytes.len()
+            && (other.extra_bytes.iter())
+                .zip(&self.extra_bytes)
+                .all(|(field, first)| field.same_layout(first));
+        if !same_extra_bytes {
+            return Err(LaszyError::invalid_parameter(
+                "filepaths",
+                path,
+                format!(
+                    "has extra bytes {:?}, where the first file has extra bytes {:?}",
+                    other.extra_bytes_names(),
+                    self.extra_bytes_names()
+                ),
+            ));
+        }
+        self.point_count += other.point_count;
+        self.version = self.version.max(other.version);
+        let (min, max) = (&mut self.bounds.min, &mut self.bounds.max);
+        (min.x, min.y, min.z) = (
+            min.x.min(other.bounds.min.x),
+            min.y.min(other.bounds.min.y),
+            min.z.min(other.bounds.min.z),
+        );
+        (max.x, max.y, max.z) = (
+            max.x.max(other.bounds.max.x),
+            max.y.max(other.bounds.max.y),
+            max.z.max(other.bounds.max.z),
+        );
+        Ok(())
+    }
+
+    /// The GeoTIFF keys of the coordinate reference system of the file, if it has them.
+    pub(crate) fn geo_keys(&self) -> Option<&GeoKeys> {
+        self.geo_keys.as_ref()
+    }
+
+    /// The fields in the extra bytes of the points.
+    pub(crate) fn extra_bytes(&self) -> &[ExtraBytesField] {
+        &self.extra_bytes
+    }
 }
@@ -0,0 +1,426 @@
+use crate::cancellation::CancellationToken;
+use crate::error::ErrorContext;
+use crate::progress::ProgressReporter;
+use crate::tiles::{invalid_point, PointId, PointSet, PointTiles, Tile};
+use crate::LaszyError;
+use kdtree::distance::squared_euclidean;
+use kdtree::KdTree;
+use las::point::Classification;
+use las::Point;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// Distance in meters around a tile within which the nearest neighbours of statistical outlier
+/// removal are found. Points with fewer neighbours within it are far from the others anyway.
+const STATISTICAL_BUFFER: f64 = 10.0;
+/// Amount of nearest neighbours whose mean height tells whether a noise point is low or high.
+const REFERENCE_NEIGHBOURS: usize = 8;
+/// Bytes of a mean neighbour distance in the file of a statistical outlier removal: the id of
+/// the point, the distance and whether it is below its neighbours.
+const DISTANCE_RECORD_SIZE: usize = 21;
+
+/// How the noise points are found.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutlierMethod {
+    /// Points whose mean distance to their `k` nearest neighbours is more than `multiplier`
+    /// standard deviations above the mean of that distance over all points, as in PDAL.
+    Statistical { k: usize, multiplier: f64 },
+    /// Points with fewer than `min_neighbours` other points within a distance of `radius`.
+    Radius { radius: f64, min_neighbours: usize },
+    /// Points that are more than `height` below or above all other points within a horizontal
+    /// distance of `radius`, such as reflections below the ground or birds above the canopy.
+    /// Points without any other points within `radius` are not noise by this method.
+    Isolated { radius: f64, height: f64 },
+}
+
+/// What happens to the noise points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoiseAction {
+    /// Classify the noise points as low noise (7) when they are below their neighbours, or else
+    /// as high noise (18).
+    #[default]
+    Classify,
+    /// Leave the noise points out.
+    Remove,
+}
+
+/// A noise filter: how noise points are found and what happens to them.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{NoiseAction, NoiseFilter, OutlierMethod};
+/// let filter = NoiseFilter::new(OutlierMethod::Statistical { k: 8, multiplier: 2.0 })
+///     .with_action(NoiseAction::Remove);
+/// assert!(filter.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseFilter {
+    pub method: OutlierMethod,
+    pub action: NoiseAction,
+}
+
+impl NoiseFilter {
+    /// A filter with `method` that classifies the noise points.
+    pub fn new(method: OutlierMethod) -> Self {
+        NoiseFilter {
+            method,
+            action: NoiseAction::default(),
+        }
+    }
+
+    pub fn with_action(mut self, action: NoiseAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        let is_distance = |value: f64| value > 0.0 && value.is_finite();
+        match self.method {
+            OutlierMethod::Statistical { k, .. } if k == 0 => Err(LaszyError::invalid_parameter(
+                "Statistical { k }",
+                k,
+                "must be at least 1",
+            )),
+            OutlierMethod::Statistical { multiplier, .. } if !multiplier.is_finite() => {
+                Err(LaszyError::invalid_parameter(
+                    "Statistical { multiplier }",
+                    multiplier,
+                    "must be a finite number",
+                ))
+            }
+            OutlierMethod::Radius { radius, .. } | OutlierMethod::Isolated { radius, .. }
+                if !is_distance(radius) =>
+            {
+                Err(LaszyError::invalid_parameter(
+                    "radius",
+                    radius,
+                    "must be larger than 0.0",
+                ))
+            }
+            OutlierMethod::Isolated { height, .. } if !(height >= 0.0 && height.is_finite()) => {
+                Err(LaszyError::invalid_parameter(
+                    "Isolated { height }",
+                    height,
+                    "must be 0.0 or larger",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Distance around a tile within which the neighbours of its points are found.
+    fn buffer(&self) -> f64 {
+        match self.method {
+            OutlierMethod::Statistical { .. } => STATISTICAL_BUFFER,
+            OutlierMethod::Radius { radius, .. } | OutlierMethod::Isolated { radius, .. } => radius,
+        }
+    }
+}
+
+/// Distance around the tiles needed by all `filters`.
+pub(crate) fn tile_buffer(filters: &[NoiseFilter]) -> f64 {
+    filters.iter().map(NoiseFilter::buffer).fold(0.0, f64::max)
+}
+
+/// What happens to a noise point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Noise {
+    pub class: Classification,
+    pub remove: bool,
+}
+
+/// The noise points found by the noise filters, as a bit per input point in a set of the removed
+/// points and a set for each noise class.
+#[derive(Debug, Default)]
+pub(crate) struct NoisePoints {
+    removed: PointSet,
+    low: PointSet,
+    high: PointSet,
+}
+
+impl NoisePoints {
+    /// Mark a point as noise. Once a filter removes a point it stays removed, and the class of
+    /// the first filter that found it is kept.
+    fn add(&mut self, id: PointId, class: Classification, action: NoiseAction) {
+        if action == NoiseAction::Remove {
+            self.removed.insert(id);
+        }
+        if !self.is_noise(id) {
+            match class {
+                Classification::LowPoint => self.low.insert(id),
+                _ => self.high.insert(id),
+            }
+        }
+    }
+
+    pub fn get(&self, id: PointId) -> Option<Noise> {
+        let class = if self.low.contains(id) {
+            Classification::LowPoint
+        } else if self.high.contains(id) {
+            Classification::HighNoise
+        } else {
+            return None;
+        };
+        let remove = self.removed.contains(id);
+        Some(Noise { class, remove })
+    }
+
+    pub fn is_noise(&self, id: PointId) -> bool {
+        self.low.contains(id) || self.high.contains(id)
+    }
+
+    /// Classify the point when it is noise, returns false when it should be left out.
+    pub fn apply(&self, id: PointId, point: &mut Point) -> bool {
+        match self.get(id) {
+            Some(Noise { remove: true, .. }) => false,
+            Some(Noise { class, .. }) => {
+                point.classification = class;
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.low.len() + self.high.len()
+    }
+}
+
+/// The class of a noise point at `z`, compared to the mean height of points around it.
+fn noise_class(z: f64, neighbour_z: Option<f64>) -> Classification {
+    match neighbour_z {
+        Some(neighbour_z) if z < neighbour_z => Classification::LowPoint,
+        _ => Classification::HighNoise,
+    }
+}
+
+/// The result of a filter for a point in the core of a tile.
+enum Verdict {
+    Clean,
+    Noise(Classification),
+    /// The mean distance to the nearest neighbours of a statistical outlier removal, and whether
+    /// the point is below them.
+    Distance(f64, bool),
+}
+
+/// The points of a tile indexed for the neighbour searches of the filters.
+struct TileIndex<'a> {
+    tile: &'a Tile,
+    index_3d: KdTree<f64, usize, [f64; 3]>,
+    index_2d: Option<KdTree<f64, usize, [f64; 2]>>,
+}
+
+impl<'a> TileIndex<'a> {
+    fn new(tile: &'a Tile, filters: &[NoiseFilter]) -> Result<Self, LaszyError> {
+        let index_3d = tile.kdtree()?;
+        let needs_2d = filters
+            .iter()
+            .any(|filter| matches!(filter.method, OutlierMethod::Isolated { .. }));
+        let mut index_2d = needs_2d.then(|| KdTree::new(2));
+        for (i, point) in tile.points.iter().enumerate() {
+            if let Some(index_2d) = &mut index_2d {
+                let [x, y, _] = point.xyz;
+                index_2d.add([x, y], i).map_err(invalid_point)?;
+            }
+        }
+        Ok(TileIndex {
+            tile,
+            index_3d,
+            index_2d,
+        })
+    }
+
+    /// The distances to and indices of the `k` points nearest to point `i`, without itself.
+    fn nearest(&self, i: usize, k: usize) -> Result<Vec<(f64, usize)>, LaszyError> {
+        let nearest = self
+            .index_3d
+            .nearest(&self.tile.points[i].xyz, k + 1, &squared_euclidean)
+            .map_err(invalid_point)?;
+        Ok(nearest
+            .into_iter()
+            .filter(|(_, j)| **j != i)
+            .take(k)
+            .map(|(distance, j)| (distance.sqrt(), *j))
+            .collect())
+    }
+
+    /// Mean height of the points nearest to point `i`, or None when there are no other points.
+    fn neighbour_z(&self, i: usize) -> Result<Option<f64>, LaszyError> {
+        let nearest = self.nearest(i, REFERENCE_NEIGHBOURS)?;
+        Ok((!nearest.is_empty()).then(|| {
+            nearest
+                .iter()
+                .map(|(_, j)| self.tile.points[*j].xyz[2])
+                .sum::<f64>()
+                / nearest.len() as f64
+        }))
+    }
+
+    fn verdict(&self, i: usize, method: OutlierMethod) -> Result<Verdict, LaszyError> {
+        let xyz = self.tile.points[i].xyz;
+        match method {
+            OutlierMethod::Statistical { k, .. } => {
+                let nearest = self.nearest(i, k)?;
+                if nearest.is_empty() {
+                    return Ok(Verdict::Noise(Classification::HighNoise));
+                }
+                let count = nearest.len() as f64;
+                let distance = nearest.iter().map(|(distance, _)| distance).sum::<f64>() / count;
+                let mean_z = nearest
+                    .iter()
+                    .map(|(_, j)| self.tile.points[*j].xyz[2])
+                    .sum::<f64>()
+                    / count;
+                Ok(Verdict::Distance(distance, xyz[2] < mean_z))
+            }
+            OutlierMethod::Radius {
+                radius,
+                min_neighbours,
+            } => {
+                let within = self
+                    .index_3d
+                    .within(&xyz, radius * radius, &squared_euclidean)
+                    .map_err(invalid_point)?;
+                // The point itself is within the radius
+                if within.len() > min_neighbours {
+                    return Ok(Verdict::Clean);
+                }
+                Ok(Verdict::Noise(noise_class(xyz[2], self.neighbour_z(i)?)))
+            }
+            OutlierMethod::Isolated { radius, height } => {
+                let index_2d = self.index_2d.as_ref().expect("built for isolated points");
+                let within = index_2d
+                    .within(&[xyz[0], xyz[1]], radius * radius, &squared_euclidean)
+                    .map_err(invalid_point)?;
+                let (lowest, highest) = within
+                    .iter()
+                    .filter(|(_, j)| **j != i)
+                    .map(|(_, j)| self.tile.points[**j].xyz[2])
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), z| {
+                        (low.min(z), high.max(z))
+                    });
+                Ok(if xyz[2] < lowest - height {
+                    Verdict::Noise(Classification::LowPoint)
+                } else if xyz[2] > highest + height {
+                    Verdict::Noise(Classification::HighNoise)
+                } else {
+                    Verdict::Clean
+                })
+            }
+        }
+    }
+}
+
+/// Running mean and sum of squared differences from the mean, after Welford.
+#[derive(Default)]
+struct Moments {
+    count: u64,
+    mean: f64,
+    squares: f64,
+}
+
+impl Moments {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.squares += delta * (value - self.mean);
+    }
+
+    fn std_dev(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            count => (self.squares / count as f64).sqrt(),
+        }
+    }
+}
+
+/// Find the noise points with the `filters`, going through the `tiles` one at a time. The mean
+/// neighbour distances of statistical outlier removals are written to a file, as their threshold
+/// is only known after all tiles.
+pub(crate) fn find(
+    mut tiles: PointTiles,
+    filters: &[NoiseFilter],
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<NoisePoints, LaszyError> {
+    tiles.finish()?;
+    let mut noise = NoisePoints::default();
+    let mut distances: Vec<(Moments, BufWriter<File>)> = Vec::new();
+    for (i, _) in filters.iter().enumerate() {
+        let path = tiles.temp_path(&format!("distances-{i}.bin"));
+        let file = File::create(&path).in_file(&path.to_string_lossy())?;
+        distances.push((Moments::default(), BufWriter::new(file)));
+    }
+
+    let tile_count = tiles.tile_count();
+    progress.start("Finding noise in tiles...", Some(tile_count as u64));
+    for tile_index in 0..tile_count {
+        cancellation.check()?;
+        progress.advance(1);
+        let tile = tiles.take_tile(tile_index)?;
+        if tile.points.is_empty() {
+            continue;
+        }
+        let index = TileIndex::new(&tile, filters)?;
+        let core: Vec<usize> = (0..tile.points.len())
+            .filter(|i| tile.is_core[*i])
+            .collect();
+        for (filter, (moments, file)) in filters.iter().zip(&mut distances) {
+            let verdicts = core
+                .par_iter()
+                .map(|i| Ok((*i, index.verdict(*i, filter.method)?)))
+                .collect::<Result<Vec<_>, LaszyError>>()?;
+            for (i, verdict) in verdicts {
+                let id = tile.points[i].id;
+                match verdict {
+                    Verdict::Clean => {}
+                    Verdict::Noise(class) => noise.add(id, class, filter.action),
+                    Verdict::Distance(distance, is_below) => {
+                        moments.add(distance);
+                        let mut record = [0; DISTANCE_RECORD_SIZE];
+                        record[0..4].copy_from_slice(&id.0.to_le_bytes());
+                        record[4..12].copy_from_slice(&id.1.to_le_bytes());
+                        record[12..20].copy_from_slice(&distance.to_le_bytes());
+                        record[20] = is_below as u8;
+                        file.write_all(&record).in_file("noise distances")?;
+                    }
+                }
+            }
+        }
+    }
+
+    for ((i, filter), (moments, file)) in filters.iter().enumerate().zip(distances) {
+        let OutlierMethod::Statistical { multiplier, .. } = filter.method else {
+            continue;
+        };
+        file.into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|file| file.sync_all())
+            .in_file("noise distances")?;
+        let threshold = moments.mean + multiplier * moments.std_dev();
+        let path = tiles.temp_path(&format!("distances-{i}.bin"));
+        let path_name = path.to_string_lossy();
+        let mut reader = BufReader::new(File::open(&path).in_file(&path_name)?);
+        let mut record = [0; DISTANCE_RECORD_SIZE];
+        for _ in 0..moments.count {
+            reader.read_exact(&mut record).in_file(&path_name)?;
+            let distance = f64::from_le_bytes(record[12..20].try_into().expect("8 bytes"));
+            if distance > threshold {
+                let id = (
+                    u32::from_le_bytes(record[0..4].try_into().expect("4 bytes")),
+                    u64::from_le_bytes(record[4..12].try_into().expect("8 bytes")),
+                );
+                let class = match record[20] {
+                    1 => Classification::LowPoint,
+                    _ => Classification::HighNoise,
+                };
+                noise.add(id, class, filter.action);
+            }
+        }
+    }
+    progress.finish(&format!("Found {} noise points", noise.len()));
+    Ok(noise)
+}
@@ -0,0 +1,251 @@
+use crate::cancellation::CancellationToken;
+use crate::geometry::LocalShape;
+use crate::hag::{GroundHeights, GroundSurface};
+use crate::progress::ProgressReporter;
+use crate::tiles::{self, PointId, PointSet, PointTiles};
+use crate::LaszyError;
+use kdtree::distance::squared_euclidean;
+use las::point::Classification;
+use las::Point;
+use rayon::prelude::*;
+
+/// Distance in meters around a tile within which the nearest neighbours of its points are found.
+pub(crate) const OBJECT_TILE_BUFFER: f64 = 5.0;
+
+/// Parameters of the classification of the points that are not ground into low (3), medium (4)
+/// and high (5) vegetation and buildings (6). Points are buildings when they are high enough
+/// above the ground and lie in a smooth plane with their nearest neighbours, the other points are
+/// vegetation by their height above the ground. Only points that are never classified,
+/// unclassified, vegetation or building are classified, other classes such as ground, noise and
+/// water are kept.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{GroundSurface, ObjectParams};
+/// let params = ObjectParams::default()
+///     .with_surface(GroundSurface::Tin)
+///     .with_high_vegetation_height(8.0)
+///     .with_min_planarity(0.6);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectParams {
+    /// The ground surface the height above ground of the points is computed from.
+    pub surface: GroundSurface,
+    /// Points lower than this above the ground keep their class.
+    pub min_height: f64,
+    /// Points from this height above the ground are medium vegetation, lower points are low
+    /// vegetation.
+    pub medium_vegetation_height: f64,
+    /// Points from this height above the ground are high vegetation.
+    pub high_vegetation_height: f64,
+    /// Points lower than this above the ground are never buildings.
+    pub building_min_height: f64,
+    /// Amount of nearest neighbours the plane through a point is fitted to.
+    pub k: usize,
+    /// Smallest planarity of the point and its neighbours of a building point, from 0.0 to 1.0.
+    pub min_planarity: f64,
+    /// Largest roughness in meters of the point and its neighbours of a building point, the root
+    /// mean square of their distances to the plane through them.
+    pub max_roughness: f64,
+}
+
+impl Default for ObjectParams {
+    fn default() -> Self {
+        ObjectParams {
+            surface: GroundSurface::Tin,
+            min_height: 0.5,
+            medium_vegetation_height: 2.0,
+            high_vegetation_height: 5.0,
+            building_min_height: 2.5,
+            k: 50,
+            min_planarity: 0.4,
+            max_roughness: 0.15,
+        }
+    }
+}
+
+impl ObjectParams {
+    pub fn with_surface(mut self, surface: GroundSurface) -> Self {
+        self.surface = surface;
+        self
+    }
+
+    pub fn with_min_height(mut self, min_height: f64) -> Self {
+        self.min_height = min_height;
+        self
+    }
+
+    pub fn with_medium_vegetation_height(mut self, medium_vegetation_height: f64) -> Self {
+        self.medium_vegetation_height = medium_vegetation_height;
+        self
+    }
+
+    pub fn with_high_vegetation_height(mut self, high_vegetation_height: f64) -> Self {
+        self.high_vegetation_height = high_vegetation_height;
+        self
+    }
+
+    pub fn with_building_min_height(mut self, building_min_height: f64) -> Self {
+        self.building_min_height = building_min_height;
+        self
+    }
+
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    pub fn with_min_planarity(mut self, min_planarity: f64) -> Self {
+        self.min_planarity = min_planarity;
+        self
+    }
+
+    pub fn with_max_roughness(mut self, max_roughness: f64) -> Self {
+        self.max_roughness = max_roughness;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        let heights = [
+            ("min_height", self.min_height),
+            ("medium_vegetation_height", self.medium_vegetation_height),
+            ("high_vegetation_height", self.high_vegetation_height),
+            ("building_min_height", self.building_min_height),
+        ];
+        for (name, height) in heights {
+            if !height.is_finite() {
+                return Err(LaszyError::invalid_parameter(
+                    name,
+                    height,
+                    "must be finite",
+                ));
+            }
+        }
+        if self.medium_vegetation_height < self.min_height {
+            return Err(LaszyError::invalid_parameter(
+                "medium_vegetation_height",
+                self.medium_vegetation_height,
+                "must be at least min_height",
+            ));
+        }
+        if self.high_vegetation_height < self.medium_vegetation_height {
+            return Err(LaszyError::invalid_parameter(
+                "high_vegetation_height",
+                self.high_vegetation_height,
+                "must be at least medium_vegetation_height",
+            ));
+        }
+        if self.k < 2 {
+            return Err(LaszyError::invalid_parameter(
+                "k",
+                self.k,
+                "must be at least 2",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.min_planarity) {
+            return Err(LaszyError::invalid_parameter(
+                "min_planarity",
+                self.min_planarity,
+                "must be between 0.0 and 1.0",
+            ));
+        }
+        if !(self.max_roughness >= 0.0 && self.max_roughness.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "max_roughness",
+                self.max_roughness,
+                "must be 0.0 or larger",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether the object classification may change the class of a point with `class`.
+pub(crate) fn is_reclassifiable(class: Classification) -> bool {
+    matches!(
+        class,
+        Classification::CreatedNeverClassified
+            | Classification::Unclassified
+            | Classification::LowVegetation
+            | Classification::MediumVegetation
+            | Classification::HighVegetation
+            | Classification::Building
+    )
+}
+
+/// Classifies points that are not ground as vegetation or building.
+pub(crate) struct ObjectClassifier<'a> {
+    pub heights: GroundHeights<'a>,
+    pub buildings: PointSet,
+    pub params: ObjectParams,
+}
+
+impl ObjectClassifier<'_> {
+    /// Classify the point as building when it was found to be one, or else as vegetation by its
+    /// height above the ground. Points that are not reclassifiable, too low or without ground
+    /// below them keep their class.
+    pub fn reclassify(&self, id: PointId, point: &mut Point) -> Result<(), LaszyError> {
+        if !is_reclassifiable(point.classification) {
+            return Ok(());
+        }
+        let Some(height) = self.heights.height_above(point)? else {
+            return Ok(());
+        };
+        let params = &self.params;
+        if height < params.min_height {
+            return Ok(());
+        }
+        point.classification = if self.buildings.contains(id) {
+            Classification::Building
+        } else if height < params.medium_vegetation_height {
+            Classification::LowVegetation
+        } else if height < params.high_vegetation_height {
+            Classification::MediumVegetation
+        } else {
+            Classification::HighVegetation
+        };
+        Ok(())
+    }
+}
+
+/// Find the building points among the candidates in the `tiles`, going through them one at a
+/// time. A point is a building point when it and its nearest neighbours are planar and smooth.
+pub(crate) fn find_buildings(
+    mut tiles: PointTiles,
+    params: &ObjectParams,
+    progress: &dyn ProgressReporter,
+    cancellation: &CancellationToken,
+) -> Result<PointSet, LaszyError> {
+    tiles.finish()?;
+    let mut buildings = PointSet::default();
+    let tile_count = tiles.tile_count();
+    progress.start("Finding buildings in tiles...", Some(tile_count as u64));
+    for tile_index in 0..tile_count {
+        cancellation.check()?;
+        progress.advance(1);
+        let tile = tiles.take_tile(tile_index)?;
+        let index = tile.kdtree()?;
+        let found = (0..tile.points.len())
+            .into_par_iter()
+            .filter(|i| tile.is_core[*i])
+            .map(|i| {
+                let point = &tile.points[i];
+                let nearest = index
+                    .nearest(&point.xyz, params.k + 1, &squared_euclidean)
+                    .map_err(tiles::invalid_point)?;
+                let neighbourhood: Vec<[f64; 3]> =
+                    nearest.iter().map(|(_, j)| tile.points[**j].xyz).collect();
+                let is_building = LocalShape::new(&neighbourhood).is_some_and(|shape| {
+                    shape.planarity() >= params.min_planarity
+                        && shape.roughness() <= params.max_roughness
+                });
+                Ok(is_building.then_some(point.id))
+            })
+            .collect::<Result<Vec<_>, LaszyError>>()?;
+        buildings.extend(found.into_iter().flatten());
+    }
+    progress.finish(&format!("Found {} building points", buildings.len()));
+    Ok(buildings)
+}
@@ -0,0 +1,119 @@
+use crate::error::ErrorContext;
+use crate::LaszyError;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What to do when the file a builder writes to already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Return `LaszyError::OutputExists` without reading any points.
+    Error,
+    /// Replace the existing file once the new file has been written completely.
+    #[default]
+    Overwrite,
+    /// Leave the existing file untouched and return without reading any points.
+    Skip,
+}
+
+/// Write an output file through a temporary file in the same directory, which is renamed to
+/// `target` only when `write` succeeds. On failure the temporary file is removed, so `target` is
+/// never left half-written.
+///
+/// returns: `Ok(None)` when the file was skipped because of the overwrite policy, otherwise the
+/// result of `write`.
+pub(crate) fn write_atomically<T>(
+    target: &Path,
+    policy: OverwritePolicy,
+    write: impl FnOnce(&Path) -> Result<T, LaszyError>,
+) -> Result<Option<T>, LaszyError> {
+    if target.exists() {
+        match policy {
+            OverwritePolicy::Error => {
+                return Err(LaszyError::OutputExists(target.display().to_string()))
+            }
+            OverwritePolicy::Skip => {
+                log::info!("Skipping {}, it already exists", target.display());
+                return Ok(None);
+            }
+            OverwritePolicy::Overwrite => {}
+        }
+    }
+    let temp_path = temp_path_for(target)?;
+    match write(&temp_path) {
+        Ok(result) => {
+            if let Err(e) = std::fs::rename(&temp_path, target) {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(e.into());
+            }
+            Ok(Some(result))
+        }
+        Err(e) => {
+            // The error of the write is more useful than one of removing a partial file
+            let _ = std::fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Hidden file next to `target`, so the final rename stays on the same filesystem. The file is
+/// created empty to reserve its name, so concurrent writes to the same target never share one.
+fn temp_path_for(target: &Path) -> Result<PathBuf, LaszyError> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    loop {
+        let temp_path = target.with_file_name(format!(
+            ".{file_name}.{}.{}.tmp",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(_) => return Ok(temp_path),
+            // Left behind by a process with the same id that crashed
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).in_file(&temp_path.display().to_string()),
+        }
+    }
+}
+
+/// Directory for intermediate files in `std::env::temp_dir`, removed with everything in it when
+/// dropped.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Result<Self, LaszyError> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "{prefix}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).in_file(&path.to_string_lossy())?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            log::warn!(
+                "Could not remove temporary directory {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
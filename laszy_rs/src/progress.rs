@@ -0,0 +1,124 @@
+//! Reporting of progress for long running builder operations.
+//!
+//! The builder never prints to stdout by itself. Progress of reading points and of the CSF
+//! simulation is passed to a `ProgressReporter`, and status messages are emitted through the
+//! `log` crate. Without the `indicatif` feature the builder uses the `LogReporter`, which only
+//! shows something when the application has installed a logger.
+
+/// Receives progress updates from the builder. A task is started, advanced zero or more times and
+/// then finished, before the next task is started.
+pub trait ProgressReporter: Send + Sync {
+    /// A new task started. `total` is the amount of steps in the task, if it is known beforehand.
+    fn start(&self, task: &str, total: Option<u64>);
+
+    /// The current task advanced by `delta` steps.
+    fn advance(&self, delta: u64);
+
+    /// Status message for the current task, for example the state of a running simulation.
+    fn message(&self, _message: &str) {}
+
+    /// The current task finished, with a short summary of the result.
+    fn finish(&self, message: &str);
+}
+
+/// Reporter that ignores all progress updates.
+pub struct SilentReporter;
+
+impl ProgressReporter for SilentReporter {
+    fn start(&self, _task: &str, _total: Option<u64>) {}
+
+    fn advance(&self, _delta: u64) {}
+
+    fn finish(&self, _message: &str) {}
+}
+
+/// Reporter that writes the start and end of each task to the `log` crate. Steps are not logged.
+pub struct LogReporter;
+
+impl ProgressReporter for LogReporter {
+    fn start(&self, task: &str, _total: Option<u64>) {
+        log::info!("{task}");
+    }
+
+    fn advance(&self, _delta: u64) {}
+
+    fn message(&self, message: &str) {
+        log::debug!("{message}");
+    }
+
+    fn finish(&self, message: &str) {
+        log::info!("{message}");
+    }
+}
+
+/// Reporter that draws a progress bar in the terminal, or a spinner when the total is unknown.
+#[cfg(feature = "indicatif")]
+#[derive(Default)]
+pub struct IndicatifReporter {
+    bar: std::sync::Mutex<Option<indicatif::ProgressBar>>,
+}
+
+#[cfg(feature = "indicatif")]
+impl IndicatifReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_bar(&self, f: impl FnOnce(&indicatif::ProgressBar)) {
+        if let Ok(bar) = self.bar.lock() {
+            if let Some(bar) = bar.as_ref() {
+                f(bar);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+impl ProgressReporter for IndicatifReporter {
+    fn start(&self, task: &str, total: Option<u64>) {
+        let bar = match total {
+            Some(total) => indicatif::ProgressBar::new(total).with_style(
+                indicatif::ProgressStyle::with_template("{msg} [{wide_bar}] {pos}/{len}")
+                    .unwrap_or_else(|_| indicatif::ProgressStyle::default_bar()),
+            ),
+            None => indicatif::ProgressBar::new_spinner(),
+        };
+        bar.set_message(task.to_string());
+        if let Ok(mut current) = self.bar.lock() {
+            *current = Some(bar);
+        }
+    }
+
+    fn advance(&self, delta: u64) {
+        self.with_bar(|bar| bar.inc(delta));
+    }
+
+    fn message(&self, message: &str) {
+        self.with_bar(|bar| bar.set_message(message.to_string()));
+    }
+
+    fn finish(&self, message: &str) {
+        self.with_bar(|bar| bar.finish_with_message(message.to_string()));
+        if let Ok(mut current) = self.bar.lock() {
+            current.take();
+        }
+    }
+}
+
+/// The reporter used by a new builder: a progress bar when the `indicatif` feature is enabled,
+/// otherwise the `LogReporter`.
+#[cfg(feature = "indicatif")]
+pub(crate) fn default_reporter() -> Box<dyn ProgressReporter> {
+    Box::new(IndicatifReporter::new())
+}
+
+#[cfg(not(feature = "indicatif"))]
+pub(crate) fn default_reporter() -> Box<dyn ProgressReporter> {
+    Box::new(LogReporter)
+}
+
+/// Amount of points between two progress updates, so a reporter receives roughly `updates`
+/// updates per pass over `point_count` points.
+pub(crate) fn step_size(point_count: u64, updates: u64) -> u64 {
+    (point_count / updates).max(1)
+}
@@ -0,0 +1,93 @@
+use crate::raster::{MemoryRaster, RasterGrid, RasterOptions, RasterSource};
+use crate::LaszyError;
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::Path;
+
+/// Write an ESRI ASCII grid, with one line per row.
+pub(crate) fn write(
+    path: &Path,
+    source: &mut dyn RasterSource,
+    options: &RasterOptions,
+) -> Result<(), LaszyError> {
+    let grid = source.grid();
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "ncols {}", grid.columns)?;
+    writeln!(file, "nrows {}", grid.rows)?;
+    writeln!(file, "xllcorner {}", grid.lower_left.0)?;
+    writeln!(file, "yllcorner {}", grid.lower_left.1)?;
+    writeln!(file, "cellsize {}", grid.cell_size)?;
+    writeln!(file, "NODATA_value {}", options.nodata)?;
+
+    let mut values = vec![0.0; grid.columns];
+    for row in 0..grid.rows {
+        source.read_row(row, &mut values)?;
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                write!(file, " ")?;
+            }
+            match value.is_nan() {
+                true => write!(file, "{}", options.nodata)?,
+                false => write!(file, "{value}")?,
+            }
+        }
+        writeln!(file)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Read an ESRI ASCII grid. Cells with the NODATA value are NaN.
+pub(crate) fn read(path: &Path) -> Result<MemoryRaster, LaszyError> {
+    let invalid = |message: String| std::io::Error::new(ErrorKind::InvalidData, message);
+    let text = std::fs::read_to_string(path)?;
+    let mut tokens = text.split_whitespace().peekable();
+    let (mut columns, mut rows, mut cell_size, mut nodata) = (None, None, None, None);
+    let (mut x, mut y, mut centred) = (None, None, false);
+    // The header lines start with a keyword, the values follow after them
+    while let Some(key) = tokens.next_if(|token| token.starts_with(char::is_alphabetic)) {
+        let value = tokens
+            .next()
+            .ok_or_else(|| invalid(format!("ESRI ASCII grid header {key} has no value")))?;
+        let number: f64 = value
+            .parse()
+            .map_err(|_| invalid(format!("Invalid value {value} of {key}")))?;
+        match key.to_lowercase().as_str() {
+            "ncols" => columns = Some(number as usize),
+            "nrows" => rows = Some(number as usize),
+            "xllcorner" => x = Some(number),
+            "yllcorner" => y = Some(number),
+            "xllcenter" => (x, centred) = (Some(number), true),
+            "yllcenter" => (y, centred) = (Some(number), true),
+            "cellsize" => cell_size = Some(number),
+            "nodata_value" => nodata = Some(number),
+            _ => return Err(invalid(format!("Unknown ESRI ASCII grid header {key}")).into()),
+        }
+    }
+    let (Some(columns), Some(rows), Some(x), Some(y), Some(cell_size)) =
+        (columns, rows, x, y, cell_size)
+    else {
+        return Err(invalid("Incomplete ESRI ASCII grid header".to_string()).into());
+    };
+    let offset = if centred { cell_size / 2.0 } else { 0.0 };
+    let grid = RasterGrid {
+        lower_left: (x - offset, y - offset),
+        cell_size,
+        rows,
+        columns,
+    };
+    let values = tokens
+        .map(|value| match value.parse::<f64>() {
+            Ok(value) if Some(value) == nodata => Ok(f64::NAN),
+            Ok(value) => Ok(value),
+            Err(_) => Err(invalid(format!("Invalid cell value {value}"))),
+        })
+        .collect::<Result<Vec<f64>, _>>()?;
+    let values = Array2::from_shape_vec((rows, columns), values).map_err(|_| {
+        invalid(format!(
+            "ESRI ASCII grid doesn't have {rows} rows of {columns} values"
+        ))
+    })?;
+    Ok(MemoryRaster { grid, values })
+}
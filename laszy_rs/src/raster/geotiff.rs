@@ -0,0 +1,593 @@
+use crate::raster::{MemoryRaster, RasterCompression, RasterGrid, RasterOptions, RasterSource};
+use crate::LaszyError;
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, Write};
+use std::path::Path;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::encoder::{TiffEncoder, TiffKind};
+use tiff::tags::{PlanarConfiguration, Tag};
+use tiff::TiffError;
+
+/// Uncompressed size of a strip of rows in bytes.
+const STRIP_SIZE: usize = 256 * 1024;
+/// Uncompressed image size from which BigTIFF is written. Classic TIFF can address 4 GiB, this
+/// leaves room for compression that doesn't make the data smaller.
+const BIG_TIFF_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Record ids of the projection VLRs of a LAS file, the same as the numbers of the GeoTIFF tags.
+const GEO_KEY_DIRECTORY_RECORD: u16 = 34735;
+const GEO_DOUBLE_PARAMS_RECORD: u16 = 34736;
+const GEO_ASCII_PARAMS_RECORD: u16 = 34737;
+/// Record id of the VLR with the OGC WKT of the coordinate reference system.
+const OGC_WKT_RECORD: u16 = 2112;
+
+/// Metadata of GDAL as XML, used for the descriptions of the bands.
+const GDAL_METADATA_TAG: u16 = 42112;
+
+const GT_MODEL_TYPE_GEO_KEY: u16 = 1024;
+const GT_RASTER_TYPE_GEO_KEY: u16 = 1025;
+const GT_CITATION_GEO_KEY: u16 = 1026;
+const GEOGRAPHIC_TYPE_GEO_KEY: u16 = 2048;
+const PROJECTED_CS_TYPE_GEO_KEY: u16 = 3072;
+const MODEL_TYPE_PROJECTED: u16 = 1;
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+const MODEL_TYPE_GEOCENTRIC: u16 = 3;
+const RASTER_PIXEL_IS_AREA: u16 = 1;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// The GeoTIFF keys describing a coordinate reference system, as stored in the GeoKeyDirectoryTag,
+/// GeoDoubleParamsTag and GeoAsciiParamsTag of a GeoTIFF, or the matching "LASF_Projection" VLRs
+/// of a LAS file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct GeoKeys {
+    directory: Vec<u16>,
+    doubles: Vec<f64>,
+    ascii: String,
+}
+
+impl GeoKeys {
+    /// The GeoTIFF keys of the projection VLRs of a LAS file, if it has them. Without GeoTIFF key
+    /// VLRs the OGC WKT VLR is used, if the file has one.
+    pub fn from_las_header(header: &las::Header) -> Option<Self> {
+        let vlr = |record_id: u16| {
+            header
+                .vlrs()
+                .iter()
+                .chain(header.evlrs())
+                .find(|vlr| vlr.user_id == "LASF_Projection" && vlr.record_id == record_id)
+        };
+        let Some(directory) = vlr(GEO_KEY_DIRECTORY_RECORD) else {
+            let wkt = vlr(OGC_WKT_RECORD)?;
+            return GeoKeys::from_wkt(String::from_utf8_lossy(&wkt.data).trim_end_matches('\0'));
+        };
+        let directory: Vec<u16> = directory
+            .data
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        if directory.len() < 4 || directory.len() < 4 + 4 * directory[3] as usize {
+            log::warn!("Ignoring the invalid GeoKeyDirectory VLR of the input");
+            return None;
+        }
+        let doubles = vlr(GEO_DOUBLE_PARAMS_RECORD)
+            .map(|vlr| {
+                vlr.data
+                    .chunks_exact(8)
+                    .map(|bytes| {
+                        let mut value = [0; 8];
+                        value.copy_from_slice(bytes);
+                        f64::from_le_bytes(value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let ascii = vlr(GEO_ASCII_PARAMS_RECORD)
+            .map(|vlr| {
+                String::from_utf8_lossy(&vlr.data)
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .unwrap_or_default();
+        Some(GeoKeys {
+            directory,
+            doubles,
+            ascii,
+        })
+    }
+
+    /// Keys with the OGC WKT of a coordinate reference system as the ESRI PE string of the
+    /// citation, which GDAL reads as the WKT. The model type is that of the outermost system in
+    /// the WKT, the projected one of a compound system with heights.
+    fn from_wkt(wkt: &str) -> Option<Self> {
+        let wkt = wkt.trim();
+        let model_type = [
+            ("PROJCS[", MODEL_TYPE_PROJECTED),
+            ("PROJCRS[", MODEL_TYPE_PROJECTED),
+            ("GEOGCS[", MODEL_TYPE_GEOGRAPHIC),
+            ("GEOGCRS[", MODEL_TYPE_GEOGRAPHIC),
+            ("GEOCCS[", MODEL_TYPE_GEOCENTRIC),
+        ]
+        .into_iter()
+        .filter_map(|(keyword, model_type)| Some((wkt.find(keyword)?, model_type)))
+        .min()
+        .map(|(_, model_type)| model_type);
+        let ascii = format!("ESRI PE String = {wkt}|");
+        let (Some(model_type), Ok(length)) = (model_type, u16::try_from(ascii.len())) else {
+            log::warn!(
+                "Ignoring the OGC WKT VLR of the input, it can't be written as GeoTIFF keys"
+            );
+            return None;
+        };
+        Some(GeoKeys {
+            directory: vec![
+                1,
+                1,
+                0,
+                2,
+                GT_MODEL_TYPE_GEO_KEY,
+                0,
+                1,
+                model_type,
+                GT_CITATION_GEO_KEY,
+                GEO_ASCII_PARAMS_RECORD,
+                length,
+                0,
+            ],
+            doubles: Vec::new(),
+            ascii,
+        })
+    }
+
+    /// Keys for the EPSG code of a coordinate reference system, of the model type of the keys of
+    /// the `input`. Without a model type in the input, codes from 4000 to 4999 are taken as
+    /// geographic systems, such as 4326 for WGS 84, and all other codes as projected systems.
+    pub fn from_epsg(epsg: u16, input: Option<&GeoKeys>) -> Self {
+        let model_type = input.and_then(GeoKeys::model_type).unwrap_or(match epsg {
+            4000..=4999 => MODEL_TYPE_GEOGRAPHIC,
+            _ => MODEL_TYPE_PROJECTED,
+        });
+        let key = match model_type {
+            MODEL_TYPE_PROJECTED => PROJECTED_CS_TYPE_GEO_KEY,
+            _ => GEOGRAPHIC_TYPE_GEO_KEY,
+        };
+        GeoKeys {
+            directory: vec![
+                1,
+                1,
+                0,
+                2,
+                GT_MODEL_TYPE_GEO_KEY,
+                0,
+                1,
+                model_type,
+                key,
+                0,
+                1,
+                epsg,
+            ],
+            doubles: Vec::new(),
+            ascii: String::new(),
+        }
+    }
+
+    /// The value of the model type key: projected, geographic or geocentric.
+    fn model_type(&self) -> Option<u16> {
+        let key_count = self.directory[3] as usize;
+        self.directory[4..4 + key_count * 4]
+            .chunks_exact(4)
+            .find(|key| key[0] == GT_MODEL_TYPE_GEO_KEY && key[1] == 0)
+            .map(|key| key[3])
+    }
+
+    /// The key directory with the raster type set to pixels that are areas, matching the
+    /// georeferencing written by this module.
+    fn directory_for_raster(&self) -> Vec<u16> {
+        let key_count = self.directory[3] as usize;
+        let mut keys: Vec<&[u16]> = self.directory[4..4 + key_count * 4]
+            .chunks_exact(4)
+            .filter(|key| key[0] != GT_RASTER_TYPE_GEO_KEY)
+            .collect();
+        let raster_type = [GT_RASTER_TYPE_GEO_KEY, 0, 1, RASTER_PIXEL_IS_AREA];
+        keys.push(&raster_type);
+        keys.sort_by_key(|key| key[0]);
+        let mut directory = self.directory[..3].to_vec();
+        directory.push(keys.len() as u16);
+        directory.extend(keys.into_iter().flatten());
+        directory
+    }
+}
+
+fn tiff_error(error: TiffError) -> LaszyError {
+    match error {
+        TiffError::IoError(error) => error.into(),
+        error => std::io::Error::other(error.to_string()).into(),
+    }
+}
+
+/// Write a GeoTIFF with 32 bit float values, written in strips of rows. The bands of a cell are
+/// interleaved.
+pub(crate) fn write(
+    path: &Path,
+    source: &mut dyn RasterSource,
+    options: &RasterOptions,
+    geo_keys: Option<&GeoKeys>,
+) -> Result<(), LaszyError> {
+    let grid = source.grid();
+    let file = BufWriter::new(File::create(path)?);
+    let size = grid.rows as u64 * grid.columns as u64 * source.bands() as u64 * 4;
+    if size < BIG_TIFF_SIZE {
+        let encoder = TiffEncoder::new(file).map_err(tiff_error)?;
+        write_image(encoder, source, options, geo_keys)
+    } else {
+        let encoder = TiffEncoder::new_big(file).map_err(tiff_error)?;
+        write_image(encoder, source, options, geo_keys)
+    }
+}
+
+fn write_image<W: Write + Seek, K: TiffKind>(
+    mut encoder: TiffEncoder<W, K>,
+    source: &mut dyn RasterSource,
+    options: &RasterOptions,
+    geo_keys: Option<&GeoKeys>,
+) -> Result<(), LaszyError> {
+    let grid = source.grid();
+    let bands = source.bands();
+    let row_size = grid.columns * bands * 4;
+    let rows_per_strip = (STRIP_SIZE / row_size).clamp(1, grid.rows.max(1));
+    let mut directory = encoder.new_directory().map_err(tiff_error)?;
+
+    let mut offsets = Vec::new();
+    let mut byte_counts = Vec::new();
+    let mut values = vec![0.0; grid.columns * bands];
+    let mut strip = Vec::with_capacity(rows_per_strip * row_size);
+    for row in 0..grid.rows {
+        source.read_row(row, &mut values)?;
+        for value in &values {
+            let value = if value.is_nan() {
+                options.nodata
+            } else {
+                *value
+            };
+            // The encoder writes the header in the byte order of the machine
+            strip.extend_from_slice(&(value as f32).to_ne_bytes());
+        }
+        if (row + 1) % rows_per_strip == 0 || row + 1 == grid.rows {
+            let data = compress(&strip, options.compression)?;
+            let offset = directory.write_data(&data[..]).map_err(tiff_error)?;
+            offsets.push(K::convert_offset(offset).map_err(tiff_error)?);
+            byte_counts.push(K::convert_offset(data.len() as u64).map_err(tiff_error)?);
+            strip.clear();
+        }
+    }
+
+    let compression: u16 = match options.compression {
+        RasterCompression::None => 1,
+        RasterCompression::Lzw => 5,
+        RasterCompression::Deflate => 8,
+    };
+    let (upper_left_x, upper_left_y) = grid.upper_left();
+    let (width, height) = (grid.columns as u32, grid.rows as u32);
+    directory
+        .write_tag(Tag::ImageWidth, width)
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::ImageLength, height)
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::BitsPerSample, &vec![32_u16; bands][..])
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::Compression, compression)
+        .map_err(tiff_error)?;
+    // BlackIsZero, for a single band of values
+    directory
+        .write_tag(Tag::PhotometricInterpretation, 1_u16)
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::SamplesPerPixel, bands as u16)
+        .map_err(tiff_error)?;
+    if bands > 1 {
+        // The bands after the first are unspecified data, not colour or transparency
+        directory
+            .write_tag(Tag::ExtraSamples, &vec![0_u16; bands - 1][..])
+            .map_err(tiff_error)?;
+    }
+    // Chunky, the bands of a cell are interleaved
+    directory
+        .write_tag(Tag::PlanarConfiguration, 1_u16)
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::RowsPerStrip, rows_per_strip as u32)
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::StripOffsets, K::convert_slice(&offsets))
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::StripByteCounts, K::convert_slice(&byte_counts))
+        .map_err(tiff_error)?;
+    // IEEE floating point
+    directory
+        .write_tag(Tag::SampleFormat, &vec![3_u16; bands][..])
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(
+            Tag::ModelPixelScaleTag,
+            &[grid.cell_size, grid.cell_size, 0.0][..],
+        )
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(
+            Tag::ModelTiepointTag,
+            &[0.0, 0.0, 0.0, upper_left_x, upper_left_y, 0.0][..],
+        )
+        .map_err(tiff_error)?;
+    directory
+        .write_tag(Tag::GdalNodata, options.nodata.to_string().as_str())
+        .map_err(tiff_error)?;
+    if let Some(metadata) = gdal_metadata(source) {
+        directory
+            .write_tag(Tag::Unknown(GDAL_METADATA_TAG), metadata.as_str())
+            .map_err(tiff_error)?;
+    }
+    if let Some(geo_keys) = geo_keys {
+        directory
+            .write_tag(
+                Tag::GeoKeyDirectoryTag,
+                &geo_keys.directory_for_raster()[..],
+            )
+            .map_err(tiff_error)?;
+        if !geo_keys.doubles.is_empty() {
+            directory
+                .write_tag(Tag::GeoDoubleParamsTag, &geo_keys.doubles[..])
+                .map_err(tiff_error)?;
+        }
+        if !geo_keys.ascii.is_empty() {
+            directory
+                .write_tag(Tag::GeoAsciiParamsTag, geo_keys.ascii.as_str())
+                .map_err(tiff_error)?;
+        }
+    }
+    directory.finish().map_err(tiff_error)?;
+    Ok(())
+}
+
+/// The band descriptions of `source` in the XML of the GDAL metadata tag, or None when the bands
+/// have no names.
+fn gdal_metadata(source: &dyn RasterSource) -> Option<String> {
+    let items: String = (0..source.bands())
+        .filter_map(|band| {
+            let name = source.band_name(band)?;
+            let name = name
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            Some(format!(
+                "<Item name=\"DESCRIPTION\" sample=\"{band}\" role=\"description\">{name}</Item>"
+            ))
+        })
+        .collect();
+    (!items.is_empty()).then(|| format!("<GDALMetadata>{items}</GDALMetadata>"))
+}
+
+fn compress(data: &[u8], compression: RasterCompression) -> Result<Vec<u8>, LaszyError> {
+    match compression {
+        RasterCompression::None => Ok(data.to_vec()),
+        RasterCompression::Deflate => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        RasterCompression::Lzw => {
+            weezl::encode::Encoder::with_tiff_size_switch(weezl::BitOrder::Msb, 8)
+                .encode(data)
+                .map_err(|e| LaszyError::from(std::io::Error::other(e.to_string())))
+        }
+    }
+}
+
+/// Error for a GeoTIFF that can't be read.
+fn invalid(message: &str) -> LaszyError {
+    std::io::Error::new(ErrorKind::InvalidData, message.to_string()).into()
+}
+
+/// A decoder of the GeoTIFF at `path`, without the default limits on the size of what it decodes,
+/// which rasters of large areas exceed.
+fn open(path: &Path) -> Result<Decoder<BufReader<File>>, LaszyError> {
+    Ok(Decoder::new(BufReader::new(File::open(path)?))
+        .map_err(tiff_error)?
+        .with_limits(Limits::unlimited()))
+}
+
+/// The grid of a GeoTIFF that is georeferenced with a pixel scale and a tiepoint, and its GDAL
+/// NODATA value.
+fn georeference<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+) -> Result<(RasterGrid, Option<f64>), LaszyError> {
+    let (columns, rows) = decoder.dimensions().map_err(tiff_error)?;
+    let scale = decoder
+        .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+        .map_err(|_| invalid("The GeoTIFF has no pixel scale"))?;
+    let tiepoint = decoder
+        .get_tag_f64_vec(Tag::ModelTiepointTag)
+        .map_err(|_| invalid("The GeoTIFF has no tiepoint"))?;
+    if scale.len() < 2 || tiepoint.len() < 6 {
+        return Err(invalid(
+            "The GeoTIFF has an invalid pixel scale or tiepoint",
+        ));
+    }
+    if scale[0] != scale[1] {
+        return Err(invalid("The cells of the GeoTIFF aren't square"));
+    }
+    let pixel_is_point = decoder
+        .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+        .unwrap_or_default()
+        .get(4..)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .any(|key| key[0] == GT_RASTER_TYPE_GEO_KEY && key[3] == RASTER_PIXEL_IS_POINT);
+    let nodata: Option<f64> = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|nodata| nodata.trim_matches(char::from(0)).trim().parse().ok());
+
+    let cell_size = scale[0];
+    let (rows, columns) = (rows as usize, columns as usize);
+    // The tiepoint ties a raster position to a position in the world, for areas the raster
+    // position is the corner of a cell and for points its centre
+    let shift = if pixel_is_point { 0.5 } else { 0.0 };
+    let left = tiepoint[3] - (tiepoint[0] + shift) * cell_size;
+    let top = tiepoint[4] + (tiepoint[1] + shift) * cell_size;
+    let grid = RasterGrid {
+        lower_left: (left, top - rows as f64 * cell_size),
+        cell_size,
+        rows,
+        columns,
+    };
+    Ok((grid, nodata))
+}
+
+/// Read the first band of a GeoTIFF that is georeferenced with a pixel scale and a tiepoint, as
+/// written by this module. Cells with the GDAL NODATA value are NaN.
+pub(crate) fn read(path: &Path) -> Result<MemoryRaster, LaszyError> {
+    let mut decoder = open(path)?;
+    let (grid, nodata) = georeference(&mut decoder)?;
+    let (rows, columns) = (grid.rows, grid.columns);
+    let values: Vec<f64> = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::F32(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(values) => values,
+        DecodingResult::U8(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::I8(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(values) => values.into_iter().map(f64::from).collect(),
+        _ => return Err(invalid("The GeoTIFF has an unsupported sample format")),
+    };
+    let samples = values.len();
+    let values = Array2::from_shape_vec((rows, columns), values)
+        .map_err(|_| {
+            invalid(&format!(
+                "The GeoTIFF has {samples} samples, expected {} for {rows} rows of {columns} cells",
+                rows * columns
+            ))
+        })?
+        .mapv(|value| match Some(value) == nodata {
+            true => f64::NAN,
+            false => value,
+        });
+    Ok(MemoryRaster { grid, values })
+}
+
+/// The cells of an image with 8 or 16 bit bands, such as an orthophoto, with the values of all
+/// bands of a cell following each other.
+pub(crate) struct ImageWindow {
+    pub grid: RasterGrid,
+    pub bands: usize,
+    pub bits_per_sample: u8,
+    pub values: Vec<u16>,
+    pub nodata: Option<f64>,
+}
+
+impl ImageWindow {
+    /// The values of the bands of the cell containing `x`, `y`, or None outside the window.
+    pub fn cell_values(&self, x: f64, y: f64) -> Option<&[u16]> {
+        let (row, column) = self.grid.cell(x, y)?;
+        let start = (row * self.grid.columns + column) * self.bands;
+        Some(&self.values[start..start + self.bands])
+    }
+}
+
+/// Read the cells of a GeoTIFF with 8 or 16 bit unsigned bands, such as an orthophoto, that are
+/// within `lower_left` to `upper_right`. Only the strips or tiles in that window are read, so the
+/// image may be much larger than the area of the points.
+pub(crate) fn read_window(
+    path: &Path,
+    lower_left: (f64, f64),
+    upper_right: (f64, f64),
+) -> Result<ImageWindow, LaszyError> {
+    let mut decoder = open(path)?;
+    let (image, nodata) = georeference(&mut decoder)?;
+    let bands = decoder
+        .find_tag_unsigned::<usize>(Tag::SamplesPerPixel)
+        .map_err(tiff_error)?
+        .unwrap_or(1);
+    let planar = decoder
+        .find_tag_unsigned::<u16>(Tag::PlanarConfiguration)
+        .map_err(tiff_error)?;
+    if planar == Some(PlanarConfiguration::Planar.to_u16()) {
+        return Err(invalid(
+            "The bands of the GeoTIFF are stored separately, only interleaved bands are supported",
+        ));
+    }
+
+    // The cells of the image from the first to past the last row and column of the window
+    let (left, top) = image.upper_left();
+    let cell = |distance: f64, round: fn(f64) -> f64, cells: usize| {
+        (round(distance / image.cell_size).max(0.0) as usize).min(cells)
+    };
+    let (first_column, end_column) = (
+        cell(lower_left.0 - left, f64::floor, image.columns),
+        cell(upper_right.0 - left, f64::ceil, image.columns),
+    );
+    let (first_row, end_row) = (
+        cell(top - upper_right.1, f64::floor, image.rows),
+        cell(top - lower_left.1, f64::ceil, image.rows),
+    );
+    let columns = end_column.saturating_sub(first_column);
+    let rows = end_row.saturating_sub(first_row);
+    let grid = RasterGrid {
+        lower_left: (
+            left + first_column as f64 * image.cell_size,
+            top - end_row.max(first_row) as f64 * image.cell_size,
+        ),
+        cell_size: image.cell_size,
+        rows,
+        columns,
+    };
+    let mut window = ImageWindow {
+        grid,
+        bands,
+        bits_per_sample: 8,
+        values: vec![0; rows * columns * bands],
+        nodata,
+    };
+    if rows == 0 || columns == 0 {
+        return Ok(window);
+    }
+
+    let (chunk_width, chunk_height) = decoder.chunk_dimensions();
+    let (chunk_width, chunk_height) = (chunk_width as usize, chunk_height as usize);
+    let chunks_across = image.columns.div_ceil(chunk_width);
+    for chunk_row in first_row / chunk_height..=(end_row - 1) / chunk_height {
+        for chunk_column in first_column / chunk_width..=(end_column - 1) / chunk_width {
+            let index = (chunk_row * chunks_across + chunk_column) as u32;
+            let (data_width, data_height) = decoder.chunk_data_dimensions(index);
+            let samples: Vec<u16> =
+                match decoder.read_chunk(index).map_err(tiff_error)? {
+                    DecodingResult::U8(values) => values.into_iter().map(u16::from).collect(),
+                    DecodingResult::U16(values) => {
+                        window.bits_per_sample = 16;
+                        values
+                    }
+                    _ => return Err(invalid(
+                        "The GeoTIFF has an unsupported sample format, the bands must be 8 or 16 \
+                         bit unsigned integers",
+                    )),
+                };
+            let (row0, column0) = (chunk_row * chunk_height, chunk_column * chunk_width);
+            let chunk_rows = row0.max(first_row)..(row0 + data_height as usize).min(end_row);
+            let chunk_columns =
+                column0.max(first_column)..(column0 + data_width as usize).min(end_column);
+            for row in chunk_rows {
+                let from =
+                    ((row - row0) * data_width as usize + chunk_columns.start - column0) * bands;
+                let to = ((row - first_row) * columns + chunk_columns.start - first_column) * bands;
+                let length = chunk_columns.len() * bands;
+                window.values[to..to + length].copy_from_slice(&samples[from..from + length]);
+            }
+        }
+    }
+    Ok(window)
+}
@@ -0,0 +1,303 @@
+//! Writing of raster outputs, such as a DTM, as ESRI ASCII grid or GeoTIFF, and reading of
+//! rasters in these formats, such as an external DTM.
+//!
+//! Rasters are written one row at a time from a `RasterSource`, so a raster never has to be in
+//! memory at once.
+
+mod asc;
+mod geotiff;
+
+use crate::error::ErrorContext;
+use crate::LaszyError;
+use ndarray::Array2;
+use std::path::Path;
+
+pub(crate) use geotiff::{GeoKeys, ImageWindow};
+
+/// Compression of GeoTIFF outputs. ESRI ASCII grids are never compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RasterCompression {
+    None,
+    #[default]
+    Deflate,
+    Lzw,
+}
+
+/// Options for writing raster outputs.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::{RasterCompression, RasterOptions};
+/// let options = RasterOptions::default()
+///     .with_compression(RasterCompression::Lzw)
+///     .with_epsg(28992);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterOptions {
+    /// Value written for cells without data.
+    pub nodata: f64,
+    /// Compression of GeoTIFF files.
+    pub compression: RasterCompression,
+    /// EPSG code of the coordinate reference system written to GeoTIFF files, with the model type
+    /// of the input file when it has one. When None, the GeoTIFF keys in the projection VLRs of
+    /// the input file are used, or else its OGC WKT VLR, if it has them.
+    pub epsg: Option<u16>,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        RasterOptions {
+            nodata: -9999.0,
+            compression: RasterCompression::default(),
+            epsg: None,
+        }
+    }
+}
+
+impl RasterOptions {
+    pub fn with_nodata(mut self, nodata: f64) -> Self {
+        self.nodata = nodata;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: RasterCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_epsg(mut self, epsg: u16) -> Self {
+        self.epsg = Some(epsg);
+        self
+    }
+}
+
+/// File format of a raster output, from the extension of its filepath.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RasterFormat {
+    Asc,
+    GeoTiff,
+}
+
+impl RasterFormat {
+    pub fn from_path(filepath: &str) -> Result<Self, LaszyError> {
+        let extension = Path::new(filepath)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("asc") => Ok(RasterFormat::Asc),
+            Some("tif") | Some("tiff") => Ok(RasterFormat::GeoTiff),
+            _ => Err(LaszyError::InvalidFileExtension(format!(
+                "{filepath}, raster outputs must end in .asc, .tif or .tiff"
+            ))),
+        }
+    }
+}
+
+/// Position and size of a raster. Rows are ordered from north to south.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct RasterGrid {
+    /// Lower left corner of the lower left cell.
+    pub lower_left: (f64, f64),
+    pub cell_size: f64,
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl RasterGrid {
+    /// The grid of cells of `cell_size` covering `lower_left` to `upper_right`. The cell edges are
+    /// at multiples of the cell size, so rasters of neighbouring areas line up.
+    pub fn covering(lower_left: (f64, f64), upper_right: (f64, f64), cell_size: f64) -> Self {
+        let snap = |value: f64| (value / cell_size).floor() * cell_size;
+        let lower_left = (snap(lower_left.0), snap(lower_left.1));
+        let cells = |length: f64| ((length / cell_size).floor() as usize + 1).max(1);
+        RasterGrid {
+            lower_left,
+            cell_size,
+            rows: cells(upper_right.1 - lower_left.1),
+            columns: cells(upper_right.0 - lower_left.0),
+        }
+    }
+
+    /// Centre of the cell at `row`, `column`, where row 0 is the northern most row.
+    pub fn cell_centre(&self, row: usize, column: usize) -> (f64, f64) {
+        (
+            self.lower_left.0 + self.cell_size * (column as f64 + 0.5),
+            self.lower_left.1 + self.cell_size * ((self.rows - row) as f64 - 0.5),
+        )
+    }
+
+    /// Row and column of the cell containing `x`, `y`, or None outside the grid.
+    pub fn cell(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        let column = ((x - self.lower_left.0) / self.cell_size).floor();
+        let row_from_south = ((y - self.lower_left.1) / self.cell_size).floor();
+        if column < 0.0 || row_from_south < 0.0 {
+            return None;
+        }
+        let (column, row_from_south) = (column as usize, row_from_south as usize);
+        (column < self.columns && row_from_south < self.rows)
+            .then(|| (self.rows - 1 - row_from_south, column))
+    }
+
+    /// Upper left corner of the upper left cell.
+    pub fn upper_left(&self) -> (f64, f64) {
+        (
+            self.lower_left.0,
+            self.lower_left.1 + self.cell_size * self.rows as f64,
+        )
+    }
+}
+
+/// Values of a raster, read one row at a time from north to south.
+pub(crate) trait RasterSource {
+    fn grid(&self) -> RasterGrid;
+
+    /// Amount of values per cell.
+    fn bands(&self) -> usize {
+        1
+    }
+
+    /// Description of a band, written to GeoTIFF files.
+    fn band_name(&self, _band: usize) -> Option<String> {
+        None
+    }
+
+    /// Fill `values` with the values of `row`, where row 0 is the northern most row. With more
+    /// than one band, the values of all bands of a cell follow each other. Rows are read in order.
+    /// Cells without data are NaN.
+    fn read_row(&mut self, row: usize, values: &mut [f64]) -> Result<(), LaszyError>;
+}
+
+/// A raster held in memory, with the northern most row first.
+pub(crate) struct MemoryRaster {
+    pub grid: RasterGrid,
+    pub values: Array2<f64>,
+}
+
+impl MemoryRaster {
+    /// A raster of `grid` without any data.
+    pub fn empty(grid: RasterGrid) -> Self {
+        MemoryRaster {
+            grid,
+            values: Array2::from_elem((grid.rows, grid.columns), f64::NAN),
+        }
+    }
+}
+
+impl MemoryRaster {
+    /// Value at `x`, `y`, bilinearly interpolated between the centres of the cells around it. Cells
+    /// without data are left out of the interpolation, and positions between the centres of the
+    /// outer cells and the edge of the raster get the value of the closest centres. None outside
+    /// the raster or when none of the cells around it have data.
+    pub fn value_at(&self, x: f64, y: f64) -> Option<f64> {
+        self.grid.cell(x, y)?;
+        let grid = self.grid;
+        let (left, top) = grid.upper_left();
+        let column = ((x - left) / grid.cell_size - 0.5).clamp(0.0, (grid.columns - 1) as f64);
+        let row = ((top - y) / grid.cell_size - 0.5).clamp(0.0, (grid.rows - 1) as f64);
+        let (column0, row0) = (column.floor() as usize, row.floor() as usize);
+        let (column1, row1) = (
+            (column0 + 1).min(grid.columns - 1),
+            (row0 + 1).min(grid.rows - 1),
+        );
+        let (dx, dy) = (column - column0 as f64, row - row0 as f64);
+        let mut weighted_sum = 0.0;
+        let mut total_weight = 0.0;
+        for (row, column, weight) in [
+            (row0, column0, (1.0 - dx) * (1.0 - dy)),
+            (row0, column1, dx * (1.0 - dy)),
+            (row1, column0, (1.0 - dx) * dy),
+            (row1, column1, dx * dy),
+        ] {
+            let value = self.values[[row, column]];
+            if !value.is_nan() && weight > 0.0 {
+                weighted_sum += weight * value;
+                total_weight += weight;
+            }
+        }
+        (total_weight > 0.0).then(|| weighted_sum / total_weight)
+    }
+}
+
+impl RasterSource for MemoryRaster {
+    fn grid(&self) -> RasterGrid {
+        self.grid
+    }
+
+    fn read_row(&mut self, row: usize, values: &mut [f64]) -> Result<(), LaszyError> {
+        for (value, cell) in values.iter_mut().zip(self.values.row(row)) {
+            *value = *cell;
+        }
+        Ok(())
+    }
+}
+
+impl<T: RasterSource + ?Sized> RasterSource for &mut T {
+    fn grid(&self) -> RasterGrid {
+        (**self).grid()
+    }
+
+    fn bands(&self) -> usize {
+        (**self).bands()
+    }
+
+    fn band_name(&self, band: usize) -> Option<String> {
+        (**self).band_name(band)
+    }
+
+    fn read_row(&mut self, row: usize, values: &mut [f64]) -> Result<(), LaszyError> {
+        (**self).read_row(row, values)
+    }
+}
+
+/// Write all rows of `source` to `path` in `format`. The coordinate reference system is taken
+/// from the EPSG code of the options, or from `geo_keys` of the input.
+pub(crate) fn write_raster(
+    path: &Path,
+    format: RasterFormat,
+    source: &mut dyn RasterSource,
+    options: &RasterOptions,
+    geo_keys: Option<&GeoKeys>,
+) -> Result<(), LaszyError> {
+    match format {
+        RasterFormat::Asc if source.bands() > 1 => Err(LaszyError::invalid_parameter(
+            "bands",
+            source.bands(),
+            "an ESRI ASCII grid has a single band, write a GeoTIFF for more bands",
+        )),
+        RasterFormat::Asc => asc::write(path, source, options),
+        RasterFormat::GeoTiff => {
+            let geo_keys = match options.epsg {
+                Some(epsg) => Some(GeoKeys::from_epsg(epsg, geo_keys)),
+                None => geo_keys.cloned(),
+            };
+            if geo_keys.is_none() {
+                log::warn!(
+                    "The input has no GeoTIFF projection keys and no EPSG code is set, the \
+                     GeoTIFF is written without coordinate reference system"
+                );
+            }
+            geotiff::write(path, source, options, geo_keys.as_ref())
+        }
+    }
+}
+
+/// Read a single band raster from an ESRI ASCII grid or a GeoTIFF, depending on the extension of
+/// `filepath`.
+pub(crate) fn read_raster(filepath: &str) -> Result<MemoryRaster, LaszyError> {
+    match RasterFormat::from_path(filepath)? {
+        RasterFormat::Asc => asc::read(Path::new(filepath)),
+        RasterFormat::GeoTiff => geotiff::read(Path::new(filepath)),
+    }
+    .in_file(filepath)
+}
+
+/// Read the cells of a GeoTIFF image with 8 or 16 bit bands, such as an orthophoto, within
+/// `lower_left` to `upper_right`.
+pub(crate) fn read_image_window(
+    filepath: &str,
+    lower_left: (f64, f64),
+    upper_right: (f64, f64),
+) -> Result<ImageWindow, LaszyError> {
+    geotiff::read_window(Path::new(filepath), lower_left, upper_right).in_file(filepath)
+}
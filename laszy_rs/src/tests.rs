@@ -1,4 +1,24 @@
-use crate::{CroppingMethod, PointCloudBuilder, ThinningMethod};
+use crate::aggregation::CellAggregator;
+use crate::csf::surface::ClothSurface;
+use crate::dsm::{self, SurfaceModel};
+use crate::dtm;
+use crate::duplicates;
+use crate::features;
+use crate::geometry::{self, LocalShape};
+use crate::ground::{morphology, pmf, ptd, smrf, MinimumSurface};
+use crate::noise::{self, NoisePoints};
+use crate::objects::{self, OBJECT_TILE_BUFFER};
+use crate::output;
+use crate::raster::{self, MemoryRaster, RasterFormat, RasterGrid};
+use crate::tiles::{PointSet, PointTiles, TILE_SIZE};
+use crate::{
+    Aggregation, Attribute, CancellationToken, CollisionHeight, ColourParams, CroppingMethod,
+    CsfParams, DsmParams, DtmParams, DuplicateAction, DuplicateParams, GeometricFeature,
+    GroundSurface, HagParams, Interpolation, LasOptions, LaszyError, Neighbourhood, NoiseAction,
+    NoiseFilter, ObjectParams, OutlierMethod, OverwritePolicy, PmfParams, PointCloud,
+    PointCloudBuilder, ProgressReporter, PtdParams, RasterCompression, RasterOptions, Reducer,
+    SilentReporter, SmrfParams, ThinningMethod, TileParams,
+};
 
 fn get_test_builder() -> PointCloudBuilder {
     let path = "test.las".to_string();
@@ -14,10 +34,28 @@ fn test_incorrect_crop() {
             upper_right: (182_997.8, 336_497.5),
         })
         .with_thinning(ThinningMethod::EveryNth { nth: 40 })
-        .with_csf_ground_reclassification(0.5, 5.0, 0.1, 1.0)
-        .to_file(&String::from("result.las"));
-    assert!(re.is_err());
+        .with_csf_ground_reclassification(CsfParams::relief().with_classification_threshold(1.0))
+        .to_file(&String::from("incorrect_crop.las"));
+    assert!(matches!(
+        re,
+        Err(LaszyError::InvalidParameter {
+            parameter: "crop",
+            ..
+        })
+    ));
     println!("Result: {:?}", re);
+    // The failed run must not leave a partial file or its temporary file behind
+    let leftovers = std::fs::read_dir(".")
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .contains("incorrect_crop.las")
+        })
+        .count();
+    assert_eq!(leftovers, 0);
 }
 
 #[test]
@@ -30,7 +68,7 @@ fn test_extreme_thinning() {
     let re = builder
         .with_crop(crop)
         .with_thinning(ThinningMethod::EveryNth { nth: 40_000_000 })
-        .with_csf_ground_reclassification(0.5, 5.0, 0.1, 1.0)
+        .with_csf_ground_reclassification(CsfParams::relief().with_classification_threshold(1.0))
         .to_file(&String::from("result.las"));
     assert!(re.is_ok());
     println!("Result: {:?}", re);
@@ -46,8 +84,2399 @@ fn test_to_file() {
     let re = builder
         .with_crop(crop)
         .with_thinning(ThinningMethod::EveryNth { nth: 40 })
-        .with_csf_ground_reclassification(0.5, 5.0, 0.1, 1.0)
+        .with_csf_ground_reclassification(CsfParams::relief().with_classification_threshold(1.0))
         .to_file(&String::from("result.las"));
     assert!(re.is_ok());
     println!("Result: {:?}", re);
 }
+
+#[test]
+fn test_cancelled_to_file() {
+    let mut builder = get_test_builder();
+    let token = CancellationToken::new();
+    token.cancel();
+    let re = builder
+        .with_cancellation_token(token)
+        .to_file(&String::from("cancelled.las"));
+    assert!(matches!(re, Err(LaszyError::Cancelled)));
+    assert!(!std::path::Path::new("cancelled.las").exists());
+}
+
+/// Cancels the run as soon as the cloth simulation starts.
+struct CancelOnSimulation(CancellationToken);
+
+impl ProgressReporter for CancelOnSimulation {
+    fn start(&self, task: &str, _total: Option<u64>) {
+        if task.starts_with("Simulating") {
+            self.0.cancel();
+        }
+    }
+
+    fn advance(&self, _delta: u64) {}
+
+    fn finish(&self, _message: &str) {}
+}
+
+#[test]
+fn test_cancel_csf_simulation() {
+    let mut builder = get_test_builder();
+    let token = builder.cancellation_token();
+    let re = builder
+        .with_csf_ground_reclassification(CsfParams::default())
+        .with_progress_reporter(Box::new(CancelOnSimulation(token)))
+        .to_cloud();
+    assert!(matches!(re, Err(LaszyError::Cancelled)));
+}
+
+#[test]
+fn test_overwrite_policy() {
+    let path = String::from("overwrite_policy.las");
+    let mut builder = get_test_builder();
+    builder.with_thinning(ThinningMethod::EveryNth { nth: 100 });
+    assert!(builder.to_file(&path).is_ok());
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+    let re = builder
+        .with_overwrite_policy(OverwritePolicy::Error)
+        .to_file(&path);
+    assert!(matches!(re, Err(LaszyError::OutputExists(_))));
+
+    let re = builder
+        .with_overwrite_policy(OverwritePolicy::Skip)
+        .to_file(&path);
+    assert!(re.is_ok());
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().modified().unwrap(),
+        modified
+    );
+
+    let re = builder
+        .with_overwrite_policy(OverwritePolicy::Overwrite)
+        .to_file(&path);
+    assert!(re.is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_concurrent_writes_to_the_same_file() {
+    let target = std::path::Path::new("concurrent_writes.txt");
+    let contents: Vec<String> = (0..8).map(|i| format!("{i}").repeat(100_000)).collect();
+    std::thread::scope(|scope| {
+        for content in &contents {
+            scope.spawn(move || {
+                output::write_atomically(target, OverwritePolicy::Overwrite, |temp_path| {
+                    for chunk in content.as_bytes().chunks(10_000) {
+                        let mut file = std::fs::OpenOptions::new().append(true).open(temp_path)?;
+                        std::io::Write::write_all(&mut file, chunk)?;
+                        std::thread::yield_now();
+                    }
+                    // No other write has touched the temporary file
+                    assert_eq!(std::fs::read_to_string(temp_path).unwrap(), *content);
+                    Ok(())
+                })
+                .unwrap();
+            });
+        }
+    });
+    let written = std::fs::read_to_string(target).unwrap();
+    std::fs::remove_file(target).unwrap();
+    assert!(contents.contains(&written));
+}
+
+#[test]
+fn test_unimplemented_thinning_is_error() {
+    let mut builder = get_test_builder();
+    let re = builder
+        .with_thinning(ThinningMethod::Grid2D {
+            cell_amount: 10,
+            max_points_per_cell: 5,
+        })
+        .to_cloud();
+    assert!(matches!(re, Err(LaszyError::NotImplemented(_))));
+}
+
+#[test]
+fn test_missing_file_error_has_path() {
+    let re = PointCloudBuilder::from_file("does_not_exist.las");
+    match re {
+        Err(LaszyError::IoError { path, .. }) => {
+            assert_eq!(path, Some("does_not_exist.las".to_string()))
+        }
+        _ => panic!("expected an IoError"),
+    }
+}
+
+#[test]
+fn test_inverted_crop() {
+    let mut builder = get_test_builder();
+    builder.with_crop(CroppingMethod::BoundingBox {
+        lower_left: (183_564.09, 332_424.13),
+        upper_right: (183_551.47, 332_414.45),
+    });
+    assert!(builder.validate().is_err());
+}
+
+#[test]
+fn test_invalid_thinning_parameters() {
+    let mut builder = get_test_builder();
+    builder.with_thinning(ThinningMethod::EveryNth { nth: 0 });
+    assert!(builder.validate().is_err());
+    builder.with_thinning(ThinningMethod::Random { percent: 1.5 });
+    assert!(builder.validate().is_err());
+    builder.with_thinning(ThinningMethod::Random { percent: 0.5 });
+    assert!(builder.validate().is_ok());
+}
+
+#[test]
+fn test_invalid_csf_parameters() {
+    let mut builder = get_test_builder();
+    builder.with_csf_ground_reclassification(CsfParams::default().with_rigidness(1.5));
+    assert!(builder.validate().is_err());
+    builder.with_csf_ground_reclassification(CsfParams::default().with_cloth_resolution(0.0));
+    assert!(builder.validate().is_err());
+    builder.with_csf_ground_reclassification(CsfParams::default());
+    assert!(builder.validate().is_ok());
+}
+
+#[test]
+fn test_csf_presets() {
+    for params in [
+        CsfParams::flat(),
+        CsfParams::relief(),
+        CsfParams::steep_slope(),
+    ] {
+        assert!(params.validate().is_ok());
+    }
+    assert!(CsfParams::flat().rigidness > CsfParams::steep_slope().rigidness);
+    assert_eq!(CsfParams::default(), CsfParams::relief());
+}
+
+#[test]
+fn test_csf_collision_height_strategies() {
+    for collision_height in [
+        CollisionHeight::Nearest,
+        CollisionHeight::Lowest,
+        CollisionHeight::Percentile { percent: 0.05 },
+        CollisionHeight::MedianOfLowest { k: 5 },
+    ] {
+        let mut builder = get_test_builder();
+        let params = CsfParams::default().with_collision_height(collision_height);
+        let cloud = builder
+            .with_csf_ground_reclassification(params)
+            .to_cloud()
+            .unwrap();
+        let ground = cloud
+            .points
+            .iter()
+            .filter(|point| point.classification == las::point::Classification::Ground)
+            .count();
+        assert!(ground > 0, "no ground points with {collision_height:?}");
+    }
+    let invalid =
+        CsfParams::default().with_collision_height(CollisionHeight::MedianOfLowest { k: 0 });
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn test_csf_collision_height_ignores_low_noise() {
+    let point = |x: f64, y: f64, z: f64| las::Point {
+        x,
+        y,
+        z,
+        ..Default::default()
+    };
+    let collision_heights = |collision_height: CollisionHeight| {
+        let params = CsfParams::default()
+            .with_cloth_resolution(1.0)
+            .with_collision_height(collision_height);
+        let mut cloth = ClothSurface::initialize((0.0, 0.0), (1.0, 1.0), params, -100.0);
+        // One low noise point and nine ground points around the particle at the origin
+        cloth.add_point(&point(0.0, 0.0, -50.0));
+        for i in 0..9 {
+            cloth.add_point(&point(0.1, 0.1, 10.0 + i as f64 * 0.1));
+        }
+        cloth.set_collision_heights().unwrap();
+        cloth.particles[[0, 0]].max_z
+    };
+    assert_eq!(collision_heights(CollisionHeight::Nearest), -50.0);
+    assert_eq!(collision_heights(CollisionHeight::Lowest), -50.0);
+    assert_eq!(
+        collision_heights(CollisionHeight::MedianOfLowest { k: 5 }),
+        10.1
+    );
+    assert_eq!(
+        collision_heights(CollisionHeight::Percentile { percent: 0.5 }),
+        10.4
+    );
+}
+
+#[test]
+fn test_csf_zero_elevation_is_not_missing() {
+    let params = CsfParams::default().with_cloth_resolution(1.0);
+    let mut cloth = ClothSurface::initialize((0.0, 0.0), (10.0, 10.0), params, -10.0);
+    for x in 0..5 {
+        for y in 0..=10 {
+            cloth.add_point(&las::Point {
+                x: x as f64,
+                y: y as f64,
+                z: 0.0,
+                ..Default::default()
+            });
+        }
+    }
+    cloth.add_point(&las::Point {
+        x: 10.0,
+        y: 10.0,
+        z: 5.0,
+        ..Default::default()
+    });
+    cloth.set_collision_heights().unwrap();
+    for particle in cloth.particles.iter().filter(|particle| particle.x < 5.0) {
+        assert_eq!(particle.max_z, 0.0);
+    }
+    assert_eq!(cloth.particles[[10, 10]].max_z, 5.0);
+}
+
+#[test]
+fn test_tiled_csf_matches_single_cloth() {
+    let classifications = |params: CsfParams| {
+        get_test_builder()
+            .with_silent_progress()
+            .with_csf_ground_reclassification(params)
+            .to_cloud()
+            .unwrap()
+            .points
+            .iter()
+            .map(|point| point.classification)
+            .collect::<Vec<_>>()
+    };
+    let params = CsfParams::default().with_cloth_resolution(1.0);
+    // With a buffer larger than the extent, every tile simulates the same cloth as without tiles
+    let single = classifications(params);
+    let tiled = classifications(params.with_tiling(20.0, 100.0));
+    assert_eq!(single, tiled);
+
+    // With a small buffer the tiles differ a little at their edges
+    let buffered = classifications(params.with_tiling(20.0, 5.0));
+    let differences = single.iter().zip(&buffered).filter(|(a, b)| a != b).count();
+    assert!(differences < single.len() / 10, "{differences} differences");
+}
+
+#[test]
+fn test_tiled_csf_dtm() {
+    let path = std::env::temp_dir().join(format!("laszy-tiled-{}.asc", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    let params = CsfParams::default()
+        .with_cloth_resolution(1.0)
+        .with_tiling(4.0, 2.0);
+    get_test_builder()
+        .with_silent_progress()
+        .to_dtm_using_csf(&path, params)
+        .unwrap();
+    let asc = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let header = |key: &str| {
+        asc.lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap()
+    };
+    let (columns, rows) = (header("ncols"), header("nrows"));
+    let values: Vec<_> = asc.lines().skip(6).collect();
+    assert_eq!(values.len(), rows);
+    for line in values {
+        assert_eq!(line.split_whitespace().count(), columns);
+    }
+    assert!(params.with_tiling(0.5, 2.0).validate().is_err());
+}
+
+fn temp_raster_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("laszy-{}-{name}", std::process::id()));
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn test_dtm_asc_is_georeferenced() {
+    let path = temp_raster_path("dtm.asc");
+    let params = CsfParams::default().with_cloth_resolution(2.0);
+    let mut builder = get_test_builder();
+    builder.with_silent_progress();
+    builder.to_dtm_using_csf(&path, params).unwrap();
+    let asc = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let header = |key: &str| {
+        asc.lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .unwrap()
+    };
+    let bounds = builder.get_metadata().bounds();
+    assert_eq!(header("xllcorner"), bounds.min.x - 1.0);
+    assert_eq!(header("yllcorner"), bounds.min.y - 1.0);
+    assert_eq!(header("cellsize"), 2.0);
+    assert_eq!(header("NODATA_value"), -9999.0);
+    let rows: Vec<_> = asc.lines().skip(6).collect();
+    assert_eq!(rows.len(), header("nrows") as usize);
+    for row in rows {
+        assert_eq!(row.split_whitespace().count(), header("ncols") as usize);
+    }
+}
+
+#[test]
+fn test_dtm_geotiff() {
+    use tiff::decoder::{Decoder, DecodingResult};
+    use tiff::tags::Tag;
+
+    let params = CsfParams::default().with_cloth_resolution(2.0);
+    let mut images = Vec::new();
+    for compression in [
+        RasterCompression::None,
+        RasterCompression::Deflate,
+        RasterCompression::Lzw,
+    ] {
+        let path = temp_raster_path(&format!("dtm-{compression:?}.tif"));
+        let mut builder = get_test_builder();
+        builder
+            .with_silent_progress()
+            .with_raster_options(
+                RasterOptions::default()
+                    .with_compression(compression)
+                    .with_epsg(28992),
+            )
+            .to_dtm_using_csf(&path, params)
+            .unwrap();
+        let mut decoder = Decoder::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let bounds = builder.get_metadata().bounds();
+        let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag).unwrap();
+        assert_eq!(scale, vec![2.0, 2.0, 0.0]);
+        let (columns, rows) = decoder.dimensions().unwrap();
+        let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag).unwrap();
+        assert_eq!(tiepoint[3], bounds.min.x - 1.0);
+        assert_eq!(tiepoint[4], bounds.min.y - 1.0 + 2.0 * rows as f64);
+        assert!(tiepoint[4] >= bounds.max.y);
+        let nodata = decoder.get_tag_ascii_string(Tag::GdalNodata).unwrap();
+        assert_eq!(nodata, "-9999");
+        let geo_keys = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag).unwrap();
+        assert_eq!(&geo_keys[geo_keys.len() - 4..], &[3072, 0, 1, 28992]);
+        let DecodingResult::F32(values) = decoder.read_image().unwrap() else {
+            panic!("GeoTIFF doesn't have 32 bit float values");
+        };
+        assert_eq!(values.len(), (columns * rows) as usize);
+        assert!(values
+            .iter()
+            .all(|z| (bounds.min.z - 15.0..=bounds.max.z).contains(&(*z as f64))));
+        std::fs::remove_file(&path).unwrap();
+        images.push(values);
+    }
+    assert_eq!(images[0], images[1]);
+    assert_eq!(images[0], images[2]);
+}
+
+#[test]
+fn test_geotiff_keys_from_wkt() {
+    use crate::raster::GeoKeys;
+    use tiff::decoder::Decoder;
+    use tiff::tags::Tag;
+
+    let wkt = "COMPD_CS[\"GDA2020 + AHD height\",GEOGCS[\"GDA2020\",DATUM[\"GDA2020\"]],\
+               VERT_CS[\"AHD height\",VERT_DATUM[\"Australian Height Datum\",2005]]]";
+    let mut builder = las::Builder::from((1, 4));
+    builder.vlrs.push(las::Vlr {
+        user_id: "LASF_Projection".to_string(),
+        record_id: 2112,
+        description: String::new(),
+        data: format!("{wkt}\0").into_bytes(),
+    });
+    let input = GeoKeys::from_las_header(&builder.into_header().unwrap());
+    assert!(input.is_some());
+
+    let grid = RasterGrid::covering((0.0, 0.0), (9.0, 9.0), 1.0);
+    let path = temp_raster_path("wkt.tif");
+    // Without an EPSG code the WKT is the citation, with one the model type of the WKT is kept
+    for (epsg, model_type, key) in [(None, 2, 1026), (Some(7844), 2, 2048)] {
+        let options = RasterOptions {
+            epsg,
+            ..Default::default()
+        };
+        let mut raster = MemoryRaster::empty(grid);
+        let format = RasterFormat::GeoTiff;
+        raster::write_raster(
+            std::path::Path::new(&path),
+            format,
+            &mut raster,
+            &options,
+            input.as_ref(),
+        )
+        .unwrap();
+        let mut decoder = Decoder::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let geo_keys = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag).unwrap();
+        assert_eq!(&geo_keys[4..8], &[1024, 0, 1, model_type]);
+        assert!(geo_keys
+            .chunks_exact(4)
+            .skip(1)
+            .any(|entry| entry[0] == key));
+        if epsg.is_none() {
+            let ascii = decoder
+                .get_tag_ascii_string(Tag::GeoAsciiParamsTag)
+                .unwrap();
+            assert_eq!(ascii, format!("ESRI PE String = {wkt}|"));
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_dtm_invalid_extension() {
+    let path = temp_raster_path("dtm.png");
+    let re = get_test_builder().to_dtm_using_csf(&path, CsfParams::default());
+    assert!(matches!(re, Err(LaszyError::InvalidFileExtension(_))));
+    assert!(!std::path::Path::new(&path).exists());
+}
+
+#[test]
+fn test_dtm_using_csf_validates_threads() {
+    let path = temp_raster_path("dtm-threads.asc");
+    let re = get_test_builder()
+        .with_threads(0)
+        .to_dtm_using_csf(&path, CsfParams::default());
+    assert!(matches!(re, Err(LaszyError::InvalidParameter { .. })));
+    assert!(!std::path::Path::new(&path).exists());
+}
+
+/// Ground points every metre on the plane z = 0.1 * x from 0 to 20, without the points in a
+/// square hole from 6 to 14.
+fn plane_with_hole() -> Vec<[f64; 3]> {
+    let mut points = Vec::new();
+    for x in 0..=20 {
+        for y in 0..=20 {
+            let in_hole = (6..=14).contains(&x) && (6..=14).contains(&y);
+            if !in_hole {
+                points.push([x as f64, y as f64, 0.1 * x as f64]);
+            }
+        }
+    }
+    points
+}
+
+fn interpolate_plane(params: DtmParams) -> MemoryRaster {
+    let grid = RasterGrid::covering((0.0, 0.0), (20.0, 20.0), 1.0);
+    dtm::interpolate(
+        plane_with_hole(),
+        grid,
+        &params,
+        &SilentReporter,
+        &CancellationToken::new(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_dtm_interpolation_of_plane() {
+    for interpolation in [
+        Interpolation::Tin,
+        Interpolation::NaturalNeighbour,
+        Interpolation::Idw {
+            power: 2.0,
+            neighbours: 1,
+        },
+    ] {
+        let dtm = interpolate_plane(DtmParams::default().with_interpolation(interpolation));
+        assert_eq!(dtm.values.dim(), (21, 21));
+        // Cell centres are half a metre from the points
+        let (x, y) = dtm.grid.cell_centre(20, 3);
+        assert_eq!((x, y), (3.5, 0.5));
+        let z = dtm.values[[20, 3]];
+        match interpolation {
+            Interpolation::Idw { .. } => {
+                assert!((z - 0.3).abs() < 1e-9 || (z - 0.4).abs() < 1e-9, "{z}")
+            }
+            _ => assert!((z - 0.35).abs() < 1e-9, "{interpolation:?} gives {z}"),
+        }
+    }
+}
+
+#[test]
+fn test_dtm_max_distance_and_void_filling() {
+    let params = DtmParams::default()
+        .with_max_distance(1.5)
+        .with_fill_voids(false);
+    let dtm = interpolate_plane(params);
+    // The centre of the hole is more than 1.5 from the closest ground point
+    let (row, column) = (10, 10);
+    assert_eq!(dtm.grid.cell_centre(row, column), (10.5, 10.5));
+    assert!(dtm.values[[row, column]].is_nan());
+    // Cells near the points are still interpolated
+    assert!((dtm.values[[20, 3]] - 0.35).abs() < 1e-9);
+
+    let dtm = interpolate_plane(params.with_fill_voids(true));
+    for ((row, column), z) in dtm.values.indexed_iter() {
+        let (x, y) = dtm.grid.cell_centre(row, column);
+        if (6.0..=14.0).contains(&x) && (6.0..=14.0).contains(&y) {
+            assert!(!z.is_nan(), "no data at {x}, {y}");
+        }
+    }
+    let filled = dtm.values[[row, column]];
+    assert!((0.6..=1.5).contains(&filled), "{filled}");
+
+    // The area outside of the ground points is not a void
+    let grid = RasterGrid::covering((0.0, 0.0), (40.0, 20.0), 1.0);
+    let dtm = dtm::interpolate(
+        plane_with_hole(),
+        grid,
+        &params.with_fill_voids(true),
+        &SilentReporter,
+        &CancellationToken::new(),
+    )
+    .unwrap();
+    assert!(dtm.values[[10, 30]].is_nan());
+    assert!(!dtm.values[[10, 10]].is_nan());
+}
+
+#[test]
+fn test_dtm_from_csf_ground() {
+    let path = temp_raster_path("interpolated-dtm.asc");
+    let mut builder = get_test_builder();
+    builder
+        .with_silent_progress()
+        .with_csf_ground_reclassification(CsfParams::default().with_cloth_resolution(2.0));
+    let params = DtmParams::default().with_resolution(2.0);
+    builder.to_dtm(&path, params).unwrap();
+    let asc = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let bounds = builder.get_metadata().bounds();
+    let values: Vec<f64> = asc
+        .lines()
+        .skip(6)
+        .flat_map(|line| line.split_whitespace())
+        .map(|value| value.parse().unwrap())
+        .collect();
+    let with_data: Vec<_> = values.iter().filter(|z| **z != -9999.0).collect();
+    assert!(with_data.len() > values.len() / 2);
+    assert!(with_data
+        .iter()
+        .all(|z| (bounds.min.z..=bounds.max.z).contains(*z)));
+}
+
+#[test]
+fn test_invalid_dtm_parameters() {
+    let builder = get_test_builder();
+    for params in [
+        DtmParams::default().with_resolution(0.0),
+        DtmParams::default().with_max_distance(-1.0),
+        DtmParams::default().with_interpolation(Interpolation::Idw {
+            power: 2.0,
+            neighbours: 0,
+        }),
+    ] {
+        let re = builder.to_dtm("invalid-dtm.asc", params);
+        assert!(matches!(re, Err(LaszyError::InvalidParameter { .. })));
+    }
+}
+
+#[test]
+fn test_dsm_highest_point_and_pit_free() {
+    let grid = RasterGrid::covering((0.0, 0.0), (4.9, 4.9), 1.0);
+    let point = |x: f64, y: f64, z: f64| las::Point {
+        x,
+        y,
+        z,
+        ..Default::default()
+    };
+    let mut dsm = SurfaceModel::new(grid);
+    for x in 0..5 {
+        for y in 0..5 {
+            let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+            match (x, y) {
+                // A gap without points
+                (1.5, 3.5) => continue,
+                // A pit
+                (2.5, 2.5) => dsm.add_point(&point(x, y, 5.0)),
+                _ => {
+                    dsm.add_point(&point(x, y, 10.0));
+                    dsm.add_point(&point(x, y, 2.0));
+                }
+            }
+        }
+    }
+    let values = dsm.finish(&DsmParams::default()).values;
+    assert_eq!(values.dim(), (5, 5));
+    assert_eq!(values[[4, 0]], 10.0);
+    assert_eq!(values[[2, 2]], 5.0);
+    assert!(values[[1, 1]].is_nan());
+
+    let mut dsm = SurfaceModel::new(grid);
+    for x in 0..5 {
+        for y in 0..5 {
+            let z = if (x, y) == (2, 2) { 5.0 } else { 10.0 };
+            if (x, y) != (1, 3) {
+                dsm.add_point(&point(x as f64 + 0.5, y as f64 + 0.5, z));
+            }
+        }
+    }
+    let values = dsm.finish(&DsmParams::default().with_pit_free(true)).values;
+    assert_eq!(values[[2, 2]], 10.0);
+    assert_eq!(values[[1, 1]], 10.0);
+}
+
+#[test]
+fn test_canopy_heights() {
+    let grid = RasterGrid::covering((0.0, 0.0), (1.5, 0.5), 1.0);
+    let mut dsm = MemoryRaster::empty(grid);
+    let mut dtm = MemoryRaster::empty(grid);
+    dsm.values.assign(&ndarray::arr2(&[[12.0, 4.0]]));
+    dtm.values.assign(&ndarray::arr2(&[[5.0, 5.0]]));
+    let chm = dsm::canopy_heights(dsm, &dtm);
+    assert_eq!(chm.values, ndarray::arr2(&[[7.0, 0.0]]));
+
+    let dsm = MemoryRaster::empty(grid);
+    let chm = dsm::canopy_heights(dsm, &dtm);
+    assert!(chm.values.iter().all(|height| height.is_nan()));
+}
+
+#[test]
+fn test_dsm_and_chm() {
+    let mut builder = get_test_builder();
+    builder
+        .with_silent_progress()
+        .with_csf_ground_reclassification(CsfParams::default().with_cloth_resolution(2.0));
+    let bounds = *builder.get_metadata().bounds();
+    let read_values = |path: &str| -> Vec<f64> {
+        let asc = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        asc.lines()
+            .skip(6)
+            .flat_map(|line| line.split_whitespace())
+            .map(|value| value.parse().unwrap())
+            .filter(|value| *value != -9999.0)
+            .collect()
+    };
+
+    let path = temp_raster_path("dsm.asc");
+    let params = DsmParams::default().with_resolution(2.0);
+    builder.to_dsm(&path, params).unwrap();
+    let dsm = read_values(&path);
+    assert!(!dsm.is_empty());
+    assert!(dsm
+        .iter()
+        .all(|z| (bounds.min.z..=bounds.max.z).contains(z)));
+    assert!(dsm.contains(&bounds.max.z));
+
+    let path = temp_raster_path("chm.asc");
+    builder
+        .to_chm(&path, params.with_pit_free(true), DtmParams::default())
+        .unwrap();
+    let chm = read_values(&path);
+    assert!(!chm.is_empty());
+    assert!(chm
+        .iter()
+        .all(|height| (0.0..=bounds.max.z - bounds.min.z).contains(height)));
+    assert!(chm.iter().any(|height| *height > 1.0));
+}
+
+#[test]
+fn test_cell_aggregation() {
+    let grid = RasterGrid::covering((0.0, 0.0), (1.0, 0.0), 1.0);
+    let aggregations = [
+        Aggregation::count(),
+        Aggregation::new(Attribute::Z, Reducer::Min),
+        Aggregation::new(Attribute::Z, Reducer::Max),
+        Aggregation::new(Attribute::Z, Reducer::Mean),
+        Aggregation::new(Attribute::Z, Reducer::StdDev),
+        Aggregation::new(Attribute::Z, Reducer::Percentile { percent: 0.75 }),
+        Aggregation::new(Attribute::Classification, Reducer::Mode),
+        Aggregation::new(Attribute::GpsTime, Reducer::Max),
+    ];
+    let mut aggregator = CellAggregator::new(grid, &aggregations);
+    for (z, class) in [(2.0, 2), (4.0, 5), (4.0, 5), (6.0, 2), (9.0, 1)] {
+        let point = las::Point {
+            x: 0.5,
+            y: 0.0,
+            z,
+            classification: las::point::Classification::new(class).unwrap(),
+            ..Default::default()
+        };
+        aggregator.add_point(&point);
+    }
+    let raster = aggregator.finish();
+    // The first column has all points, the second none
+    let cells: Vec<f64> = raster.bands.iter().map(|band| band[[0, 0]]).collect();
+    assert_eq!(&cells[..4], &[5.0, 2.0, 9.0, 5.0]);
+    assert!((cells[4] - 5.6_f64.sqrt()).abs() < 1e-12);
+    assert_eq!(cells[5], 6.0);
+    // Classes 2 and 5 both have two points, the lowest wins
+    assert_eq!(cells[6], 2.0);
+    // The points don't have a GPS time
+    assert!(cells[7].is_nan());
+    let empty: Vec<f64> = raster.bands.iter().map(|band| band[[0, 1]]).collect();
+    assert_eq!(empty[0], 0.0);
+    assert!(empty[1..].iter().all(|value| value.is_nan()));
+
+    let names: Vec<String> = aggregations.iter().map(Aggregation::name).collect();
+    assert_eq!(names[0], "count");
+    assert_eq!(names[4], "z_stddev");
+    assert_eq!(names[5], "z_p75");
+    assert_eq!(names[6], "classification_mode");
+}
+
+#[test]
+fn test_to_raster_bands() {
+    use std::io::{Read, Seek, SeekFrom};
+    use tiff::decoder::Decoder;
+    use tiff::tags::Tag;
+
+    let path = temp_raster_path("aggregated.tif");
+    let mut builder = get_test_builder();
+    let aggregations = [
+        Aggregation::count(),
+        Aggregation::new(Attribute::Z, Reducer::Max),
+        Aggregation::new(Attribute::Intensity, Reducer::Percentile { percent: 0.95 }),
+    ];
+    builder
+        .with_silent_progress()
+        .with_raster_options(RasterOptions::default().with_compression(RasterCompression::None))
+        .to_raster(&path, 5.0, &aggregations)
+        .unwrap();
+    let bounds = *builder.get_metadata().bounds();
+
+    let mut file = std::fs::File::open(&path).unwrap();
+    let mut decoder = Decoder::new(&mut file).unwrap();
+    let (columns, rows) = decoder.dimensions().unwrap();
+    assert_eq!(decoder.get_tag_u32(Tag::SamplesPerPixel).unwrap(), 3);
+    let metadata = decoder.get_tag_ascii_string(Tag::Unknown(42112)).unwrap();
+    for (band, name) in ["count", "z_max", "intensity_p95"].iter().enumerate() {
+        assert!(metadata.contains(&format!(
+            "<Item name=\"DESCRIPTION\" sample=\"{band}\" role=\"description\">{name}</Item>"
+        )));
+    }
+    // The decoder can't read images with several bands, so read the uncompressed strips
+    let offsets = decoder.get_tag_u64_vec(Tag::StripOffsets).unwrap();
+    let byte_counts = decoder.get_tag_u64_vec(Tag::StripByteCounts).unwrap();
+    drop(decoder);
+    let mut values = Vec::new();
+    for (offset, byte_count) in offsets.into_iter().zip(byte_counts) {
+        let mut strip = vec![0; byte_count as usize];
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.read_exact(&mut strip).unwrap();
+        values.extend(
+            strip
+                .chunks_exact(4)
+                .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()) as f64),
+        );
+    }
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(values.len(), (columns * rows * 3) as usize);
+    let cells: Vec<&[f64]> = values.chunks_exact(3).collect();
+    let total: f64 = cells.iter().map(|cell| cell[0]).sum();
+    assert_eq!(total, builder.get_metadata().point_count() as f64);
+    for cell in cells {
+        if cell[0] == 0.0 {
+            assert_eq!(cell[1], -9999.0);
+        } else {
+            assert!((bounds.min.z - 0.01..=bounds.max.z + 0.01).contains(&cell[1]));
+            assert!(cell[2] >= 0.0);
+        }
+    }
+}
+
+#[test]
+fn test_invalid_raster_aggregations() {
+    let builder = get_test_builder();
+    let path = temp_raster_path("aggregated.asc");
+    let aggregations = [
+        Aggregation::count(),
+        Aggregation::new(Attribute::Z, Reducer::Mean),
+    ];
+    for (resolution, aggregations) in [
+        (1.0, &aggregations[..]),
+        (1.0, &[][..]),
+        (0.0, &aggregations[..1]),
+        (
+            1.0,
+            &[Aggregation::new(
+                Attribute::Z,
+                Reducer::Percentile { percent: 95.0 },
+            )][..],
+        ),
+    ] {
+        let result = builder.to_raster(&path, resolution, aggregations);
+        assert!(matches!(result, Err(LaszyError::InvalidParameter { .. })));
+    }
+    assert!(!std::path::Path::new(&path).exists());
+}
+
+#[test]
+fn test_raster_round_trip_and_bilinear_values() {
+    let grid = RasterGrid::covering((10.0, 20.0), (19.0, 27.0), 2.0);
+    let mut dtm = MemoryRaster::empty(grid);
+    for ((row, column), value) in dtm.values.indexed_iter_mut() {
+        let (x, y) = grid.cell_centre(row, column);
+        *value = 2.0 * x + 3.0 * y;
+    }
+    dtm.values[[0, 0]] = f64::NAN;
+    for (name, format) in [
+        ("read.asc", RasterFormat::Asc),
+        ("read.tif", RasterFormat::GeoTiff),
+    ] {
+        let path = temp_raster_path(name);
+        let options = RasterOptions::default().with_compression(RasterCompression::Lzw);
+        raster::write_raster(
+            std::path::Path::new(&path),
+            format,
+            &mut dtm,
+            &options,
+            None,
+        )
+        .unwrap();
+        let read = raster::read_raster(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.grid, dtm.grid);
+        assert!(read.values[[0, 0]].is_nan());
+        assert_eq!(
+            read.values.slice(ndarray::s![1.., ..]),
+            dtm.values.slice(ndarray::s![1.., ..])
+        );
+    }
+
+    // Between the centres of cells with data a plane is interpolated exactly
+    assert!((dtm.value_at(14.3, 22.7).unwrap() - (2.0 * 14.3 + 3.0 * 22.7)).abs() < 1e-9);
+    // Towards the edge the value of the closest centres is used
+    assert_eq!(dtm.value_at(19.5, 20.5), Some(2.0 * 19.0 + 3.0 * 21.0));
+    // Next to a cell without data only the other cells are used
+    assert_eq!(dtm.value_at(11.5, 27.5), Some(2.0 * 13.0 + 3.0 * 27.0));
+    assert_eq!(dtm.value_at(9.0, 25.0), None);
+}
+
+#[test]
+fn test_height_above_ground_extra_bytes() {
+    use las::Read;
+
+    let path = temp_raster_path("hag.las");
+    let mut builder = get_test_builder();
+    builder
+        .with_silent_progress()
+        .with_height_above_ground(HagParams::default())
+        .to_file(&path)
+        .unwrap();
+    let mut reader = las::Reader::from_path(&path).unwrap();
+    let header = reader.header().clone();
+    assert_eq!(header.point_format().extra_bytes, 8);
+    let vlr = header
+        .vlrs()
+        .iter()
+        .find(|vlr| vlr.user_id == "LASF_Spec" && vlr.record_id == 4)
+        .unwrap();
+    assert_eq!(vlr.data.len(), 192);
+    assert_eq!(vlr.data[2], 10);
+    assert_eq!(&vlr.data[4..21], b"HeightAboveGround");
+    assert_eq!(vlr.data[21], 0);
+    let points: Vec<las::Point> = reader.points().map(|point| point.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    let heights: Vec<(f64, bool)> = points
+        .iter()
+        .map(|point| {
+            let height = f64::from_le_bytes(point.extra_bytes[..].try_into().unwrap());
+            (
+                height,
+                point.classification == las::point::Classification::Ground,
+            )
+        })
+        .collect();
+    assert_eq!(heights.len() as u64, builder.get_metadata().point_count());
+    // The TIN goes through all ground points
+    let ground: Vec<f64> = heights
+        .iter()
+        .filter(|(_, is_ground)| *is_ground)
+        .map(|(height, _)| *height)
+        .collect();
+    let on_tin = ground.iter().filter(|height| height.abs() < 0.01).count();
+    assert!(on_tin as f64 > 0.9 * ground.len() as f64);
+    assert!(heights.iter().any(|(height, _)| *height > 1.0));
+}
+
+#[test]
+fn test_height_above_ground_of_cloth_replaces_z() {
+    let mut builder = get_test_builder();
+    let params = CsfParams::default().with_cloth_resolution(2.0);
+    let cloud = builder
+        .with_silent_progress()
+        .with_csf_ground_reclassification(params)
+        .with_height_above_ground(
+            HagParams::default()
+                .with_surface(GroundSurface::Cloth)
+                .with_replace_z(true),
+        )
+        .to_cloud()
+        .unwrap();
+    assert!(!cloud.is_empty());
+    // Points are ground when they are close to the cloth
+    for point in &cloud.points {
+        let is_ground = point.classification == las::point::Classification::Ground;
+        assert_eq!(is_ground, point.z.abs() < params.classification_threshold);
+        assert!(point.extra_bytes.is_empty());
+    }
+}
+
+#[test]
+fn test_invalid_height_above_ground() {
+    let mut builder = get_test_builder();
+    builder.with_height_above_ground(HagParams::default().with_surface(GroundSurface::Cloth));
+    assert!(matches!(
+        builder.validate(),
+        Err(LaszyError::InvalidParameter { .. })
+    ));
+    builder.with_height_above_ground(HagParams::default().with_surface(GroundSurface::Dtm {
+        path: "dtm.png".to_string(),
+    }));
+    assert!(matches!(
+        builder.validate(),
+        Err(LaszyError::InvalidFileExtension(_))
+    ));
+    builder.with_height_above_ground(HagParams::default().with_surface(GroundSurface::Dtm {
+        path: temp_raster_path("missing-dtm.asc"),
+    }));
+    assert!(builder.validate().is_ok());
+    assert!(matches!(
+        builder.to_cloud(),
+        Err(LaszyError::IoError { path: Some(_), .. })
+    ));
+}
+
+#[test]
+fn test_opening_removes_small_objects() {
+    let mut values = ndarray::Array2::zeros((7, 7));
+    values[[3, 3]] = 5.0;
+    values[[0, 5]] = 2.0;
+    values[[0, 6]] = 2.0;
+    let opened = morphology::opening(&values, 1);
+    assert!(opened.iter().all(|value| *value == 0.0));
+    // A plateau larger than the window keeps its shape
+    values.slice_mut(ndarray::s![1..6, 1..6]).fill(3.0);
+    assert_eq!(
+        morphology::opening(&values, 1).slice(ndarray::s![1..6, 1..6]),
+        values.slice(ndarray::s![1..6, 1..6])
+    );
+}
+
+/// The lowest points of a gentle slope with a building of 8 by 8 meters and 10 meters high.
+fn slope_with_building() -> (MinimumSurface, Vec<las::Point>) {
+    let grid = RasterGrid::covering((0.0, 0.0), (39.9, 39.9), 1.0);
+    let mut minimum = MinimumSurface::new(grid);
+    let mut points = Vec::new();
+    for x in 0..40 {
+        for y in 0..40 {
+            let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+            let is_building = (16.0..24.0).contains(&x) && (16.0..24.0).contains(&y);
+            let point = las::Point {
+                x,
+                y,
+                z: 0.05 * x + if is_building { 10.0 } else { 0.0 },
+                ..Default::default()
+            };
+            minimum.add_point(&point);
+            points.push(point);
+        }
+    }
+    (minimum, points)
+}
+
+#[test]
+fn test_morphological_filters_remove_building() {
+    let cancellation = CancellationToken::new();
+    let grounds = [
+        {
+            let (minimum, points) = slope_with_building();
+            let params = SmrfParams::default().with_window(10.0);
+            let ground =
+                smrf::filter(minimum.finish(), &params, &SilentReporter, &cancellation).unwrap();
+            (ground, points)
+        },
+        {
+            let (minimum, points) = slope_with_building();
+            let params = PmfParams::default().with_max_window(20.0);
+            let ground =
+                pmf::filter(minimum.finish(), &params, &SilentReporter, &cancellation).unwrap();
+            (ground, points)
+        },
+    ];
+    for (ground, points) in grounds {
+        for point in points {
+            let is_building = point.z > 0.05 * point.x + 1.0;
+            assert_eq!(
+                ground.is_ground_point(&point),
+                !is_building,
+                "{}, {}",
+                point.x,
+                point.y
+            );
+        }
+    }
+}
+
+#[test]
+fn test_smrf_and_pmf_ground_reclassification() {
+    let ground_points = |builder: &mut PointCloudBuilder| {
+        builder
+            .with_silent_progress()
+            .to_cloud()
+            .unwrap()
+            .points
+            .iter()
+            .filter(|point| point.classification == las::point::Classification::Ground)
+            .count()
+    };
+    let smrf =
+        ground_points(get_test_builder().with_smrf_ground_reclassification(SmrfParams::default()));
+    let pmf =
+        ground_points(get_test_builder().with_pmf_ground_reclassification(PmfParams::default()));
+    let csf =
+        ground_points(get_test_builder().with_csf_ground_reclassification(CsfParams::default()));
+    // The filters differ in the details, but find about the same ground
+    for ground in [smrf, pmf] {
+        assert!(
+            ground.abs_diff(csf) < csf / 5,
+            "{ground} ground points, {csf} with CSF"
+        );
+    }
+}
+
+#[test]
+fn test_invalid_morphological_filter_parameters() {
+    let mut builder = get_test_builder();
+    builder.with_smrf_ground_reclassification(SmrfParams::default().with_cell_size(0.0));
+    assert!(builder.validate().is_err());
+    builder.with_smrf_ground_reclassification(SmrfParams::default().with_slope(-0.1));
+    assert!(builder.validate().is_err());
+    builder.with_smrf_ground_reclassification(SmrfParams::default());
+    assert!(builder.validate().is_ok());
+    builder.with_pmf_ground_reclassification(PmfParams::default().with_max_window(2.0));
+    assert!(builder.validate().is_err());
+    builder.with_pmf_ground_reclassification(PmfParams::default().with_max_distance(0.1));
+    assert!(builder.validate().is_err());
+    builder.with_pmf_ground_reclassification(PmfParams::default());
+    assert!(builder.validate().is_ok());
+}
+
+#[test]
+fn test_tin_densification_removes_building() {
+    let (_, points) = slope_with_building();
+    let xyz = points
+        .iter()
+        .map(|point| [point.x, point.y, point.z])
+        .collect();
+    let extent = ((0.0, 0.0), (40.0, 40.0));
+    let params = PtdParams::default().with_max_building_size(20.0);
+    let cancellation = CancellationToken::new();
+    let tin = ptd::filter(xyz, extent, &params, &SilentReporter, &cancellation).unwrap();
+    for point in points {
+        let is_building = point.z > 0.05 * point.x + 1.0;
+        assert_eq!(
+            tin.is_ground_point(&point),
+            !is_building,
+            "{}, {}",
+            point.x,
+            point.y
+        );
+    }
+}
+
+#[test]
+fn test_ptd_ground_reclassification() {
+    let classes = |builder: &mut PointCloudBuilder| {
+        builder
+            .with_silent_progress()
+            .with_thinning(ThinningMethod::EveryNth { nth: 4 })
+            .to_cloud()
+            .unwrap()
+            .points
+            .iter()
+            .map(|point| point.classification == las::point::Classification::Ground)
+            .collect::<Vec<_>>()
+    };
+    let params = PtdParams::default()
+        .with_max_building_size(20.0)
+        .with_iteration_angle(15.0);
+    let ptd = classes(get_test_builder().with_ptd_ground_reclassification(params));
+    let file = classes(&mut get_test_builder());
+    // The ground of the file was classified with other software and parameters
+    let agreement = ptd.iter().zip(&file).filter(|(a, b)| a == b).count();
+    assert!(
+        agreement > ptd.len() * 9 / 10,
+        "{agreement} of {}",
+        ptd.len()
+    );
+}
+
+#[test]
+fn test_invalid_ptd_parameters() {
+    let mut builder = get_test_builder();
+    builder.with_ptd_ground_reclassification(PtdParams::default().with_max_building_size(0.0));
+    assert!(builder.validate().is_err());
+    builder.with_ptd_ground_reclassification(PtdParams::default().with_iteration_angle(95.0));
+    assert!(builder.validate().is_err());
+    builder.with_ptd_ground_reclassification(PtdParams::default().with_iteration_distance(-1.0));
+    assert!(builder.validate().is_err());
+    builder.with_ptd_ground_reclassification(PtdParams::default());
+    assert!(builder.validate().is_ok());
+}
+
+/// Find the noise in a sloped plane of points 4 m apart over 3 by 3 noise tiles, with a low
+/// outlier as its second to last point and a high outlier as its last point.
+fn noise_in_plane(filter: NoiseFilter) -> (NoisePoints, u64) {
+    let plane = |x: f64| 0.1 * x;
+    let mut points: Vec<las::Point> = (0..61 * 61)
+        .map(|i| las::Point {
+            x: (i % 61) as f64 * 4.0,
+            y: (i / 61) as f64 * 4.0,
+            z: plane((i % 61) as f64 * 4.0),
+            ..Default::default()
+        })
+        .collect();
+    // Next to the border between tiles
+    for (x, y, dz) in [(99.0, 101.0, -10.0), (150.5, 49.0, 30.0)] {
+        points.push(las::Point {
+            x,
+            y,
+            z: plane(x) + dz,
+            ..Default::default()
+        });
+    }
+    let buffer = noise::tile_buffer(&[filter]);
+    let mut tiles =
+        PointTiles::new((0.0, 0.0), (240.0, 240.0), TILE_SIZE, buffer, "noise").unwrap();
+    assert_eq!(tiles.tile_count(), 9);
+    for (i, point) in points.iter().enumerate() {
+        tiles.add_point((0, i as u64), point).unwrap();
+    }
+    let cancellation = CancellationToken::new();
+    let noise = noise::find(tiles, &[filter], &SilentReporter, &cancellation).unwrap();
+    (noise, points.len() as u64)
+}
+
+#[test]
+fn test_noise_filters_find_outliers() {
+    let methods = [
+        OutlierMethod::Statistical {
+            k: 8,
+            multiplier: 3.0,
+        },
+        OutlierMethod::Radius {
+            radius: 5.0,
+            min_neighbours: 2,
+        },
+        OutlierMethod::Isolated {
+            radius: 9.0,
+            height: 5.0,
+        },
+    ];
+    for method in methods {
+        let (noise, count) = noise_in_plane(NoiseFilter::new(method));
+        // The corners of the plane are further from their neighbours than the other points
+        let corners = [0, 60, 60 * 61, 61 * 61 - 1];
+        let corner_noise = corners.iter().filter(|i| noise.is_noise((0, **i))).count();
+        assert_eq!(noise.len() - corner_noise, 2, "{method:?}");
+        let low = noise.get((0, count - 2)).unwrap();
+        assert_eq!(
+            low.class,
+            las::point::Classification::LowPoint,
+            "{method:?}"
+        );
+        let high = noise.get((0, count - 1)).unwrap();
+        assert_eq!(
+            high.class,
+            las::point::Classification::HighNoise,
+            "{method:?}"
+        );
+        assert!(!high.remove);
+    }
+    let filter = NoiseFilter::new(methods[0]).with_action(NoiseAction::Remove);
+    let (noise, count) = noise_in_plane(filter);
+    assert!(noise.get((0, count - 1)).unwrap().remove);
+}
+
+#[test]
+fn test_noise_filter_classifies_or_removes() {
+    let method = OutlierMethod::Statistical {
+        k: 8,
+        multiplier: 2.0,
+    };
+    let is_noise = |point: &las::Point| {
+        use las::point::Classification::{HighNoise, LowPoint};
+        matches!(point.classification, LowPoint | HighNoise)
+    };
+    let thinning = || ThinningMethod::EveryNth { nth: 10 };
+    let original = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .to_cloud()
+        .unwrap();
+    let classified = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .with_noise_filter(NoiseFilter::new(method))
+        .to_cloud()
+        .unwrap();
+    let noise_count = classified.points.iter().filter(|p| is_noise(p)).count();
+    let original_noise = original.points.iter().filter(|p| is_noise(p)).count();
+    assert!(noise_count > original_noise);
+    assert_eq!(classified.len(), original.len());
+
+    let removed = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .with_noise_filter(NoiseFilter::new(method).with_action(NoiseAction::Remove))
+        .to_cloud()
+        .unwrap();
+    assert_eq!(
+        removed.len(),
+        original.len() - (noise_count - original_noise)
+    );
+}
+
+#[test]
+fn test_noise_filter_with_random_thinning() {
+    let filter = NoiseFilter::new(OutlierMethod::Statistical {
+        k: 8,
+        multiplier: 2.0,
+    });
+    let new_noise = |original: &PointCloud, classified: &PointCloud| {
+        use las::point::Classification::{HighNoise, LowPoint};
+        let is_noise = |point: &las::Point| matches!(point.classification, LowPoint | HighNoise);
+        let count = |cloud: &PointCloud| cloud.points.iter().filter(|p| is_noise(p)).count();
+        (count(classified) - count(original)) as f64 / classified.len() as f64
+    };
+    // The noise is found in the points the thinning keeps, which are the same in every output
+    let mut builder = get_test_builder();
+    builder
+        .with_silent_progress()
+        .with_thinning(ThinningMethod::Random { percent: 0.2 });
+    let original = builder.to_cloud().unwrap();
+    let classified = builder.with_noise_filter(filter).to_cloud().unwrap();
+    assert_eq!(classified.len(), original.len());
+    for (before, after) in original.points.iter().zip(&classified.points) {
+        assert_eq!((before.x, before.y, before.z), (after.x, after.y, after.z));
+    }
+    // About as much noise as with a thinning that keeps the same share of the points
+    let mut builder = get_test_builder();
+    builder
+        .with_silent_progress()
+        .with_thinning(ThinningMethod::EveryNth { nth: 5 });
+    let every_nth = new_noise(
+        &builder.to_cloud().unwrap(),
+        &builder.with_noise_filter(filter).to_cloud().unwrap(),
+    );
+    let random = new_noise(&original, &classified);
+    assert!(random > every_nth / 2.0, "{random} and {every_nth}");
+}
+
+#[test]
+fn test_invalid_noise_filters() {
+    let invalid = [
+        OutlierMethod::Statistical {
+            k: 0,
+            multiplier: 2.0,
+        },
+        OutlierMethod::Statistical {
+            k: 8,
+            multiplier: f64::NAN,
+        },
+        OutlierMethod::Radius {
+            radius: 0.0,
+            min_neighbours: 2,
+        },
+        OutlierMethod::Isolated {
+            radius: 5.0,
+            height: -1.0,
+        },
+    ];
+    for method in invalid {
+        let mut builder = get_test_builder();
+        builder.with_noise_filter(NoiseFilter::new(method));
+        assert!(builder.validate().is_err(), "{method:?}");
+    }
+}
+
+#[test]
+fn test_near_duplicates() {
+    let point = |x: f64, gps_time: f64| las::Point {
+        x,
+        y: 99.999,
+        z: 1.0,
+        gps_time: Some(gps_time),
+        ..Default::default()
+    };
+    // Pairs of points across the border between tiles, at growing distances
+    let points = [
+        point(99.999, 1.0),
+        point(100.0, 1.0),
+        point(199.999, 1.0),
+        point(200.003, 1.0),
+        point(50.0, 1.0),
+        point(50.0, 2.0),
+        point(150.0, 1.0),
+        point(150.01, 1.0),
+    ];
+    let find = |params: DuplicateParams| {
+        let mut tiles = PointTiles::new(
+            (0.0, 0.0),
+            (250.0, 250.0),
+            TILE_SIZE,
+            params.tolerance,
+            "duplicates",
+        )
+        .unwrap();
+        for (i, point) in points.iter().enumerate() {
+            tiles.add_point((i as u32 % 2, i as u64), point).unwrap();
+        }
+        let cancellation = CancellationToken::new();
+        let duplicates = duplicates::find(tiles, &params, &SilentReporter, &cancellation).unwrap();
+        (0..points.len())
+            .filter(|i| duplicates.is_duplicate((*i as u32 % 2, *i as u64)))
+            .collect::<Vec<_>>()
+    };
+    let params = DuplicateParams::default().with_tolerance(0.005);
+    assert_eq!(find(params), vec![1, 3, 5]);
+    assert_eq!(find(params.with_same_gps_time(true)), vec![1, 3]);
+    assert_eq!(find(DuplicateParams::default()), vec![5]);
+}
+
+#[test]
+fn test_duplicate_chains() {
+    let point = |x: f64| las::Point {
+        x,
+        y: 50.0,
+        z: 1.0,
+        ..Default::default()
+    };
+    // Chains of points each within the tolerance of the next, in a tile and across tiles
+    let chains = [[50.0, 50.8, 51.6], [99.6, 100.4, 101.2]];
+    for reversed in [false, true] {
+        let mut points: Vec<las::Point> = chains.iter().flatten().map(|x| point(*x)).collect();
+        if reversed {
+            points.reverse();
+        }
+        let params = DuplicateParams::default().with_tolerance(1.0);
+        let mut tiles = PointTiles::new(
+            (0.0, 0.0),
+            (199.0, 99.0),
+            TILE_SIZE,
+            params.tolerance,
+            "chains",
+        )
+        .unwrap();
+        for (i, point) in points.iter().enumerate() {
+            tiles.add_point((0, i as u64), point).unwrap();
+        }
+        let cancellation = CancellationToken::new();
+        let duplicates = duplicates::find(tiles, &params, &SilentReporter, &cancellation).unwrap();
+        // Only the middle point of each chain is a duplicate of a point that is kept
+        let found: Vec<usize> = (0..points.len())
+            .filter(|i| duplicates.is_duplicate((0, *i as u64)))
+            .collect();
+        assert_eq!(found, vec![1, 4], "reversed: {reversed}");
+    }
+}
+
+#[test]
+fn test_point_set() {
+    let mut set = PointSet::default();
+    set.extend([(1, 200), (0, 3), (1, 63), (1, 200)]);
+    assert_eq!(set.len(), 3);
+    assert!(set.contains((0, 3)) && set.contains((1, 63)) && set.contains((1, 200)));
+    assert!(!set.contains((0, 200)) && !set.contains((1, 64)) && !set.contains((2, 0)));
+    assert!(!set.contains((0, 1_000_000)));
+}
+
+#[test]
+fn test_duplicates_across_files() {
+    let thinning = || ThinningMethod::EveryNth { nth: 10 };
+    let single = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .to_cloud()
+        .unwrap();
+    let mut builder = PointCloudBuilder::from_files(&["test.las", "test.las"]).unwrap();
+    let metadata = get_test_builder().get_metadata().point_count();
+    assert_eq!(builder.get_metadata().point_count(), 2 * metadata);
+    // The duplicates are removed before the thinning
+    let removed = builder
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .with_duplicate_detection(DuplicateParams::default())
+        .to_cloud()
+        .unwrap();
+    assert_eq!(removed.len(), single.len());
+    let params = DuplicateParams::default().with_action(DuplicateAction::Withhold);
+    let withheld = builder
+        .with_thinning(ThinningMethod::None)
+        .with_duplicate_detection(params)
+        .to_cloud()
+        .unwrap();
+    let withheld_count = withheld.points.iter().filter(|p| p.is_withheld).count();
+    assert_eq!(withheld.len() as u64, 2 * metadata);
+    assert_eq!(withheld_count as u64, metadata);
+}
+
+#[test]
+fn test_invalid_duplicate_detection() {
+    assert!(matches!(
+        PointCloudBuilder::from_files(&[]),
+        Err(LaszyError::InvalidParameter {
+            parameter: "filepaths",
+            ..
+        })
+    ));
+    let mut builder = get_test_builder();
+    builder.with_duplicate_detection(DuplicateParams::default().with_tolerance(-0.1));
+    assert!(builder.validate().is_err());
+}
+
+#[test]
+fn test_local_shape() {
+    let plane: Vec<[f64; 3]> = (0..100)
+        .map(|i| {
+            let (x, y) = ((i % 10) as f64, (i / 10) as f64);
+            [x, y, 0.5 * x + 2.0]
+        })
+        .collect();
+    let shape = LocalShape::new(&plane).unwrap();
+    // The slope stretches the plane in x
+    assert!((shape.planarity() - 1.0 / 1.25).abs() < 1e-9);
+    assert!(shape.roughness() < 1e-6);
+    let normal = shape.eigenvectors[2];
+    assert!((normal[0].abs() - 0.5 / 1.25_f64.sqrt()).abs() < 1e-9);
+    let line: Vec<[f64; 3]> = (0..10).map(|i| [i as f64, 2.0 * i as f64, 0.0]).collect();
+    assert!(LocalShape::new(&line).unwrap().planarity() < 1e-9);
+    assert!(LocalShape::new(&line[..2]).is_none());
+    let (values, _) =
+        geometry::symmetric_eigen([[2.0, 1.0, 0.0], [1.0, 2.0, 0.0], [0.0, 0.0, 5.0]]);
+    for (value, expected) in values.iter().zip([5.0, 3.0, 1.0]) {
+        assert!((value - expected).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_find_buildings_in_roof_and_tree() {
+    use rand::{Rng, SeedableRng};
+    // A sloped roof across the border between tiles, and a tree next to it
+    let mut points: Vec<las::Point> = (0..40 * 40)
+        .map(|i| {
+            let x = 90.0 + (i % 40) as f64 * 0.5;
+            las::Point {
+                x,
+                y: 40.0 + (i / 40) as f64 * 0.5,
+                z: 10.0 - 0.2 * (x - 90.0),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let roof = points.len();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(45);
+    for _ in 0..1000 {
+        points.push(las::Point {
+            x: 120.0 + rng.gen_range(-3.0..3.0),
+            y: 50.0 + rng.gen_range(-3.0..3.0),
+            z: 8.0 + rng.gen_range(-3.0..3.0),
+            ..Default::default()
+        });
+    }
+    let mut tiles = PointTiles::new(
+        (0.0, 0.0),
+        (199.0, 99.0),
+        TILE_SIZE,
+        OBJECT_TILE_BUFFER,
+        "objects",
+    )
+    .unwrap();
+    assert_eq!(tiles.tile_count(), 2);
+    for (i, point) in points.iter().enumerate() {
+        tiles.add_point((0, i as u64), point).unwrap();
+    }
+    let cancellation = CancellationToken::new();
+    let params = ObjectParams::default();
+    let buildings =
+        objects::find_buildings(tiles, &params, &SilentReporter, &cancellation).unwrap();
+    // Only the edges of the roof are linear rather than planar
+    let is_interior = |i: usize| (2..38).contains(&(i % 40)) && (2..38).contains(&(i / 40));
+    for i in (0..roof).filter(|i| is_interior(*i)) {
+        assert!(buildings.contains((0, i as u64)), "{i}");
+    }
+    assert!((roof..points.len()).all(|i| !buildings.contains((0, i as u64))));
+}
+
+#[test]
+fn test_object_reclassification() {
+    use las::point::Classification;
+    let original = get_test_builder()
+        .with_silent_progress()
+        .to_cloud()
+        .unwrap();
+    // With the heights above ground as z, to check the height bands
+    let hag = HagParams::default().with_replace_z(true);
+    let params = ObjectParams::default();
+    let reclassified = get_test_builder()
+        .with_silent_progress()
+        .with_object_reclassification(params.clone())
+        .with_height_above_ground(hag)
+        .to_cloud()
+        .unwrap();
+    assert_eq!(reclassified.len(), original.len());
+    let (mut true_buildings, mut buildings) = (0, 0);
+    for (before, after) in original.points.iter().zip(&reclassified.points) {
+        let height = after.z;
+        let expected = match before.classification {
+            Classification::Ground => Some(Classification::Ground),
+            _ if height < params.min_height => Some(before.classification),
+            _ if after.classification == Classification::Building => None,
+            _ if height < params.medium_vegetation_height => Some(Classification::LowVegetation),
+            _ if height < params.high_vegetation_height => Some(Classification::MediumVegetation),
+            _ => Some(Classification::HighVegetation),
+        };
+        match expected {
+            Some(class) => assert_eq!(after.classification, class, "{height}"),
+            None => assert!(height >= params.building_min_height, "{height}"),
+        }
+        if after.classification == Classification::Building {
+            buildings += 1;
+            if before.classification == Classification::Building {
+                true_buildings += 1;
+            }
+        }
+    }
+    // The buildings of the file were classified with other software and parameters
+    let file_buildings = original
+        .points
+        .iter()
+        .filter(|point| point.classification == Classification::Building)
+        .count();
+    assert!(
+        true_buildings > buildings / 2,
+        "{true_buildings} of {buildings}"
+    );
+    assert!(
+        true_buildings > file_buildings / 2,
+        "{true_buildings} of {file_buildings}"
+    );
+}
+
+#[test]
+fn test_object_reclassification_with_random_thinning() {
+    use las::point::Classification;
+    // Share of the building points of the file that are found as buildings
+    let found_buildings = |thinning: ThinningMethod| {
+        let mut builder = get_test_builder();
+        builder.with_silent_progress().with_thinning(thinning);
+        let original = builder.to_cloud().unwrap();
+        let reclassified = builder
+            .with_object_reclassification(ObjectParams::default())
+            .to_cloud()
+            .unwrap();
+        assert_eq!(reclassified.len(), original.len());
+        let is_building = |point: &las::Point| point.classification == Classification::Building;
+        let file_buildings = original.points.iter().filter(|p| is_building(p)).count();
+        let true_buildings = original
+            .points
+            .iter()
+            .zip(&reclassified.points)
+            .filter(|(before, after)| is_building(before) && is_building(after))
+            .count();
+        true_buildings as f64 / file_buildings as f64
+    };
+    // The buildings are found in the points the thinning keeps, which are the same in every output
+    let random = found_buildings(ThinningMethod::Random { percent: 0.5 });
+    let every_nth = found_buildings(ThinningMethod::EveryNth { nth: 2 });
+    assert!(random > 0.8 * every_nth, "{random} and {every_nth}");
+}
+
+#[test]
+fn test_invalid_object_reclassification() {
+    let invalid = [
+        ObjectParams::default().with_k(1),
+        ObjectParams::default().with_min_planarity(1.5),
+        ObjectParams::default().with_max_roughness(-0.1),
+        ObjectParams::default().with_high_vegetation_height(1.0),
+        ObjectParams::default().with_min_height(f64::NAN),
+    ];
+    for params in invalid {
+        assert!(params.validate().is_err(), "{params:?}");
+    }
+    let mut builder = get_test_builder();
+    builder
+        .with_object_reclassification(ObjectParams::default().with_surface(GroundSurface::Cloth));
+    assert!(builder.validate().is_err());
+    builder.with_csf_ground_reclassification(CsfParams::default());
+    assert!(builder.validate().is_ok());
+}
+
+#[test]
+fn test_geometric_features_of_shapes() {
+    let wall: Vec<[f64; 3]> = (0..100)
+        .map(|i| [(i % 10) as f64, 5.0, (i / 10) as f64])
+        .collect();
+    let shape = LocalShape::new(&wall).unwrap();
+    assert!((shape.normal()[1].abs() - 1.0).abs() < 1e-9);
+    assert!((shape.verticality() - 1.0).abs() < 1e-9);
+    assert!(shape.curvature() < 1e-9);
+    let line: Vec<[f64; 3]> = (0..10).map(|i| [i as f64, i as f64, i as f64]).collect();
+    let shape = LocalShape::new(&line).unwrap();
+    assert!((shape.linearity() - 1.0).abs() < 1e-9);
+    assert!(shape.sphericity() < 1e-9);
+    let cube: Vec<[f64; 3]> = (0..27)
+        .map(|i| [(i % 3) as f64, (i / 3 % 3) as f64, (i / 9) as f64])
+        .collect();
+    let shape = LocalShape::new(&cube).unwrap();
+    assert!((shape.sphericity() - 1.0).abs() < 1e-9);
+    assert!((shape.curvature() - 1.0 / 3.0).abs() < 1e-9);
+    // Normals point up
+    let roof: Vec<[f64; 3]> = (0..100)
+        .map(|i| {
+            let (x, y) = ((i % 10) as f64, (i / 10) as f64);
+            [x, y, 10.0 - x]
+        })
+        .collect();
+    let normal = LocalShape::new(&roof).unwrap().normal();
+    let expected = [0.5_f64.sqrt(), 0.0, 0.5_f64.sqrt()];
+    for (value, expected) in normal.iter().zip(expected) {
+        assert!((value - expected).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_features_in_tiles() {
+    // A plane across the border between tiles
+    let points: Vec<las::Point> = (0..60 * 20)
+        .map(|i| las::Point {
+            x: 80.0 + (i % 60) as f64 * 0.5,
+            y: 10.0 + (i / 60) as f64 * 0.5,
+            z: 1.0,
+            ..Default::default()
+        })
+        .collect();
+    for neighbourhood in [
+        Neighbourhood::Knn { k: 8 },
+        Neighbourhood::Radius { radius: 1.0 },
+    ] {
+        let buffer = neighbourhood.buffer();
+        let mut tiles =
+            PointTiles::new((0.0, 0.0), (199.0, 99.0), TILE_SIZE, buffer, "features").unwrap();
+        assert_eq!(tiles.tile_count(), 2);
+        // Every other point, so the points without features are read as well
+        for (i, point) in points.iter().enumerate().step_by(2) {
+            tiles.add_point((0, i as u64), point).unwrap();
+        }
+        let cancellation = CancellationToken::new();
+        let store = features::compute(tiles, neighbourhood, &SilentReporter, &cancellation);
+        let mut reader = store.unwrap().reader();
+        for i in 0..points.len() + 10 {
+            let values = reader.read((0, i as u64)).unwrap();
+            if i % 2 == 1 || i >= points.len() {
+                assert!(values.iter().all(|value| value.is_nan()), "{i}");
+                continue;
+            }
+            assert!((values[2] - 1.0).abs() < 1e-9, "{i}: {values:?}");
+            assert!(values[3].abs() < 1e-9, "{i}: {values:?}");
+        }
+        assert!(reader.read((1, 0)).unwrap()[0].is_nan());
+    }
+}
+
+#[test]
+fn test_geometric_features_extra_bytes() {
+    use las::Read;
+
+    let thinning = || ThinningMethod::EveryNth { nth: 10 };
+    let hag = HagParams::default();
+    let neighbourhood = Neighbourhood::Knn { k: 8 };
+    let cloud = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .with_height_above_ground(hag.clone())
+        .with_geometric_features(neighbourhood)
+        .to_cloud()
+        .unwrap();
+    let normals = cloud.normals().unwrap();
+    let verticality = cloud.feature(GeometricFeature::Verticality).unwrap();
+    assert_eq!(normals.len(), cloud.len());
+    for (normal, verticality) in normals.iter().zip(&verticality) {
+        let length = normal.iter().map(|value| value * value).sum::<f64>().sqrt();
+        assert!((length - 1.0).abs() < 1e-6);
+        assert!(normal[2] >= 0.0);
+        assert!((1.0 - normal[2] - verticality).abs() < 1e-9);
+    }
+    assert!(get_test_builder().to_cloud().unwrap().normals().is_none());
+
+    let path = temp_raster_path("features.las");
+    get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .with_height_above_ground(hag)
+        .with_geometric_features(neighbourhood)
+        .to_file(&path)
+        .unwrap();
+    let mut reader = las::Reader::from_path(&path).unwrap();
+    assert_eq!(reader.header().point_format().extra_bytes, 9 * 8);
+    let vlr = reader
+        .header()
+        .vlrs()
+        .iter()
+        .find(|vlr| vlr.user_id == "LASF_Spec" && vlr.record_id == 4)
+        .unwrap()
+        .clone();
+    assert_eq!(vlr.data.len(), 9 * 192);
+    assert_eq!(&vlr.data[192 + 4..192 + 11], b"NormalX");
+    let points: Vec<las::Point> = reader.points().map(|point| point.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(points.len(), cloud.len());
+    for (point, normal) in points.iter().zip(&normals) {
+        let value =
+            |i: usize| f64::from_le_bytes(point.extra_bytes[i * 8..i * 8 + 8].try_into().unwrap());
+        assert_eq!([value(1), value(2), value(3)], *normal);
+    }
+}
+
+#[test]
+fn test_geometric_features_with_random_thinning() {
+    // The features are computed for the points the thinning keeps, which are the same in every
+    // pass, so all written points have them
+    let cloud = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(ThinningMethod::Random { percent: 0.2 })
+        .with_geometric_features(Neighbourhood::Knn { k: 8 })
+        .to_cloud()
+        .unwrap();
+    let normals = cloud.normals().unwrap();
+    assert!(normals.iter().flatten().all(|value| value.is_finite()));
+}
+
+#[test]
+fn test_invalid_geometric_features() {
+    for neighbourhood in [
+        Neighbourhood::Knn { k: 2 },
+        Neighbourhood::Radius { radius: 0.0 },
+        Neighbourhood::Radius { radius: f64::NAN },
+    ] {
+        let mut builder = get_test_builder();
+        builder.with_geometric_features(neighbourhood);
+        assert!(builder.validate().is_err(), "{neighbourhood:?}");
+    }
+}
+
+/// Descriptor of an extra bytes field in the extra bytes VLR, with a no data value and a scale.
+fn extra_bytes_descriptor(
+    data_type: u8,
+    name: &str,
+    no_data: Option<u64>,
+    scale: Option<f64>,
+) -> Vec<u8> {
+    let mut descriptor = vec![0; 192];
+    descriptor[2] = data_type;
+    descriptor[4..4 + name.len()].copy_from_slice(name.as_bytes());
+    if let Some(no_data) = no_data {
+        descriptor[3] |= 1;
+        descriptor[40..48].copy_from_slice(&no_data.to_le_bytes());
+    }
+    if let Some(scale) = scale {
+        descriptor[3] |= 1 << 3;
+        descriptor[112..120].copy_from_slice(&scale.to_le_bytes());
+    }
+    descriptor
+}
+
+/// Write every 10th point of the test file to `path` with 5 extra bytes described by the
+/// descriptors in `data`: a reflectance, a deviation and 2 bytes of 7. Returns the reflectance
+/// with a scale of 0.01 and the deviation of the points.
+fn write_extra_bytes_file(path: &str, data: Vec<u8>) -> Vec<(f64, u8)> {
+    use las::{Read, Write};
+
+    let mut builder = las::Builder::default();
+    builder.point_format.extra_bytes = 5;
+    if !data.is_empty() {
+        builder.vlrs.push(las::Vlr {
+            user_id: "LASF_Spec".to_string(),
+            record_id: 4,
+            description: "Extra bytes".to_string(),
+            data,
+        });
+    }
+    let mut writer = las::Writer::from_path(path, builder.into_header().unwrap()).unwrap();
+    let mut reader = las::Reader::from_path("test.las").unwrap();
+    let mut expected = Vec::new();
+    for (i, point) in reader.points().step_by(10).enumerate() {
+        let mut point = point.unwrap();
+        let reflectance = (i % 2000) as i16 - 1000;
+        let deviation = (i % 256) as u8;
+        point.extra_bytes = reflectance.to_le_bytes().to_vec();
+        point.extra_bytes.extend([deviation, 7, 7]);
+        writer.write(point).unwrap();
+        expected.push((reflectance as f64 * 0.01, deviation));
+    }
+    writer.close().unwrap();
+    expected
+}
+
+#[test]
+fn test_extra_bytes_survive_processing() {
+    // A file with a scaled reflectance, a deviation with a no data value and 2 undescribed bytes
+    let path = temp_raster_path("extra_bytes.las");
+    let mut data = extra_bytes_descriptor(4, "Reflectance", None, Some(0.01));
+    data.extend(extra_bytes_descriptor(1, "Deviation", Some(255), None));
+    let expected = write_extra_bytes_file(&path, data);
+    let cloud = PointCloudBuilder::from_file(&path)
+        .unwrap()
+        .to_cloud()
+        .unwrap();
+    let names = ["Reflectance", "Deviation", "undocumented 1"];
+    assert_eq!(cloud.extra_bytes_names(), names);
+    let reflectance = cloud.extra_bytes("Reflectance").unwrap();
+    let deviation = cloud.extra_bytes("Deviation").unwrap();
+    for (i, (expected_reflectance, expected_deviation)) in expected.iter().enumerate() {
+        assert!((reflectance[i] - expected_reflectance).abs() < 1e-9);
+        if *expected_deviation == 255 {
+            assert!(deviation[i].is_nan());
+        } else {
+            assert_eq!(deviation[i], *expected_deviation as f64);
+        }
+    }
+    assert!(cloud.extra_bytes("undocumented 1").is_none());
+    assert!(cloud.extra_bytes("Intensity").is_none());
+
+    // The fields of the input come before the height above ground in the output
+    let output = temp_raster_path("extra_bytes_hag.las");
+    PointCloudBuilder::from_file(&path)
+        .unwrap()
+        .with_silent_progress()
+        .with_height_above_ground(HagParams::default())
+        .to_file(&output)
+        .unwrap();
+    let mut builder = PointCloudBuilder::from_file(&output).unwrap();
+    let processed = builder.to_cloud().unwrap();
+    std::fs::remove_file(&output).unwrap();
+    let mut names = names.to_vec();
+    names.push("HeightAboveGround");
+    assert_eq!(processed.extra_bytes_names(), names);
+    assert_eq!(processed.extra_bytes("Reflectance").unwrap(), reflectance);
+    let heights = processed.extra_bytes("HeightAboveGround").unwrap();
+    assert!(heights.iter().any(|height| height.is_finite()));
+    assert!(processed
+        .points
+        .iter()
+        .all(|p| p.extra_bytes[3..5] == [7, 7]));
+
+    // Files with different extra bytes can't be read together
+    let undescribed = temp_raster_path("extra_bytes_undescribed.las");
+    write_extra_bytes_file(&undescribed, Vec::new());
+    let result = PointCloudBuilder::from_files(&[&path, &undescribed]);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&undescribed).unwrap();
+    assert!(matches!(
+        result,
+        Err(LaszyError::InvalidParameter {
+            parameter: "filepaths",
+            ..
+        })
+    ));
+}
+
+/// The points of the LAS/LAZ file at `path`, with its point format and LAS version.
+fn read_las(path: &str) -> (las::point::Format, las::Version, Vec<las::Point>) {
+    use las::Read;
+
+    let mut reader = las::Reader::from_path(path).unwrap();
+    let header = reader.header().clone();
+    let points = reader.points().map(|point| point.unwrap()).collect();
+    (*header.point_format(), header.version(), points)
+}
+
+#[test]
+fn test_point_format_conversion() {
+    let thinning = || ThinningMethod::EveryNth { nth: 10 };
+    let original = get_test_builder()
+        .with_thinning(thinning())
+        .to_cloud()
+        .unwrap();
+
+    // Upgrading gives the points a GPS time and colour, the version is raised to support them
+    let upgraded = temp_raster_path("format_7.las");
+    get_test_builder()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .with_height_above_ground(HagParams::default())
+        .with_las_options(LasOptions::default().with_point_format(7))
+        .to_file(&upgraded)
+        .unwrap();
+    let (format, version, points) = read_las(&upgraded);
+    assert_eq!(format.to_u8().unwrap(), 7);
+    assert_eq!(format.extra_bytes, 8);
+    assert_eq!(version, las::Version::new(1, 4));
+    assert_eq!(points.len(), original.len());
+    for (point, original) in points.iter().zip(&original.points) {
+        assert_eq!((point.x, point.y), (original.x, original.y));
+        assert_eq!(point.classification, original.classification);
+        // las reads a GPS time of 0.0 as None
+        assert_eq!(point.gps_time, None);
+        assert_eq!(point.color, Some(las::Color::new(0, 0, 0)));
+    }
+
+    // Those points can be written again with the same format
+    let copy = temp_raster_path("format_7_copy.las");
+    PointCloudBuilder::from_file(&upgraded)
+        .unwrap()
+        .to_file(&copy)
+        .unwrap();
+    let (format, _, points) = read_las(&copy);
+    std::fs::remove_file(&copy).unwrap();
+    assert_eq!(format.to_u8().unwrap(), 7);
+    assert_eq!(points.len(), original.len());
+
+    // Downgrading drops them again, in the version that is asked for
+    let downgraded = temp_raster_path("format_0.las");
+    PointCloudBuilder::from_file(&upgraded)
+        .unwrap()
+        .with_las_options(
+            LasOptions::default()
+                .with_point_format(0)
+                .with_version(1, 2),
+        )
+        .to_file(&downgraded)
+        .unwrap();
+    std::fs::remove_file(&upgraded).unwrap();
+    let (format, version, points) = read_las(&downgraded);
+    std::fs::remove_file(&downgraded).unwrap();
+    assert_eq!(format.to_u8().unwrap(), 0);
+    assert_eq!(format.extra_bytes, 8);
+    assert_eq!(version, las::Version::new(1, 2));
+    assert!(points
+        .iter()
+        .all(|p| p.gps_time.is_none() && p.color.is_none()));
+}
+
+#[test]
+fn test_point_format_conversion_clamps_extended_values() {
+    use las::Write;
+
+    let path = temp_raster_path("format_6.las");
+    let mut builder = las::Builder::from((1, 4));
+    builder.point_format = las::point::Format::new(6).unwrap();
+    let mut writer = las::Writer::from_path(&path, builder.into_header().unwrap()).unwrap();
+    let points = [(9, 10, 40, 2), (2, 3, 6, 1)];
+    for (return_number, number_of_returns, class, scanner_channel) in points {
+        writer
+            .write(las::Point {
+                return_number,
+                number_of_returns,
+                classification: las::point::Classification::new(class).unwrap(),
+                scanner_channel,
+                gps_time: Some(12.5),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    writer.close().unwrap();
+
+    let output = temp_raster_path("format_1.las");
+    PointCloudBuilder::from_file(&path)
+        .unwrap()
+        .with_las_options(LasOptions::default().with_point_format(1))
+        .to_file(&output)
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let (format, version, points) = read_las(&output);
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(format.to_u8().unwrap(), 1);
+    // The version of the input is kept
+    assert_eq!(version, las::Version::new(1, 4));
+    let fields: Vec<_> = points
+        .iter()
+        .map(|p| {
+            let class = u8::from(p.classification);
+            (
+                p.return_number,
+                p.number_of_returns,
+                class,
+                p.scanner_channel,
+            )
+        })
+        .collect();
+    assert_eq!(fields, [(7, 7, 1, 0), (2, 3, 6, 0)]);
+    assert!(points.iter().all(|p| p.gps_time == Some(12.5)));
+}
+
+#[test]
+fn test_invalid_las_options() {
+    for (options, parameter) in [
+        (LasOptions::default().with_point_format(11), "point_format"),
+        (LasOptions::default().with_version(2, 0), "version"),
+        (LasOptions::default().with_version(1, 5), "version"),
+        (
+            LasOptions::default()
+                .with_point_format(6)
+                .with_version(1, 3),
+            "version",
+        ),
+    ] {
+        let mut builder = get_test_builder();
+        builder.with_las_options(options);
+        assert!(matches!(
+            builder.validate(),
+            Err(LaszyError::InvalidParameter { parameter: p, .. }) if p == parameter
+        ));
+    }
+}
+
+/// Left and top of the orthophotos of the tests, which cover the western part of the test file
+/// with cells of 1 m.
+const ORTHOPHOTO_CORNER: (f64, f64) = (183_530.0, 332_440.0);
+const ORTHOPHOTO_SIZE: (u32, u32) = (30, 70);
+
+/// Write an RGB orthophoto with 8 or 16 bit bands to `path`, in strips of 4 rows. The bands of a
+/// cell are its column, its row and 200, except in column 10, which is NODATA.
+fn write_orthophoto(path: &str, sixteen_bit: bool) {
+    use tiff::encoder::{colortype, TiffEncoder};
+    use tiff::tags::Tag;
+
+    let (columns, rows) = ORTHOPHOTO_SIZE;
+    let mut values = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            match column {
+                10 => values.extend([7, 7, 7]),
+                _ => values.extend([column as u16, row as u16, 200]),
+            }
+        }
+    }
+    let mut encoder = TiffEncoder::new(std::fs::File::create(path).unwrap()).unwrap();
+    let (left, top) = ORTHOPHOTO_CORNER;
+    macro_rules! write_image {
+        ($colortype:ty, $values:expr) => {{
+            let mut image = encoder.new_image::<$colortype>(columns, rows).unwrap();
+            let tags = image.encoder();
+            tags.write_tag(Tag::ModelPixelScaleTag, &[1.0, 1.0, 0.0][..])
+                .unwrap();
+            tags.write_tag(Tag::ModelTiepointTag, &[0.0, 0.0, 0.0, left, top, 0.0][..])
+                .unwrap();
+            tags.write_tag(Tag::GdalNodata, "7").unwrap();
+            image.rows_per_strip(4).unwrap();
+            image.write_data(&$values).unwrap();
+        }};
+    }
+    if sixteen_bit {
+        write_image!(colortype::RGB16, values);
+    } else {
+        let values: Vec<u8> = values.iter().map(|value| *value as u8).collect();
+        write_image!(colortype::RGB8, values);
+    }
+}
+
+/// The colour of a point at `x`, `y` from an orthophoto of `write_orthophoto` with `scale`, or
+/// None when it is outside the image or in the NODATA column.
+fn orthophoto_colour(x: f64, y: f64, scale: u16) -> Option<las::Color> {
+    let column = (x - ORTHOPHOTO_CORNER.0).floor() as u16;
+    // Points on the edge between two rows are in the northern row
+    let bottom = ORTHOPHOTO_CORNER.1 - ORTHOPHOTO_SIZE.1 as f64;
+    let row = ORTHOPHOTO_SIZE.1 as u16 - 1 - (y - bottom).floor() as u16;
+    (column < ORTHOPHOTO_SIZE.0 as u16 && column != 10)
+        .then(|| las::Color::new(column * scale, row * scale, 200 * scale))
+}
+
+#[test]
+fn test_colourisation() {
+    let orthophoto = temp_raster_path("orthophoto.tif");
+    write_orthophoto(&orthophoto, false);
+    let params = ColourParams::new(&orthophoto);
+    let cloud = get_test_builder()
+        .with_silent_progress()
+        .with_colourisation(params.clone())
+        .to_cloud()
+        .unwrap();
+    let black = las::Color::new(0, 0, 0);
+    let mut coloured = 0;
+    for point in &cloud.points {
+        let expected = orthophoto_colour(point.x, point.y, 257);
+        coloured += expected.is_some() as usize;
+        assert_eq!(point.color, Some(expected.unwrap_or(black)));
+    }
+    assert!(coloured > 1000 && coloured < cloud.len() - 1000);
+
+    // Only the cells in the cropped area are read
+    let cropped = get_test_builder()
+        .with_silent_progress()
+        .with_crop(CroppingMethod::BoundingBox {
+            lower_left: (183_545.0, 332_400.0),
+            upper_right: (183_570.0, 332_420.0),
+        })
+        .with_colourisation(params.clone())
+        .to_cloud()
+        .unwrap();
+    assert!(!cropped.is_empty());
+    for point in &cropped.points {
+        let expected = orthophoto_colour(point.x, point.y, 257);
+        assert_eq!(point.color, Some(expected.unwrap_or(black)));
+    }
+
+    // Point format 0 is upgraded to 2, which LAS 1.2 supports
+    let path = temp_raster_path("coloured.las");
+    get_test_builder()
+        .with_silent_progress()
+        .with_thinning(ThinningMethod::EveryNth { nth: 10 })
+        .with_colourisation(params)
+        .to_file(&path)
+        .unwrap();
+    std::fs::remove_file(&orthophoto).unwrap();
+    let (format, version, points) = read_las(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(format.to_u8().unwrap(), 2);
+    assert_eq!(version, las::Version::new(1, 2));
+    for point in &points {
+        let expected = orthophoto_colour(point.x, point.y, 257);
+        assert_eq!(point.color, Some(expected.unwrap_or(black)));
+    }
+}
+
+#[test]
+fn test_colourisation_with_16_bit_bands() {
+    let orthophoto = temp_raster_path("orthophoto_16.tif");
+    write_orthophoto(&orthophoto, true);
+    let cloud = get_test_builder()
+        .with_silent_progress()
+        .with_thinning(ThinningMethod::EveryNth { nth: 10 })
+        .with_colourisation(ColourParams::new(&orthophoto).with_bands(3, 2, 1))
+        .to_cloud()
+        .unwrap();
+    std::fs::remove_file(&orthophoto).unwrap();
+    for point in &cloud.points {
+        let expected = orthophoto_colour(point.x, point.y, 1)
+            .map(|colour| las::Color::new(colour.blue, colour.green, colour.red));
+        assert_eq!(
+            point.color,
+            Some(expected.unwrap_or(las::Color::new(0, 0, 0)))
+        );
+    }
+}
+
+#[test]
+fn test_invalid_colourisation() {
+    let mut builder = get_test_builder();
+    builder.with_colourisation(ColourParams::new("orthophoto.asc"));
+    assert!(matches!(
+        builder.validate(),
+        Err(LaszyError::InvalidFileExtension(_))
+    ));
+    builder.with_colourisation(ColourParams::new("orthophoto.tif").with_bands(0, 1, 2));
+    assert!(builder.validate().is_err());
+    builder
+        .with_colourisation(ColourParams::new("orthophoto.tif"))
+        .with_las_options(LasOptions::default().with_point_format(1));
+    assert!(matches!(
+        builder.validate(),
+        Err(LaszyError::InvalidParameter {
+            parameter: "point_format",
+            ..
+        })
+    ));
+
+    let orthophoto = temp_raster_path("orthophoto_bands.tif");
+    write_orthophoto(&orthophoto, false);
+    let result = get_test_builder()
+        .with_silent_progress()
+        .with_colourisation(ColourParams::new(&orthophoto).with_bands(1, 2, 4))
+        .to_cloud();
+    std::fs::remove_file(&orthophoto).unwrap();
+    assert!(matches!(
+        result,
+        Err(LaszyError::InvalidParameter {
+            parameter: "bands",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_read_geotiff_with_several_bands() {
+    let orthophoto = temp_raster_path("orthophoto_read.tif");
+    write_orthophoto(&orthophoto, false);
+    let result = raster::read_raster(&orthophoto);
+    std::fs::remove_file(&orthophoto).unwrap();
+    let Err(e) = result else {
+        panic!("a GeoTIFF with three bands was read as one band");
+    };
+    let message = e.to_string();
+    assert!(
+        message.contains("6300 samples, expected 2100 for 70 rows of 30 cells"),
+        "{message}"
+    );
+}
+
+/// The files in the `directory`, sorted by name.
+fn files_in(directory: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<_> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_to_tiles() {
+    let directory = std::env::temp_dir().join(format!("laszy-{}-tiles", std::process::id()));
+    let thinning = || ThinningMethod::EveryNth { nth: 10 };
+    let cloud = PointCloudBuilder::from_files(&["test.las", "test.las"])
+        .unwrap()
+        .with_silent_progress()
+        .with_thinning(thinning())
+        .to_cloud()
+        .unwrap();
+    // Tiles of 20 m aligned to 5 m, with fewer open files than tiles so tiles are written in parts
+    let params = TileParams::new(20.0)
+        .with_origin(5.0, 5.0)
+        .with_name("{minx}_{maxy}.las")
+        .with_max_open_files(8);
+    let mut builder = PointCloudBuilder::from_files(&["test.las", "test.las"]).unwrap();
+    builder.with_silent_progress().with_thinning(thinning());
+    let paths = builder
+        .to_tiles(&directory.to_string_lossy(), params.clone())
+        .unwrap();
+    assert_eq!(paths.len(), 12);
+    assert_eq!(files_in(&directory).len(), 12);
+    let mut count = 0;
+    for path in &paths {
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .unwrap()
+            .to_string_lossy();
+        let (min_x, max_y) = name.split_once('_').unwrap();
+        let (min_x, max_y): (f64, f64) = (min_x.parse().unwrap(), max_y.parse().unwrap());
+        assert_eq!((min_x - 5.0) % 20.0, 0.0);
+        let in_tile = |point: &&las::Point| {
+            (min_x..min_x + 20.0).contains(&point.x) && (max_y - 20.0..max_y).contains(&point.y)
+        };
+        let (_, _, points) = read_las(path);
+        // The points keep their order, also when the tile was written in several parts
+        let expected: Vec<_> = cloud.points.iter().filter(in_tile).collect();
+        assert_eq!(points.len(), expected.len());
+        for (point, expected) in points.iter().zip(expected) {
+            assert_eq!(
+                (point.x, point.y, point.z),
+                (expected.x, expected.y, expected.z)
+            );
+        }
+        count += points.len();
+    }
+    assert_eq!(count, cloud.len());
+
+    let params = params.with_buffer(2.0).with_name("{minx}_{maxy}.laz");
+    let paths = builder
+        .to_tiles(&directory.to_string_lossy(), params)
+        .unwrap();
+    assert_eq!(files_in(&directory).len(), 24);
+    let mut count = 0;
+    for path in &paths {
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .unwrap()
+            .to_string_lossy();
+        let (min_x, max_y) = name.split_once('_').unwrap();
+        let (min_x, max_y): (f64, f64) = (min_x.parse().unwrap(), max_y.parse().unwrap());
+        let (format, _, points) = read_las(path);
+        assert!(format.is_compressed);
+        for point in &points {
+            assert!((min_x - 2.0..min_x + 22.0).contains(&point.x));
+            assert!((max_y - 22.0..max_y + 2.0).contains(&point.y));
+        }
+        count += points.len();
+    }
+    assert!(count > cloud.len());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Cancels the run when it first reports progress on the points.
+struct CancelOnAdvance(CancellationToken);
+
+impl ProgressReporter for CancelOnAdvance {
+    fn start(&self, _task: &str, _total: Option<u64>) {}
+
+    fn advance(&self, _delta: u64) {
+        self.0.cancel();
+    }
+
+    fn finish(&self, _message: &str) {}
+}
+
+#[test]
+fn test_to_tiles_overwrite_policy_and_cancellation() {
+    let directory = std::env::temp_dir().join(format!("laszy-{}-tiles-policy", std::process::id()));
+    let directory_path = directory.to_string_lossy().into_owned();
+    let params = TileParams::new(50.0).with_name("{minx}_{miny}.las");
+    let mut builder = get_test_builder();
+    builder
+        .with_silent_progress()
+        .with_thinning(ThinningMethod::EveryNth { nth: 100 });
+    let paths = builder.to_tiles(&directory_path, params.clone()).unwrap();
+    assert_eq!(
+        files_in(&directory),
+        vec![
+            "183500_332350.las",
+            "183500_332400.las",
+            "183550_332350.las",
+            "183550_332400.las"
+        ]
+    );
+
+    let re = builder
+        .with_overwrite_policy(OverwritePolicy::Error)
+        .to_tiles(&directory_path, params.clone());
+    assert!(matches!(re, Err(LaszyError::OutputExists(_))));
+    std::fs::remove_file(&paths[0]).unwrap();
+    let skipped = builder
+        .with_overwrite_policy(OverwritePolicy::Skip)
+        .to_tiles(&directory_path, params.clone())
+        .unwrap();
+    assert_eq!(skipped, vec![paths[0].clone()]);
+
+    // A run cancelled halfway leaves neither tiles nor parts of tiles behind
+    let token = builder.cancellation_token();
+    let re = builder
+        .with_progress_reporter(Box::new(CancelOnAdvance(token)))
+        .to_tiles(
+            &directory_path,
+            params.with_name("cancelled_{minx}_{miny}.las"),
+        );
+    assert!(matches!(re, Err(LaszyError::Cancelled)));
+    assert_eq!(files_in(&directory).len(), 4);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_invalid_tile_params() {
+    assert!(TileParams::new(0.0).validate().is_err());
+    assert!(TileParams::new(1000.0)
+        .with_buffer(-1.0)
+        .validate()
+        .is_err());
+    assert!(TileParams::new(1000.0)
+        .with_origin(f64::NAN, 0.0)
+        .validate()
+        .is_err());
+    assert!(TileParams::new(1000.0)
+        .with_max_open_files(0)
+        .validate()
+        .is_err());
+    assert!(matches!(
+        TileParams::new(1000.0)
+            .with_name("{minx}_{miny}.txt")
+            .validate(),
+        Err(LaszyError::InvalidFileExtension(_))
+    ));
+    for name in ["{minx}.laz", "{minx}_{y}.laz", "tile_{minx}_{miny.laz"] {
+        assert!(matches!(
+            TileParams::new(1000.0).with_name(name).validate(),
+            Err(LaszyError::InvalidParameter {
+                parameter: "name",
+                ..
+            })
+        ));
+    }
+    assert!(TileParams::new(1000.0)
+        .with_name("tiles_{maxx}-{maxy}.las")
+        .validate()
+        .is_ok());
+}
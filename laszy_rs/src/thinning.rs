@@ -1,4 +1,5 @@
-use rand::Rng;
+use crate::tiles::PointId;
+use crate::LaszyError;
 
 #[derive(Default)]
 pub enum ThinningMethod {
@@ -24,30 +25,66 @@ pub enum ThinningMethod {
 }
 
 impl ThinningMethod {
-    pub fn is_included(&self, i: usize) -> bool {
+    /// Whether the point with `id`, the `i`th point that can be thinned, is kept. The random
+    /// thinning draws from `seed` and `id`, so every pass over the points keeps the same points.
+    /// The method must be validated first.
+    pub(crate) fn is_included(&self, id: PointId, i: usize, seed: u64) -> bool {
         match self {
             ThinningMethod::None => true,
+            ThinningMethod::Random { percent } => random_fraction(id, seed) < *percent,
+            ThinningMethod::EveryNth { nth } => i.is_multiple_of(*nth),
+            _ => true,
+        }
+    }
+
+    /// Check that the parameters of the method are valid and that the method is implemented.
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        match self {
+            ThinningMethod::None => Ok(()),
             ThinningMethod::Random { percent } => {
-                let mut rng = rand::thread_rng();
-                rng.gen_bool(*percent)
-            }
-            ThinningMethod::EveryNth { nth } => i % nth == 0,
-            ThinningMethod::EveryNthRandom { nth } => {
-                let mut rng = rand::thread_rng();
-                panic!("Not implemented");
+                if !(0.0..=1.0).contains(percent) {
+                    return Err(LaszyError::invalid_parameter(
+                        "Random { percent }",
+                        percent,
+                        "must be between 0.0 and 1.0",
+                    ));
+                }
+                Ok(())
             }
-            ThinningMethod::Grid2D {
-                cell_amount,
-                max_points_per_cell,
-            } => {
-                panic!("Not implemented");
-            }
-            ThinningMethod::Grid3D {
-                cell_amount,
-                max_points_per_cell,
-            } => {
-                panic!("Not implemented");
+            ThinningMethod::EveryNth { nth } => {
+                if *nth == 0 {
+                    return Err(LaszyError::invalid_parameter(
+                        "EveryNth { nth }",
+                        nth,
+                        "must be at least 1",
+                    ));
+                }
+                Ok(())
             }
+            ThinningMethod::EveryNthRandom { .. } => Err(LaszyError::NotImplemented(
+                "ThinningMethod::EveryNthRandom".to_string(),
+            )),
+            ThinningMethod::Grid2D { .. } => Err(LaszyError::NotImplemented(
+                "ThinningMethod::Grid2D".to_string(),
+            )),
+            ThinningMethod::Grid3D { .. } => Err(LaszyError::NotImplemented(
+                "ThinningMethod::Grid3D".to_string(),
+            )),
         }
     }
 }
+
+/// A fraction in [0, 1) that looks random, drawn from the `seed` and the `id` of a point.
+fn random_fraction((file_index, i): PointId, seed: u64) -> f64 {
+    let hash = mix(mix(seed ^ u64::from(file_index)) ^ i);
+    // The 53 highest bits fill the mantissa of a float
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+/// The finalizer of SplitMix64, which spreads every bit of `value` over the whole hash.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
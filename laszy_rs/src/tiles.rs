@@ -0,0 +1,279 @@
+//! Points sorted into square tiles with a buffer around them, kept in temporary files, to process
+//! the neighbourhoods of the points of a large extent with bounded memory.
+
+use crate::error::ErrorContext;
+use crate::output::TempDir;
+use crate::LaszyError;
+use kdtree::KdTree;
+use las::Point;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+/// Size in meters of the tiles the points are sorted into to search the neighbourhoods of points.
+pub(crate) const TILE_SIZE: f64 = 100.0;
+/// Bytes of points buffered per tile before they are appended to the file of the tile.
+const BUCKET_BUFFER_SIZE: usize = 16 * 1024;
+/// Bytes of a point in the file of a tile: the index of its file as a little endian u32, its
+/// index in that file as a u64, and x, y, z and the GPS time as f64, with NaN without GPS time.
+const RECORD_SIZE: usize = 44;
+
+/// Where a point is in the input: the index of its file and its index in that file.
+pub(crate) type PointId = (u32, u64);
+
+/// A set of points of the input, with a bit per point of each file up to the last point in the
+/// set, so it takes at most a bit per input point however many points are in it.
+#[derive(Debug, Default)]
+pub(crate) struct PointSet {
+    files: Vec<Vec<u64>>,
+    len: usize,
+}
+
+impl PointSet {
+    pub fn insert(&mut self, (file_index, i): PointId) {
+        let file_index = file_index as usize;
+        if self.files.len() <= file_index {
+            self.files.resize_with(file_index + 1, Vec::new);
+        }
+        let words = &mut self.files[file_index];
+        let word = (i / 64) as usize;
+        if words.len() <= word {
+            words.resize(word + 1, 0);
+        }
+        let bit = 1 << (i % 64);
+        if words[word] & bit == 0 {
+            words[word] |= bit;
+            self.len += 1;
+        }
+    }
+
+    pub fn remove(&mut self, (file_index, i): PointId) {
+        let word = self
+            .files
+            .get_mut(file_index as usize)
+            .and_then(|words| words.get_mut((i / 64) as usize));
+        let bit = 1 << (i % 64);
+        if let Some(word) = word.filter(|word| **word & bit != 0) {
+            *word &= !bit;
+            self.len -= 1;
+        }
+    }
+
+    pub fn contains(&self, (file_index, i): PointId) -> bool {
+        self.files
+            .get(file_index as usize)
+            .and_then(|words| words.get((i / 64) as usize))
+            .is_some_and(|word| word & (1 << (i % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Extend<PointId> for PointSet {
+    fn extend<T: IntoIterator<Item = PointId>>(&mut self, ids: T) {
+        for id in ids {
+            self.insert(id);
+        }
+    }
+}
+
+/// The x, y, z and GPS time of a point, with where it is in the input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TiledPoint {
+    pub id: PointId,
+    pub xyz: [f64; 3],
+    pub gps_time: Option<f64>,
+}
+
+/// The points of a tile, including those in its buffer.
+pub(crate) struct Tile {
+    pub points: Vec<TiledPoint>,
+    /// Whether each point is in the tile itself rather than in its buffer. Each point is in the
+    /// core of exactly one tile.
+    pub is_core: Vec<bool>,
+}
+
+impl Tile {
+    /// The points of the tile in a kd-tree of their x, y and z, with their index in `points`.
+    pub fn kdtree(&self) -> Result<KdTree<f64, usize, [f64; 3]>, LaszyError> {
+        let mut index = KdTree::new(3);
+        for (i, point) in self.points.iter().enumerate() {
+            let (file_index, point_index) = point.id;
+            index.add(point.xyz, i).map_err(|e| {
+                LaszyError::InvalidPoint(format!(
+                    "point {point_index} of input file {file_index}: {e}"
+                ))
+            })?;
+        }
+        Ok(index)
+    }
+}
+
+/// The error of a search in a kd-tree of points, which fails for points that are not finite.
+pub(crate) fn invalid_point(e: kdtree::ErrorKind) -> LaszyError {
+    LaszyError::InvalidPoint(e.to_string())
+}
+
+/// Points sorted into tiles of `tile_size`, each with the points within `buffer` around it.
+pub(crate) struct PointTiles {
+    lower_left: (f64, f64),
+    tile_size: f64,
+    buffer: f64,
+    rows: usize,
+    columns: usize,
+    dir: TempDir,
+    buckets: Vec<Vec<u8>>,
+}
+
+impl PointTiles {
+    pub fn new(
+        lower_left: (f64, f64),
+        upper_right: (f64, f64),
+        tile_size: f64,
+        buffer: f64,
+        prefix: &str,
+    ) -> Result<Self, LaszyError> {
+        let tiles = |length: f64| ((length / tile_size).floor() as usize + 1).max(1);
+        let rows = tiles(upper_right.1 - lower_left.1);
+        let columns = tiles(upper_right.0 - lower_left.0);
+        Ok(PointTiles {
+            lower_left,
+            tile_size,
+            buffer,
+            rows,
+            columns,
+            dir: TempDir::new(prefix)?,
+            buckets: vec![Vec::new(); rows * columns],
+        })
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Index of the row or column of the tiles at `offset` from the lower left corner, where the
+    /// tiles at the edges extend outwards.
+    fn index(&self, offset: f64, tiles: usize) -> usize {
+        ((offset / self.tile_size).floor().max(0.0) as usize).min(tiles - 1)
+    }
+
+    /// Row and column of the tile at `x`, `y`.
+    fn tile_at(&self, x: f64, y: f64) -> (usize, usize) {
+        (
+            self.index(y - self.lower_left.1, self.rows),
+            self.index(x - self.lower_left.0, self.columns),
+        )
+    }
+
+    /// The rows or columns of the tiles whose buffer reaches `offset`.
+    fn reaching(&self, offset: f64, tiles: usize) -> RangeInclusive<usize> {
+        self.index(offset - self.buffer, tiles)..=self.index(offset + self.buffer, tiles)
+    }
+
+    /// Add a point to every tile it falls in, including the buffers of the tiles.
+    pub fn add_point(&mut self, id: PointId, point: &Point) -> Result<(), LaszyError> {
+        let rows = self.reaching(point.y - self.lower_left.1, self.rows);
+        let columns = self.reaching(point.x - self.lower_left.0, self.columns);
+        for row in rows {
+            for column in columns.clone() {
+                let tile = row * self.columns + column;
+                let bucket = &mut self.buckets[tile];
+                bucket.extend_from_slice(&id.0.to_le_bytes());
+                bucket.extend_from_slice(&id.1.to_le_bytes());
+                let gps_time = point.gps_time.unwrap_or(f64::NAN);
+                for value in [point.x, point.y, point.z, gps_time] {
+                    bucket.extend_from_slice(&value.to_le_bytes());
+                }
+                if bucket.len() >= BUCKET_BUFFER_SIZE {
+                    self.flush_bucket(tile)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn points_path(&self, tile: usize) -> PathBuf {
+        self.dir.path().join(format!("tile-{tile}.bin"))
+    }
+
+    fn flush_bucket(&mut self, tile: usize) -> Result<(), LaszyError> {
+        let path = self.points_path(tile);
+        let path_name = path.to_string_lossy();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .in_file(&path_name)?;
+        file.write_all(&self.buckets[tile]).in_file(&path_name)?;
+        self.buckets[tile].clear();
+        Ok(())
+    }
+
+    /// A temporary file next to the tiles, removed with them.
+    pub fn temp_path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Write out the points that are still buffered, after all points are added.
+    pub fn finish(&mut self) -> Result<(), LaszyError> {
+        for tile in 0..self.buckets.len() {
+            if !self.buckets[tile].is_empty() {
+                self.flush_bucket(tile)?;
+            }
+        }
+        self.buckets = vec![Vec::new(); self.buckets.len()];
+        Ok(())
+    }
+
+    /// Read and remove the points of a tile, after `finish`.
+    pub fn take_tile(&self, tile: usize) -> Result<Tile, LaszyError> {
+        let points = self.read_tile(tile)?;
+        let path = self.points_path(tile);
+        if path.exists() {
+            std::fs::remove_file(&path).in_file(&path.to_string_lossy())?;
+        }
+        Ok(points)
+    }
+
+    /// Read the points of a tile, after `finish`, keeping them to be read again.
+    pub fn read_tile(&self, tile: usize) -> Result<Tile, LaszyError> {
+        let path = self.points_path(tile);
+        if !path.exists() {
+            return Ok(Tile {
+                points: Vec::new(),
+                is_core: Vec::new(),
+            });
+        }
+        let path_name = path.to_string_lossy();
+        let mut bytes = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .in_file(&path_name)?;
+        let points: Vec<TiledPoint> = bytes
+            .chunks_exact(RECORD_SIZE)
+            .map(|record| {
+                let bytes = |at: usize| record[at..at + 8].try_into().expect("8 bytes");
+                let value = |i: usize| f64::from_le_bytes(bytes(12 + i * 8));
+                TiledPoint {
+                    id: (
+                        u32::from_le_bytes(record[0..4].try_into().expect("4 bytes")),
+                        u64::from_le_bytes(bytes(4)),
+                    ),
+                    xyz: [value(0), value(1), value(2)],
+                    gps_time: Some(value(3)).filter(|gps_time| !gps_time.is_nan()),
+                }
+            })
+            .collect();
+        let is_core = points
+            .iter()
+            .map(|point| {
+                let (row, column) = self.tile_at(point.xyz[0], point.xyz[1]);
+                row * self.columns + column == tile
+            })
+            .collect();
+        Ok(Tile { points, is_core })
+    }
+}
@@ -0,0 +1,421 @@
+//! Splitting the points over a regular grid of LAS/LAZ files.
+
+use crate::error::ErrorContext;
+use crate::las_output::PointConversion;
+use crate::output::{self, OverwritePolicy};
+use crate::LaszyError;
+use las::{Header, Point, Read, Reader, Write, Writer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Placeholders in the name of the tiles, replaced by the bounds of a tile.
+const PLACEHOLDERS: [&str; 4] = ["{minx}", "{miny}", "{maxx}", "{maxy}"];
+
+/// Parameters of the tiles written by `PointCloudBuilder::to_tiles`. The tiles are squares of the
+/// tile size, aligned to the origin: a tile has its lower left corner at the origin plus a whole
+/// number of tiles in x and y. Points on the left or bottom edge of a tile belong to that tile.
+///
+/// # Examples
+///
+/// ```
+/// use laszy::TileParams;
+/// // Tiles of 1 km with a buffer of 20 m, named like 183000_332000.laz
+/// let params = TileParams::new(1000.0).with_buffer(20.0);
+/// assert!(params.validate().is_ok());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileParams {
+    /// Width and height of the tiles in meters.
+    pub tile_size: f64,
+    /// Point the grid of tiles is aligned to, (0.0, 0.0) by default.
+    pub origin: (f64, f64),
+    /// Points within this many meters outside a tile are also written to it, so the tiles overlap.
+    /// Tiles that only have such buffer points are not written. 0.0 by default.
+    pub buffer: f64,
+    /// Name of the tile files, where {minx}, {miny}, {maxx} and {maxy} are replaced by the bounds
+    /// of a tile. Must end in .las or .laz, "{minx}_{miny}.laz" by default.
+    pub name: String,
+    /// Largest number of tile files that are open at the same time, 64 by default. When more tiles
+    /// are being written, the one written to longest ago is closed, and the parts of a tile are
+    /// joined at the end. This keeps the memory use bounded for inputs that are not sorted, but
+    /// when points jump between more tiles than this, writing the many parts is slow.
+    pub max_open_files: usize,
+}
+
+impl TileParams {
+    pub fn new(tile_size: f64) -> Self {
+        TileParams {
+            tile_size,
+            origin: (0.0, 0.0),
+            buffer: 0.0,
+            name: "{minx}_{miny}.laz".to_string(),
+            max_open_files: 64,
+        }
+    }
+
+    pub fn with_origin(mut self, x: f64, y: f64) -> Self {
+        self.origin = (x, y);
+        self
+    }
+
+    pub fn with_buffer(mut self, buffer: f64) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), LaszyError> {
+        if !(self.tile_size > 0.0 && self.tile_size.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "tile_size",
+                self.tile_size,
+                "must be larger than 0.0",
+            ));
+        }
+        if !(self.origin.0.is_finite() && self.origin.1.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "origin",
+                format!("({}, {})", self.origin.0, self.origin.1),
+                "must be finite",
+            ));
+        }
+        if !(self.buffer >= 0.0 && self.buffer.is_finite()) {
+            return Err(LaszyError::invalid_parameter(
+                "buffer",
+                self.buffer,
+                "must be 0.0 or larger",
+            ));
+        }
+        if !self.name.ends_with(".las") && !self.name.ends_with(".laz") {
+            return Err(LaszyError::InvalidFileExtension(format!(
+                "{}, the name of the tiles must end in .las or .laz",
+                self.name
+            )));
+        }
+        let mut rest = self.name.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map_or(rest.len(), |end| start + end + 1);
+            let placeholder = &rest[start..end];
+            if !PLACEHOLDERS.contains(&placeholder) {
+                return Err(LaszyError::invalid_parameter(
+                    "name",
+                    &self.name,
+                    format!(
+                        "unknown placeholder {placeholder}, use {}",
+                        PLACEHOLDERS.join(", ")
+                    ),
+                ));
+            }
+            rest = &rest[end..];
+        }
+        let has = |placeholder: &str| self.name.contains(placeholder);
+        if !(has("{minx}") || has("{maxx}")) || !(has("{miny}") || has("{maxy}")) {
+            return Err(LaszyError::invalid_parameter(
+                "name",
+                &self.name,
+                "must have an x and a y placeholder, so the tiles get different names",
+            ));
+        }
+        if self.max_open_files == 0 {
+            return Err(LaszyError::invalid_parameter(
+                "max_open_files",
+                0,
+                "must be at least 1",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.name.ends_with(".laz")
+    }
+
+    /// The column or row of the tile that `coordinate` is in, along the axis of `origin`.
+    fn index(&self, coordinate: f64, origin: f64) -> i64 {
+        ((coordinate - origin) / self.tile_size).floor() as i64
+    }
+
+    /// The tile a point at `x` and `y` is in, and the tiles it is in the buffer of.
+    fn tiles_of(&self, x: f64, y: f64) -> (TileKey, Vec<TileKey>) {
+        let (origin_x, origin_y) = self.origin;
+        let own = (self.index(x, origin_x), self.index(y, origin_y));
+        let mut buffers = Vec::new();
+        if self.buffer > 0.0 {
+            let buffer = self.buffer;
+            for column in self.index(x - buffer, origin_x)..=self.index(x + buffer, origin_x) {
+                let rows = self.index(y - buffer, origin_y)..=self.index(y + buffer, origin_y);
+                buffers.extend(rows.map(|row| (column, row)).filter(|key| *key != own));
+            }
+        }
+        (own, buffers)
+    }
+
+    /// The file name of the tile in `column` and `row`.
+    fn file_name(&self, (column, row): TileKey) -> String {
+        let min_x = self.origin.0 + column as f64 * self.tile_size;
+        let min_y = self.origin.1 + row as f64 * self.tile_size;
+        let bounds = [min_x, min_y, min_x + self.tile_size, min_y + self.tile_size];
+        PLACEHOLDERS
+            .iter()
+            .zip(bounds)
+            .fold(self.name.clone(), |name, (placeholder, value)| {
+                name.replace(placeholder, &coordinate(value))
+            })
+    }
+}
+
+/// `value` rounded to micrometers, without trailing zeros, so 183000.0 becomes "183000".
+fn coordinate(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+    // Adding 0.0 turns -0.0 into 0.0
+    format!("{}", rounded + 0.0)
+}
+
+/// Column and row of a tile in the grid.
+type TileKey = (i64, i64);
+
+/// A tile being written, in one or more parts. Its parts are removed when it is dropped.
+struct Tile {
+    path: PathBuf,
+    parts: Vec<PathBuf>,
+    writer: Option<Writer<BufWriter<File>>>,
+    /// When the tile was last written to, to close the tile written to longest ago.
+    last_write: u64,
+    /// Number of points in the tile itself, without the buffer points.
+    own_points: u64,
+    /// The file already exists and the overwrite policy skips it.
+    is_skipped: bool,
+}
+
+impl Tile {
+    fn current_part(&self) -> String {
+        self.parts
+            .last()
+            .map(|part| part.display().to_string())
+            .unwrap_or_default()
+    }
+
+    fn close_writer(&mut self) -> Result<(), LaszyError> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.close().in_file(&self.current_part())?;
+        }
+        Ok(())
+    }
+
+    /// Write the parts of the tile to its file. Returns the path of the file, or None when it was
+    /// skipped or only has buffer points.
+    fn finish(
+        &mut self,
+        header: &Header,
+        policy: OverwritePolicy,
+    ) -> Result<Option<String>, LaszyError> {
+        self.close_writer()?;
+        if self.is_skipped || self.own_points == 0 {
+            return Ok(None);
+        }
+        let path = self.path.display().to_string();
+        let written = output::write_atomically(&self.path, policy, |temp_path| {
+            if let [part] = self.parts.as_slice() {
+                return std::fs::rename(part, temp_path).in_file(&path);
+            }
+            join_parts(&self.parts, temp_path, header).in_file(&path)
+        })?;
+        Ok(written.map(|_| path))
+    }
+}
+
+impl Drop for Tile {
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            // The part is removed anyway
+            let _ = writer.close();
+        }
+        for part in &self.parts {
+            // Parts that were renamed to the tile no longer exist
+            let _ = std::fs::remove_file(part);
+        }
+    }
+}
+
+/// Write the points of the `parts` of a tile, in order, to a single file at `target`.
+fn join_parts(parts: &[PathBuf], target: &Path, header: &Header) -> Result<(), LaszyError> {
+    let file = File::create(target)?;
+    let mut writer = Writer::new(BufWriter::new(file), header.clone())?;
+    // The las crate reads a GPS time or NIR of 0 as None
+    let conversion = PointConversion::new(header.point_format());
+    for part in parts {
+        let part_path = part.display().to_string();
+        let file = File::open(part).in_file(&part_path)?;
+        let mut reader = Reader::new(BufReader::new(file)).in_file(&part_path)?;
+        for (i, point) in reader.points().enumerate() {
+            let mut point = point.at_point(&part_path, i as u64)?;
+            conversion.convert(&mut point);
+            writer.write(point)?;
+        }
+    }
+    writer.close()?;
+    Ok(())
+}
+
+/// Writes points to the tiles they are in, keeping a limited number of files open.
+pub(crate) struct TileWriter {
+    params: TileParams,
+    directory: PathBuf,
+    header: Header,
+    overwrite: OverwritePolicy,
+    tiles: HashMap<TileKey, Tile>,
+    /// The tiles with an open writer by when they were last written to, oldest first.
+    open: BTreeMap<u64, TileKey>,
+    writes: u64,
+}
+
+impl TileWriter {
+    /// Write tiles with the `header`, which has the point format and VLRs of all tiles, in the
+    /// `directory`.
+    pub fn new(
+        directory: &Path,
+        params: TileParams,
+        header: Header,
+        overwrite: OverwritePolicy,
+    ) -> Self {
+        TileWriter {
+            params,
+            directory: directory.to_path_buf(),
+            header,
+            overwrite,
+            tiles: HashMap::new(),
+            open: BTreeMap::new(),
+            writes: 0,
+        }
+    }
+
+    /// Write `point` to its tile, and to the tiles it is in the buffer of.
+    pub fn write(&mut self, point: Point) -> Result<(), LaszyError> {
+        let (own, buffers) = self.params.tiles_of(point.x, point.y);
+        for key in buffers {
+            self.write_to(key, point.clone(), false)?;
+        }
+        self.write_to(own, point, true)
+    }
+
+    fn write_to(&mut self, key: TileKey, point: Point, is_own: bool) -> Result<(), LaszyError> {
+        let tile = self.open_tile(key, is_own)?;
+        if let Some(writer) = tile.writer.as_mut() {
+            if let Err(e) = writer.write(point) {
+                return Err(e).in_file(&tile.current_part());
+            }
+        }
+        Ok(())
+    }
+
+    /// The tile with `key` with an open writer, unless the tile is skipped. Closes the tile that
+    /// was written to longest ago when too many files are open.
+    fn open_tile(&mut self, key: TileKey, is_own: bool) -> Result<&mut Tile, LaszyError> {
+        self.writes += 1;
+        if !self.tiles.contains_key(&key) {
+            let tile = self.new_tile(key)?;
+            self.tiles.insert(key, tile);
+        }
+        let tile = &self.tiles[&key];
+        if tile.writer.is_some() {
+            self.open.remove(&tile.last_write);
+        } else if !tile.is_skipped {
+            if self.open.len() >= self.params.max_open_files {
+                self.close_oldest()?;
+            }
+            let tile = self
+                .tiles
+                .get_mut(&key)
+                .expect("the tile was just inserted");
+            let file_name = tile
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let part = tile.path.with_file_name(format!(
+                ".{file_name}.{}.{}.part",
+                std::process::id(),
+                tile.parts.len()
+            ));
+            let part_path = part.display().to_string();
+            tile.parts.push(part);
+            let file = File::create(tile.parts.last().expect("a part was just added"))
+                .in_file(&part_path)?;
+            tile.writer =
+                Some(Writer::new(BufWriter::new(file), self.header.clone()).in_file(&part_path)?);
+        }
+        let tile = self
+            .tiles
+            .get_mut(&key)
+            .expect("the tile was just inserted");
+        tile.last_write = self.writes;
+        if tile.writer.is_some() {
+            self.open.insert(self.writes, key);
+        }
+        if is_own {
+            tile.own_points += 1;
+        }
+        Ok(tile)
+    }
+
+    fn new_tile(&self, key: TileKey) -> Result<Tile, LaszyError> {
+        let path = self.directory.join(self.params.file_name(key));
+        let mut is_skipped = false;
+        if path.exists() {
+            match self.overwrite {
+                OverwritePolicy::Error => {
+                    return Err(LaszyError::OutputExists(path.display().to_string()))
+                }
+                OverwritePolicy::Skip => {
+                    log::info!("Skipping {}, it already exists", path.display());
+                    is_skipped = true;
+                }
+                OverwritePolicy::Overwrite => {}
+            }
+        }
+        Ok(Tile {
+            path,
+            parts: Vec::new(),
+            writer: None,
+            last_write: 0,
+            own_points: 0,
+            is_skipped,
+        })
+    }
+
+    /// Close the writer of the tile that was written to longest ago.
+    fn close_oldest(&mut self) -> Result<(), LaszyError> {
+        let oldest = self.open.pop_first();
+        if let Some(tile) = oldest.and_then(|(_, key)| self.tiles.get_mut(&key)) {
+            tile.close_writer()?;
+        }
+        Ok(())
+    }
+
+    /// Write the tiles to their files, ordered by row and column. Returns the paths of the files
+    /// that were written.
+    pub fn finish(self) -> Result<Vec<String>, LaszyError> {
+        let mut tiles: Vec<_> = self.tiles.into_iter().collect();
+        tiles.sort_by_key(|((column, row), _)| (*row, *column));
+        let mut paths = Vec::new();
+        for (_, mut tile) in tiles {
+            if let Some(path) = tile.finish(&self.header, self.overwrite)? {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+}